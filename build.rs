@@ -1,5 +1,40 @@
+use std::{
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use skerry_codegen::SkerryGenerator;
 
+/// Exposes the commit and build time to `src/build_info.rs`
+fn emit_build_info() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // Reproducible builds pin the date through SOURCE_DATE_EPOCH
+    let built_at = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=AKAREKO_GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=AKAREKO_BUILT_AT={}", built_at);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
 fn main() -> Result<(), skerry_codegen::SkerryCodeGenError> {
+    emit_build_info();
     SkerryGenerator::new().generate()
 }
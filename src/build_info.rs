@@ -0,0 +1,17 @@
+//! What build is running, filled in by `build.rs`
+
+use crate::types::Timestamp;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short commit hash, `unknown` when built outside of a git checkout
+pub const GIT_HASH: &str = env!("AKAREKO_GIT_HASH");
+
+pub fn built_at() -> Timestamp {
+    Timestamp::new(env!("AKAREKO_BUILT_AT").parse().unwrap_or(0))
+}
+
+/// `0.1.0 (abc123def456)`
+pub fn describe() -> String {
+    format!("{} ({})", VERSION, GIT_HASH)
+}
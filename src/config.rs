@@ -1,4 +1,4 @@
-use std::num::NonZero;
+use std::{num::NonZero, time::Duration};
 
 use serde::{Deserialize, Serialize};
 use skerry::skerry;
@@ -9,7 +9,9 @@ use yosemite::RouterApi;
 use crate::{
     db::user::I2PAddress,
     helpers::b32_from_pub_b64,
-    types::{PrivateKey, PublicKey, Timestamp},
+    server::{client::update::UpdateConfig, opds::OpdsConfig, simulator::NetworkSimulation},
+    storage::StorageConfig,
+    types::{PrivateKey, PublicKey, Secret, Timestamp},
 };
 
 pub const DEFAULT_SAM_TCP_PORT: u16 = 7656;
@@ -22,14 +24,181 @@ pub struct KeyPair {
     public_key: PublicKey,
 }
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct SchedulerConfig {
     pub full_sync_interval: Timestamp,
+    /// Peers synced with more recently than this aren't picked again
+    pub exchange_cooldown: Timestamp,
+    /// How often what we published is announced again to trusted peers, 0
+    /// turns it off
+    pub republish_interval: Timestamp,
 }
 
 impl Default for SchedulerConfig {
     fn default() -> Self {
         Self {
             full_sync_interval: Timestamp::new(60 * 5), // 5 minutes
+            exchange_cooldown: Timestamp::new(60 * 30), // 30 minutes
+            republish_interval: Timestamp::new(60 * 60 * 24), // 1 day
+        }
+    }
+}
+
+/// Requests each peer can send the server, as a bucket of tokens refilled
+/// over time. Reconnecting doesn't give a peer a new bucket.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Requests a peer can send at once before it has to slow down
+    pub burst: u32,
+    /// Requests a peer can keep sending every minute, 0 turns the limit off
+    pub per_minute: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            burst: 60,
+            per_minute: 300,
+        }
+    }
+}
+
+/// How long peers get before they're given up on. A peer that goes silent
+/// would otherwise hold its connection, and a server slot, forever.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    /// Time a peer connected to the server has to send its next request
+    pub idle_secs: u64,
+    /// Time the server has to read a request and send all of its response,
+    /// and the client to get each response and each item streamed after it
+    pub request_secs: u64,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            idle_secs: 60,
+            // Responses stream many items over a slow network
+            request_secs: 300,
+        }
+    }
+}
+
+impl TimeoutConfig {
+    pub fn idle(&self) -> Duration {
+        Duration::from_secs(self.idle_secs.max(1))
+    }
+
+    pub fn request(&self) -> Duration {
+        Duration::from_secs(self.request_secs.max(1))
+    }
+}
+
+/// Limits on a content record, checked on what peers send and on what we
+/// publish. Records past any of them are dropped whole.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ContentLimitsConfig {
+    /// Files listed in the manifest
+    pub max_entries: usize,
+    pub max_title_chars: usize,
+    /// Characters in the source and in each manifest path
+    pub max_path_chars: usize,
+    /// Size of the whole record once encoded, in bytes
+    pub max_encoded_bytes: u64,
+}
+
+impl Default for ContentLimitsConfig {
+    fn default() -> Self {
+        Self {
+            // A volume split in pages
+            max_entries: 2000,
+            max_title_chars: 300,
+            max_path_chars: 512,
+            max_encoded_bytes: 1 << 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ToastCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ToastCorner {
+    pub const ALL: [ToastCorner; 4] = [
+        ToastCorner::TopLeft,
+        ToastCorner::TopRight,
+        ToastCorner::BottomLeft,
+        ToastCorner::BottomRight,
+    ];
+
+    pub fn is_left(self) -> bool {
+        matches!(self, ToastCorner::TopLeft | ToastCorner::BottomLeft)
+    }
+
+    pub fn is_top(self) -> bool {
+        matches!(self, ToastCorner::TopLeft | ToastCorner::TopRight)
+    }
+}
+
+impl std::fmt::Display for ToastCorner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToastCorner::TopLeft => write!(f, "Top left"),
+            ToastCorner::TopRight => write!(f, "Top right"),
+            ToastCorner::BottomLeft => write!(f, "Bottom left"),
+            ToastCorner::BottomRight => write!(f, "Bottom right"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ToastConfig {
+    pub corner: ToastCorner,
+    /// Toasts shown at once, the rest wait behind a counter until one is
+    /// dismissed
+    pub max_visible: u8,
+}
+
+impl Default for ToastConfig {
+    fn default() -> Self {
+        Self {
+            corner: ToastCorner::BottomRight,
+            max_visible: 3,
+        }
+    }
+}
+
+/// Node that only stores and forwards what it exchanges, nothing is shown to
+/// the operator. Needs a restart to take effect.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RelayOnlyConfig {
+    pub enabled: bool,
+    /// How long relayed indexes and content are kept after they were last
+    /// exchanged
+    pub retention: Timestamp,
+    /// How often expired records are looked for
+    pub prune_interval: Timestamp,
+    /// Size of the database past which the least recently exchanged records
+    /// are evicted, 0 for no limit
+    pub max_database_mb: u64,
+}
+
+impl Default for RelayOnlyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            retention: Timestamp::new(60 * 60 * 24 * 14), // 2 weeks
+            prune_interval: Timestamp::new(60 * 60),      // 1 hour
+            max_database_mb: 1024,
         }
     }
 }
@@ -54,7 +223,7 @@ pub struct AkarekoConfig {
     sam_tcp_port: u16,
     sam_udp_port: u16,
 
-    eepsite_key: String,
+    eepsite_key: Secret<String>,
     eepsite_address: I2PAddress,
 
     dev_mode: bool,
@@ -62,14 +231,40 @@ pub struct AkarekoConfig {
     image_viewer_preferences: ImageViewerPreferences,
 
     max_client_connections: u16,
+    /// Peers the server talks to at once, the rest are told it's busy
+    max_server_connections: u16,
+    rate_limit: RateLimitConfig,
+    timeouts: TimeoutConfig,
+    content_limits: ContentLimitsConfig,
+    /// Clearnet tracker hosts kept in the magnets we publish, every other one
+    /// is stripped. I2P trackers are always kept.
+    allowed_trackers: Vec<String>,
+    /// The torrent client only reaches peers through I2P: no DHT, local
+    /// discovery or clearnet trackers. Needs a restart to take effect.
+    i2p_only_torrents: bool,
     scheduler_config: SchedulerConfig,
 
     is_relay: bool,
+    relay_only: RelayOnlyConfig,
 
     save_metadata_on_disk: bool,
     pub metadata_source: MetadataSource,
 
     word_filter: WordFilter,
+    /// Toast posts from others that mention our key
+    notify_mentions: bool,
+    toasts: ToastConfig,
+    /// Users vouched for by this many of our fully trusted users are raised
+    /// to trusted, 0 turns it off
+    vouch_threshold: u8,
+
+    opds: OpdsConfig,
+
+    storage: StorageConfig,
+
+    network_simulation: NetworkSimulation,
+
+    updates: UpdateConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -136,16 +331,30 @@ impl Default for AkarekoConfig {
             keypair: KeyPair::new(PrivateKey::new()),
             sam_tcp_port: DEFAULT_SAM_TCP_PORT,
             sam_udp_port: DEFAULT_SAM_UDP_PORT,
-            eepsite_key: String::new(),
+            eepsite_key: Secret::default(),
             eepsite_address: I2PAddress::new(""),
             dev_mode: false,
             is_relay: false,
+            relay_only: RelayOnlyConfig::default(),
             max_client_connections: 8,
+            max_server_connections: 32,
+            rate_limit: RateLimitConfig::default(),
+            timeouts: TimeoutConfig::default(),
+            content_limits: ContentLimitsConfig::default(),
+            allowed_trackers: vec![],
+            i2p_only_torrents: false,
             scheduler_config: SchedulerConfig::default(),
             image_viewer_preferences: ImageViewerPreferences::default(),
             save_metadata_on_disk: true,
             metadata_source: MetadataSource::Mangadex,
             word_filter: WordFilter::None,
+            notify_mentions: true,
+            toasts: ToastConfig::default(),
+            vouch_threshold: 0,
+            opds: OpdsConfig::default(),
+            storage: StorageConfig::default(),
+            network_simulation: NetworkSimulation::default(),
+            updates: UpdateConfig::default(),
         }
     }
 }
@@ -190,13 +399,13 @@ impl AkarekoConfig {
         config
     }
 
-    pub fn eepsite_key(&self) -> &String {
+    pub fn eepsite_key(&self) -> &Secret<String> {
         &self.eepsite_key
     }
 
     pub fn set_eepsite_data(&mut self, eepsite_address: I2PAddress, eepsite_key: String) {
         self.eepsite_address = eepsite_address;
-        self.eepsite_key = eepsite_key;
+        self.eepsite_key = Secret::new(eepsite_key);
     }
 
     pub fn eepsite_address(&self) -> &I2PAddress {
@@ -251,6 +460,34 @@ impl AkarekoConfig {
         self.max_client_connections
     }
 
+    pub fn max_server_connections(&self) -> u16 {
+        self.max_server_connections
+    }
+
+    pub fn rate_limit(&self) -> &RateLimitConfig {
+        &self.rate_limit
+    }
+
+    pub fn timeouts(&self) -> &TimeoutConfig {
+        &self.timeouts
+    }
+
+    pub fn content_limits(&self) -> &ContentLimitsConfig {
+        &self.content_limits
+    }
+
+    pub fn allowed_trackers(&self) -> &[String] {
+        &self.allowed_trackers
+    }
+
+    pub fn i2p_only_torrents(&self) -> bool {
+        self.i2p_only_torrents
+    }
+
+    pub fn set_i2p_only_torrents(&mut self, i2p_only_torrents: bool) {
+        self.i2p_only_torrents = i2p_only_torrents;
+    }
+
     pub fn dev_mode(&self) -> bool {
         self.dev_mode
     }
@@ -259,8 +496,62 @@ impl AkarekoConfig {
         self.dev_mode = dev_mode;
     }
 
+    /// Relay-only nodes always relay
     pub fn is_relay(&self) -> bool {
-        self.is_relay
+        self.is_relay || self.relay_only.enabled
+    }
+
+    pub fn relay_only(&self) -> &RelayOnlyConfig {
+        &self.relay_only
+    }
+
+    pub fn set_relay_only(&mut self, enabled: bool) {
+        self.relay_only.enabled = enabled;
+    }
+
+    pub fn notify_mentions(&self) -> bool {
+        self.notify_mentions
+    }
+
+    pub fn set_notify_mentions(&mut self, notify_mentions: bool) {
+        self.notify_mentions = notify_mentions;
+    }
+
+    pub fn toasts(&self) -> &ToastConfig {
+        &self.toasts
+    }
+
+    pub fn set_toast_corner(&mut self, corner: ToastCorner) {
+        self.toasts.corner = corner;
+    }
+
+    pub fn set_max_visible_toasts(&mut self, max_visible: u8) {
+        self.toasts.max_visible = max_visible;
+    }
+
+    pub fn vouch_threshold(&self) -> u8 {
+        self.vouch_threshold
+    }
+
+    pub fn set_vouch_threshold(&mut self, vouch_threshold: u8) {
+        self.vouch_threshold = vouch_threshold;
+    }
+
+    pub fn opds(&self) -> &OpdsConfig {
+        &self.opds
+    }
+
+    /// Conditions peer streams are put through, only ever set in dev mode
+    pub fn network_simulation(&self) -> Option<NetworkSimulation> {
+        (self.dev_mode && self.network_simulation.enabled).then(|| self.network_simulation.clone())
+    }
+
+    pub fn updates(&self) -> &UpdateConfig {
+        &self.updates
+    }
+
+    pub fn storage(&self) -> &StorageConfig {
+        &self.storage
     }
 
     // pub fn set_is_relay(&mut self, is_relay: bool) {
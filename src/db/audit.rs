@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "surrealdb")]
+use surrealdb::{Surreal, engine::local::Db};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Repositories, changes::DataKind},
+    errors::DatabaseError,
+    types::Timestamp,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub enum AuditKind {
+    Trust,
+    Mute,
+    Unmute,
+    /// Deleted, and kept from syncing back if it says so in the detail
+    Delete,
+    /// Allowed to sync again after being deleted
+    Unsuppress,
+}
+
+impl std::fmt::Display for AuditKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditKind::Trust => write!(f, "Trust"),
+            AuditKind::Mute => write!(f, "Mute"),
+            AuditKind::Unmute => write!(f, "Unmute"),
+            AuditKind::Delete => write!(f, "Delete"),
+            AuditKind::Unsuppress => write!(f, "Unsuppress"),
+        }
+    }
+}
+
+/// A trust or moderation decision taken on this node, and why. Entries are
+/// only ever added, there's nothing to edit or remove them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub struct AuditEntry {
+    pub kind: AuditKind,
+    /// Base64 of the key, hash or signature the decision is about
+    pub subject: String,
+    /// Name or title of the subject when it was taken, so the log still
+    /// reads after the subject is gone
+    pub label: String,
+    /// What changed, like the trust levels before and after
+    pub detail: String,
+    pub reason: String,
+    pub at: Timestamp,
+}
+
+impl AuditEntry {
+    pub const TABLE_NAME: &'static str = "audit_log";
+
+    pub fn new(
+        kind: AuditKind,
+        subject: String,
+        label: String,
+        detail: String,
+        reason: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            subject,
+            label,
+            detail,
+            reason: reason.into(),
+            at: Timestamp::now(),
+        }
+    }
+
+    /// Entry for deleting `subject`, `suppressed` if it's also kept from
+    /// syncing back
+    pub fn deleted(
+        subject: String,
+        label: String,
+        suppressed: bool,
+        reason: impl Into<String>,
+    ) -> Self {
+        let detail = match suppressed {
+            true => "Kept from syncing back",
+            false => "Can sync back",
+        };
+        Self::new(
+            AuditKind::Delete,
+            subject,
+            label,
+            detail.to_string(),
+            reason,
+        )
+    }
+}
+
+/// Appends `entry`, for repositories that only hold the connection
+#[cfg(feature = "surrealdb")]
+pub(crate) async fn append_audit(db: &Surreal<Db>, entry: AuditEntry) -> Result<(), DatabaseError> {
+    use surrealdb_types::Value;
+
+    let _: Vec<Value> = db.insert(AuditEntry::TABLE_NAME).content(entry).await?;
+
+    Ok(())
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn record_audit(&self, mut entry: AuditEntry) -> Result<(), DatabaseError> {
+        entry.at = self.now();
+        append_audit(&self.db, entry).await?;
+
+        self.notify(DataKind::AuditLog);
+
+        Ok(())
+    }
+
+    /// Newest first
+    pub async fn get_audit_log(&self, take: usize) -> Result<Vec<AuditEntry>, DatabaseError> {
+        let entries: Vec<AuditEntry> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} ORDER BY at DESC LIMIT $take;",
+                AuditEntry::TABLE_NAME
+            ))
+            .bind(("take", take))
+            .await?
+            .take(0)?;
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditKind;
+    use crate::{
+        db::{
+            Repositories,
+            user::{I2PAddress, TrustLevel, User},
+        },
+        types::{PrivateKey, Timestamp},
+    };
+
+    #[tokio::test]
+    async fn only_actual_trust_changes_are_logged() {
+        let repo = Repositories::in_memory().await;
+        let user = User::new_signed(
+            "Spammer".to_string(),
+            Timestamp::new(0),
+            &PrivateKey::new(),
+            I2PAddress::new("spam.b32.i2p"),
+        );
+
+        let user = repo
+            .user()
+            .change_trust(user, TrustLevel::Untrusted, "Added")
+            .await
+            .unwrap();
+        let user = repo
+            .user()
+            .change_trust(user, TrustLevel::Ignore, "Spam")
+            .await
+            .unwrap();
+        repo.user()
+            .change_trust(user, TrustLevel::Ignore, "Spam again")
+            .await
+            .unwrap();
+
+        let log = repo.get_audit_log(10).await.unwrap();
+        assert_eq!(log.len(), 2);
+        assert!(log.iter().all(|e| e.kind == AuditKind::Trust));
+        assert!(
+            log.iter()
+                .any(|e| e.detail == "Added as Untrusted" && e.reason == "Added")
+        );
+        assert!(
+            log.iter()
+                .any(|e| e.detail == "Untrusted to Ignored" && e.reason == "Spam")
+        );
+    }
+}
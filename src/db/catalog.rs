@@ -0,0 +1,513 @@
+//! Catalog snapshots, everything a relay knows in one signed blob so a new
+//! node that trusts it can bootstrap in a single request instead of syncing
+//! event by event. Each snapshot carries the relay's generation, followers
+//! ask for what changed since the one they have.
+
+use std::{collections::HashMap, io::Read};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use surrealdb_types::SurrealValue;
+#[cfg(feature = "surrealdb")]
+use surrealdb_types::Value;
+
+use crate::{
+    db::{
+        Repositories, ToBytes,
+        content_source::{ContentSource, SourceKind},
+        event::EventType,
+        index::{Index, content::Content, tags::IndexTag},
+        user::{I2PAddress, TrustLevel},
+    },
+    errors::{DatabaseError, DecodeError, EncodeError},
+    types::{Hash, PrivateKey, PublicKey, Signature, Timestamp, Topic},
+};
+
+/// Content older than this is left out of full snapshots, indexes are all sent
+pub const SNAPSHOT_CONTENT_WINDOW: i64 = 60 * 60 * 24 * 90; // 90 days
+
+/// Snapshots within this of the last generation reuse it, so frequent
+/// requests don't pile up generations. Followers may get a few records twice.
+const GENERATION_INTERVAL: i64 = 60 * 10; // 10 minutes
+
+/// A snapshot can't expand past this, so a hostile relay can't exhaust memory
+const MAX_SNAPSHOT_SIZE: u64 = 256 * 1024 * 1024;
+
+const COMPRESSION_LEVEL: i32 = 9;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "Content<I>: Serialize",
+    deserialize = "Content<I>: DeserializeOwned"
+))]
+pub struct Catalog<I: IndexTag> {
+    pub indexes: Vec<Index<I>>,
+    pub contents: Vec<Content<I>>,
+}
+
+/// Point in a relay's history, what it stored after `created_at` belongs to
+/// the next generation
+#[derive(Debug, Clone, PartialEq, Eq, SurrealValue)]
+pub struct CatalogGeneration {
+    pub generation: u64,
+    pub created_at: Timestamp,
+}
+
+impl CatalogGeneration {
+    pub const TABLE_NAME: &'static str = "catalog_generations";
+}
+
+/// Last generation imported from a relay
+#[derive(Debug, Clone, PartialEq, Eq, SurrealValue)]
+pub struct CatalogFollow {
+    #[surreal(rename = "id")]
+    pub relay: PublicKey,
+    pub generation: u64,
+}
+
+impl CatalogFollow {
+    pub const TABLE_NAME: &'static str = "catalog_follows";
+}
+
+/// Someone whose records are in a fetched catalog
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CatalogSource {
+    pub key: PublicKey,
+    pub name: String,
+    /// `None` for a key we have no user for
+    pub trust: Option<TrustLevel>,
+    pub indexes: usize,
+    pub contents: usize,
+}
+
+/// A verified catalog held back until its sources are reviewed, nothing of it
+/// is stored before [`Repositories::land_catalog`]
+#[derive(Debug, Clone)]
+pub struct PendingCatalog<I: IndexTag> {
+    pub relay: PublicKey,
+    pub relay_address: I2PAddress,
+    pub generation: u64,
+    /// Generation the changes are since, `None` for a full snapshot
+    pub since: Option<u64>,
+    pub catalog: Catalog<I>,
+    /// Most records first
+    pub sources: Vec<CatalogSource>,
+}
+
+impl<I: IndexTag> PartialEq for PendingCatalog<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.relay == other.relay
+            && self.generation == other.generation
+            && self.since == other.since
+    }
+}
+
+impl<I: IndexTag> Eq for PendingCatalog<I> {}
+
+impl<I: IndexTag> std::hash::Hash for PendingCatalog<I> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.relay.hash(state);
+        self.generation.hash(state);
+        self.since.hash(state);
+    }
+}
+
+/// What the review of a [`PendingCatalog`] decided. Records of blocked sources
+/// are left out and the sources muted, trust only applies to known users.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SourceDecisions {
+    pub blocked: Vec<PublicKey>,
+    pub trust: Vec<(PublicKey, TrustLevel)>,
+}
+
+/// What a catalog snapshot added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CatalogImport {
+    pub indexes: usize,
+    pub contents: usize,
+    /// Records left out because their source was blocked
+    pub blocked: usize,
+    /// Generation the changes were since, `None` for a full snapshot
+    pub since: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogSnapshot {
+    pub relay: PublicKey,
+    /// [`IndexTag::TAG`] of the catalog inside
+    pub tag: String,
+    pub generation: u64,
+    /// Generation the catalog holds changes since, `None` if it's complete
+    pub since: Option<u64>,
+    pub created_at: Timestamp,
+    /// zstd compressed [`Catalog`]
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+    signature: Signature,
+}
+
+impl CatalogSnapshot {
+    fn sign_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.relay.as_bytes().to_vec();
+        bytes.extend(self.tag.as_bytes());
+        bytes.push(0);
+        bytes.extend(self.generation.to_le_bytes());
+        match self.since {
+            Some(since) => {
+                bytes.push(1);
+                bytes.extend(since.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes.extend(self.created_at.to_bytes());
+        bytes.extend(Hash::digest(&self.data).inner());
+        bytes
+    }
+
+    pub fn new_signed<I: IndexTag>(
+        catalog: &Catalog<I>,
+        generation: u64,
+        since: Option<u64>,
+        priv_key: &PrivateKey,
+    ) -> Result<Self, EncodeError> {
+        let encoded = postcard::to_allocvec(catalog).map_err(|_| EncodeError::InvalidData)?;
+        let data = zstd::encode_all(encoded.as_slice(), COMPRESSION_LEVEL)?;
+
+        let mut snapshot = Self {
+            relay: priv_key.public_key(),
+            tag: I::TAG.to_string(),
+            generation,
+            since,
+            created_at: Timestamp::now(),
+            data,
+            signature: Signature::empty(),
+        };
+        snapshot.signature = priv_key.sign(&snapshot.sign_bytes());
+
+        Ok(snapshot)
+    }
+
+    pub fn verify(&self) -> bool {
+        self.relay.verify(&self.sign_bytes(), &self.signature)
+    }
+
+    /// Unpacks the catalog, the records inside still have to be verified one
+    /// by one
+    pub fn open<I: IndexTag>(&self) -> Result<Catalog<I>, DecodeError> {
+        if self.tag != I::TAG {
+            return Err(DecodeError::InvalidData);
+        }
+
+        let mut encoded = Vec::new();
+        zstd::Decoder::new(self.data.as_slice())?
+            .take(MAX_SNAPSHOT_SIZE + 1)
+            .read_to_end(&mut encoded)?;
+        if encoded.len() as u64 > MAX_SNAPSHOT_SIZE {
+            return Err(DecodeError::InvalidData);
+        }
+
+        postcard::from_bytes(&encoded).map_err(|_| DecodeError::InvalidData)
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    /// Generation new snapshots are made at, a new one is started if the last
+    /// is older than [`GENERATION_INTERVAL`]
+    pub async fn current_catalog_generation(&self) -> Result<CatalogGeneration, DatabaseError> {
+        let query = format!(
+            "SELECT * FROM {} ORDER BY generation DESC LIMIT 1;",
+            CatalogGeneration::TABLE_NAME
+        );
+        let last: Option<CatalogGeneration> = self.db.query(query).await?.take(0)?;
+
+        let now = self.now();
+        if let Some(last) = &last
+            && last.created_at > now - GENERATION_INTERVAL
+        {
+            return Ok(last.clone());
+        }
+
+        let generation = CatalogGeneration {
+            generation: last.map_or(1, |g| g.generation + 1),
+            created_at: now,
+        };
+        let _: Option<Value> = self
+            .db
+            .upsert((
+                CatalogGeneration::TABLE_NAME,
+                generation.generation.to_string(),
+            ))
+            .content(generation.clone())
+            .await?;
+
+        Ok(generation)
+    }
+
+    pub async fn get_catalog_generation(
+        &self,
+        generation: u64,
+    ) -> Result<Option<CatalogGeneration>, DatabaseError> {
+        let generation: Option<CatalogGeneration> = self
+            .db
+            .select((CatalogGeneration::TABLE_NAME, generation.to_string()))
+            .await?;
+        Ok(generation)
+    }
+
+    /// Everything shareable, or only what was stored after `since` was started
+    pub async fn build_catalog<I: IndexTag>(
+        &self,
+        since: Option<&CatalogGeneration>,
+    ) -> Result<Catalog<I>, DatabaseError> {
+        let Some(since) = since else {
+            let content_since = self.now() - SNAPSHOT_CONTENT_WINDOW;
+            return Ok(Catalog {
+                indexes: self.index().get_all_indexes::<I>(None, None).await?,
+                contents: self.index().get_recent_contents::<I>(content_since).await?,
+            });
+        };
+
+        let topics = |event_type: EventType| async move {
+            let topics: Vec<Topic> = self
+                .db
+                .query(
+                    "SELECT VALUE topic FROM events WHERE timestamp >= $timestamp AND event_type = $event_type;",
+                )
+                .bind(("timestamp", since.created_at))
+                .bind(("event_type", event_type))
+                .await?
+                .take(0)?;
+            Ok::<_, DatabaseError>(topics)
+        };
+
+        let hashes: Vec<Hash> = topics(I::EVENT_TYPE)
+            .await?
+            .into_iter()
+            .map(|t| Hash::new(t.to_inner()))
+            .collect();
+        let signatures: Vec<Signature> = topics(I::CONTENT_EVENT_TYPE)
+            .await?
+            .into_iter()
+            // SAFETY: Content topics are made from signatures
+            .map(|t| unsafe { Signature::from_bytes_unchecked(t.to_inner()) })
+            .collect();
+
+        Ok(Catalog {
+            indexes: self.index().get_indexes::<I>(&hashes).await?,
+            contents: self.index().get_contents::<I>(&signatures).await?,
+        })
+    }
+
+    /// Who the records of `catalog` come from, with how many each
+    pub async fn catalog_sources<I: IndexTag>(
+        &self,
+        catalog: &Catalog<I>,
+    ) -> Result<Vec<CatalogSource>, DatabaseError> {
+        let mut counts: HashMap<PublicKey, (usize, usize)> = HashMap::new();
+        for index in &catalog.indexes {
+            counts.entry(index.source().clone()).or_default().0 += 1;
+        }
+        for content in &catalog.contents {
+            counts.entry(content.poster().clone()).or_default().1 += 1;
+        }
+
+        let mut sources = Vec::with_capacity(counts.len());
+        for (key, (indexes, contents)) in counts {
+            let trust = self.user().get_user(&key).await?.map(|u| *u.trust());
+            sources.push(CatalogSource {
+                name: self.user().get_display_name(&key).await?,
+                key,
+                trust,
+                indexes,
+                contents,
+            });
+        }
+        sources.sort_by_key(|s| std::cmp::Reverse(s.indexes + s.contents));
+
+        Ok(sources)
+    }
+
+    /// Applies the review of a fetched catalog, then stores what's left of it
+    pub async fn land_catalog<I: IndexTag>(
+        &self,
+        pending: PendingCatalog<I>,
+        decisions: &SourceDecisions,
+    ) -> Result<CatalogImport, DatabaseError> {
+        for (key, trust) in &decisions.trust {
+            if let Some(user) = self.user().get_user(key).await? {
+                self.user()
+                    .change_trust(user, *trust, "Set while reviewing a catalog")
+                    .await?;
+            }
+        }
+        for key in &decisions.blocked {
+            self.user()
+                .set_muted(key.clone(), true, "Blocked while reviewing a catalog")
+                .await?;
+        }
+
+        let mut imported = CatalogImport {
+            indexes: 0,
+            contents: 0,
+            blocked: 0,
+            since: pending.since,
+        };
+
+        for index in pending.catalog.indexes {
+            if decisions.blocked.contains(index.source()) {
+                imported.blocked += 1;
+                continue;
+            }
+            self.index().add_index(index).await?;
+            imported.indexes += 1;
+        }
+
+        for content in pending.catalog.contents {
+            if decisions.blocked.contains(content.poster()) {
+                imported.blocked += 1;
+                continue;
+            }
+            let source = ContentSource::from_content(
+                &content,
+                pending.relay_address.clone(),
+                SourceKind::Sent,
+            );
+            self.index().add_content(content).await?;
+            self.add_content_source(source).await?;
+            imported.contents += 1;
+        }
+
+        self.set_catalog_follow(CatalogFollow {
+            relay: pending.relay,
+            generation: pending.generation,
+        })
+        .await?;
+
+        Ok(imported)
+    }
+
+    pub async fn get_catalog_follow(
+        &self,
+        relay: &PublicKey,
+    ) -> Result<Option<CatalogFollow>, DatabaseError> {
+        let follow: Option<CatalogFollow> = self
+            .db
+            .select((CatalogFollow::TABLE_NAME, relay.to_base64()))
+            .await?;
+        Ok(follow)
+    }
+
+    pub async fn set_catalog_follow(&self, follow: CatalogFollow) -> Result<(), DatabaseError> {
+        let _: Option<Value> = self
+            .db
+            .upsert((CatalogFollow::TABLE_NAME, follow.relay.to_base64()))
+            .content(follow)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Catalog, CatalogGeneration, CatalogSnapshot, PendingCatalog, SourceDecisions};
+    use crate::{
+        db::{Repositories, index::tags::MangaTag, user::I2PAddress},
+        server::fixtures::index,
+        types::{PrivateKey, Timestamp},
+    };
+
+    #[test]
+    fn snapshot_round_trip() {
+        let relay = PrivateKey::new();
+        let index = index("Bootstrapped", &PrivateKey::new());
+        let catalog = Catalog {
+            indexes: vec![index.clone()],
+            contents: vec![],
+        };
+
+        let mut snapshot = CatalogSnapshot::new_signed(&catalog, 1, None, &relay).unwrap();
+        assert!(snapshot.verify());
+        assert_eq!(snapshot.open::<MangaTag>().unwrap().indexes, vec![index]);
+
+        snapshot.since = Some(0);
+        assert!(!snapshot.verify());
+    }
+
+    #[tokio::test]
+    async fn differential_catalog_only_has_later_records() {
+        let repo = Repositories::in_memory().await;
+        let (old, new) = (
+            index("Old", &PrivateKey::new()),
+            index("New", &PrivateKey::new()),
+        );
+
+        repo.index().add_index(old.clone()).await.unwrap();
+        let generation = CatalogGeneration {
+            generation: 1,
+            created_at: Timestamp::now() + 1,
+        };
+        // Events are timed in seconds
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        repo.index().add_index(new.clone()).await.unwrap();
+
+        let full = repo.build_catalog::<MangaTag>(None).await.unwrap();
+        assert_eq!(full.indexes.len(), 2);
+
+        let diff = repo
+            .build_catalog::<MangaTag>(Some(&generation))
+            .await
+            .unwrap();
+        assert_eq!(diff.indexes, vec![new]);
+    }
+
+    #[tokio::test]
+    async fn recent_generation_is_reused() {
+        let repo = Repositories::in_memory().await;
+
+        let first = repo.current_catalog_generation().await.unwrap();
+        let second = repo.current_catalog_generation().await.unwrap();
+
+        assert_eq!(first.generation, 1);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn blocked_sources_are_left_out() {
+        let repo = Repositories::in_memory().await;
+        let (kept, blocked) = (
+            index("Kept", &PrivateKey::new()),
+            index("Blocked", &PrivateKey::new()),
+        );
+        let catalog = Catalog {
+            indexes: vec![kept.clone(), blocked.clone()],
+            contents: vec![],
+        };
+
+        let sources = repo.catalog_sources(&catalog).await.unwrap();
+        assert_eq!(sources.len(), 2);
+        assert!(sources.iter().all(|s| s.trust.is_none() && s.indexes == 1));
+
+        let pending = PendingCatalog {
+            relay: PrivateKey::new().public_key(),
+            relay_address: I2PAddress::new("relay.b32.i2p"),
+            generation: 1,
+            since: None,
+            catalog,
+            sources,
+        };
+        let decisions = SourceDecisions {
+            blocked: vec![blocked.source().clone()],
+            trust: vec![],
+        };
+        let imported = repo.land_catalog(pending, &decisions).await.unwrap();
+
+        assert_eq!((imported.indexes, imported.blocked), (1, 1));
+        let stored = repo
+            .index()
+            .get_all_indexes::<MangaTag>(None, None)
+            .await
+            .unwrap();
+        assert_eq!(stored, vec![kept]);
+        assert!(repo.user().is_muted(blocked.source()).await.unwrap());
+    }
+}
@@ -0,0 +1,60 @@
+use tokio::sync::broadcast;
+
+/// What kind of data was written to the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataKind {
+    Users,
+    Indexes,
+    Contents,
+    Follows,
+    Posts,
+    TorrentLinks,
+    Suppressions,
+    History,
+    AuditLog,
+}
+
+impl DataKind {
+    pub const ALL: [DataKind; 9] = [
+        DataKind::Users,
+        DataKind::Indexes,
+        DataKind::Contents,
+        DataKind::Follows,
+        DataKind::Posts,
+        DataKind::TorrentLinks,
+        DataKind::Suppressions,
+        DataKind::History,
+        DataKind::AuditLog,
+    ];
+}
+
+/// Broadcasts a [`DataKind`] every time the repositories write something, so
+/// whoever shows that data can refresh it instead of polling.
+#[derive(Debug, Clone)]
+pub struct DataChanges {
+    tx: broadcast::Sender<DataKind>,
+}
+
+impl DataChanges {
+    const CAPACITY: usize = 64;
+
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(Self::CAPACITY);
+        Self { tx }
+    }
+
+    pub fn notify(&self, kind: DataKind) {
+        // No one listening is fine, happens on the server side and in tests
+        let _ = self.tx.send(kind);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DataKind> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for DataChanges {
+    fn default() -> Self {
+        Self::new()
+    }
+}
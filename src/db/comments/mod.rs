@@ -3,7 +3,8 @@ use surrealdb::types::SurrealValue;
 
 use crate::{
     db::{Timestamp, ToBytes},
-    types::{PublicKey, Signature, Topic},
+    errors::PostError,
+    types::{Link, PublicKey, Signature, Topic},
 };
 
 // ==================== End Imports ====================
@@ -37,8 +38,23 @@ impl std::hash::Hash for Post {
     }
 }
 
+impl PartialEq for Post {
+    fn eq(&self, other: &Self) -> bool {
+        self.signature == other.signature
+    }
+}
+
+impl Eq for Post {}
+
 impl Post {
     pub const TABLE_NAME: &str = "posts";
+    /// Longest content a post can have, in characters
+    pub const MAX_LENGTH: usize = 4000;
+    /// Posts taken from a single source per hour when syncing, the rest of what
+    /// it sends is dropped
+    pub const MAX_PER_HOUR: usize = 30;
+    /// Longest excerpt of a post put in a quote reply, in characters
+    pub const QUOTE_EXCERPT_CHARS: usize = 200;
 
     pub fn new(
         content: String,
@@ -61,7 +77,15 @@ impl Post {
         timestamp: Timestamp,
         topic: Topic,
         priv_key: &crate::types::PrivateKey,
-    ) -> Self {
+    ) -> Result<Self, PostError> {
+        let length = content.chars().count();
+        if length > Self::MAX_LENGTH {
+            return Err(PostError::TooLong {
+                allowed: Self::MAX_LENGTH,
+                actual: length,
+            });
+        }
+
         let mut comment = Self::new(
             content,
             timestamp,
@@ -70,7 +94,7 @@ impl Post {
             Signature::empty(),
         );
         comment.sign(priv_key);
-        comment
+        Ok(comment)
     }
 
     fn sign_bytes(&self) -> Vec<u8> {
@@ -89,4 +113,227 @@ impl Post {
         let to_verify = self.sign_bytes();
         self.source.verify(&to_verify, &self.signature)
     }
+
+    pub fn is_within_limits(&self) -> bool {
+        self.content.chars().count() <= Self::MAX_LENGTH
+    }
+
+    /// Handles mentioned in the post, without the `@` and without repeats
+    pub fn mentions(&self) -> Vec<&str> {
+        let mut mentions = vec![];
+        let segments = post_segments(&self.content)
+            .into_iter()
+            .flat_map(|s| match s {
+                PostSegment::Spoiler(hidden) => mention_segments(hidden),
+                s => vec![s],
+            });
+        for segment in segments {
+            if let PostSegment::Mention(handle) = segment
+                && !mentions.contains(&handle)
+            {
+                mentions.push(handle);
+            }
+        }
+        mentions
+    }
+
+    /// Links in the post outside of spoilers, without repeats
+    pub fn links(&self) -> Vec<Link> {
+        let mut links = vec![];
+        for segment in post_segments(&self.content) {
+            if let PostSegment::Text(text) = segment {
+                for link in Link::find_all(text) {
+                    if !links.contains(&link) {
+                        links.push(link);
+                    }
+                }
+            }
+        }
+        links
+    }
+
+    /// Start of a reply to this post: its content quoted, without the quote
+    /// it opened with and with spoilers blanked out, then a mention of its
+    /// author
+    pub fn quote_reply(&self) -> String {
+        let (_, body) = split_quote(&self.content);
+        let excerpt: String = post_segments(body)
+            .into_iter()
+            .map(|s| match s {
+                PostSegment::Text(text) => text.to_string(),
+                PostSegment::Mention(handle) => format!("@{}", handle),
+                PostSegment::Spoiler(_) => "[spoiler]".to_string(),
+            })
+            .collect();
+        let excerpt = excerpt.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let mut quoted: String = excerpt.chars().take(Self::QUOTE_EXCERPT_CHARS).collect();
+        if quoted.len() < excerpt.len() {
+            quoted.push_str("...");
+        }
+        format!("> {}\n@{} ", quoted, self.source.fingerprint())
+    }
+}
+
+/// Splits the lines starting with `>` at the top of a post from the rest,
+/// the quoted lines are returned without their `>`
+pub fn split_quote(content: &str) -> (Vec<&str>, &str) {
+    let mut quoted = vec![];
+    let mut rest = content;
+    while let Some(line) = rest.strip_prefix('>') {
+        let (line, next) = line.split_once('\n').unwrap_or((line, ""));
+        quoted.push(line.trim());
+        rest = next;
+    }
+    (quoted, rest)
+}
+
+/// Piece of a post's content. A mention is an `@` at the start of the post or
+/// after whitespace followed by a key fingerprint or a petname, so petnames
+/// with spaces or punctuation in them can't be mentioned. Spoilers are wrapped
+/// in `||`, an unclosed `||` is left as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostSegment<'a> {
+    Text(&'a str),
+    /// Handle without the `@`
+    Mention(&'a str),
+    /// Text between the `||`, mentions in it aren't split out
+    Spoiler(&'a str),
+}
+
+fn is_handle_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_'
+}
+
+pub fn post_segments(content: &str) -> Vec<PostSegment<'_>> {
+    let mut segments = vec![];
+    let mut rest = content;
+
+    while let Some(start) = rest.find("||") {
+        let Some(len) = rest[start + 2..].find("||") else {
+            break;
+        };
+
+        segments.extend(mention_segments(&rest[..start]));
+        segments.push(PostSegment::Spoiler(&rest[start + 2..start + 2 + len]));
+        rest = &rest[start + 2 + len + 2..];
+    }
+    segments.extend(mention_segments(rest));
+
+    segments
+}
+
+fn mention_segments(content: &str) -> Vec<PostSegment<'_>> {
+    let mut segments = vec![];
+    let mut text_start = 0;
+    let mut previous: Option<char> = None;
+
+    for (i, c) in content.char_indices() {
+        let at_boundary = previous.is_none_or(char::is_whitespace);
+        previous = Some(c);
+        if c != '@' || !at_boundary {
+            continue;
+        }
+
+        let rest = &content[i + 1..];
+        let len = rest.find(|c| !is_handle_char(c)).unwrap_or(rest.len());
+        if len == 0 {
+            continue;
+        }
+
+        if text_start < i {
+            segments.push(PostSegment::Text(&content[text_start..i]));
+        }
+        segments.push(PostSegment::Mention(&rest[..len]));
+        text_start = i + 1 + len;
+    }
+
+    if text_start < content.len() {
+        segments.push(PostSegment::Text(&content[text_start..]));
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Post, PostSegment, post_segments, split_quote};
+    use crate::types::{Hash, Link, PrivateKey, Timestamp, Topic};
+
+    #[test]
+    fn mentions_need_a_boundary() {
+        assert_eq!(
+            post_segments("@a1b2-c3d4-e5f6 thanks, mail me at me@example.i2p or @Kiri."),
+            vec![
+                PostSegment::Mention("a1b2-c3d4-e5f6"),
+                PostSegment::Text(" thanks, mail me at me@example.i2p or "),
+                PostSegment::Mention("Kiri"),
+                PostSegment::Text("."),
+            ]
+        );
+        assert_eq!(post_segments("@ alone"), vec![PostSegment::Text("@ alone")]);
+    }
+
+    #[test]
+    fn spoilers_need_closing() {
+        assert_eq!(
+            post_segments("so ||@a1b2-c3d4-e5f6 dies|| in the end || or not"),
+            vec![
+                PostSegment::Text("so "),
+                PostSegment::Spoiler("@a1b2-c3d4-e5f6 dies"),
+                PostSegment::Text(" in the end || or not"),
+            ]
+        );
+    }
+
+    #[test]
+    fn links_in_spoilers_stay_hidden() {
+        let (shown, hidden) = (
+            Link::Index(Hash::digest(b"shown")),
+            Link::Index(Hash::digest(b"hidden")),
+        );
+        let priv_key = PrivateKey::new();
+        let post = Post::new_signed(
+            format!("read {} ||then {}||", shown, hidden),
+            Timestamp::new(0),
+            Topic::from_bytes([0; 64]),
+            &priv_key,
+        )
+        .unwrap();
+
+        assert_eq!(post.links(), vec![shown]);
+    }
+
+    #[test]
+    fn quote_replies_hide_spoilers_and_older_quotes() {
+        let priv_key = PrivateKey::new();
+        let post = Post::new_signed(
+            format!(
+                "> first\n> second\nso ||it ends||\nwell {}",
+                "a".repeat(300)
+            ),
+            Timestamp::new(0),
+            Topic::from_bytes([0; 64]),
+            &priv_key,
+        )
+        .unwrap();
+
+        let reply = post.quote_reply();
+        let (quoted, rest) = split_quote(&reply);
+
+        assert_eq!(quoted.len(), 1);
+        assert!(quoted[0].starts_with("so [spoiler] well aaa"));
+        assert!(quoted[0].ends_with("..."));
+        assert_eq!(quoted[0].chars().count(), Post::QUOTE_EXCERPT_CHARS + 3);
+        assert_eq!(rest, format!("@{} ", priv_key.public_key().fingerprint()));
+    }
+
+    #[test]
+    fn only_leading_lines_are_quoted() {
+        assert_eq!(
+            split_quote(">one\n> two\nreply\n> not a quote"),
+            (vec!["one", "two"], "reply\n> not a quote")
+        );
+        assert_eq!(split_quote("no quote"), (vec![], "no quote"));
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use const_format::formatcp;
 use fastbloom::BloomFilter;
@@ -9,12 +9,14 @@ use tracing::info;
 use crate::{
     db::{
         BLOOM_FILTER_FALSE_POSITIVE_RATE, PaginateResponse, Repositories,
+        changes::DataKind,
         comments::{Post, Topic},
+        create_error,
         event::{Event, EventType, insert_event},
         user::User,
     },
     errors::DatabaseError,
-    types::{Signature, Timestamp},
+    types::{PublicKey, Signature, Timestamp},
 };
 
 #[skerry]
@@ -22,7 +24,7 @@ impl Repositories {
     pub async fn add_post(&self, post: Post) -> Result<Post, DatabaseError> {
         let transaction = self.db.clone().begin().await?;
 
-        let timestamp = Timestamp::now();
+        let timestamp = self.now();
 
         let event = Event {
             timestamp,
@@ -32,19 +34,29 @@ impl Repositories {
 
         insert_event(vec![event], &transaction).await?;
 
+        let id = post.signature.as_base64();
         let result: Option<Post> = transaction
-            .create((Post::TABLE_NAME, post.signature.as_base64()))
+            .create((Post::TABLE_NAME, id.clone()))
             .content(post)
-            .await?;
+            .await
+            .map_err(create_error(Post::TABLE_NAME, &id))?;
 
         let post = match result {
             Some(post) => post,
-            None => return Err(DatabaseError::Unknown),
+            None => {
+                return Err(DatabaseError::UnexpectedRecordCount {
+                    table: Post::TABLE_NAME.to_string(),
+                    expected: 1,
+                    actual: 0,
+                });
+            }
         };
 
         transaction.commit().await?;
         info!("Created post: {}", post.signature.as_base64());
 
+        self.notify(DataKind::Posts);
+
         Ok(post)
     }
 
@@ -105,15 +117,211 @@ impl Repositories {
                 values: (r.data, HashSet::from_iter(r.users)),
                 total: r.total,
             }),
-            None => Err(DatabaseError::Unknown),
+            None => Err(DatabaseError::EmptyResponse {
+                table: Post::TABLE_NAME.to_string(),
+            }),
+        }
+    }
+
+    /// Posts of `topic` matching `query` through the full-text index on their
+    /// content, oldest first like [`Self::get_posts_by_topic`]
+    pub async fn search_posts(
+        &self,
+        topic: Topic,
+        query: String,
+        take: usize,
+        skip: usize,
+    ) -> Result<PaginateResponse<Vec<Post>>, DatabaseError> {
+        const QUERY: &str = formatcp!(
+            "
+            LET $rows = (
+                SELECT *
+                FROM {0}
+                WHERE topic = $topic AND content @@ $query
+                ORDER BY timestamp ASC
+                LIMIT $take
+                START $skip
+            );
+
+            {{
+                total: count(
+                    SELECT *
+                    FROM {0}
+                    WHERE topic = $topic AND content @@ $query
+                ),
+                data: $rows
+            }}
+            ",
+            Post::TABLE_NAME
+        );
+
+        #[derive(SurrealValue)]
+        struct Response {
+            total: usize,
+            data: Vec<Post>,
+        }
+
+        let result: Option<Response> = self
+            .db
+            .query(QUERY)
+            .bind(("topic", topic))
+            .bind(("query", query))
+            .bind(("take", take))
+            .bind(("skip", skip))
+            .await?
+            .take(1)?;
+
+        match result {
+            Some(r) => Ok(PaginateResponse {
+                values: r.data,
+                total: r.total,
+            }),
+            None => Err(DatabaseError::EmptyResponse {
+                table: Post::TABLE_NAME.to_string(),
+            }),
+        }
+    }
+
+    /// Posts from others that were stored after `since` and mention `pub_key`
+    /// by its fingerprint, along with when the newest post looked at was stored
+    /// so the next call can start from there
+    pub async fn posts_mentioning(
+        &self,
+        pub_key: &PublicKey,
+        since: Timestamp,
+    ) -> Result<(Vec<Post>, Timestamp), DatabaseError> {
+        #[derive(SurrealValue)]
+        struct Stored {
+            topic: Topic,
+            timestamp: Timestamp,
         }
+
+        let stored: Vec<Stored> = self
+            .db
+            .query(
+                "SELECT topic, timestamp FROM events WHERE timestamp > $timestamp AND event_type = $event_type;",
+            )
+            .bind(("timestamp", since))
+            .bind(("event_type", EventType::Post))
+            .await?
+            .take(0)?;
+        let checked = stored.iter().map(|s| s.timestamp).max().unwrap_or(since);
+
+        let fingerprint = pub_key.fingerprint();
+        let mut posts = vec![];
+        for s in stored {
+            // SAFETY: Post topics are made from signatures
+            let signature = unsafe { Signature::from_bytes_unchecked(s.topic.to_inner()) };
+            let post: Option<Post> = self
+                .db
+                .select((Post::TABLE_NAME, signature.as_base64()))
+                .await?;
+
+            if let Some(post) = post
+                && &post.source != pub_key
+                && post
+                    .mentions()
+                    .iter()
+                    .any(|m| m.eq_ignore_ascii_case(&fingerprint))
+            {
+                posts.push(post);
+            }
+        }
+
+        Ok((posts, checked))
+    }
+
+    pub async fn count_posts_by_topic(&self, topic: Topic) -> Result<usize, DatabaseError> {
+        const QUERY: &str = formatcp!(
+            "SELECT VALUE count() FROM {0} WHERE topic = $topic GROUP ALL",
+            Post::TABLE_NAME
+        );
+
+        let count: Option<usize> = self.db.query(QUERY).bind(("topic", topic)).await?.take(0)?;
+
+        Ok(count.unwrap_or_default())
+    }
+
+    /// Post counts of each of `topics` in a single query, topics without posts
+    /// are left out
+    pub async fn count_posts_by_topics(
+        &self,
+        topics: Vec<Topic>,
+    ) -> Result<HashMap<Topic, usize>, DatabaseError> {
+        const QUERY: &str = formatcp!(
+            "SELECT topic, count() AS posts FROM {0} WHERE topic IN $topics GROUP BY topic",
+            Post::TABLE_NAME
+        );
+
+        if topics.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        #[derive(SurrealValue)]
+        struct Row {
+            topic: Topic,
+            posts: usize,
+        }
+
+        let rows: Vec<Row> = self
+            .db
+            .query(QUERY)
+            .bind(("topics", topics))
+            .await?
+            .take(0)?;
+
+        Ok(rows.into_iter().map(|r| (r.topic, r.posts)).collect())
+    }
+
+    /// How many posts `source` has that claim to have been made since `since`
+    pub async fn count_posts_by_source(
+        &self,
+        source: &PublicKey,
+        since: Timestamp,
+    ) -> Result<usize, DatabaseError> {
+        const QUERY: &str = formatcp!(
+            "SELECT VALUE count() FROM {0} WHERE source = $source AND timestamp >= $since GROUP ALL",
+            Post::TABLE_NAME
+        );
+
+        let count: Option<usize> = self
+            .db
+            .query(QUERY)
+            .bind(("source", source.clone()))
+            .bind(("since", since))
+            .await?
+            .take(0)?;
+
+        Ok(count.unwrap_or_default())
+    }
+
+    /// Newest `take` posts signed by `source`
+    pub async fn get_posts_by_source(
+        &self,
+        source: &PublicKey,
+        take: usize,
+    ) -> Result<Vec<Post>, DatabaseError> {
+        const QUERY: &str = formatcp!(
+            "SELECT * FROM {0} WHERE source = $source ORDER BY timestamp DESC LIMIT $take",
+            Post::TABLE_NAME
+        );
+
+        let posts: Vec<Post> = self
+            .db
+            .query(QUERY)
+            .bind(("source", source.clone()))
+            .bind(("take", take))
+            .await?
+            .take(0)?;
+
+        Ok(posts)
     }
 
     pub async fn make_posts_filter(
         &self,
         topic: Topic,
         timestamp: Option<Timestamp>,
-    ) -> Result<BloomFilter, e![Surreal]> {
+    ) -> Result<BloomFilter, DatabaseError> {
         let query_str: String = format!(
             "
                 SELECT * FROM {0} WHERE topic = $topic {1};
@@ -136,12 +344,12 @@ impl Repositories {
 
         let mut filter = BloomFilter::with_false_pos(BLOOM_FILTER_FALSE_POSITIVE_RATE)
             .expected_items(result.len());
-        filter.insert(&result);
+        filter.insert_all(&result);
 
         Ok(filter)
     }
 
-    pub async fn get_posts(&self, signatures: &[Signature]) -> Result<Vec<Post>, e![Surreal]> {
+    pub async fn get_posts(&self, signatures: &[Signature]) -> Result<Vec<Post>, DatabaseError> {
         let ids: Vec<RecordId> = signatures
             .iter()
             .map(|s| RecordId::new(Post::TABLE_NAME, s.as_base64()))
@@ -198,4 +406,135 @@ impl Repositories {
 
         Ok(filtered_posts)
     }
+
+    /// A page of the posts of `topic` made since `after`, oldest first with
+    /// the signature breaking ties so pages never overlap
+    pub async fn get_posts_page(
+        &self,
+        topic: Topic,
+        after: Option<Timestamp>,
+        take: usize,
+        skip: usize,
+    ) -> Result<Vec<Post>, DatabaseError> {
+        const QUERY: &str = formatcp!(
+            "
+            SELECT *
+            FROM {0}
+            WHERE topic = $topic AND timestamp >= $after
+            ORDER BY timestamp ASC, id ASC
+            LIMIT $take
+            START $skip
+            ",
+            Post::TABLE_NAME
+        );
+
+        let posts: Vec<Post> = self
+            .db
+            .query(QUERY)
+            .bind(("topic", topic))
+            .bind(("after", after.unwrap_or(Timestamp::new(0))))
+            .bind(("take", take))
+            .bind(("skip", skip))
+            .await?
+            .take(0)?;
+
+        Ok(posts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{Repositories, Timestamp, comments::Post},
+        types::{PrivateKey, Topic},
+    };
+
+    #[tokio::test]
+    async fn mentions_are_found_by_fingerprint() {
+        let repo = Repositories::in_memory().await;
+        let (me, other) = (PrivateKey::new(), PrivateKey::new());
+        let topic = Topic::from_bytes([1; 64]);
+        let since = Timestamp::new(0);
+
+        for (content, author) in [
+            (
+                format!("@{} what do you think?", me.public_key().fingerprint()),
+                &other,
+            ),
+            ("nobody mentioned here".to_string(), &other),
+            (
+                format!("@{} talking to myself", me.public_key().fingerprint()),
+                &me,
+            ),
+        ] {
+            let post = Post::new_signed(content, Timestamp::now(), topic.clone(), author).unwrap();
+            repo.add_post(post).await.unwrap();
+        }
+
+        let (mentions, checked) = repo
+            .posts_mentioning(&me.public_key(), since)
+            .await
+            .unwrap();
+
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].source, other.public_key());
+        assert!(checked > since);
+    }
+
+    #[tokio::test]
+    async fn posts_are_counted_per_topic() {
+        let repo = Repositories::in_memory().await;
+        let priv_key = PrivateKey::new();
+        let topics = [1, 2, 3].map(|b| Topic::from_bytes([b; 64]));
+
+        for (i, topic) in [&topics[0], &topics[0], &topics[1]].into_iter().enumerate() {
+            let post = Post::new_signed(
+                format!("post {}", i),
+                Timestamp::now(),
+                topic.clone(),
+                &priv_key,
+            )
+            .unwrap();
+            repo.add_post(post).await.unwrap();
+        }
+
+        let counts = repo.count_posts_by_topics(topics.to_vec()).await.unwrap();
+
+        assert_eq!(counts.get(&topics[0]), Some(&2));
+        assert_eq!(counts.get(&topics[1]), Some(&1));
+        assert_eq!(counts.get(&topics[2]), None);
+    }
+
+    #[tokio::test]
+    async fn search_only_matches_the_topic() {
+        let repo = Repositories::in_memory().await;
+        let priv_key = PrivateKey::new();
+        let (topic, other) = (Topic::from_bytes([1; 64]), Topic::from_bytes([2; 64]));
+
+        for (content, topic) in [
+            ("The translation of chapter 3 is great", &topic),
+            ("Anyone seeding volume 2?", &topic),
+            ("Chapter 3 translation here too", &other),
+        ] {
+            let post = Post::new_signed(
+                content.to_string(),
+                Timestamp::now(),
+                topic.clone(),
+                &priv_key,
+            )
+            .unwrap();
+            repo.add_post(post).await.unwrap();
+        }
+
+        let found = repo
+            .search_posts(topic, "translation".to_string(), 10, 0)
+            .await
+            .unwrap();
+
+        assert_eq!(found.total, 1);
+        assert_eq!(
+            found.values[0].content,
+            "The translation of chapter 3 is great"
+        );
+    }
 }
@@ -0,0 +1,259 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Repositories, index::content::Content, index::tags::IndexTag, user::I2PAddress},
+    errors::DatabaseError,
+    storage::path_safe_base64,
+    types::{Hash, Signature, Timestamp},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub enum SourceKind {
+    /// The peer sent us the record itself
+    Sent,
+    /// The peer only told us it has the content
+    Announced,
+}
+
+/// Remembers which peers had a content, so it can be asked for from whoever is
+/// most likely to have it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub struct ContentSource {
+    #[surreal(rename = "id")]
+    pub id: String,
+    pub content: Signature,
+    pub index: Hash,
+    pub peer: I2PAddress,
+    pub kind: SourceKind,
+    pub last_seen: Timestamp,
+}
+
+impl ContentSource {
+    pub const TABLE_NAME: &'static str = "content_sources";
+
+    pub fn new(
+        content: Signature,
+        index: Hash,
+        peer: I2PAddress,
+        kind: SourceKind,
+    ) -> ContentSource {
+        ContentSource {
+            // One record per content and peer, seeing it again only refreshes it
+            id: format!(
+                "{}@{}",
+                path_safe_base64(&content.as_base64()),
+                peer.inner()
+            ),
+            content,
+            index,
+            peer,
+            kind,
+            last_seen: Timestamp::now(),
+        }
+    }
+
+    pub fn from_content<I: IndexTag>(
+        content: &Content<I>,
+        peer: I2PAddress,
+        kind: SourceKind,
+    ) -> ContentSource {
+        ContentSource::new(
+            content.signature().clone(),
+            content.index_hash().clone(),
+            peer,
+            kind,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, SurrealValue)]
+pub struct PeerAvailability {
+    pub peer: I2PAddress,
+    /// How many contents of the index the peer had
+    pub count: usize,
+}
+
+/// How many distinct peers had each index and content, a rough idea of how
+/// widely it has spread on the network
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Popularity {
+    pub indexes: HashMap<Hash, usize>,
+    pub contents: HashMap<Signature, usize>,
+}
+
+impl Popularity {
+    pub fn index(&self, index: &Hash) -> usize {
+        self.indexes.get(index).copied().unwrap_or_default()
+    }
+
+    pub fn content(&self, content: &Signature) -> usize {
+        self.contents.get(content).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn add_content_source(&self, source: ContentSource) -> Result<(), DatabaseError> {
+        self.add_content_sources(vec![source]).await
+    }
+
+    /// Records all of `sources` in a single write
+    pub async fn add_content_sources(
+        &self,
+        sources: Vec<ContentSource>,
+    ) -> Result<(), DatabaseError> {
+        if sources.is_empty() {
+            return Ok(());
+        }
+
+        // A peer that sent the record is a better source than one that only
+        // announced it, seeing an announcement later doesn't change that
+        let query = format!(
+            "INSERT INTO {} $sources ON DUPLICATE KEY UPDATE
+                index = $input.index,
+                last_seen = $input.last_seen,
+                kind = IF kind = $sent THEN kind ELSE $input.kind END;",
+            ContentSource::TABLE_NAME
+        );
+
+        self.db
+            .query(query)
+            .bind(("sources", sources))
+            .bind(("sent", SourceKind::Sent))
+            .await?
+            .check()?;
+
+        Ok(())
+    }
+
+    pub async fn get_content_sources(
+        &self,
+        content: &Signature,
+    ) -> Result<Vec<ContentSource>, DatabaseError> {
+        let query = format!(
+            "SELECT * FROM {} WHERE content = $content ORDER BY last_seen DESC;",
+            ContentSource::TABLE_NAME
+        );
+
+        let sources: Vec<ContentSource> = self
+            .db
+            .query(query)
+            .bind(("content", content.clone()))
+            .await?
+            .take(0)?;
+
+        Ok(sources)
+    }
+
+    /// Peers that had content of `index`, the ones that had the most first
+    pub async fn get_index_availability(
+        &self,
+        index: &Hash,
+    ) -> Result<Vec<PeerAvailability>, DatabaseError> {
+        let query = format!(
+            "SELECT peer, count() AS count FROM {} WHERE index = $index
+                GROUP BY peer ORDER BY count DESC;",
+            ContentSource::TABLE_NAME
+        );
+
+        let peers: Vec<PeerAvailability> = self
+            .db
+            .query(query)
+            .bind(("index", index.clone()))
+            .await?
+            .take(0)?;
+
+        Ok(peers)
+    }
+
+    /// Peers that relayed or announced each index and content
+    pub async fn get_popularity(&self) -> Result<Popularity, DatabaseError> {
+        let sources: Vec<ContentSource> = self.db.select(ContentSource::TABLE_NAME).await?;
+
+        let mut popularity = Popularity::default();
+        let mut index_peers = HashSet::new();
+        for source in sources {
+            // Sources are already one per content and peer
+            *popularity.contents.entry(source.content).or_default() += 1;
+            if index_peers.insert((source.index.clone(), source.peer)) {
+                *popularity.indexes.entry(source.index).or_default() += 1;
+            }
+        }
+
+        Ok(popularity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentSource, SourceKind};
+    use crate::{
+        db::{Repositories, user::I2PAddress},
+        types::{Hash, PrivateKey},
+    };
+
+    #[tokio::test]
+    async fn announcing_keeps_a_sent_source() {
+        let repo = Repositories::in_memory().await;
+        let content = PrivateKey::new().sign(b"content");
+        let index = Hash::digest(b"index");
+        let peer = I2PAddress::new("peer.b32.i2p");
+
+        repo.add_content_source(ContentSource::new(
+            content.clone(),
+            index.clone(),
+            peer.clone(),
+            SourceKind::Sent,
+        ))
+        .await
+        .unwrap();
+        repo.add_content_source(ContentSource::new(
+            content.clone(),
+            index.clone(),
+            peer.clone(),
+            SourceKind::Announced,
+        ))
+        .await
+        .unwrap();
+
+        let sources = repo.get_content_sources(&content).await.unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].kind, SourceKind::Sent);
+
+        let availability = repo.get_index_availability(&index).await.unwrap();
+        assert_eq!(availability[0].peer, peer);
+        assert_eq!(availability[0].count, 1);
+    }
+
+    #[tokio::test]
+    async fn popularity_counts_distinct_peers() {
+        let repo = Repositories::in_memory().await;
+        let key = PrivateKey::new();
+        let (first, second) = (key.sign(b"first"), key.sign(b"second"));
+        let index = Hash::digest(b"index");
+
+        for (content, peer) in [
+            (&first, "a.b32.i2p"),
+            (&second, "a.b32.i2p"),
+            (&first, "b.b32.i2p"),
+        ] {
+            repo.add_content_source(ContentSource::new(
+                content.clone(),
+                index.clone(),
+                I2PAddress::new(peer),
+                SourceKind::Sent,
+            ))
+            .await
+            .unwrap();
+        }
+
+        let popularity = repo.get_popularity().await.unwrap();
+        assert_eq!(popularity.index(&index), 2);
+        assert_eq!(popularity.content(&first), 2);
+        assert_eq!(popularity.content(&second), 1);
+        assert_eq!(popularity.index(&Hash::digest(b"other")), 0);
+    }
+}
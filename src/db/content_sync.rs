@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Repositories, user::I2PAddress},
+    errors::DatabaseError,
+    types::{Hash, Timestamp},
+};
+
+/// Last time the contents of an index were taken from a peer, the next
+/// [`GetContents`](crate::server::handler::index::GetContents) only asks for
+/// what was created since
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SurrealValue)]
+pub struct ContentSync {
+    /// Base64 of the index hash followed by the address, one per pair
+    #[surreal(rename = "id")]
+    id: String,
+    pub index_hash: Hash,
+    pub address: I2PAddress,
+    pub synced_at: Timestamp,
+}
+
+impl ContentSync {
+    pub const TABLE_NAME: &'static str = "content_syncs";
+
+    pub fn new(index_hash: Hash, address: I2PAddress, synced_at: Timestamp) -> Self {
+        Self {
+            id: format!("{}{}", index_hash.as_base64(), address.inner()),
+            index_hash,
+            address,
+            synced_at,
+        }
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    /// When the contents of `index_hash` were last taken from `address`,
+    /// `None` if they never were
+    pub async fn get_content_synced_at(
+        &self,
+        index_hash: &Hash,
+        address: &I2PAddress,
+    ) -> Result<Option<Timestamp>, DatabaseError> {
+        let id = ContentSync::new(index_hash.clone(), address.clone(), Timestamp::new(0)).id;
+        let sync: Option<ContentSync> = self.db.select((ContentSync::TABLE_NAME, id)).await?;
+
+        Ok(sync.map(|s| s.synced_at))
+    }
+
+    /// Should only be called once every content up to `synced_at` was taken
+    pub async fn record_content_sync(
+        &self,
+        index_hash: &Hash,
+        address: &I2PAddress,
+        synced_at: Timestamp,
+    ) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let sync = ContentSync::new(index_hash.clone(), address.clone(), synced_at);
+        let _: Option<Value> = self
+            .db
+            .upsert((ContentSync::TABLE_NAME, sync.id.clone()))
+            .content(sync)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{Repositories, user::I2PAddress},
+        types::{Hash, Timestamp},
+    };
+
+    #[tokio::test]
+    async fn synced_at_is_per_peer() {
+        let repo = Repositories::in_memory().await;
+        let index = Hash::new([1; 64]);
+        let (peer, other) = (
+            I2PAddress::new("peer.b32.i2p"),
+            I2PAddress::new("other.b32.i2p"),
+        );
+
+        repo.record_content_sync(&index, &peer, Timestamp::new(10))
+            .await
+            .unwrap();
+        repo.record_content_sync(&index, &peer, Timestamp::new(20))
+            .await
+            .unwrap();
+
+        let synced_at = repo.get_content_synced_at(&index, &peer).await.unwrap();
+        assert_eq!(synced_at, Some(Timestamp::new(20)));
+        let never = repo.get_content_synced_at(&index, &other).await.unwrap();
+        assert_eq!(never, None);
+    }
+}
@@ -75,7 +75,9 @@ pub async fn get_paginated_events(
         return Ok((response.data, (response.total / per_page) + 1));
     }
 
-    Err(DatabaseError::Unknown)
+    Err(DatabaseError::EmptyResponse {
+        table: "events".to_string(),
+    })
 }
 
 #[skerry]
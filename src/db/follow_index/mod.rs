@@ -1,7 +1,13 @@
+use std::fmt::{Display, Formatter};
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use surrealdb_types::SurrealValue;
 
 use crate::{
-    db::{SurrealPhantom, Timestamp, index::tags::IndexTag},
+    db::{
+        SurrealPhantom, Timestamp,
+        index::{Index, content::Content, tags::IndexTag},
+    },
     types::Hash,
 };
 
@@ -12,13 +18,45 @@ pub use surreal::IndexFollowRepository;
 #[cfg(feature = "sqlite")]
 mod sqlite;
 
+/// How new content of a followed index is announced
+#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, Hash, PartialEq, Eq, Default)]
+#[repr(u8)]
+pub enum NotificationPreference {
+    /// Shown inside the app
+    #[default]
+    Toast,
+    /// Sent to the desktop, so it's seen with the window closed
+    Desktop,
+    Silent,
+}
+
+impl NotificationPreference {
+    /// Used for selecting in UI
+    pub const ALL: [NotificationPreference; 3] = [
+        NotificationPreference::Toast,
+        NotificationPreference::Desktop,
+        NotificationPreference::Silent,
+    ];
+}
+
+impl Display for NotificationPreference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotificationPreference::Toast => write!(f, "In app"),
+            NotificationPreference::Desktop => write!(f, "Desktop"),
+            NotificationPreference::Silent => write!(f, "Nothing"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "surrealdb", derive(SurrealValue))]
 pub struct IndexFollow<T: IndexTag> {
     #[cfg_attr(feature = "surrealdb", surreal(rename = "id"))]
     index: Hash,
+    /// Content stored after this was already announced
     last_check: Timestamp,
-    notify: bool,
+    notify: NotificationPreference,
     _phantom: SurrealPhantom<T>,
 }
 
@@ -27,7 +65,7 @@ impl<T: IndexTag> IndexFollow<T> {
         format!("{}_follows", T::TAG)
     }
 
-    pub fn new(index: Hash, notify: bool, last_check: Timestamp) -> Self {
+    pub fn new(index: Hash, notify: NotificationPreference, last_check: Timestamp) -> Self {
         Self {
             index,
             last_check,
@@ -35,4 +73,20 @@ impl<T: IndexTag> IndexFollow<T> {
             _phantom: SurrealPhantom::default(),
         }
     }
+
+    pub fn index(&self) -> &Hash {
+        &self.index
+    }
+
+    pub fn notify(&self) -> NotificationPreference {
+        self.notify
+    }
+}
+
+/// Content of a followed index that arrived since the last check
+#[derive(Debug, Clone)]
+pub struct Arrival<T: IndexTag> {
+    pub index: Index<T>,
+    pub notify: NotificationPreference,
+    pub contents: Vec<Content<T>>,
 }
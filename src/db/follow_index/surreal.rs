@@ -1,22 +1,59 @@
+use std::collections::HashMap;
+
 use surrealdb::{Surreal, engine::local::Db};
+use surrealdb_types::SurrealValue;
 use tracing::info;
 
 use crate::{
     db::{
-        follow_index::IndexFollow,
+        Repositories, Timestamp,
+        changes::{DataChanges, DataKind},
+        create_error,
+        follow_index::{Arrival, IndexFollow, NotificationPreference},
         index::{Index, tags::IndexTag},
     },
     errors::DatabaseError,
-    types::Hash,
+    types::{Hash, Signature, Topic},
 };
 
+impl SurrealValue for NotificationPreference {
+    fn kind_of() -> surrealdb_types::Kind {
+        surrealdb_types::Kind::Number
+    }
+
+    fn into_value(self) -> surrealdb_types::Value {
+        (self as u8).into_value()
+    }
+
+    fn from_value(value: surrealdb_types::Value) -> Result<Self, surrealdb::Error>
+    where
+        Self: Sized,
+    {
+        // Follows used to only store whether to notify at all
+        if let surrealdb_types::Value::Bool(notify) = value {
+            return Ok(match notify {
+                true => NotificationPreference::Toast,
+                false => NotificationPreference::Silent,
+            });
+        }
+
+        let value = u8::from_value(value)?;
+        value.try_into().map_err(
+            |e: num_enum::TryFromPrimitiveError<NotificationPreference>| {
+                surrealdb::Error::internal(e.to_string())
+            },
+        )
+    }
+}
+
 pub struct IndexFollowRepository<'a> {
     db: &'a Surreal<Db>,
+    changes: &'a DataChanges,
 }
 
 impl<'a> IndexFollowRepository<'a> {
-    pub fn new(db: &'a Surreal<Db>) -> IndexFollowRepository<'a> {
-        IndexFollowRepository { db }
+    pub fn new(db: &'a Surreal<Db>, changes: &'a DataChanges) -> IndexFollowRepository<'a> {
+        IndexFollowRepository { db, changes }
     }
 }
 
@@ -25,18 +62,27 @@ impl<'a> IndexFollowRepository<'a> {
         &self,
         follow: IndexFollow<T>,
     ) -> Result<IndexFollow<T>, DatabaseError> {
+        let table = IndexFollow::<T>::table_name();
+        let id = follow.index.as_base64();
+
         let result: Option<IndexFollow<T>> = self
             .db
-            .create(IndexFollow::<T>::table_name())
+            .create(table.clone())
             .content(follow)
-            .await?;
+            .await
+            .map_err(create_error(&table, &id))?;
 
         match result {
             Some(follow) => {
                 info!("Added follow: {}", follow.index);
+                self.changes.notify(DataKind::Follows);
                 Ok(follow)
             }
-            None => Err(DatabaseError::Unknown),
+            None => Err(DatabaseError::UnexpectedRecordCount {
+                table,
+                expected: 1,
+                actual: 0,
+            }),
         }
     }
 
@@ -58,6 +104,8 @@ impl<'a> IndexFollowRepository<'a> {
             .delete((IndexFollow::<T>::table_name(), index.as_base64()))
             .await?;
 
+        self.changes.notify(DataKind::Follows);
+
         Ok(())
     }
 
@@ -86,4 +134,173 @@ impl<'a> IndexFollowRepository<'a> {
 
         Ok(result)
     }
+
+    pub async fn set_notification_preference<T: IndexTag>(
+        &self,
+        index: Hash,
+        notify: NotificationPreference,
+    ) -> Result<(), DatabaseError> {
+        self.db
+            .query("UPDATE type::record($table, $id) SET notify = $notify;")
+            .bind(("table", IndexFollow::<T>::table_name()))
+            .bind(("id", index.as_base64()))
+            .bind(("notify", notify))
+            .await?
+            .check()?;
+
+        self.changes.notify(DataKind::Follows);
+
+        Ok(())
+    }
+}
+
+impl Repositories {
+    /// Content of followed indexes stored since each follow was last checked,
+    /// grouped by index. Checking moves every follow past what was found, so
+    /// the same content is only returned once. Silent follows are moved along
+    /// too but left out of the result.
+    pub async fn take_arrivals<T: IndexTag>(&self) -> Result<Vec<Arrival<T>>, DatabaseError> {
+        let table = IndexFollow::<T>::table_name();
+        let follows: Vec<IndexFollow<T>> = self.db.select(table.as_str()).await?;
+        let Some(since) = follows.iter().map(|f| f.last_check).min() else {
+            return Ok(vec![]);
+        };
+
+        #[derive(SurrealValue)]
+        struct Stored {
+            topic: Topic,
+            timestamp: Timestamp,
+        }
+
+        let stored: Vec<Stored> = self
+            .db
+            .query(
+                "SELECT topic, timestamp FROM events WHERE timestamp > $timestamp AND event_type = $event_type;",
+            )
+            .bind(("timestamp", since))
+            .bind(("event_type", T::CONTENT_EVENT_TYPE))
+            .await?
+            .take(0)?;
+        let Some(checked) = stored.iter().map(|s| s.timestamp).max() else {
+            return Ok(vec![]);
+        };
+
+        let stored_at: HashMap<Signature, Timestamp> = stored
+            .into_iter()
+            // SAFETY: Content topics are made from signatures
+            .map(|s| {
+                (
+                    unsafe { Signature::from_bytes_unchecked(s.topic.to_inner()) },
+                    s.timestamp,
+                )
+            })
+            .collect();
+        let signatures: Vec<Signature> = stored_at.keys().cloned().collect();
+        let contents = self.index().get_contents::<T>(&signatures).await?;
+
+        let mut arrivals = vec![];
+        for follow in follows {
+            if follow.notify == NotificationPreference::Silent {
+                continue;
+            }
+
+            let new: Vec<_> = contents
+                .iter()
+                .filter(|c| {
+                    c.index_hash() == &follow.index
+                        && stored_at
+                            .get(c.signature())
+                            .is_some_and(|t| *t > follow.last_check)
+                })
+                .cloned()
+                .collect();
+            if new.is_empty() {
+                continue;
+            }
+
+            if let Some(index) = self.index().get_index::<T>(&follow.index).await? {
+                arrivals.push(Arrival {
+                    index,
+                    notify: follow.notify,
+                    contents: new,
+                });
+            }
+        }
+
+        self.db
+            .query(format!(
+                "UPDATE {table} SET last_check = $checked WHERE last_check < $checked;"
+            ))
+            .bind(("checked", checked))
+            .await?
+            .check()?;
+
+        Ok(arrivals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{
+            Repositories, Timestamp,
+            follow_index::{IndexFollow, NotificationPreference},
+            index::tags::MangaTag,
+        },
+        errors::DatabaseError,
+        server::fixtures::{content, index},
+        types::{Hash, PrivateKey},
+    };
+
+    #[tokio::test]
+    async fn following_twice_is_a_conflict() {
+        let repo = Repositories::in_memory().await;
+        let index = Hash::digest(b"index");
+
+        repo.index_follow()
+            .add_index_follow(IndexFollow::<MangaTag>::new(
+                index.clone(),
+                NotificationPreference::Toast,
+                Timestamp::new(0),
+            ))
+            .await
+            .unwrap();
+
+        let again = repo
+            .index_follow()
+            .add_index_follow(IndexFollow::<MangaTag>::new(
+                index,
+                NotificationPreference::Toast,
+                Timestamp::new(0),
+            ))
+            .await;
+
+        assert!(matches!(again, Err(DatabaseError::AlreadyExists { .. })));
+    }
+
+    #[tokio::test]
+    async fn arrivals_are_only_taken_once() {
+        let repo = Repositories::in_memory().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Followed", &priv_key);
+        repo.index().add_index(index.clone()).await.unwrap();
+        repo.index_follow()
+            .add_index_follow(IndexFollow::<MangaTag>::new(
+                index.hash().clone(),
+                NotificationPreference::Desktop,
+                Timestamp::new(0),
+            ))
+            .await
+            .unwrap();
+
+        let content = content(&index, 1.0, &priv_key);
+        repo.index().add_content(content.clone()).await.unwrap();
+
+        let arrivals = repo.take_arrivals::<MangaTag>().await.unwrap();
+        assert_eq!(arrivals.len(), 1);
+        assert_eq!(arrivals[0].notify, NotificationPreference::Desktop);
+        assert_eq!(arrivals[0].contents, vec![content]);
+
+        assert!(repo.take_arrivals::<MangaTag>().await.unwrap().is_empty());
+    }
 }
@@ -1 +1,166 @@
+//! Private groups, content tagged with a group is only served to peers that
+//! prove they know the group secret.
 
+use base64::{Engine as _, prelude::BASE64_URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use rand::{RngCore, rngs::OsRng};
+use sha2::Sha512;
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Repositories, user::I2PAddress},
+    errors::{Base64Error, DatabaseError},
+    types::Hash,
+};
+
+type GroupMac = Hmac<Sha512>;
+
+/// Random bytes in a group secret
+pub const SECRET_LEN: usize = 32;
+
+/// A group we are part of. The id is derived from the secret so members agree
+/// on it without sharing anything else. The id is sent in clear, secrets are
+/// always [`SECRET_LEN`] random bytes so it can't be brute-forced back into
+/// one.
+#[derive(Clone, PartialEq, Eq, SurrealValue)]
+pub struct Group {
+    #[surreal(rename = "id")]
+    id: Hash,
+    pub name: String,
+    secret: String,
+}
+
+impl Group {
+    pub const TABLE_NAME: &'static str = "groups";
+
+    /// New group with a freshly generated secret
+    pub fn generate(name: String) -> Self {
+        let mut secret = [0u8; SECRET_LEN];
+        OsRng.fill_bytes(&mut secret);
+        Self::new(name, BASE64_URL_SAFE_NO_PAD.encode(secret))
+    }
+
+    /// Group shared with us as `secret`, which must be one made by
+    /// [`Group::generate`]
+    pub fn join(name: String, secret: String) -> Result<Self, Base64Error> {
+        let bytes = BASE64_URL_SAFE_NO_PAD.decode(&secret)?;
+        if bytes.len() != SECRET_LEN {
+            return Err(Base64Error::InvalidLength {
+                expected: SECRET_LEN,
+                actual: bytes.len(),
+            });
+        }
+
+        Ok(Self::new(name, secret))
+    }
+
+    fn new(name: String, secret: String) -> Self {
+        Self {
+            id: Self::id_for(&secret),
+            name,
+            secret,
+        }
+    }
+
+    fn id_for(secret: &str) -> Hash {
+        let mut bytes = b"akareko-group:".to_vec();
+        bytes.extend(secret.as_bytes());
+        Hash::digest(&bytes)
+    }
+
+    pub fn id(&self) -> &Hash {
+        &self.id
+    }
+
+    fn mac(&self, nonce: &[u8], address: &I2PAddress) -> GroupMac {
+        let mut mac =
+            GroupMac::new_from_slice(self.secret.as_bytes()).expect("HMAC takes keys of any size");
+        mac.update(nonce);
+        // Ties the proof to whoever answers the challenge, so it can't be
+        // relayed to the server from another connection
+        mac.update(address.inner().as_bytes());
+        mac
+    }
+
+    /// Answer to the server `nonce` for the peer connecting from `address`
+    pub fn prove(&self, nonce: &[u8], address: &I2PAddress) -> Vec<u8> {
+        self.mac(nonce, address).finalize().into_bytes().to_vec()
+    }
+
+    pub fn verify(&self, nonce: &[u8], address: &I2PAddress, proof: &[u8]) -> bool {
+        self.mac(nonce, address).verify_slice(proof).is_ok()
+    }
+}
+
+impl std::fmt::Debug for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Group")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn add_group(&self, group: Group) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Option<Value> = self
+            .db
+            .upsert((Group::TABLE_NAME, group.id.as_base64()))
+            .content(group)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_group(&self, id: &Hash) -> Result<Option<Group>, DatabaseError> {
+        let group: Option<Group> = self.db.select((Group::TABLE_NAME, id.as_base64())).await?;
+        Ok(group)
+    }
+
+    pub async fn get_groups(&self) -> Result<Vec<Group>, DatabaseError> {
+        let groups: Vec<Group> = self.db.select(Group::TABLE_NAME).await?;
+        Ok(groups)
+    }
+
+    pub async fn remove_group(&self, id: &Hash) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Option<Value> = self.db.delete((Group::TABLE_NAME, id.as_base64())).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Group;
+    use crate::db::user::I2PAddress;
+
+    #[test]
+    fn proof_is_tied_to_secret_and_address() {
+        let group = Group::generate("Friends".to_string());
+        let other = Group::generate("Friends".to_string());
+        let address = I2PAddress::new("member.b32.i2p");
+        let nonce = [7u8; 32];
+
+        let proof = group.prove(&nonce, &address);
+
+        assert!(group.verify(&nonce, &address, &proof));
+        assert!(!other.verify(&nonce, &address, &proof));
+        assert!(!group.verify(&nonce, &I2PAddress::new("relay.b32.i2p"), &proof));
+        assert!(!group.verify(&[8u8; 32], &address, &proof));
+    }
+
+    #[test]
+    fn only_generated_secrets_can_be_joined() {
+        let group = Group::generate("Friends".to_string());
+        let joined = Group::join("Friends".to_string(), group.secret.clone()).unwrap();
+
+        assert_eq!(joined.id(), group.id());
+        assert!(Group::join("Friends".to_string(), "hunter2".to_string()).is_err());
+        assert!(Group::join("Friends".to_string(), "aGVsbG8".to_string()).is_err());
+    }
+}
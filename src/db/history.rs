@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{
+        Repositories,
+        changes::DataKind,
+        index::{
+            content::{Content, ContentType},
+            tags::IndexTag,
+        },
+    },
+    errors::DatabaseError,
+    types::{Signature, Timestamp},
+};
+
+/// Last time a chapter was opened in the reader and where it was left, one
+/// entry per content. Kept apart from follows, it's only about reading.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub struct HistoryEntry {
+    #[surreal(rename = "id")]
+    pub content: Signature,
+    /// [`IndexTag::TAG`] of the content
+    pub tag: String,
+    pub title: String,
+    /// Page the reader was on, starting at 1
+    pub entry: u32,
+    pub read_at: Timestamp,
+}
+
+impl HistoryEntry {
+    pub const TABLE_NAME: &'static str = "history";
+
+    pub fn from_content<I: IndexTag, S: ContentType<I>>(
+        content: &Content<I, S>,
+        entry: u32,
+    ) -> Self {
+        Self {
+            content: content.signature().clone(),
+            tag: I::TAG.to_string(),
+            title: format!("Ch. {}: {}", content.enumeration(), content.title()),
+            entry,
+            read_at: Timestamp::now(),
+        }
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn record_history(&self, entry: HistoryEntry) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Vec<Value> = self
+            .db
+            .upsert(HistoryEntry::TABLE_NAME)
+            .content(entry)
+            .await?;
+
+        self.notify(DataKind::History);
+
+        Ok(())
+    }
+
+    /// Most recently read first
+    pub async fn get_history(&self, take: usize) -> Result<Vec<HistoryEntry>, DatabaseError> {
+        let entries: Vec<HistoryEntry> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} ORDER BY read_at DESC LIMIT $take;",
+                HistoryEntry::TABLE_NAME
+            ))
+            .bind(("take", take))
+            .await?
+            .take(0)?;
+
+        Ok(entries)
+    }
+
+    pub async fn clear_history(&self) -> Result<(), DatabaseError> {
+        self.db
+            .query(format!("DELETE {};", HistoryEntry::TABLE_NAME))
+            .await?
+            .check()?;
+
+        self.notify(DataKind::History);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HistoryEntry;
+    use crate::{
+        db::{
+            Repositories,
+            index::tags::{IndexTag, MangaTag},
+        },
+        types::{Signature, Timestamp},
+    };
+
+    fn entry(content: Signature, entry: u32, read_at: i64) -> HistoryEntry {
+        HistoryEntry {
+            content,
+            tag: MangaTag::TAG.to_string(),
+            title: "Ch. 1: Start".to_string(),
+            entry,
+            read_at: Timestamp::new(read_at),
+        }
+    }
+
+    #[tokio::test]
+    async fn reopening_moves_the_entry_up() {
+        let repo = Repositories::in_memory().await;
+        let first = Signature::empty();
+        let second = unsafe { Signature::from_bytes_unchecked([1; 64]) };
+
+        repo.record_history(entry(first.clone(), 3, 10))
+            .await
+            .unwrap();
+        repo.record_history(entry(second.clone(), 1, 20))
+            .await
+            .unwrap();
+        repo.record_history(entry(first.clone(), 7, 30))
+            .await
+            .unwrap();
+
+        let history = repo.get_history(10).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].content, first);
+        assert_eq!(history[0].entry, 7);
+        assert_eq!(history[1].content, second);
+
+        repo.clear_history().await.unwrap();
+        assert!(repo.get_history(10).await.unwrap().is_empty());
+    }
+}
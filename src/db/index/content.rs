@@ -2,7 +2,13 @@ use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use surrealdb_types::SurrealValue;
 
 use crate::{
-    db::{Magnet, ToBytes, index::tags::IndexTag},
+    config::ContentLimitsConfig,
+    db::{
+        Magnet, ToBytes,
+        index::{manifest::ManifestEntry, relay_trail::RelayHop, tags::IndexTag},
+    },
+    errors::ContentLimitError,
+    server::protocol::since_v2,
     types::{Hash, PrivateKey, PublicKey, Signature, Timestamp},
 };
 
@@ -56,6 +62,43 @@ pub struct Content<T: IndexTag, S: ContentType<T> = InternalContent> {
 
     pub extra_metadata: T::ExtraMetadata,
 
+    /// Hashes of the files, empty if the publisher didn't include them
+    #[serde(with = "since_v2")]
+    pub manifest: Vec<ManifestEntry>,
+
+    /// HTTP URL on an eepsite serving the same files, handed to the torrent
+    /// client as a web seed when the swarm has nothing to offer. See
+    /// [`is_valid_web_seed`].
+    #[serde(with = "since_v2")]
+    pub web_seed: Option<String>,
+
+    // Unsigned Fields
+    /// Nodes this content went through before reaching us, empty if it came
+    /// straight from the poster. See [`RelayHop`].
+    #[serde(with = "since_v2")]
+    pub(super) relay_trail: Vec<RelayHop>,
+
+    /// Extracted from the magnet when the content is stored, used to find
+    /// different content pointing at the same torrent.
+    #[serde(skip)]
+    pub(crate) info_hash: Option<String>,
+
+    /// Imported from a local folder or archive, `source` is then a path on
+    /// this machine and the entry is never offered to other nodes.
+    #[serde(skip)]
+    pub(crate) local_only: bool,
+
+    /// Private group the content was shared in, it's then only served to peers
+    /// that proved they are part of it. See [`Group`](crate::db::group::Group).
+    #[serde(skip)]
+    pub(crate) group_id: Option<Hash>,
+
+    /// Last time a node in relay-only mode exchanged it. Such content is kept
+    /// for other peers only, it isn't shown and is pruned once it's been
+    /// unused for the retention.
+    #[serde(skip)]
+    pub(crate) relayed_at: Option<Timestamp>,
+
     /// Each tag will use this differently, videos will count seconds, comics
     /// will count pages, etc.
     /// If count is 0 any progress above 0 will be considered as fully seen.
@@ -90,6 +133,8 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
         enumeration: f32,
         end: Option<f32>,
         extra_metadata: T::ExtraMetadata,
+        manifest: Vec<ManifestEntry>,
+        web_seed: Option<String>,
     ) -> Self {
         Self {
             signature,
@@ -102,6 +147,13 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
             enumeration,
             end,
             extra_metadata,
+            manifest,
+            web_seed,
+            relay_trail: vec![],
+            info_hash: None,
+            local_only: false,
+            group_id: None,
+            relayed_at: None,
             progress: 0,
             count: 1,
         }
@@ -116,6 +168,8 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
         enumeration: f32,
         end: Option<f32>,
         extra_metadata: &T::ExtraMetadata,
+        manifest: &[ManifestEntry],
+        web_seed: Option<&str>,
     ) -> Vec<u8> {
         let mut bytes: Vec<u8> = index_hash.inner().to_vec().to_vec();
         bytes.extend(timestamp.to_bytes());
@@ -127,6 +181,13 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
             bytes.extend(end.to_le_bytes());
         }
         bytes.extend(extra_metadata.to_bytes());
+        // Nothing is added without a manifest so older signatures still verify
+        for entry in manifest {
+            bytes.extend(entry.to_bytes());
+        }
+        if let Some(web_seed) = web_seed {
+            bytes.extend(web_seed.as_bytes());
+        }
         bytes
     }
 
@@ -139,6 +200,8 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
         enumeration: f32,
         end: Option<f32>,
         extra_metadata: T::ExtraMetadata,
+        manifest: Vec<ManifestEntry>,
+        web_seed: Option<String>,
         priv_key: &PrivateKey,
     ) -> Self {
         let to_sign = Self::id_bytes(
@@ -150,6 +213,8 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
             enumeration,
             end,
             &extra_metadata,
+            &manifest,
+            web_seed.as_deref(),
         );
         let signature = priv_key.sign(&to_sign);
 
@@ -164,6 +229,8 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
             enumeration,
             end,
             extra_metadata,
+            manifest,
+            web_seed,
         )
     }
 
@@ -177,6 +244,8 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
             self.enumeration,
             self.end,
             &self.extra_metadata,
+            &self.manifest,
+            self.web_seed.as_deref(),
         );
         self.poster.verify(&to_verify, &self.signature)
     }
@@ -205,6 +274,22 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
         &self.signature
     }
 
+    pub fn poster(&self) -> &PublicKey {
+        &self.poster
+    }
+
+    pub fn is_local_only(&self) -> bool {
+        self.local_only
+    }
+
+    pub fn group_id(&self) -> Option<&Hash> {
+        self.group_id.as_ref()
+    }
+
+    pub fn is_relayed(&self) -> bool {
+        self.relayed_at.is_some()
+    }
+
     pub fn update_progress(&mut self, progress: u32) {
         self.progress = progress;
     }
@@ -217,3 +302,167 @@ impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
         self.progress as f32 / self.count as f32 * 100.0
     }
 }
+
+impl<T: IndexTag> Content<T> {
+    /// Refuses records packed past `limits`, checked before the encoded size
+    /// so oversized fields are reported as such
+    pub fn check_limits(&self, limits: &ContentLimitsConfig) -> Result<(), ContentLimitError> {
+        if self.manifest.len() > limits.max_entries {
+            return Err(ContentLimitError::TooManyEntries {
+                allowed: limits.max_entries,
+                actual: self.manifest.len(),
+            });
+        }
+
+        let title_chars = self.title.chars().count();
+        if title_chars > limits.max_title_chars {
+            return Err(ContentLimitError::TitleTooLong {
+                allowed: limits.max_title_chars,
+                actual: title_chars,
+            });
+        }
+
+        let paths = std::iter::once(&self.source).chain(self.manifest.iter().map(|e| &e.path));
+        if let Some(path_chars) = paths
+            .map(|p| p.chars().count())
+            .find(|&n| n > limits.max_path_chars)
+        {
+            return Err(ContentLimitError::PathTooLong {
+                allowed: limits.max_path_chars,
+                actual: path_chars,
+            });
+        }
+
+        let encoded = postcard::to_allocvec(self).map_or(u64::MAX, |b| b.len() as u64);
+        if encoded > limits.max_encoded_bytes {
+            return Err(ContentLimitError::TooLarge {
+                allowed: limits.max_encoded_bytes,
+                actual: encoded,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Web seeds must stay inside I2P, a plain http URL pointing at an `.i2p`
+/// host. Anything else would have the torrent client leave the network.
+pub fn is_valid_web_seed(url: &str) -> bool {
+    match url::Url::parse(url) {
+        Ok(url) => {
+            url.scheme() == "http"
+                && url
+                    .host_str()
+                    .is_some_and(|h| h.ends_with(".i2p") && h.len() > ".i2p".len())
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::{MangaChapter, MangaTag},
+        helpers::Language,
+        server::protocol::{AkarekoProtocolVersion, with_wire_version},
+    };
+
+    #[test]
+    fn packed_content_is_refused() {
+        let priv_key = PrivateKey::new();
+        let limits = ContentLimitsConfig::default();
+        let entry = |path: &str| ManifestEntry {
+            path: path.to_string(),
+            hash: Hash::new([0; 64]),
+        };
+        let packed = |manifest: Vec<ManifestEntry>, title: String| {
+            Content::<MangaTag>::new_signed(
+                Hash::new([0; 64]),
+                Timestamp::new(0),
+                Magnet(String::new()),
+                String::new(),
+                title,
+                1.0,
+                None,
+                MangaChapter::new(Language::Unknown),
+                manifest,
+                None,
+                &priv_key,
+            )
+        };
+
+        assert!(
+            packed(vec![entry("a")], "Chapter 1".to_string())
+                .check_limits(&limits)
+                .is_ok()
+        );
+        assert!(matches!(
+            packed(vec![entry("a"); limits.max_entries + 1], String::new()).check_limits(&limits),
+            Err(ContentLimitError::TooManyEntries { .. })
+        ));
+        assert!(matches!(
+            packed(vec![], "t".repeat(limits.max_title_chars + 1)).check_limits(&limits),
+            Err(ContentLimitError::TitleTooLong { .. })
+        ));
+        assert!(matches!(
+            packed(
+                vec![entry(&"p".repeat(limits.max_path_chars + 1))],
+                String::new()
+            )
+            .check_limits(&limits),
+            Err(ContentLimitError::PathTooLong { .. })
+        ));
+        assert!(matches!(
+            packed(vec![entry("a")], String::new()).check_limits(&ContentLimitsConfig {
+                max_encoded_bytes: 16,
+                ..limits
+            }),
+            Err(ContentLimitError::TooLarge { .. })
+        ));
+    }
+
+    /// V1 peers get the layout content had before the manifest, web seed and
+    /// relay trail were added
+    #[tokio::test]
+    async fn v1_layout_is_unchanged() {
+        let content = Content::<MangaTag>::new_signed(
+            Hash::new([0; 64]),
+            Timestamp::new(0),
+            Magnet(String::new()),
+            String::new(),
+            "Chapter 1".to_string(),
+            1.0,
+            None,
+            MangaChapter::new(Language::Unknown),
+            vec![],
+            None,
+            &PrivateKey::new(),
+        );
+
+        let v1 = with_wire_version(AkarekoProtocolVersion::V1, async {
+            postcard::to_allocvec(&content).unwrap()
+        })
+        .await;
+        let latest = postcard::to_allocvec(&content).unwrap();
+
+        assert_eq!(latest, [v1.clone(), vec![0, 0, 0]].concat());
+        let decoded = with_wire_version(AkarekoProtocolVersion::V1, async {
+            postcard::from_bytes::<Content<MangaTag>>(&v1).unwrap()
+        })
+        .await;
+        assert!(decoded.verify());
+    }
+
+    #[test]
+    fn web_seed_must_be_an_eepsite() {
+        assert!(is_valid_web_seed("http://akareko.i2p/files/chapter-1/"));
+        assert!(is_valid_web_seed(
+            "http://ukeu3k5oycgaauneqgtnvselmt4yemvoilkln7jpvamvfx7dnkdq.b32.i2p/a.cbz"
+        ));
+        assert!(!is_valid_web_seed("https://example.com/a.cbz"));
+        assert!(!is_valid_web_seed("http://example.com/a.cbz"));
+        assert!(!is_valid_web_seed("http://.i2p/a.cbz"));
+        assert!(!is_valid_web_seed("not a url"));
+    }
+}
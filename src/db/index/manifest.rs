@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use surrealdb_types::SurrealValue;
+use tokio::io::AsyncReadExt;
+
+use crate::{db::ToBytes, storage::sanitize_source, types::Hash};
+
+/// Hash of one file of a content, signed together with it so a download can
+/// be checked against what the author published and not only against the
+/// torrent pieces.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// Relative to the content source, empty when the source is the file
+    /// itself
+    pub path: String,
+    pub hash: Hash,
+}
+
+impl ToBytes for ManifestEntry {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.path.to_bytes();
+        bytes.extend(self.hash.inner());
+        bytes
+    }
+}
+
+/// Result of checking downloaded files against a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestCheck {
+    Verified,
+    Missing(String),
+    Mismatch(String),
+}
+
+impl ManifestCheck {
+    pub fn is_verified(&self) -> bool {
+        matches!(self, ManifestCheck::Verified)
+    }
+}
+
+async fn hash_file(path: &Path) -> std::io::Result<Hash> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha512::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(Hash::new(hasher.finalize().into()))
+}
+
+/// Hashes every file under `source` so it can be signed with the content.
+/// Entries are sorted by path so the same files always sign the same.
+pub async fn build_manifest(source: &Path) -> std::io::Result<Vec<ManifestEntry>> {
+    if source.is_file() {
+        return Ok(vec![ManifestEntry {
+            path: String::new(),
+            hash: hash_file(source).await?,
+        }]);
+    }
+
+    let mut entries = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(relative) = pending.pop() {
+        let mut dir = tokio::fs::read_dir(source.join(&relative)).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let path = relative.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                pending.push(path);
+            } else {
+                entries.push(ManifestEntry {
+                    // Always `/` so manifests made on windows check elsewhere
+                    path: path
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    hash: hash_file(&source.join(&path)).await?,
+                });
+            }
+        }
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Checks the files under `source` against `manifest`, stopping at the first
+/// one that is missing or differs
+pub async fn verify_manifest(source: &Path, manifest: &[ManifestEntry]) -> ManifestCheck {
+    for entry in manifest {
+        let Some(relative) = sanitize_source(&entry.path) else {
+            return ManifestCheck::Mismatch(entry.path.clone());
+        };
+
+        // Joining an empty path would add a trailing separator
+        let path = match relative.as_os_str().is_empty() {
+            true => source.to_path_buf(),
+            false => source.join(relative),
+        };
+
+        match hash_file(&path).await {
+            Ok(hash) if hash == entry.hash => {}
+            Ok(_) => return ManifestCheck::Mismatch(entry.path.clone()),
+            Err(_) => return ManifestCheck::Missing(entry.path.clone()),
+        }
+    }
+
+    ManifestCheck::Verified
+}
@@ -5,13 +5,16 @@ use uuid::Uuid;
 use crate::{
     db::{SurrealPhantom, ToBytes, index::tags::IndexTag},
     helpers::SanitizedString,
-    types::{Hash, PrivateKey, PublicKey, Signature},
+    types::{Hash, PrivateKey, PublicKey, Signature, Timestamp},
 };
 
 // ==================== End Imports ====================
 
 pub mod content;
+pub mod manifest;
 pub mod metadata;
+pub mod relay_trail;
+pub mod release;
 pub mod tags;
 
 #[cfg(feature = "surrealdb")]
@@ -53,6 +56,12 @@ pub struct Index<T: IndexTag> {
 
     out_links: IndexLinks,
 
+    // Unsigned Fields
+    /// Last time a node in relay-only mode exchanged it, see
+    /// [`Content::is_relayed`](content::Content::is_relayed)
+    #[serde(skip)]
+    pub(crate) relayed_at: Option<Timestamp>,
+
     _phantom: SurrealPhantom<T>,
 }
 
@@ -85,6 +94,7 @@ impl<T: IndexTag> Index<T> {
             out_links,
             source,
             signature,
+            relayed_at: None,
             _phantom: SurrealPhantom::default(),
         }
     }
@@ -157,4 +167,8 @@ impl<T: IndexTag> Index<T> {
     pub fn signature(&self) -> &Signature {
         &self.signature
     }
+
+    pub fn is_relayed(&self) -> bool {
+        self.relayed_at.is_some()
+    }
 }
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{
+        ToBytes,
+        index::{
+            content::{Content, ContentType},
+            tags::IndexTag,
+        },
+    },
+    types::{PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+/// Hops after this are not recorded, the content is still relayed.
+pub const MAX_RELAY_HOPS: usize = 8;
+
+/// A node that passed the content along. Each hop signs the content signature
+/// together with the previous hop so hops can't be removed or reordered
+/// without breaking the rest of the trail.
+#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RelayHop {
+    pub relay: PublicKey,
+    pub timestamp: Timestamp,
+    pub signature: Signature,
+}
+
+impl RelayHop {
+    fn verification_bytes(
+        content_signature: &Signature,
+        previous: Option<&RelayHop>,
+        relay: &PublicKey,
+        timestamp: &Timestamp,
+    ) -> Vec<u8> {
+        let mut bytes = content_signature.as_ref().to_vec();
+        if let Some(previous) = previous {
+            bytes.extend(previous.signature.as_ref());
+        }
+        bytes.extend(relay.as_bytes());
+        bytes.extend(timestamp.to_bytes());
+        bytes
+    }
+}
+
+impl<T: IndexTag, S: ContentType<T>> Content<T, S> {
+    pub fn relay_trail(&self) -> &[RelayHop] {
+        &self.relay_trail
+    }
+
+    /// Adds ourselves to the trail before sending the content to someone else.
+    /// Nothing is added when we are the poster, are already in the trail or
+    /// the trail is full.
+    pub fn append_relay_hop(&mut self, priv_key: &PrivateKey) {
+        let relay = priv_key.public_key();
+
+        if self.poster() == &relay
+            || self.relay_trail.len() >= MAX_RELAY_HOPS
+            || self.relay_trail.iter().any(|h| h.relay == relay)
+        {
+            return;
+        }
+
+        let timestamp = Timestamp::now();
+        let to_sign = RelayHop::verification_bytes(
+            self.signature(),
+            self.relay_trail.last(),
+            &relay,
+            &timestamp,
+        );
+
+        self.relay_trail.push(RelayHop {
+            signature: priv_key.sign(&to_sign),
+            relay,
+            timestamp,
+        });
+    }
+
+    pub fn verify_relay_trail(&self) -> bool {
+        if self.relay_trail.len() > MAX_RELAY_HOPS {
+            return false;
+        }
+
+        let mut previous = None;
+        for hop in self.relay_trail.iter() {
+            let to_verify = RelayHop::verification_bytes(
+                self.signature(),
+                previous,
+                &hop.relay,
+                &hop.timestamp,
+            );
+
+            if !hop.relay.verify(&to_verify, &hop.signature) {
+                return false;
+            }
+
+            previous = Some(hop);
+        }
+
+        true
+    }
+
+    /// The trail isn't covered by the content signature, so a broken one is
+    /// dropped instead of rejecting the whole content.
+    pub fn clear_relay_trail(&mut self) {
+        self.relay_trail.clear();
+    }
+}
@@ -0,0 +1,333 @@
+//! A release put together step by step before anything is signed: the index
+//! it belongs to, the chapters in it and the torrent carrying them. Nothing
+//! here touches the database, signing happens in memory so the result can be
+//! reviewed before it's stored or announced.
+
+use std::{fmt::Display, path::Path};
+
+use anawt::InfoHash;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        Magnet,
+        index::{
+            Index, IndexLinks,
+            content::{Content, is_valid_web_seed},
+            manifest::build_manifest,
+            tags::{MangaChapter, MangaTag},
+        },
+    },
+    helpers::Language,
+    types::{PrivateKey, Timestamp},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexDraft {
+    Existing(Index<MangaTag>),
+    New {
+        title: String,
+        release_date: i32,
+        mangadex: String,
+    },
+}
+
+impl Default for IndexDraft {
+    fn default() -> Self {
+        IndexDraft::New {
+            title: String::new(),
+            release_date: 0,
+            mangadex: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterDraft {
+    pub title: String,
+    /// Path inside the torrent
+    pub source: String,
+    pub enumeration: String,
+}
+
+/// Shared by every chapter in the release
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TorrentDraft {
+    pub magnet: String,
+    /// Local copy of the torrent's files, chapters are hashed from
+    /// `files_path/source` and seeded from here once published
+    pub files_path: String,
+    pub web_seed: String,
+    /// Clearnet tracker hosts kept in the magnet, see
+    /// [`Magnet::scrub_trackers`]
+    pub allowed_trackers: Vec<String>,
+}
+
+impl TorrentDraft {
+    /// The magnet as it's signed, without the trackers that would leak the
+    /// release, and what was taken out of it
+    pub fn scrubbed_magnet(&self) -> (Magnet, Vec<String>) {
+        Magnet(self.magnet.clone()).scrub_trackers(&self.allowed_trackers)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReleaseDraft {
+    pub index: IndexDraft,
+    pub chapters: Vec<ChapterDraft>,
+    pub torrent: TorrentDraft,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DraftProblem {
+    MissingTitle,
+    InvalidMangadexId,
+    NoChapters,
+    /// Chapters are counted from 1, as they're shown
+    MissingChapterTitle(usize),
+    MissingChapterSource(usize),
+    InvalidEnumeration(usize),
+    DuplicateEnumeration(usize),
+    InvalidMagnet,
+    MissingFiles,
+    InvalidWebSeed,
+}
+
+impl Display for DraftProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DraftProblem::MissingTitle => write!(f, "The index needs a title"),
+            DraftProblem::InvalidMangadexId => write!(f, "The MangaDex id isn't a valid UUID"),
+            DraftProblem::NoChapters => write!(f, "Add at least one chapter"),
+            DraftProblem::MissingChapterTitle(i) => write!(f, "Chapter {} needs a title", i),
+            DraftProblem::MissingChapterSource(i) => {
+                write!(f, "Chapter {} needs its path in the torrent", i)
+            }
+            DraftProblem::InvalidEnumeration(i) => {
+                write!(f, "Chapter {} enumeration isn't a number", i)
+            }
+            DraftProblem::DuplicateEnumeration(i) => {
+                write!(f, "Chapter {} has the same enumeration as another", i)
+            }
+            DraftProblem::InvalidMagnet => write!(f, "The magnet link isn't valid"),
+            DraftProblem::MissingFiles => write!(f, "The local files don't exist"),
+            DraftProblem::InvalidWebSeed => write!(f, "The fallback URL must be an eepsite"),
+        }
+    }
+}
+
+impl IndexDraft {
+    pub fn problems(&self) -> Vec<DraftProblem> {
+        let mut problems = vec![];
+        if let IndexDraft::New {
+            title, mangadex, ..
+        } = self
+        {
+            if title.trim().is_empty() {
+                problems.push(DraftProblem::MissingTitle);
+            }
+            if !mangadex.is_empty() && Uuid::parse_str(mangadex).is_err() {
+                problems.push(DraftProblem::InvalidMangadexId);
+            }
+        }
+        problems
+    }
+}
+
+impl ReleaseDraft {
+    pub fn chapter_problems(&self) -> Vec<DraftProblem> {
+        if self.chapters.is_empty() {
+            return vec![DraftProblem::NoChapters];
+        }
+
+        let mut problems = vec![];
+        let mut seen = vec![];
+        for (i, chapter) in self.chapters.iter().enumerate() {
+            let n = i + 1;
+            if chapter.title.trim().is_empty() {
+                problems.push(DraftProblem::MissingChapterTitle(n));
+            }
+            if chapter.source.trim().is_empty() {
+                problems.push(DraftProblem::MissingChapterSource(n));
+            }
+            match chapter.enumeration.parse::<f32>() {
+                Ok(e) if seen.contains(&e) => problems.push(DraftProblem::DuplicateEnumeration(n)),
+                Ok(e) => seen.push(e),
+                Err(_) => problems.push(DraftProblem::InvalidEnumeration(n)),
+            }
+        }
+        problems
+    }
+
+    pub fn torrent_problems(&self) -> Vec<DraftProblem> {
+        let mut problems = vec![];
+        if InfoHash::from_magnet(&self.torrent.magnet).is_err() {
+            problems.push(DraftProblem::InvalidMagnet);
+        }
+        if !self.torrent.files_path.is_empty() && !Path::new(&self.torrent.files_path).exists() {
+            problems.push(DraftProblem::MissingFiles);
+        }
+        if !self.torrent.web_seed.is_empty() && !is_valid_web_seed(&self.torrent.web_seed) {
+            problems.push(DraftProblem::InvalidWebSeed);
+        }
+        problems
+    }
+
+    pub fn problems(&self) -> Vec<DraftProblem> {
+        let mut problems = self.index.problems();
+        problems.extend(self.chapter_problems());
+        problems.extend(self.torrent_problems());
+        problems
+    }
+
+    /// Hashes the local files and signs everything with `priv_key`. The
+    /// draft should have no [`problems`](Self::problems) left.
+    pub async fn sign(&self, priv_key: &PrivateKey) -> std::io::Result<Release> {
+        let (index, new_index) = match &self.index {
+            IndexDraft::Existing(index) => (index.clone(), false),
+            IndexDraft::New {
+                title,
+                release_date,
+                mangadex,
+            } => {
+                let links = IndexLinks {
+                    myanimelist: None,
+                    mangadex: Uuid::parse_str(mangadex).ok(),
+                };
+                let index =
+                    Index::new_signed(title.trim().to_string(), *release_date, links, priv_key);
+                (index, true)
+            }
+        };
+
+        let web_seed = match self.torrent.web_seed.is_empty() {
+            true => None,
+            false => Some(self.torrent.web_seed.clone()),
+        };
+        let (magnet, _) = self.torrent.scrubbed_magnet();
+        let timestamp = Timestamp::now();
+
+        let mut contents = Vec::with_capacity(self.chapters.len());
+        for chapter in &self.chapters {
+            let manifest = match self.torrent.files_path.is_empty() {
+                true => vec![],
+                false => {
+                    build_manifest(&Path::new(&self.torrent.files_path).join(&chapter.source))
+                        .await?
+                }
+            };
+
+            contents.push(Content::new_signed(
+                index.hash().clone(),
+                timestamp,
+                magnet.clone(),
+                chapter.source.clone(),
+                chapter.title.trim().to_string(),
+                chapter.enumeration.parse().unwrap_or_default(),
+                None,
+                MangaChapter::new(Language::Unknown),
+                manifest,
+                web_seed.clone(),
+                priv_key,
+            ));
+        }
+
+        Ok(Release {
+            index,
+            new_index,
+            contents,
+            files_path: self.torrent.files_path.clone(),
+        })
+    }
+}
+
+/// Everything a draft turns into, signed and ready to be stored and shared
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub index: Index<MangaTag>,
+    /// Whether the index is ours to store, or one we already have
+    pub new_index: bool,
+    pub contents: Vec<Content<MangaTag>>,
+    pub files_path: String,
+}
+
+impl PartialEq for Release {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+            && self
+                .contents
+                .iter()
+                .map(|c| c.signature())
+                .eq(other.contents.iter().map(|c| c.signature()))
+    }
+}
+
+impl Eq for Release {}
+
+impl std::hash::Hash for Release {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash().hash(state);
+        for content in &self.contents {
+            content.signature().hash(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chapter_problems_are_numbered() {
+        let draft = ReleaseDraft {
+            chapters: vec![
+                ChapterDraft {
+                    title: "One".to_string(),
+                    source: "one".to_string(),
+                    enumeration: "1".to_string(),
+                },
+                ChapterDraft {
+                    title: String::new(),
+                    source: "two".to_string(),
+                    enumeration: "1".to_string(),
+                },
+                ChapterDraft {
+                    title: "Three".to_string(),
+                    source: String::new(),
+                    enumeration: "three".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            draft.chapter_problems(),
+            vec![
+                DraftProblem::MissingChapterTitle(2),
+                DraftProblem::DuplicateEnumeration(2),
+                DraftProblem::MissingChapterSource(3),
+                DraftProblem::InvalidEnumeration(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn existing_index_needs_nothing() {
+        assert!(
+            IndexDraft::default()
+                .problems()
+                .contains(&DraftProblem::MissingTitle)
+        );
+
+        let index = Index::new_signed(
+            "Title".to_string(),
+            0,
+            IndexLinks {
+                myanimelist: None,
+                mangadex: None,
+            },
+            &PrivateKey::new(),
+        );
+        assert!(IndexDraft::Existing(index).problems().is_empty());
+    }
+}
@@ -80,11 +80,15 @@ impl IndexRepository<MangaTag> {
         match created {
             Ok(n) => match n {
                 Some(n) => Ok(n),
-                None => Err(DatabaseError::Unknown),
+                None => Err(DatabaseError::UnexpectedRecordCount {
+                    table: T::CONTENT_TABLE.to_string(),
+                    expected: 1,
+                    actual: 0,
+                }),
             },
             Err(e) => {
                 info!("Error: {}", e);
-                Err(DatabaseError::Unknown)
+                Err(e.into())
             }
         }
     }
@@ -1,34 +1,75 @@
+use std::collections::HashSet;
+
 use fastbloom::BloomFilter;
 use surrealdb::{Surreal, engine::local::Db, types::RecordId};
 use surrealdb_types::Value;
 
 use crate::{
+    config::ContentLimitsConfig,
     db::{
         BLOOM_FILTER_FALSE_POSITIVE_RATE, Content,
+        changes::{DataChanges, DataKind},
+        comments::Post,
+        content_source::ContentSource,
         event::{Event, insert_event, remove_event},
+        follow_index::IndexFollow,
         index::{Index, IndexTag},
+        revocation::any_revoked,
+        suppression::any_suppressed,
+        torrent_link::TorrentLink,
     },
     errors::DatabaseError,
-    types::{Hash, Signature, Timestamp, Topic},
+    types::{Clock, Hash, PublicKey, Signature, Timestamp, Topic},
 };
 
 // ==================== End Imports ====================
 
 pub struct IndexRepository<'a> {
     db: &'a Surreal<Db>,
+    changes: &'a DataChanges,
+    relay_only: bool,
+    content_limits: &'a ContentLimitsConfig,
+    clock: &'a dyn Clock,
 }
 
 impl<'a> IndexRepository<'a> {
-    pub fn new(db: &'a Surreal<Db>) -> IndexRepository<'a> {
-        IndexRepository { db }
+    pub fn new(
+        db: &'a Surreal<Db>,
+        changes: &'a DataChanges,
+        relay_only: bool,
+        content_limits: &'a ContentLimitsConfig,
+        clock: &'a dyn Clock,
+    ) -> IndexRepository<'a> {
+        IndexRepository {
+            db,
+            changes,
+            relay_only,
+            content_limits,
+            clock,
+        }
     }
 }
 
 impl<'a> IndexRepository<'a> {
-    pub async fn add_index<T: IndexTag>(&self, index: Index<T>) -> Result<Index<T>, DatabaseError> {
+    /// A suppressed or revoked index is returned as is without being stored
+    pub async fn add_index<T: IndexTag>(
+        &self,
+        mut index: Index<T>,
+    ) -> Result<Index<T>, DatabaseError> {
+        let ids = [index.hash().as_base64()];
+        if any_suppressed(self.db, &ids).await?
+            || any_revoked(self.db, &ids, index.source()).await?
+        {
+            return Ok(index);
+        }
+
+        if self.relay_only {
+            index.relayed_at = Some(self.clock.now());
+        }
+
         let transaction = self.db.clone().begin().await?;
 
-        let timestamp = Timestamp::now();
+        let timestamp = self.clock.now();
 
         let event = Event {
             timestamp,
@@ -42,18 +83,49 @@ impl<'a> IndexRepository<'a> {
 
         let r = match created.len() {
             1 => created.into_iter().next().unwrap(),
-            _ => return Err(DatabaseError::Unknown),
+            actual => {
+                return Err(DatabaseError::UnexpectedRecordCount {
+                    table: T::TAG.to_string(),
+                    expected: 1,
+                    actual,
+                });
+            }
         };
 
         transaction.commit().await?;
 
+        self.changes.notify(DataKind::Indexes);
+
         Ok(r)
     }
 
-    pub async fn add_content<T: IndexTag>(&self, content: Content<T>) -> Result<(), DatabaseError> {
+    /// Suppressed contents, or contents of a suppressed index, are skipped, so
+    /// are those their poster revoked. Contents past the
+    /// [`ContentLimitsConfig`] are refused.
+    pub async fn add_content<T: IndexTag>(
+        &self,
+        mut content: Content<T>,
+    ) -> Result<(), DatabaseError> {
+        content.check_limits(self.content_limits)?;
+
+        let ids = [
+            content.signature().as_base64(),
+            content.index_hash().as_base64(),
+        ];
+        if any_suppressed(self.db, &ids).await?
+            || any_revoked(self.db, &ids, content.poster()).await?
+        {
+            return Ok(());
+        }
+
+        content.info_hash = content.magnet_link.info_hash();
+        if self.relay_only {
+            content.relayed_at = Some(self.clock.now());
+        }
+
         let transaction = self.db.clone().begin().await?;
 
-        let timestamp = Timestamp::now();
+        let timestamp = self.clock.now();
 
         let event = Event {
             timestamp,
@@ -70,14 +142,118 @@ impl<'a> IndexRepository<'a> {
 
         transaction.commit().await?;
 
+        self.changes.notify(DataKind::Contents);
+
         Ok(())
     }
 
+    /// Stores content imported from local files. No event is created so it
+    /// never reaches other nodes through sync.
+    pub async fn add_local_content<T: IndexTag>(
+        &self,
+        mut content: Content<T>,
+    ) -> Result<(), DatabaseError> {
+        content.local_only = true;
+        content.info_hash = None;
+
+        let _: Vec<Value> = self.db.upsert(T::CONTENT_TABLE).content(content).await?;
+
+        self.changes.notify(DataKind::Contents);
+
+        Ok(())
+    }
+
+    /// Stores content shared in a private group. Like local content no event is
+    /// created, it's only handed to peers that prove they are in the group.
+    pub async fn add_group_content<T: IndexTag>(
+        &self,
+        mut content: Content<T>,
+        group: Hash,
+    ) -> Result<(), DatabaseError> {
+        content.check_limits(self.content_limits)?;
+        content.info_hash = content.magnet_link.info_hash();
+        content.group_id = Some(group);
+
+        let _: Vec<Value> = self.db.upsert(T::CONTENT_TABLE).content(content).await?;
+
+        self.changes.notify(DataKind::Contents);
+
+        Ok(())
+    }
+
+    pub async fn get_group_contents<T: IndexTag>(
+        &self,
+        group: Hash,
+        index_hash: Hash,
+    ) -> Result<Vec<Content<T>>, DatabaseError> {
+        let query = format!(
+            "SELECT * FROM {} WHERE index_hash = $index_hash AND group_id = $group_id;",
+            T::CONTENT_TABLE
+        );
+
+        let results: Vec<Content<T>> = self
+            .db
+            .query(query)
+            .bind(("index_hash", index_hash))
+            .bind(("group_id", group))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
+    pub async fn get_contents_by_info_hash<T: IndexTag>(
+        &self,
+        info_hash: &str,
+    ) -> Result<Vec<Content<T>>, DatabaseError> {
+        let query_str = format!(
+            "SELECT * FROM {} WHERE info_hash = $info_hash;",
+            T::CONTENT_TABLE
+        );
+
+        let results: Vec<Content<T>> = self
+            .db
+            .query(query_str)
+            .bind(("info_hash", info_hash.to_string()))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
+    /// Groups of content that point at the same torrent, each group has at
+    /// least 2 entries
+    pub async fn get_info_hash_conflicts<T: IndexTag>(
+        &self,
+    ) -> Result<Vec<Vec<Content<T>>>, DatabaseError> {
+        let query_str = format!(
+            "SELECT * FROM {0} WHERE info_hash IN (
+                SELECT VALUE info_hash FROM (
+                    SELECT info_hash, count() AS total FROM {0}
+                    WHERE info_hash != NONE GROUP BY info_hash
+                ) WHERE total > 1
+            ) ORDER BY info_hash;",
+            T::CONTENT_TABLE
+        );
+
+        let results: Vec<Content<T>> = self.db.query(query_str).await?.take(0)?;
+
+        let mut conflicts: Vec<Vec<Content<T>>> = vec![];
+        for content in results {
+            match conflicts.last_mut() {
+                Some(group) if group[0].info_hash == content.info_hash => group.push(content),
+                _ => conflicts.push(vec![content]),
+            }
+        }
+
+        Ok(conflicts)
+    }
+
     pub async fn update_content_progress<T: IndexTag>(
         &self,
         signature: Signature,
         progress: u32,
-    ) -> Result<Option<Content<T>>, DatabaseError> {
+    ) -> Result<Content<T>, DatabaseError> {
         let query = format!("UPDATE $id SET progress = $progress");
 
         let content: Option<Content<T>> = self
@@ -88,6 +264,15 @@ impl<'a> IndexRepository<'a> {
             .await?
             .take(0)?;
 
+        let Some(content) = content else {
+            return Err(DatabaseError::NotFound {
+                table: T::CONTENT_TABLE.to_string(),
+                id: signature.as_base64(),
+            });
+        };
+
+        self.changes.notify(DataKind::Contents);
+
         Ok(content)
     }
 
@@ -95,7 +280,7 @@ impl<'a> IndexRepository<'a> {
         &self,
         signature: Signature,
         count: u32,
-    ) -> Result<Option<Content<I>>, DatabaseError> {
+    ) -> Result<Content<I>, DatabaseError> {
         let query = format!("UPDATE $id SET count = $count");
 
         let content: Option<Content<I>> = self
@@ -106,6 +291,15 @@ impl<'a> IndexRepository<'a> {
             .await?
             .take(0)?;
 
+        let Some(content) = content else {
+            return Err(DatabaseError::NotFound {
+                table: I::CONTENT_TABLE.to_string(),
+                id: signature.as_base64(),
+            });
+        };
+
+        self.changes.notify(DataKind::Contents);
+
         Ok(content)
     }
 
@@ -125,6 +319,268 @@ impl<'a> IndexRepository<'a> {
 
         transaction.commit().await?;
 
+        self.changes.notify(DataKind::Contents);
+
+        Ok(())
+    }
+
+    /// Removes the index along with everything local that hangs from it: its
+    /// contents and their progress, the follow, the posts on it and its
+    /// entries, torrent links and known sources. Returns the removed contents
+    /// so their files can be cleaned up too.
+    pub async fn delete_index<T: IndexTag>(
+        &self,
+        hash: &Hash,
+    ) -> Result<Vec<Content<T>>, DatabaseError> {
+        let Some(index) = self.get_index::<T>(hash).await? else {
+            return Err(DatabaseError::NotFound {
+                table: T::TAG.to_string(),
+                id: hash.as_base64(),
+            });
+        };
+
+        let contents: Vec<Content<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE index_hash = $index_hash;",
+                T::CONTENT_TABLE
+            ))
+            .bind(("index_hash", hash.clone()))
+            .await?
+            .take(0)?;
+
+        let signatures: Vec<Signature> = contents.iter().map(|c| c.signature().clone()).collect();
+
+        let mut event_topics: Vec<Topic> = vec![Topic::from_index(&index)];
+        event_topics.extend(contents.iter().map(Topic::from_content));
+
+        let mut post_topics: Vec<Topic> = vec![Topic::from_index(&index)];
+        post_topics.extend(
+            contents
+                .iter()
+                .map(|c| Topic::from_entry(&index, c.enumeration())),
+        );
+
+        let transaction = self.db.clone().begin().await?;
+
+        transaction
+            .query(format!(
+                "DELETE FROM events WHERE topic IN $event_topics;
+                DELETE FROM {posts} WHERE topic IN $post_topics;
+                DELETE FROM {contents} WHERE index_hash = $index_hash;
+                DELETE FROM {links} WHERE content IN $signatures;
+                DELETE FROM {sources} WHERE index = $index_hash;
+                DELETE $follow;
+                DELETE $index;",
+                posts = Post::TABLE_NAME,
+                contents = T::CONTENT_TABLE,
+                links = TorrentLink::TABLE_NAME,
+                sources = ContentSource::TABLE_NAME,
+            ))
+            .bind(("event_topics", event_topics))
+            .bind(("post_topics", post_topics))
+            .bind(("index_hash", hash.clone()))
+            .bind(("signatures", signatures))
+            .bind((
+                "follow",
+                RecordId::new(IndexFollow::<T>::table_name(), hash.as_base64()),
+            ))
+            .bind(("index", RecordId::new(T::TAG, hash.as_base64())))
+            .await?
+            .check()?;
+
+        transaction.commit().await?;
+
+        for kind in [
+            DataKind::Indexes,
+            DataKind::Contents,
+            DataKind::Follows,
+            DataKind::Posts,
+            DataKind::TorrentLinks,
+        ] {
+            self.changes.notify(kind);
+        }
+
+        Ok(contents)
+    }
+
+    /// Removes relayed contents last exchanged before `cutoff`, then relayed
+    /// indexes as old that no content points to anymore, skipping anything
+    /// published by `keep`. Returns how many records went.
+    pub async fn prune_relayed<T: IndexTag>(
+        &self,
+        cutoff: Timestamp,
+        keep: &HashSet<PublicKey>,
+    ) -> Result<usize, DatabaseError> {
+        let expired = |publisher: &str| {
+            format!(
+                "WHERE relayed_at != NONE AND relayed_at < $cutoff
+                    AND {publisher} NOT IN $keep"
+            )
+        };
+        let keep: Vec<PublicKey> = keep.iter().cloned().collect();
+
+        let contents: Vec<Content<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} {};",
+                T::CONTENT_TABLE,
+                expired("poster")
+            ))
+            .bind(("cutoff", cutoff))
+            .bind(("keep", keep.clone()))
+            .await?
+            .take(0)?;
+
+        let indexes: Vec<Index<T>> = self
+            .db
+            .query(format!("SELECT * FROM {} {};", T::TAG, expired("source")))
+            .bind(("cutoff", cutoff))
+            .bind(("keep", keep))
+            .await?
+            .take(0)?;
+
+        self.remove_relayed(contents, indexes).await
+    }
+
+    /// Removes up to `limit` of the least recently exchanged relayed contents,
+    /// and as many indexes, skipping anything published by `keep`. Indexes
+    /// that still have content are left alone. Returns how many records went.
+    pub async fn evict_relayed<T: IndexTag>(
+        &self,
+        limit: usize,
+        keep: &HashSet<PublicKey>,
+    ) -> Result<usize, DatabaseError> {
+        // Kept records are left out before the limit, or a batch of them
+        // would hold back everything older
+        let oldest = |publisher: &str| {
+            format!(
+                "WHERE relayed_at != NONE AND {publisher} NOT IN $keep
+                    ORDER BY relayed_at ASC LIMIT $limit"
+            )
+        };
+        let keep: Vec<PublicKey> = keep.iter().cloned().collect();
+
+        let contents: Vec<Content<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} {};",
+                T::CONTENT_TABLE,
+                oldest("poster")
+            ))
+            .bind(("limit", limit))
+            .bind(("keep", keep.clone()))
+            .await?
+            .take(0)?;
+
+        let indexes: Vec<Index<T>> = self
+            .db
+            .query(format!("SELECT * FROM {} {};", T::TAG, oldest("source")))
+            .bind(("limit", limit))
+            .bind(("keep", keep))
+            .await?
+            .take(0)?;
+
+        self.remove_relayed(contents, indexes).await
+    }
+
+    /// Removes `contents` and those `indexes` no other content points to, with
+    /// their events and known sources
+    async fn remove_relayed<T: IndexTag>(
+        &self,
+        contents: Vec<Content<T>>,
+        indexes: Vec<Index<T>>,
+    ) -> Result<usize, DatabaseError> {
+        let signatures: Vec<Signature> = contents.iter().map(|c| c.signature().clone()).collect();
+        let content_ids: Vec<RecordId> = signatures
+            .iter()
+            .map(|s| RecordId::new(T::CONTENT_TABLE, s.as_base64()))
+            .collect();
+
+        let candidates: Vec<Hash> = indexes.iter().map(|i| i.hash().clone()).collect();
+        let still_used: HashSet<Hash> = self
+            .db
+            .query(format!(
+                "SELECT VALUE index_hash FROM {} WHERE index_hash IN $candidates
+                    AND id NOT IN $contents;",
+                T::CONTENT_TABLE
+            ))
+            .bind(("candidates", candidates))
+            .bind(("contents", content_ids.clone()))
+            .await?
+            .take::<Vec<Hash>>(0)?
+            .into_iter()
+            .collect();
+
+        let indexes: Vec<Index<T>> = indexes
+            .into_iter()
+            .filter(|i| !still_used.contains(i.hash()))
+            .collect();
+
+        if contents.is_empty() && indexes.is_empty() {
+            return Ok(0);
+        }
+
+        let mut topics: Vec<Topic> = contents.iter().map(Topic::from_content).collect();
+        topics.extend(indexes.iter().map(Topic::from_index));
+        let index_ids: Vec<RecordId> = indexes
+            .iter()
+            .map(|i| RecordId::new(T::TAG, i.hash().as_base64()))
+            .collect();
+
+        let transaction = self.db.clone().begin().await?;
+
+        transaction
+            .query(format!(
+                "DELETE FROM events WHERE topic IN $topics;
+                DELETE $contents;
+                DELETE FROM {sources} WHERE content IN $signatures;
+                DELETE $indexes;",
+                sources = ContentSource::TABLE_NAME,
+            ))
+            .bind(("topics", topics))
+            .bind(("contents", content_ids))
+            .bind(("signatures", signatures))
+            .bind(("indexes", index_ids))
+            .await?
+            .check()?;
+
+        transaction.commit().await?;
+
+        self.changes.notify(DataKind::Indexes);
+        self.changes.notify(DataKind::Contents);
+
+        Ok(contents.len() + indexes.len())
+    }
+
+    /// Marks relayed records as just exchanged, so they are the last to be
+    /// pruned or evicted. Does nothing outside relay-only mode.
+    pub async fn touch_relayed<T: IndexTag>(
+        &self,
+        indexes: &[Hash],
+        contents: &[Signature],
+    ) -> Result<(), DatabaseError> {
+        if !self.relay_only || (indexes.is_empty() && contents.is_empty()) {
+            return Ok(());
+        }
+
+        let mut ids: Vec<RecordId> = indexes
+            .iter()
+            .map(|h| RecordId::new(T::TAG, h.as_base64()))
+            .collect();
+        ids.extend(
+            contents
+                .iter()
+                .map(|s| RecordId::new(T::CONTENT_TABLE, s.as_base64())),
+        );
+
+        self.db
+            .query("UPDATE $ids SET relayed_at = $now WHERE relayed_at != NONE;")
+            .bind(("ids", ids))
+            .bind(("now", self.clock.now()))
+            .await?
+            .check()?;
+
         Ok(())
     }
 
@@ -162,6 +618,66 @@ impl<'a> IndexRepository<'a> {
         Ok(filtered_indexes)
     }
 
+    /// Page of [`Self::get_all_indexes`] without the filter, in a stable
+    /// order so a table can be walked page by page
+    pub async fn get_all_indexes_page<T: IndexTag>(
+        &self,
+        timestamp: Option<Timestamp>,
+        start: usize,
+        take: usize,
+    ) -> Result<Vec<Index<T>>, DatabaseError> {
+        let query_str = format!(
+            "SELECT * FROM {} {} ORDER BY id LIMIT $take START $start;",
+            T::TAG,
+            if timestamp.is_some() {
+                "WHERE timestamp >= $timestamp"
+            } else {
+                ""
+            }
+        );
+
+        let mut query = self
+            .db
+            .query(query_str)
+            .bind(("take", take))
+            .bind(("start", start));
+
+        if let Some(timestamp) = timestamp {
+            query = query.bind(("timestamp", timestamp));
+        }
+
+        Ok(query.await?.take(0)?)
+    }
+
+    /// Indexes whose title contains `query`, ignoring case. `query` is
+    /// expected lowercase already.
+    pub async fn search_indexes<T: IndexTag>(
+        &self,
+        query: &str,
+        take: usize,
+    ) -> Result<Vec<Index<T>>, DatabaseError> {
+        let query_str = format!(
+            "
+            SELECT *
+            FROM {}
+            WHERE string::contains(string::lowercase(title), $query)
+            ORDER BY title ASC
+            LIMIT $take;
+            ",
+            T::TAG
+        );
+
+        let results: Vec<Index<T>> = self
+            .db
+            .query(query_str)
+            .bind(("query", query.to_string()))
+            .bind(("take", take))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
     pub async fn get_indexes<T: IndexTag>(
         &self,
         hashes: &[Hash],
@@ -192,7 +708,7 @@ impl<'a> IndexRepository<'a> {
 
         let results: Vec<Content<T>> = self
             .db
-            .query("SELECT * FROM $ids")
+            .query("SELECT * FROM $ids WHERE local_only != true AND group_id = NONE")
             .bind(("ids", ids))
             .await?
             .take(0)?;
@@ -200,6 +716,87 @@ impl<'a> IndexRepository<'a> {
         Ok(results)
     }
 
+    /// Shareable content published since `timestamp`, of any index
+    pub async fn get_recent_contents<T: IndexTag>(
+        &self,
+        timestamp: Timestamp,
+    ) -> Result<Vec<Content<T>>, DatabaseError> {
+        let results: Vec<Content<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE timestamp >= $timestamp AND local_only != true AND group_id = NONE;",
+                T::CONTENT_TABLE
+            ))
+            .bind(("timestamp", timestamp))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
+    /// Shareable content published by `poster`
+    pub async fn get_contents_by_poster<T: IndexTag>(
+        &self,
+        poster: &PublicKey,
+    ) -> Result<Vec<Content<T>>, DatabaseError> {
+        let results: Vec<Content<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE poster = $poster AND local_only != true AND group_id = NONE;",
+                T::CONTENT_TABLE
+            ))
+            .bind(("poster", poster.clone()))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
+    /// Up to `take` indexes signed by `source`
+    pub async fn get_indexes_by_source<T: IndexTag>(
+        &self,
+        source: &PublicKey,
+        take: usize,
+    ) -> Result<Vec<Index<T>>, DatabaseError> {
+        let indexes: Vec<Index<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE source = $source LIMIT $take;",
+                T::TAG
+            ))
+            .bind(("source", source.clone()))
+            .bind(("take", take))
+            .await?
+            .take(0)?;
+
+        Ok(indexes)
+    }
+
+    /// Indexes `own` published or published shareable content under, what
+    /// we keep announcing so our releases don't age out of relays
+    pub async fn get_published_index_hashes<T: IndexTag>(
+        &self,
+        own: &PublicKey,
+    ) -> Result<Vec<Hash>, DatabaseError> {
+        let indexes: Vec<Index<T>> = self
+            .db
+            .query(format!("SELECT * FROM {} WHERE source = $own;", T::TAG))
+            .bind(("own", own.clone()))
+            .await?
+            .take(0)?;
+
+        let contents = self.get_contents_by_poster::<T>(own).await?;
+
+        let mut seen = HashSet::new();
+        Ok(indexes
+            .iter()
+            .map(|i| i.hash())
+            .chain(contents.iter().map(|c| c.index_hash()))
+            .filter(|h| seen.insert(*h))
+            .cloned()
+            .collect())
+    }
+
     pub async fn get_index<T: IndexTag>(
         &self,
         hash: &Hash,
@@ -208,6 +805,25 @@ impl<'a> IndexRepository<'a> {
         Ok(result)
     }
 
+    /// Every content of the index we have, local only and group content
+    /// included. For showing to ourselves, never to peers.
+    pub async fn get_index_contents<T: IndexTag>(
+        &self,
+        index_hash: Hash,
+    ) -> Result<Vec<Content<T>>, DatabaseError> {
+        let results: Vec<Content<T>> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE index_hash = $index_hash;",
+                T::CONTENT_TABLE
+            ))
+            .bind(("index_hash", index_hash))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
     pub async fn get_filtered_index_contents<T: IndexTag>(
         &self,
         index_hash: Hash,
@@ -215,10 +831,10 @@ impl<'a> IndexRepository<'a> {
         filter: Option<BloomFilter>,
     ) -> Result<Vec<Content<T>>, DatabaseError> {
         let query_str: String = format!(
-            "SELECT * FROM {} WHERE index_hash = $index_hash {};",
+            "SELECT * FROM {} WHERE index_hash = $index_hash AND local_only != true AND group_id = NONE {};",
             T::CONTENT_TABLE,
             if timestamp.is_some() {
-                "WHERE timestamp >= $timestamp"
+                "AND timestamp >= $timestamp"
             } else {
                 ""
             }
@@ -272,8 +888,163 @@ impl<'a> IndexRepository<'a> {
 
         let mut filter = BloomFilter::with_false_pos(BLOOM_FILTER_FALSE_POSITIVE_RATE)
             .expected_items(result.len());
-        filter.insert(&result);
+        filter.insert_all(&result);
 
         Ok(filter)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc};
+
+    use crate::{
+        db::{Repositories, index::tags::MangaTag},
+        server::fixtures::{content, index},
+        types::{ManualClock, PrivateKey, Timestamp},
+    };
+
+    #[tokio::test]
+    async fn contents_are_filtered_by_timestamp() {
+        let repo = Repositories::in_memory().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Dated", &priv_key);
+        repo.index().add_index(index.clone()).await.unwrap();
+        repo.index()
+            .add_content(content(&index, 1.0, &priv_key))
+            .await
+            .unwrap();
+
+        let indexes = repo
+            .index()
+            .get_all_indexes::<MangaTag>(Some(Timestamp::new(0)), None)
+            .await;
+        assert!(indexes.is_ok());
+
+        for (since, expected) in [(Timestamp::new(0), 1), (Timestamp::now() + 60, 0)] {
+            let contents = repo
+                .index()
+                .get_filtered_index_contents::<MangaTag>(index.hash().clone(), Some(since), None)
+                .await
+                .unwrap();
+            assert_eq!(contents.len(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_relayed_records_are_pruned() {
+        let mut repo = Repositories::in_memory().await;
+        let (relayed, kept) = (
+            index("Relayed", &PrivateKey::new()),
+            index("Kept", &PrivateKey::new()),
+        );
+
+        repo.relay_only = true;
+        repo.index().add_index(relayed.clone()).await.unwrap();
+        repo.relay_only = false;
+        repo.index().add_index(kept.clone()).await.unwrap();
+
+        let pruned = repo
+            .index()
+            .prune_relayed::<MangaTag>(Timestamp::now() + 1, &HashSet::new())
+            .await
+            .unwrap();
+
+        assert_eq!(pruned, 1);
+        let stored = repo.index().get_all_indexes::<MangaTag>(None, None).await;
+        assert_eq!(stored.unwrap(), vec![kept]);
+    }
+
+    #[tokio::test]
+    async fn pruning_spares_kept_sources() {
+        let mut repo = Repositories::in_memory().await;
+        let (pruned, trusted) = (
+            index("Pruned", &PrivateKey::new()),
+            index("Trusted", &PrivateKey::new()),
+        );
+
+        repo.relay_only = true;
+        repo.index().add_index(pruned).await.unwrap();
+        repo.index().add_index(trusted.clone()).await.unwrap();
+
+        let keep = HashSet::from([trusted.source().clone()]);
+        let count = repo
+            .index()
+            .prune_relayed::<MangaTag>(Timestamp::now() + 1, &keep)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 1);
+        let stored = repo.index().get_all_indexes::<MangaTag>(None, None).await;
+        assert_eq!(stored.unwrap(), vec![trusted]);
+    }
+
+    #[tokio::test]
+    async fn eviction_spares_kept_sources() {
+        let mut repo = Repositories::in_memory().await;
+        let (evicted, trusted) = (
+            index("Evicted", &PrivateKey::new()),
+            index("Trusted", &PrivateKey::new()),
+        );
+
+        repo.relay_only = true;
+        repo.index().add_index(evicted.clone()).await.unwrap();
+        repo.index().add_index(trusted.clone()).await.unwrap();
+
+        let keep = HashSet::from([trusted.source().clone()]);
+        let count = repo
+            .index()
+            .evict_relayed::<MangaTag>(10, &keep)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 1);
+        let stored = repo.index().get_all_indexes::<MangaTag>(None, None).await;
+        assert_eq!(stored.unwrap(), vec![trusted]);
+    }
+
+    #[tokio::test]
+    async fn eviction_isnt_held_back_by_older_kept_records() {
+        let clock = Arc::new(ManualClock::new(Timestamp::new(1_000)));
+        let mut repo = Repositories::in_memory().await.with_clock(clock.clone());
+        let (trusted, evicted) = (
+            index("Trusted", &PrivateKey::new()),
+            index("Evicted", &PrivateKey::new()),
+        );
+
+        repo.relay_only = true;
+        repo.index().add_index(trusted.clone()).await.unwrap();
+        clock.advance(60);
+        repo.index().add_index(evicted).await.unwrap();
+
+        let keep = HashSet::from([trusted.source().clone()]);
+        let count = repo
+            .index()
+            .evict_relayed::<MangaTag>(1, &keep)
+            .await
+            .unwrap();
+
+        assert_eq!(count, 1);
+        let stored = repo.index().get_all_indexes::<MangaTag>(None, None).await;
+        assert_eq!(stored.unwrap(), vec![trusted]);
+    }
+
+    #[tokio::test]
+    async fn published_indexes_are_ours_only() {
+        let repo = Repositories::in_memory().await;
+        let (ours, theirs) = (
+            index("Ours", &PrivateKey::new()),
+            index("Theirs", &PrivateKey::new()),
+        );
+        repo.index().add_index(ours.clone()).await.unwrap();
+        repo.index().add_index(theirs).await.unwrap();
+
+        let published = repo
+            .index()
+            .get_published_index_hashes::<MangaTag>(ours.source())
+            .await
+            .unwrap();
+
+        assert_eq!(published, vec![ours.hash().clone()]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Consistency pass over the content/index graph. Run at startup, where it
+//! only reports, and from the settings, where problems can be repaired.
+
+use std::collections::{HashMap, HashSet};
+
+#[cfg(feature = "surrealdb")]
+use surrealdb_types::Value;
+
+use crate::{
+    db::{
+        Repositories,
+        audit::AuditEntry,
+        changes::DataKind,
+        index::{Index, content::Content, tags::IndexTag},
+        suppression::{SuppressedKind, Suppression},
+        user::User,
+    },
+    errors::DatabaseError,
+    types::{Hash, PublicKey, Signature, Timestamp},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Contents whose index isn't stored, grouped by that index
+    pub orphaned_contents: HashMap<Hash, Vec<Signature>>,
+    /// Indexes whose signature doesn't verify, with their title
+    pub invalid_indexes: Vec<(Hash, String)>,
+    /// Record ids of users whose key can't be a public key
+    pub malformed_users: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn orphaned_count(&self) -> usize {
+        self.orphaned_contents.values().map(Vec::len).sum()
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.orphaned_contents.is_empty()
+            && self.invalid_indexes.is_empty()
+            && self.malformed_users.is_empty()
+    }
+}
+
+/// Fixes for what [`IntegrityReport`] finds. Fetching missing indexes again
+/// needs the network so it's done by the client, see
+/// [`AkarekoClient::fetch_indexes`](crate::server::client::AkarekoClient::fetch_indexes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Repair {
+    DeleteOrphanedContents,
+    /// Deletes invalid indexes and suppresses them so they aren't synced back,
+    /// they can be let in again from the suppression list
+    QuarantineInvalidIndexes,
+    DeleteInvalidIndexes,
+    DeleteMalformedUsers,
+}
+
+/// A user id is malformed if it isn't base64 of a valid ed25519 key
+fn is_malformed_key(id: &str) -> bool {
+    PublicKey::from_base64(id).map_or(true, |key| !key.is_valid())
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn check_integrity<T: IndexTag>(&self) -> Result<IntegrityReport, DatabaseError> {
+        let indexes: Vec<Index<T>> = self.db.select(T::TAG).await?;
+        let stored: HashSet<Hash> = indexes.iter().map(|i| i.hash().clone()).collect();
+
+        let invalid_indexes = indexes
+            .into_iter()
+            .filter(|i| !i.verify())
+            .map(|i| (i.hash().clone(), i.title().clone()))
+            .collect();
+
+        let contents: Vec<Content<T>> = self.db.select(T::CONTENT_TABLE).await?;
+        let mut orphaned_contents: HashMap<Hash, Vec<Signature>> = HashMap::new();
+        for content in contents {
+            if !stored.contains(content.index_hash()) {
+                orphaned_contents
+                    .entry(content.index_hash().clone())
+                    .or_default()
+                    .push(content.signature().clone());
+            }
+        }
+
+        // Keys are read as plain strings, a malformed one would fail to
+        // deserialize the whole user
+        let user_ids: Vec<String> = self
+            .db
+            .query(format!(
+                "SELECT VALUE record::id(id) FROM {};",
+                User::TABLE_NAME
+            ))
+            .await?
+            .take(0)?;
+        let malformed_users = user_ids
+            .into_iter()
+            .filter(|id| is_malformed_key(id))
+            .collect();
+
+        Ok(IntegrityReport {
+            orphaned_contents,
+            invalid_indexes,
+            malformed_users,
+        })
+    }
+
+    /// Applies `repair` to what `report` found, returns how many records it
+    /// touched
+    pub async fn repair_integrity<T: IndexTag>(
+        &self,
+        report: &IntegrityReport,
+        repair: Repair,
+    ) -> Result<usize, DatabaseError> {
+        match repair {
+            Repair::DeleteOrphanedContents => {
+                for signature in report.orphaned_contents.values().flatten() {
+                    self.index().remove_content::<T>(signature.clone()).await?;
+                    self.record_audit(AuditEntry::deleted(
+                        signature.as_base64(),
+                        signature.as_base64(),
+                        false,
+                        "Its index isn't stored",
+                    ))
+                    .await?;
+                }
+                Ok(report.orphaned_count())
+            }
+            Repair::QuarantineInvalidIndexes | Repair::DeleteInvalidIndexes => {
+                for (hash, title) in &report.invalid_indexes {
+                    if repair == Repair::QuarantineInvalidIndexes {
+                        self.suppress(Suppression {
+                            id: hash.as_base64(),
+                            kind: SuppressedKind::Index,
+                            title: title.clone(),
+                            created_at: Timestamp::now(),
+                        })
+                        .await?;
+                    }
+                    self.index().delete_index::<T>(hash).await?;
+                    self.record_audit(AuditEntry::deleted(
+                        hash.as_base64(),
+                        title.clone(),
+                        repair == Repair::QuarantineInvalidIndexes,
+                        "Its signature doesn't verify",
+                    ))
+                    .await?;
+                }
+                Ok(report.invalid_indexes.len())
+            }
+            Repair::DeleteMalformedUsers => {
+                for id in &report.malformed_users {
+                    let _: Option<Value> = self.db.delete((User::TABLE_NAME, id.as_str())).await?;
+                    self.record_audit(AuditEntry::deleted(
+                        id.clone(),
+                        id.clone(),
+                        false,
+                        "Its key can't be a public key",
+                    ))
+                    .await?;
+                }
+                self.notify(DataKind::Users);
+                Ok(report.malformed_users.len())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Repair, is_malformed_key};
+    use crate::{
+        db::{Repositories, index::tags::MangaTag},
+        server::fixtures::{content, index},
+        types::PrivateKey,
+    };
+
+    #[test]
+    fn malformed_keys_are_detected() {
+        assert!(!is_malformed_key(
+            &PrivateKey::new().public_key().to_base64()
+        ));
+        assert!(is_malformed_key("not base64!"));
+        assert!(is_malformed_key("AAAA"));
+    }
+
+    #[tokio::test]
+    async fn orphaned_contents_are_found_and_deleted() {
+        let repo = Repositories::in_memory().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Gone", &priv_key);
+        let content = content(&index, 1.0, &priv_key);
+        repo.index().add_content(content.clone()).await.unwrap();
+
+        let report = repo.check_integrity::<MangaTag>().await.unwrap();
+        assert_eq!(
+            report.orphaned_contents.get(index.hash()),
+            Some(&vec![content.signature().clone()])
+        );
+
+        let repaired = repo
+            .repair_integrity::<MangaTag>(&report, Repair::DeleteOrphanedContents)
+            .await
+            .unwrap();
+        assert_eq!(repaired, 1);
+        assert!(repo.check_integrity::<MangaTag>().await.unwrap().is_clean());
+    }
+}
@@ -1,6 +1,7 @@
 use skerry::skerry;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
 use serde::{Deserialize, Serialize};
 use surrealdb::{
     Surreal,
@@ -12,35 +13,59 @@ use tracing::info;
 #[cfg(feature = "surrealdb")]
 use crate::db::follow_index::IndexFollowRepository;
 use crate::db::{
+    catalog::{CatalogFollow, CatalogGeneration},
+    changes::{DataChanges, DataKind},
     comments::Post,
+    content_source::ContentSource,
     follow_index::IndexFollow,
+    group::Group,
+    history::HistoryEntry,
     index::tags::{IndexTag, MangaTag},
+    suppression::Suppression,
+    torrent_link::TorrentLink,
 };
 use crate::errors::DatabaseError;
-use crate::types::Timestamp;
+use crate::types::{Clock, SystemClock, Timestamp};
 use crate::{
-    config::AkarekoConfig,
+    config::{AkarekoConfig, ContentLimitsConfig},
     db::{
         index::IndexRepository,
-        user::{User, UserRepository},
+        user::{
+            Attestation, MutedUploader, PeerStats, PeerSyncPolicy, Petname, User, UserRepository,
+        },
     },
 };
 use crate::{db::index::content::Content, types::PublicKey};
 
 // ==================== End Imports ====================
 
+pub mod audit;
+pub mod catalog;
+pub mod changes;
 pub mod comments;
+pub mod content_source;
+pub mod content_sync;
 pub mod event;
 pub mod follow_index;
 pub mod group;
+pub mod history;
 pub mod index;
+pub mod integrity;
+pub mod revocation;
 pub mod schedule;
 #[cfg(feature = "diesel")]
 pub mod schema;
+pub mod stats;
+pub mod subscription;
+pub mod suppression;
+pub mod torrent_link;
 pub mod user;
 
 pub const BLOOM_FILTER_FALSE_POSITIVE_RATE: f64 = 0.0001;
 
+/// Where [`Repositories::initialize`] keeps the database
+pub const DATABASE_PATH: &str = "./database/surreal";
+
 #[derive(Deserialize)]
 pub struct PaginateResponse<T> {
     pub values: T,
@@ -67,10 +92,76 @@ impl ToBytes for String {
 #[serde(transparent)]
 pub struct Magnet(pub String);
 
+impl Magnet {
+    /// v1 info hash as lowercase hex, base32 hashes are converted so the same
+    /// torrent always gives the same string. `None` if there's no `btih`.
+    pub fn info_hash(&self) -> Option<String> {
+        let params = self.0.strip_prefix("magnet:?")?;
+
+        params.split('&').find_map(|param| {
+            let hash = param.strip_prefix("xt=urn:btih:")?;
+            match hash.len() {
+                40 if hash.chars().all(|c| c.is_ascii_hexdigit()) => Some(hash.to_lowercase()),
+                32 => BASE32_NOPAD
+                    .decode(hash.to_uppercase().as_bytes())
+                    .ok()
+                    .map(|b| HEXLOWER.encode(&b)),
+                _ => None,
+            }
+        })
+    }
+
+    /// Drops the trackers and sources (`tr`, `ws`, `xs` and `as`) that aren't
+    /// on I2P or in `allowed`, the torrent layer would otherwise reach them
+    /// over clearnet. Returns the cleaned magnet and the URLs removed.
+    pub fn scrub_trackers(&self, allowed: &[String]) -> (Magnet, Vec<String>) {
+        let Some(params) = self.0.strip_prefix("magnet:?") else {
+            return (self.clone(), vec![]);
+        };
+
+        let mut kept = vec![];
+        let mut removed = vec![];
+        for param in params.split('&') {
+            let Some((key, value)) = url::form_urlencoded::parse(param.as_bytes()).next() else {
+                continue;
+            };
+            // Numbered as `tr.1` when there are several
+            let name = key.split('.').next().unwrap_or_default();
+            if !matches!(name, "tr" | "ws" | "xs" | "as") {
+                kept.push(param);
+                continue;
+            }
+
+            let host = url::Url::parse(&value)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_lowercase));
+            match host {
+                Some(host)
+                    if host.ends_with(".i2p")
+                        || allowed.iter().any(|a| a.eq_ignore_ascii_case(&host)) =>
+                {
+                    kept.push(param)
+                }
+                _ => removed.push(value.into_owned()),
+            }
+        }
+
+        (Magnet(format!("magnet:?{}", kept.join("&"))), removed)
+    }
+}
+
 #[derive(Clone)]
 pub struct Repositories {
     #[cfg(feature = "surrealdb")]
     pub db: Surreal<Db>,
+    changes: DataChanges,
+    /// Indexes and content stored are marked as relayed, see
+    /// [`RelayOnlyConfig`](crate::config::RelayOnlyConfig)
+    relay_only: bool,
+    /// Content past these isn't stored, read from the config when the
+    /// database is loaded
+    content_limits: ContentLimitsConfig,
+    clock: Arc<dyn Clock>,
 }
 
 impl std::fmt::Debug for Repositories {
@@ -116,21 +207,59 @@ impl Repositories {
             MangaTag::CONTENT_TABLE,
             &IndexFollow::<MangaTag>::table_name(),
             User::TABLE_NAME,
+            Petname::TABLE_NAME,
+            PeerStats::TABLE_NAME,
+            PeerSyncPolicy::TABLE_NAME,
+            MutedUploader::TABLE_NAME,
+            Attestation::TABLE_NAME,
             Post::TABLE_NAME,
             FullSyncTarget::TABLE_NAME,
+            TorrentLink::TABLE_NAME,
+            ContentSource::TABLE_NAME,
+            Group::TABLE_NAME,
+            Suppression::TABLE_NAME,
+            CatalogGeneration::TABLE_NAME,
+            CatalogFollow::TABLE_NAME,
+            HistoryEntry::TABLE_NAME,
             "events",
         ] {
             init_query.push_str(&format!("DEFINE TABLE IF NOT EXISTS {};\n", table));
         }
 
         init_query.push_str(
-            "DEFINE INDEX IF NOT EXISTS eventStamps ON TABLE events FIELDS timestamp, event_type;",
+            "DEFINE INDEX IF NOT EXISTS eventStamps ON TABLE events FIELDS timestamp, event_type;\n",
+        );
+        init_query.push_str(&format!(
+            "DEFINE INDEX IF NOT EXISTS {0}_index ON TABLE {0} FIELDS index;\n",
+            ContentSource::TABLE_NAME
+        ));
+        init_query.push_str(
+            "DEFINE ANALYZER IF NOT EXISTS post_text TOKENIZERS class FILTERS lowercase, ascii;\n",
         );
+        init_query.push_str(&format!(
+            "DEFINE INDEX IF NOT EXISTS {0}_content ON TABLE {0} FIELDS content FULLTEXT ANALYZER post_text BM25;\n",
+            Post::TABLE_NAME
+        ));
+
+        for table in [MangaTag::CONTENT_TABLE] {
+            init_query.push_str(&format!(
+                "DEFINE INDEX IF NOT EXISTS {0}_info_hash ON TABLE {0} FIELDS info_hash;\n",
+                table
+            ));
+        }
 
         db.query(init_query).await.unwrap();
-        Self { db }
+        Self {
+            db,
+            changes: DataChanges::new(),
+            relay_only: false,
+            content_limits: ContentLimitsConfig::default(),
+            clock: Arc::new(SystemClock),
+        }
     }
 
+    /// Throwaway database that only lives in memory, for tests of anything that
+    /// needs repositories
     pub async fn in_memory() -> Self {
         let db: Surreal<Db> = Surreal::new::<surrealdb::engine::local::Mem>(())
             .await
@@ -138,13 +267,26 @@ impl Repositories {
         Self::setup(db).await
     }
 
+    /// Repositories that were never connected, every query fails. For tests
+    /// of how database errors are handled.
+    #[cfg(test)]
+    pub fn disconnected() -> Self {
+        Self {
+            db: Surreal::init(),
+            changes: DataChanges::new(),
+            relay_only: false,
+            content_limits: ContentLimitsConfig::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
     pub async fn initialize(config: &AkarekoConfig) -> Self {
-        let db: Surreal<Db> = Surreal::new::<SurrealKv>("./database/surreal")
-            .await
-            .unwrap();
+        let db: Surreal<Db> = Surreal::new::<SurrealKv>(DATABASE_PATH).await.unwrap();
 
         info!("Initializing SurrealDB");
-        let repositories = Self::setup(db).await;
+        let mut repositories = Self::setup(db).await;
+        repositories.relay_only = config.relay_only().enabled;
+        repositories.content_limits = config.content_limits().clone();
         info!("Initialized SurrealDB");
 
         {
@@ -155,7 +297,7 @@ impl Repositories {
 
                     let mut user = User::new_signed(
                         "Anon".to_string(),
-                        Timestamp::now(),
+                        repositories.now(),
                         &config.private_key(),
                         config.eepsite_address().clone(),
                     );
@@ -201,16 +343,66 @@ impl Repositories {
         Ok(addresses)
     }
 
+    /// Same repositories reading the time from `clock`
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
+    }
+
+    pub fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    /// Fails when the database stopped answering
+    pub async fn health(&self) -> Result<(), DatabaseError> {
+        self.db.health().await?;
+        Ok(())
+    }
+
     pub fn user(&self) -> UserRepository<'_> {
-        UserRepository::new(&self.db)
+        UserRepository::new(&self.db, &self.changes, self.clock())
     }
 
     pub fn index(&self) -> IndexRepository<'_> {
-        IndexRepository::new(&self.db)
+        IndexRepository::new(
+            &self.db,
+            &self.changes,
+            self.relay_only,
+            &self.content_limits,
+            self.clock(),
+        )
     }
 
     pub fn index_follow(&self) -> IndexFollowRepository<'_> {
-        IndexFollowRepository::new(&self.db)
+        IndexFollowRepository::new(&self.db, &self.changes)
+    }
+
+    pub fn changes(&self) -> &DataChanges {
+        &self.changes
+    }
+
+    pub(crate) fn notify(&self, kind: DataKind) {
+        self.changes.notify(kind);
+    }
+}
+
+/// Error for a `CREATE` of `table:id`, surreal refuses to create a record that
+/// is already there and only tells us through the message
+pub(crate) fn create_error(
+    table: &str,
+    id: &str,
+) -> impl FnOnce(surrealdb::Error) -> DatabaseError {
+    let (table, id) = (table.to_string(), id.to_string());
+    move |e| {
+        if e.to_string().contains("already exists") {
+            DatabaseError::AlreadyExists { table, id }
+        } else {
+            e.into()
+        }
     }
 }
 
@@ -248,3 +440,54 @@ mod surreal {
 }
 #[cfg(feature = "surrealdb")]
 pub use surreal::*;
+
+#[cfg(test)]
+mod tests {
+    use super::Magnet;
+
+    #[test]
+    fn magnet_info_hash() {
+        let hex = Magnet(
+            "magnet:?xt=urn:btih:C12FE1C06BBA254A9DC9F519B335AA7C1367A88A&dn=test".to_string(),
+        );
+        assert_eq!(
+            hex.info_hash().as_deref(),
+            Some("c12fe1c06bba254a9dc9f519b335aa7c1367a88a")
+        );
+
+        let base32 =
+            Magnet("magnet:?dn=test&xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK".to_string());
+        assert_eq!(base32.info_hash(), hex.info_hash());
+
+        assert_eq!(Magnet("magnet:?dn=test".to_string()).info_hash(), None);
+        assert_eq!(Magnet("not a magnet".to_string()).info_hash(), None);
+    }
+    #[test]
+    fn clearnet_trackers_are_scrubbed() {
+        let magnet = Magnet(
+            "magnet:?xt=urn:btih:C12FE1C06BBA254A9DC9F519B335AA7C1367A88A&dn=test\
+            &tr=udp%3A%2F%2Ftracker.example.org%3A1337%2Fannounce\
+            &tr=http%3A%2F%2Ftracker2.i2p%2Fa\
+            &tr.1=http%3A%2F%2Ftracker.friends.net%2Fannounce\
+            &ws=https%3A%2F%2Fcdn.example.org%2Ftest"
+                .to_string(),
+        );
+
+        let (scrubbed, removed) = magnet.scrub_trackers(&["tracker.friends.net".to_string()]);
+
+        assert_eq!(
+            scrubbed.0,
+            "magnet:?xt=urn:btih:C12FE1C06BBA254A9DC9F519B335AA7C1367A88A&dn=test\
+            &tr=http%3A%2F%2Ftracker2.i2p%2Fa\
+            &tr.1=http%3A%2F%2Ftracker.friends.net%2Fannounce"
+        );
+        assert_eq!(
+            removed,
+            vec![
+                "udp://tracker.example.org:1337/announce",
+                "https://cdn.example.org/test"
+            ]
+        );
+        assert_eq!(scrubbed.info_hash(), magnet.info_hash());
+    }
+}
@@ -0,0 +1,233 @@
+//! Signed retractions of an index or a content by whoever published it. They
+//! travel between peers like the records they point to, each node deletes the
+//! target and keeps the revocation so the target isn't stored again.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "surrealdb")]
+use surrealdb::{Surreal, engine::local::Db};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{
+        Repositories, ToBytes,
+        index::{Index, content::Content, tags::IndexTag},
+    },
+    errors::DatabaseError,
+    types::{Hash, PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub enum RevokedKind {
+    Index,
+    Content,
+}
+
+impl RevokedKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            RevokedKind::Index => 0,
+            RevokedKind::Content => 1,
+        }
+    }
+}
+
+/// `publisher` takes back the index or content `target`. It only counts when
+/// `publisher` is the key that signed the target, anyone else's is ignored.
+/// Revoking an index also keeps out the contents its publisher posted in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SurrealValue)]
+pub struct Revocation {
+    #[surreal(rename = "id")]
+    pub signature: Signature,
+    pub kind: RevokedKind,
+    /// Base64 of the index hash or content signature
+    pub target: String,
+    pub publisher: PublicKey,
+    pub timestamp: Timestamp,
+
+    // Unsigned fields
+    /// When we stored it, peers ask for the ones stored since they last
+    /// exchanged with us
+    #[serde(skip)]
+    pub(crate) received_at: Option<Timestamp>,
+}
+
+impl Revocation {
+    pub const TABLE_NAME: &'static str = "revocations";
+
+    fn sign_bytes(
+        kind: RevokedKind,
+        target: &str,
+        publisher: &PublicKey,
+        timestamp: &Timestamp,
+    ) -> Vec<u8> {
+        let mut bytes = vec![kind.to_byte()];
+        bytes.extend(target.as_bytes());
+        bytes.extend(publisher.as_bytes());
+        bytes.extend(timestamp.to_bytes());
+        bytes
+    }
+
+    fn new_signed(kind: RevokedKind, target: String, priv_key: &PrivateKey) -> Self {
+        let publisher = priv_key.public_key();
+        let timestamp = Timestamp::now();
+        let signature = priv_key.sign(&Self::sign_bytes(kind, &target, &publisher, &timestamp));
+
+        Self {
+            signature,
+            kind,
+            target,
+            publisher,
+            timestamp,
+            received_at: None,
+        }
+    }
+
+    pub fn for_index<I: IndexTag>(index: &Index<I>, priv_key: &PrivateKey) -> Self {
+        Self::new_signed(RevokedKind::Index, index.hash().as_base64(), priv_key)
+    }
+
+    pub fn for_content<I: IndexTag>(content: &Content<I>, priv_key: &PrivateKey) -> Self {
+        Self::new_signed(
+            RevokedKind::Content,
+            content.signature().as_base64(),
+            priv_key,
+        )
+    }
+
+    pub fn verify(&self) -> bool {
+        let bytes = Self::sign_bytes(self.kind, &self.target, &self.publisher, &self.timestamp);
+        self.publisher.verify(&bytes, &self.signature)
+    }
+}
+
+/// Whether any of `ids` was revoked by `publisher`
+#[cfg(feature = "surrealdb")]
+pub(crate) async fn any_revoked(
+    db: &Surreal<Db>,
+    ids: &[String],
+    publisher: &PublicKey,
+) -> Result<bool, DatabaseError> {
+    let revoked: Vec<String> = db
+        .query(format!(
+            "SELECT VALUE target FROM {} WHERE target IN $ids AND publisher = $publisher LIMIT 1;",
+            Revocation::TABLE_NAME
+        ))
+        .bind(("ids", ids.to_vec()))
+        .bind(("publisher", publisher.clone()))
+        .await?
+        .take(0)?;
+
+    Ok(!revoked.is_empty())
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    /// Deletes what `revocation` points to and keeps it so the target isn't
+    /// stored again. A target we have that was signed by another key is left
+    /// alone and `false` is returned, the revocation isn't kept then, same for
+    /// one we already have. The revocation has to be verified first.
+    pub async fn revoke<T: IndexTag>(
+        &self,
+        mut revocation: Revocation,
+    ) -> Result<bool, DatabaseError> {
+        use surrealdb_types::Value;
+
+        // Stored again it would look new and be passed around forever
+        let id = (Revocation::TABLE_NAME, revocation.signature.as_base64());
+        let known: Option<Revocation> = self.db.select(id.clone()).await?;
+        if known.is_some() {
+            return Ok(false);
+        }
+
+        match revocation.kind {
+            RevokedKind::Index => {
+                let Ok(hash) = Hash::from_base64(&revocation.target) else {
+                    return Ok(false);
+                };
+                match self.index().get_index::<T>(&hash).await? {
+                    Some(index) if index.source() != &revocation.publisher => return Ok(false),
+                    Some(_) => {
+                        self.index().delete_index::<T>(&hash).await?;
+                    }
+                    None => {}
+                }
+            }
+            RevokedKind::Content => {
+                let Ok(signature) = Signature::from_base64(&revocation.target) else {
+                    return Ok(false);
+                };
+                let contents = self
+                    .index()
+                    .get_contents::<T>(std::slice::from_ref(&signature))
+                    .await?;
+                match contents.first() {
+                    Some(content) if content.poster() != &revocation.publisher => {
+                        return Ok(false);
+                    }
+                    Some(_) => self.index().remove_content::<T>(signature).await?,
+                    None => {}
+                }
+            }
+        }
+
+        revocation.received_at = Some(self.now());
+        let _: Option<Value> = self.db.upsert(id).content(revocation).await?;
+
+        Ok(true)
+    }
+
+    /// Up to `take` revocations stored since `since`, oldest first
+    pub async fn get_revocations_since(
+        &self,
+        since: Timestamp,
+        take: usize,
+    ) -> Result<Vec<Revocation>, DatabaseError> {
+        let revocations: Vec<Revocation> = self
+            .db
+            .query(format!(
+                "SELECT * FROM {} WHERE received_at >= $since ORDER BY received_at LIMIT $take;",
+                Revocation::TABLE_NAME
+            ))
+            .bind(("since", since))
+            .bind(("take", take))
+            .await?
+            .take(0)?;
+
+        Ok(revocations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Revocation;
+    use crate::{
+        db::{Repositories, index::tags::MangaTag},
+        server::fixtures::{content, index},
+        types::{PrivateKey, Timestamp},
+    };
+
+    #[tokio::test]
+    async fn revoked_content_stays_out() {
+        let repo = Repositories::in_memory().await;
+        let publisher = PrivateKey::new();
+        let index = index("Revoked", &publisher);
+        let content = content(&index, 1.0, &publisher);
+        repo.index().add_index(index.clone()).await.unwrap();
+        repo.index().add_content(content.clone()).await.unwrap();
+
+        let forged = Revocation::for_content(&content, &PrivateKey::new());
+        assert!(forged.verify());
+        assert!(!repo.revoke::<MangaTag>(forged).await.unwrap());
+
+        let revocation = Revocation::for_content(&content, &publisher);
+        assert!(repo.revoke::<MangaTag>(revocation.clone()).await.unwrap());
+        assert!(!repo.revoke::<MangaTag>(revocation).await.unwrap());
+        repo.index().add_content(content.clone()).await.unwrap();
+
+        let signatures = std::slice::from_ref(content.signature());
+        let stored = repo.index().get_contents::<MangaTag>(signatures).await;
+        assert!(stored.unwrap().is_empty());
+        let since = repo.get_revocations_since(Timestamp::new(0), 10).await;
+        assert_eq!(since.unwrap().len(), 1);
+    }
+}
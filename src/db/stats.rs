@@ -0,0 +1,108 @@
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Repositories, index::tags::IndexTag, torrent_link::TorrentLink},
+    errors::DatabaseError,
+    types::{PublicKey, Timestamp},
+};
+
+/// How many uploaders are listed in [`TagStats::top_uploaders`]
+const TOP_UPLOADERS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, SurrealValue)]
+pub struct UploaderCount {
+    pub poster: PublicKey,
+    pub count: usize,
+}
+
+/// Summary of everything stored for one [`IndexTag`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagStats {
+    pub tag: &'static str,
+    pub indexes: usize,
+    pub contents: usize,
+    /// Content we have a torrent for, the rest is only metadata
+    pub downloaded: usize,
+    pub top_uploaders: Vec<UploaderCount>,
+    /// Content added per month as `("YYYY-MM", count)`, oldest first
+    pub growth: Vec<(String, usize)>,
+}
+
+/// Buckets timestamps by month, months without any entries are left out
+pub fn monthly_growth(timestamps: &[Timestamp]) -> Vec<(String, usize)> {
+    let mut months: Vec<(String, usize)> = vec![];
+
+    let mut timestamps = timestamps.to_vec();
+    timestamps.sort();
+
+    for timestamp in timestamps {
+        let Ok(date) = time::OffsetDateTime::from_unix_timestamp(timestamp.as_secs()) else {
+            continue;
+        };
+        let month = format!("{:04}-{:02}", date.year(), date.month() as u8);
+
+        match months.last_mut() {
+            Some((last, count)) if *last == month => *count += 1,
+            _ => months.push((month, 1)),
+        }
+    }
+
+    months
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn get_tag_stats<I: IndexTag>(&self) -> Result<TagStats, DatabaseError> {
+        let query = format!(
+            "SELECT VALUE count() FROM {index} GROUP ALL;
+            SELECT VALUE count() FROM {content} GROUP ALL;
+            SELECT VALUE count() FROM {links} WHERE tag = $tag GROUP ALL;
+            SELECT poster, count() AS count FROM {content}
+                GROUP BY poster ORDER BY count DESC LIMIT {top};
+            SELECT VALUE timestamp FROM {content};",
+            index = I::TAG,
+            content = I::CONTENT_TABLE,
+            links = TorrentLink::TABLE_NAME,
+            top = TOP_UPLOADERS,
+        );
+
+        let mut response = self.db.query(query).bind(("tag", I::TAG)).await?;
+
+        let indexes: Option<usize> = response.take(0)?;
+        let contents: Option<usize> = response.take(1)?;
+        let downloaded: Option<usize> = response.take(2)?;
+        let top_uploaders: Vec<UploaderCount> = response.take(3)?;
+        let timestamps: Vec<Timestamp> = response.take(4)?;
+
+        Ok(TagStats {
+            tag: I::TAG,
+            indexes: indexes.unwrap_or_default(),
+            contents: contents.unwrap_or_default(),
+            downloaded: downloaded.unwrap_or_default(),
+            top_uploaders,
+            growth: monthly_growth(&timestamps),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::monthly_growth;
+    use crate::types::Timestamp;
+
+    #[test]
+    fn growth_is_bucketed_by_month() {
+        let jan = 1_704_067_200; // 2024-01-01
+        let feb = 1_706_745_600; // 2024-02-01
+        let timestamps = [
+            Timestamp::new(feb),
+            Timestamp::new(jan),
+            Timestamp::new(jan + 60),
+        ];
+
+        assert_eq!(
+            monthly_growth(&timestamps),
+            vec![("2024-01".to_string(), 2), ("2024-02".to_string(), 1)]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Repositories, user::I2PAddress},
+    errors::DatabaseError,
+    types::{Hash, Timestamp},
+};
+
+/// Subscriptions not renewed for this long are dropped, peers renew theirs
+/// on every routine exchange
+pub const SUBSCRIPTION_TTL: Timestamp = Timestamp::new(30 * 24 * 60 * 60);
+
+/// A peer that asked to be pushed the new contents of one of our indexes as
+/// soon as we publish them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, SurrealValue)]
+pub struct Subscription {
+    /// Base64 of the index hash followed by the address, one per pair
+    #[surreal(rename = "id")]
+    id: String,
+    pub index_hash: Hash,
+    pub address: I2PAddress,
+    pub renewed_at: Timestamp,
+}
+
+impl Subscription {
+    pub const TABLE_NAME: &'static str = "subscriptions";
+
+    pub fn new(index_hash: Hash, address: I2PAddress, renewed_at: Timestamp) -> Self {
+        Self {
+            id: format!("{}{}", index_hash.as_base64(), address.inner()),
+            index_hash,
+            address,
+            renewed_at,
+        }
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    /// Subscribes `address` to each of `index_hashes`, or renews it, and drops
+    /// the subscriptions that expired
+    pub async fn subscribe(
+        &self,
+        index_hashes: &[Hash],
+        address: &I2PAddress,
+    ) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let now = self.now();
+        for hash in index_hashes {
+            let subscription = Subscription::new(hash.clone(), address.clone(), now);
+            let _: Option<Value> = self
+                .db
+                .upsert((Subscription::TABLE_NAME, subscription.id.clone()))
+                .content(subscription)
+                .await?;
+        }
+
+        self.db
+            .query(format!(
+                "DELETE FROM {} WHERE renewed_at < $since;",
+                Subscription::TABLE_NAME
+            ))
+            .bind(("since", now - SUBSCRIPTION_TTL))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Addresses to push the new contents of `index_hash` to
+    pub async fn get_subscribers(
+        &self,
+        index_hash: &Hash,
+    ) -> Result<Vec<I2PAddress>, DatabaseError> {
+        let addresses: Vec<I2PAddress> = self
+            .db
+            .query(format!(
+                "SELECT VALUE address FROM {} WHERE index_hash = $index_hash AND renewed_at >= $since;",
+                Subscription::TABLE_NAME
+            ))
+            .bind(("index_hash", index_hash.clone()))
+            .bind(("since", self.now() - SUBSCRIPTION_TTL))
+            .await?
+            .take(0)?;
+
+        Ok(addresses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{Repositories, user::I2PAddress},
+        types::Hash,
+    };
+
+    #[tokio::test]
+    async fn renewing_keeps_one_subscription() {
+        let repo = Repositories::in_memory().await;
+        let (followed, other) = (Hash::new([1; 64]), Hash::new([2; 64]));
+        let peer = I2PAddress::new("peer.b32.i2p");
+
+        repo.subscribe(&[followed.clone()], &peer).await.unwrap();
+        repo.subscribe(&[followed.clone()], &peer).await.unwrap();
+
+        assert_eq!(repo.get_subscribers(&followed).await.unwrap(), vec![peer]);
+        assert!(repo.get_subscribers(&other).await.unwrap().is_empty());
+    }
+}
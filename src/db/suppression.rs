@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "surrealdb")]
+use surrealdb::{Surreal, engine::local::Db};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{
+        Repositories,
+        changes::DataKind,
+        index::{Index, content::Content, tags::IndexTag},
+    },
+    errors::DatabaseError,
+    types::Timestamp,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub enum SuppressedKind {
+    Index,
+    Content,
+}
+
+/// Something deleted on purpose that must not come back through sync. Checked
+/// by [`IndexRepository::add_index`](crate::db::index::IndexRepository::add_index)
+/// and [`IndexRepository::add_content`](crate::db::index::IndexRepository::add_content),
+/// suppressing an index also keeps out all of its contents.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub struct Suppression {
+    /// Base64 of the index hash or content signature
+    #[surreal(rename = "id")]
+    pub id: String,
+    pub kind: SuppressedKind,
+    /// Only so the settings list says what it is
+    pub title: String,
+    pub created_at: Timestamp,
+}
+
+impl Suppression {
+    pub const TABLE_NAME: &'static str = "suppressions";
+
+    pub fn from_index<I: IndexTag>(index: &Index<I>) -> Self {
+        Self {
+            id: index.hash().as_base64(),
+            kind: SuppressedKind::Index,
+            title: index.title().clone(),
+            created_at: Timestamp::now(),
+        }
+    }
+
+    pub fn from_content<I: IndexTag>(content: &Content<I>) -> Self {
+        Self {
+            id: content.signature().as_base64(),
+            kind: SuppressedKind::Content,
+            title: format!("Ch. {}: {}", content.enumeration(), content.title()),
+            created_at: Timestamp::now(),
+        }
+    }
+}
+
+/// Whether any of `ids` is suppressed
+#[cfg(feature = "surrealdb")]
+pub(crate) async fn any_suppressed(
+    db: &Surreal<Db>,
+    ids: &[String],
+) -> Result<bool, DatabaseError> {
+    for id in ids {
+        let suppression: Option<Suppression> =
+            db.select((Suppression::TABLE_NAME, id.as_str())).await?;
+        if suppression.is_some() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn suppress(&self, suppression: Suppression) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Option<Value> = self
+            .db
+            .upsert((Suppression::TABLE_NAME, suppression.id.clone()))
+            .content(suppression)
+            .await?;
+
+        self.notify(DataKind::Suppressions);
+
+        Ok(())
+    }
+
+    pub async fn get_suppressions(&self) -> Result<Vec<Suppression>, DatabaseError> {
+        let query = format!(
+            "SELECT * FROM {} ORDER BY created_at DESC;",
+            Suppression::TABLE_NAME
+        );
+
+        let suppressions: Vec<Suppression> = self.db.query(query).await?.take(0)?;
+
+        Ok(suppressions)
+    }
+
+    /// Lets it be synced again, nothing that was deleted comes back until then
+    pub async fn unsuppress(&self, id: &str) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Option<Value> = self.db.delete((Suppression::TABLE_NAME, id)).await?;
+
+        self.notify(DataKind::Suppressions);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Suppression;
+    use crate::{
+        db::{Repositories, index::tags::MangaTag},
+        server::fixtures::index,
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn suppressed_index_is_not_stored() {
+        let repo = Repositories::in_memory().await;
+        let index = index("Unwanted", &PrivateKey::new());
+        let suppression = Suppression::from_index(&index);
+
+        repo.suppress(suppression.clone()).await.unwrap();
+        repo.index().add_index(index.clone()).await.unwrap();
+        assert!(
+            repo.index()
+                .get_index::<MangaTag>(index.hash())
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        repo.unsuppress(&suppression.id).await.unwrap();
+        assert!(repo.get_suppressions().await.unwrap().is_empty());
+        repo.index().add_index(index.clone()).await.unwrap();
+        assert!(
+            repo.index()
+                .get_index::<MangaTag>(index.hash())
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use surrealdb_types::SurrealValue;
+
+use crate::{
+    db::{Magnet, Repositories, changes::DataKind, index::content::Content, index::tags::IndexTag},
+    errors::DatabaseError,
+    types::{Signature, Timestamp},
+};
+
+/// Remembers which content a torrent was downloaded for, so a torrent can be
+/// traced back to its chapter without going through every magnet.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, SurrealValue)]
+pub struct TorrentLink {
+    /// Info hash as given by [`Magnet::info_hash`]
+    #[surreal(rename = "id")]
+    pub info_hash: String,
+    pub magnet: Magnet,
+    pub content: Signature,
+    /// [`IndexTag::TAG`] of the content
+    pub tag: String,
+    pub title: String,
+    pub path: String,
+    pub created_at: Timestamp,
+    /// Whether the files matched the signed manifest once downloaded, `None`
+    /// if not checked yet or the content has no manifest
+    #[serde(default)]
+    pub verified: Option<bool>,
+    /// When the torrent finished downloading, `None` while it's still going
+    #[serde(default)]
+    pub completed_at: Option<Timestamp>,
+}
+
+impl TorrentLink {
+    pub const TABLE_NAME: &'static str = "torrent_links";
+
+    pub fn from_content<I: IndexTag>(content: &Content<I>, path: String) -> Option<Self> {
+        Some(Self {
+            info_hash: content.magnet_link.info_hash()?,
+            magnet: content.magnet_link.clone(),
+            content: content.signature().clone(),
+            tag: I::TAG.to_string(),
+            title: format!("Ch. {}: {}", content.enumeration(), content.title()),
+            path,
+            created_at: Timestamp::now(),
+            verified: None,
+            completed_at: None,
+        })
+    }
+}
+
+#[cfg(feature = "surrealdb")]
+impl Repositories {
+    pub async fn upsert_torrent_link(&self, link: TorrentLink) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Vec<Value> = self
+            .db
+            .upsert(TorrentLink::TABLE_NAME)
+            .content(link)
+            .await?;
+
+        self.notify(DataKind::TorrentLinks);
+
+        Ok(())
+    }
+
+    pub async fn get_torrent_link(
+        &self,
+        info_hash: &str,
+    ) -> Result<Option<TorrentLink>, DatabaseError> {
+        let link: Option<TorrentLink> =
+            self.db.select((TorrentLink::TABLE_NAME, info_hash)).await?;
+
+        Ok(link)
+    }
+
+    pub async fn get_torrent_links(&self) -> Result<Vec<TorrentLink>, DatabaseError> {
+        let links: Vec<TorrentLink> = self.db.select(TorrentLink::TABLE_NAME).await?;
+        Ok(links)
+    }
+
+    pub async fn remove_torrent_link(&self, info_hash: &str) -> Result<(), DatabaseError> {
+        use surrealdb_types::Value;
+
+        let _: Option<Value> = self.db.delete((TorrentLink::TABLE_NAME, info_hash)).await?;
+
+        self.notify(DataKind::TorrentLinks);
+
+        Ok(())
+    }
+}
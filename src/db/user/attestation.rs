@@ -0,0 +1,65 @@
+//! Signed statements of one key vouching for another, shared with peers so
+//! those vouched for by enough of our fully trusted users can be trusted too.
+
+use serde::{Deserialize, Serialize};
+use surrealdb::types::SurrealValue;
+
+use crate::{
+    db::{ToBytes, user::TrustLevel},
+    types::{PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+/// `voucher` vouches for `subject` at `level`. Only the latest one from each
+/// voucher about a subject is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "surrealdb", derive(SurrealValue))]
+pub struct Attestation {
+    #[cfg_attr(feature = "surrealdb", surreal(rename = "id"))]
+    pub signature: Signature,
+    pub voucher: PublicKey,
+    pub subject: PublicKey,
+    pub level: TrustLevel,
+    pub timestamp: Timestamp,
+}
+
+impl Attestation {
+    pub const TABLE_NAME: &str = "attestations";
+
+    fn sign_bytes(
+        voucher: &PublicKey,
+        subject: &PublicKey,
+        level: TrustLevel,
+        timestamp: &Timestamp,
+    ) -> Vec<u8> {
+        let mut bytes = voucher.as_bytes().to_vec();
+        bytes.extend(subject.as_bytes());
+        bytes.push(level.into());
+        bytes.extend(timestamp.to_bytes());
+        bytes
+    }
+
+    pub fn new_signed(subject: PublicKey, level: TrustLevel, priv_key: &PrivateKey) -> Self {
+        let voucher = priv_key.public_key();
+        let timestamp = Timestamp::now();
+        let signature = priv_key.sign(&Self::sign_bytes(&voucher, &subject, level, &timestamp));
+
+        Self {
+            signature,
+            voucher,
+            subject,
+            level,
+            timestamp,
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        let bytes = Self::sign_bytes(&self.voucher, &self.subject, self.level, &self.timestamp);
+        self.voucher.verify(&bytes, &self.signature)
+    }
+
+    /// Whether it counts towards elevating the subject, vouching for yourself
+    /// doesn't
+    pub fn vouches(&self) -> bool {
+        self.level >= TrustLevel::Trusted && self.voucher != self.subject
+    }
+}
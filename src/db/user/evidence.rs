@@ -0,0 +1,238 @@
+//! Why we blocked a user, bundled into a file for other operators. The
+//! records are carried as they were signed, so whoever imports the bundle
+//! checks them against the user's key instead of taking our word for it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        ToBytes,
+        comments::Post,
+        index::{Index, content::Content, tags::MangaTag},
+        user::User,
+    },
+    types::{PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+/// Records of each kind put in a bundle, newest first
+pub const MAX_EVIDENCE_PER_KIND: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EvidenceRecord {
+    Post(Post),
+    Index(Index<MangaTag>),
+    Content(Content<MangaTag>),
+}
+
+impl EvidenceRecord {
+    /// Key the record claims to be signed with
+    pub fn signer(&self) -> &PublicKey {
+        match self {
+            EvidenceRecord::Post(post) => &post.source,
+            EvidenceRecord::Index(index) => index.source(),
+            EvidenceRecord::Content(content) => content.poster(),
+        }
+    }
+
+    pub fn signature(&self) -> &Signature {
+        match self {
+            EvidenceRecord::Post(post) => &post.signature,
+            EvidenceRecord::Index(index) => index.signature(),
+            EvidenceRecord::Content(content) => content.signature(),
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        match self {
+            EvidenceRecord::Post(post) => post.verify(),
+            EvidenceRecord::Index(index) => index.verify(),
+            EvidenceRecord::Content(content) => content.verify(),
+        }
+    }
+}
+
+/// A record along with what its signature check gave on the reporter's node
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceItem {
+    pub record: EvidenceRecord,
+    pub verified: bool,
+}
+
+impl EvidenceItem {
+    pub fn checked(record: EvidenceRecord) -> Self {
+        Self {
+            verified: record.verify(),
+            record,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvidenceBundle {
+    /// The reported user's own signed record
+    pub user: User,
+    pub reason: String,
+    pub items: Vec<EvidenceItem>,
+    pub exported_at: Timestamp,
+    /// Who reports the user, unsigned bundles are taken as is
+    pub reporter: Option<(PublicKey, Signature)>,
+}
+
+impl EvidenceBundle {
+    pub fn new(user: User, reason: String, items: Vec<EvidenceItem>) -> Self {
+        Self {
+            user,
+            reason,
+            items,
+            exported_at: Timestamp::now(),
+            reporter: None,
+        }
+    }
+
+    pub fn new_signed(
+        user: User,
+        reason: String,
+        items: Vec<EvidenceItem>,
+        priv_key: &PrivateKey,
+    ) -> Self {
+        let mut bundle = Self::new(user, reason, items);
+        let signature = priv_key.sign(&bundle.sign_bytes());
+        bundle.reporter = Some((priv_key.public_key(), signature));
+        bundle
+    }
+
+    fn sign_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.exported_at.to_bytes();
+        bytes.extend(self.user.pub_key().as_bytes());
+        bytes.extend(self.reason.as_bytes());
+        bytes.push(0);
+        for item in &self.items {
+            bytes.extend(item.record.signature().as_ref());
+            bytes.push(item.verified as u8);
+        }
+        bytes
+    }
+
+    /// Unsigned bundles verify, there's nothing to check
+    pub fn verify(&self) -> bool {
+        match &self.reporter {
+            Some((pub_key, signature)) => pub_key.verify(&self.sign_bytes(), signature),
+            None => true,
+        }
+    }
+
+    /// Checks every record again with our own keys
+    pub fn check(&self) -> EvidenceCheck {
+        let mut check = EvidenceCheck {
+            user_verified: self.user.verify(),
+            ..Default::default()
+        };
+
+        for item in &self.items {
+            let signed_by_user =
+                item.record.signer() == self.user.pub_key() && item.record.verify();
+            if signed_by_user {
+                check.confirmed += 1;
+            } else {
+                check.unconfirmed += 1;
+            }
+            if signed_by_user != item.verified {
+                check.disputed += 1;
+            }
+        }
+
+        check
+    }
+}
+
+/// What checking a bundle on our side gave
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvidenceCheck {
+    /// The user record itself is signed by its key
+    pub user_verified: bool,
+    /// Records signed by the reported user
+    pub confirmed: usize,
+    /// Records that fail their signature or belong to someone else, they
+    /// prove nothing about the user
+    pub unconfirmed: usize,
+    /// Records our check disagrees with the reporter's on
+    pub disputed: usize,
+}
+
+impl EvidenceCheck {
+    /// Only records the user provably signed are grounds to block them
+    pub fn is_conclusive(&self) -> bool {
+        self.user_verified && self.confirmed > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::user::I2PAddress, types::Topic};
+
+    fn post(content: &str, priv_key: &PrivateKey) -> Post {
+        Post::new_signed(
+            content.to_string(),
+            Timestamp::new(1_700_000_000),
+            Topic::from_bytes([0; 64]),
+            priv_key,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn only_records_signed_by_the_user_count() {
+        let spammer = PrivateKey::new();
+        let user = User::new_signed(
+            "Spammer".to_string(),
+            Timestamp::new(0),
+            &spammer,
+            I2PAddress::new("spam.b32.i2p"),
+        );
+        let mut forged = post("forged", &spammer);
+        forged.content = "changed after signing".to_string();
+
+        let items = vec![
+            EvidenceItem::checked(EvidenceRecord::Post(post("spam", &spammer))),
+            EvidenceItem::checked(EvidenceRecord::Post(forged)),
+            EvidenceItem::checked(EvidenceRecord::Post(post("other", &PrivateKey::new()))),
+        ];
+        let bundle =
+            EvidenceBundle::new_signed(user, "Spam".to_string(), items, &PrivateKey::new());
+
+        assert!(bundle.verify());
+        assert_eq!(
+            bundle.check(),
+            EvidenceCheck {
+                user_verified: true,
+                confirmed: 1,
+                unconfirmed: 2,
+                disputed: 1,
+            }
+        );
+        assert!(bundle.check().is_conclusive());
+    }
+
+    #[test]
+    fn tampered_bundles_fail() {
+        let spammer = PrivateKey::new();
+        let user = User::new_signed(
+            "Spammer".to_string(),
+            Timestamp::new(0),
+            &spammer,
+            I2PAddress::new("spam.b32.i2p"),
+        );
+        let mut bundle = EvidenceBundle::new_signed(
+            user,
+            "Spam".to_string(),
+            vec![EvidenceItem::checked(EvidenceRecord::Post(post(
+                "spam", &spammer,
+            )))],
+            &PrivateKey::new(),
+        );
+        bundle.reason = "Something else".to_string();
+
+        assert!(!bundle.verify());
+    }
+}
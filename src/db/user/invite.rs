@@ -0,0 +1,107 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use qrcode::{QrCode, render::svg};
+
+use crate::{db::user::I2PAddress, errors::InviteError, types::PublicKey};
+
+const INVITE_PREFIX: &str = "akareko:";
+/// Smallest side of the QR code, in pixels
+const QR_SIZE: u32 = 200;
+
+/// Everything needed to add a peer in a single string, formatted as
+/// `akareko:<public key>@<i2p address>`.
+///
+/// The public key is only a claim, the peer still has to be asked with `who`
+/// and the returned user checked against it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Invite {
+    pub pub_key: PublicKey,
+    pub address: I2PAddress,
+}
+
+impl Invite {
+    pub fn new(pub_key: PublicKey, address: I2PAddress) -> Self {
+        Self { pub_key, address }
+    }
+
+    /// The invite as an SVG QR code, for scanning it instead of copying it
+    pub fn to_qr_svg(&self) -> Result<String, InviteError> {
+        let code = QrCode::new(self.to_string())?;
+        Ok(code
+            .render::<svg::Color>()
+            .min_dimensions(QR_SIZE, QR_SIZE)
+            .build())
+    }
+}
+
+impl Display for Invite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}@{}",
+            INVITE_PREFIX,
+            self.pub_key.to_base64(),
+            self.address
+        )
+    }
+}
+
+impl FromStr for Invite {
+    type Err = InviteError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(invite) = s.trim().strip_prefix(INVITE_PREFIX) else {
+            return Err(InviteError::MissingPrefix);
+        };
+
+        let Some((pub_key, address)) = invite.split_once('@') else {
+            return Err(InviteError::MissingAddress);
+        };
+
+        if address.is_empty() {
+            return Err(InviteError::MissingAddress);
+        }
+
+        Ok(Invite {
+            pub_key: PublicKey::from_base64(pub_key)?,
+            address: I2PAddress::new(address),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PrivateKey;
+
+    #[test]
+    fn invite_round_trip() {
+        let invite = Invite::new(
+            PrivateKey::new().public_key(),
+            I2PAddress::new("ukeu3k5oycgaauneqgtnvselmt4yemvoilkln7jpvamvfx7dnkdq.b32.i2p"),
+        );
+
+        let parsed: Invite = invite.to_string().parse().unwrap();
+        assert_eq!(parsed, invite);
+    }
+
+    #[test]
+    fn qr_code_fits_an_invite() {
+        let invite = Invite::new(
+            PrivateKey::new().public_key(),
+            I2PAddress::new("ukeu3k5oycgaauneqgtnvselmt4yemvoilkln7jpvamvfx7dnkdq.b32.i2p"),
+        );
+
+        assert!(invite.to_qr_svg().unwrap().starts_with("<?xml"));
+    }
+
+    #[test]
+    fn invite_rejects_garbage() {
+        assert!("".parse::<Invite>().is_err());
+        assert!("akareko:".parse::<Invite>().is_err());
+        assert!("akareko:abc@".parse::<Invite>().is_err());
+    }
+}
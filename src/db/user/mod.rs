@@ -1,5 +1,7 @@
 use std::fmt::{Display, Formatter};
 
+use bitflags::bitflags;
+
 #[cfg(feature = "diesel")]
 use diesel::{
     Selectable,
@@ -17,6 +19,17 @@ use crate::{
     types::{PrivateKey, PublicKey, Signable, Signature},
 };
 
+mod attestation;
+pub use attestation::Attestation;
+mod invite;
+pub use invite::Invite;
+mod trust_list;
+pub use trust_list::{TrustConflict, TrustEntry, TrustList, TrustMerge};
+mod evidence;
+pub use evidence::{
+    EvidenceBundle, EvidenceCheck, EvidenceItem, EvidenceRecord, MAX_EVIDENCE_PER_KIND,
+};
+
 #[cfg(feature = "sqlite")]
 mod sqlite;
 #[cfg(feature = "sqlite")]
@@ -35,9 +48,13 @@ pub use surreal::UserRepository;
     Hash,
     PartialEq,
     Eq,
+    PartialOrd,
+    Ord,
     Default, // FromSqlRow,
     // AsExpression,
     EnumIter,
+    Serialize,
+    Deserialize,
 )]
 // #[diesel(sql_type = diesel::sql_types::Integer)]
 #[repr(u8)]
@@ -266,8 +283,173 @@ impl User {
     }
 }
 
+/// Local nickname for a public key. Never sent to other peers, it's only used
+/// to recognize them regardless of the name they sign their records with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "surrealdb", derive(SurrealValue))]
+pub struct Petname {
+    #[cfg_attr(feature = "surrealdb", surreal(rename = "id"))]
+    pub pub_key: PublicKey,
+    pub name: String,
+}
+
+impl Petname {
+    pub const TABLE_NAME: &str = "petnames";
+}
+
+bitflags! {
+    /// What we exchange with a peer, checked on both sides of a connection:
+    /// by the client before storing what the peer sent, and by the server
+    /// before answering the peer.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct SyncPolicy: u8 {
+        const ACCEPT_INDEXES = 1;
+        const ACCEPT_CONTENT = 1 << 1;
+        const ACCEPT_POSTS = 1 << 2;
+        /// Let the peer fetch what we have published
+        const SEND_PUBLISHED = 1 << 3;
+    }
+}
+
+impl Default for SyncPolicy {
+    fn default() -> Self {
+        SyncPolicy::all()
+    }
+}
+
+/// Sync policy set for a public key, peers without one use
+/// [`SyncPolicy::default`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "surrealdb", derive(SurrealValue))]
+pub struct PeerSyncPolicy {
+    #[cfg_attr(feature = "surrealdb", surreal(rename = "id"))]
+    pub pub_key: PublicKey,
+    pub policy: SyncPolicy,
+}
+
+impl PeerSyncPolicy {
+    pub const TABLE_NAME: &str = "sync_policies";
+}
+
+/// Uploader whose indexes and content are hidden from the library. Their
+/// records are still stored and relayed to others, muting is local only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "surrealdb", derive(SurrealValue))]
+pub struct MutedUploader {
+    #[cfg_attr(feature = "surrealdb", surreal(rename = "id"))]
+    pub pub_key: PublicKey,
+    pub muted_at: Timestamp,
+}
+
+impl MutedUploader {
+    pub const TABLE_NAME: &str = "muted_uploaders";
+}
+
+/// How a peer has been answering our pings and when we last synced with it,
+/// local only
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "surrealdb", derive(SurrealValue))]
+pub struct PeerStats {
+    #[cfg_attr(feature = "surrealdb", surreal(rename = "id"))]
+    pub pub_key: PublicKey,
+    /// Round trip of the last successful ping
+    pub last_rtt_ms: Option<u64>,
+    /// When we first heard of the user, through its record or a connection
+    pub first_seen: Option<Timestamp>,
+    pub last_seen: Option<Timestamp>,
+    pub successes: u32,
+    pub failures: u32,
+    /// Last completed event sync, peers synced recently are skipped
+    pub last_exchange: Option<Timestamp>,
+}
+
+impl PeerStats {
+    pub const TABLE_NAME: &str = "peer_stats";
+
+    pub fn new(pub_key: PublicKey) -> Self {
+        Self {
+            pub_key,
+            last_rtt_ms: None,
+            first_seen: None,
+            last_seen: None,
+            successes: 0,
+            failures: 0,
+            last_exchange: None,
+        }
+    }
+
+    pub fn seen(&mut self, at: Timestamp) {
+        self.first_seen.get_or_insert(at);
+        self.last_seen = Some(at);
+    }
+}
+
 impl Display for User {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.name)
     }
 }
+
+/// Order the user list is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UserSort {
+    #[default]
+    Name,
+    /// Most trusted first
+    Trust,
+    /// Most recently seen first, never seen last
+    LastSeen,
+    /// Most recently synced with first, never synced last
+    LastExchange,
+    /// Users with an address first
+    Address,
+}
+
+impl UserSort {
+    /// Used for selecting in UI
+    pub const ALL: [UserSort; 5] = [
+        UserSort::Name,
+        UserSort::Trust,
+        UserSort::LastSeen,
+        UserSort::LastExchange,
+        UserSort::Address,
+    ];
+}
+
+impl Display for UserSort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserSort::Name => write!(f, "Name"),
+            UserSort::Trust => write!(f, "Trust"),
+            UserSort::LastSeen => write!(f, "Last seen"),
+            UserSort::LastExchange => write!(f, "Last exchange"),
+            UserSort::Address => write!(f, "Address"),
+        }
+    }
+}
+
+/// Which users the user list shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum UserFilter {
+    #[default]
+    All,
+    /// Trusted and above
+    Trusted,
+    /// Ignored users
+    Blocked,
+}
+
+impl UserFilter {
+    /// Used for selecting in UI
+    pub const ALL: [UserFilter; 3] = [UserFilter::All, UserFilter::Trusted, UserFilter::Blocked];
+}
+
+impl Display for UserFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserFilter::All => write!(f, "All"),
+            UserFilter::Trusted => write!(f, "Trusted"),
+            UserFilter::Blocked => write!(f, "Blocked"),
+        }
+    }
+}
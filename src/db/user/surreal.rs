@@ -1,19 +1,28 @@
+use std::collections::{HashMap, HashSet};
+
 use surrealdb::{Surreal, engine::local::Db, types::RecordId};
 use surrealdb_types::{SurrealValue, Value};
 
 use crate::{
     db::{
+        audit::{AuditEntry, AuditKind, append_audit},
+        changes::{DataChanges, DataKind},
         event::{Event, EventType, insert_event},
-        user::TrustLevel,
+        user::{
+            Attestation, I2PAddress, MutedUploader, PeerStats, PeerSyncPolicy, Petname, SyncPolicy,
+            TrustConflict, TrustEntry, TrustLevel, TrustList, TrustMerge, UserFilter, UserSort,
+        },
     },
     errors::DatabaseError,
-    types::{PublicKey, Timestamp, Topic},
+    types::{Clock, PublicKey, Timestamp, Topic},
 };
 
 use super::User;
 
 pub struct UserRepository<'a> {
     db: &'a Surreal<Db>,
+    changes: &'a DataChanges,
+    clock: &'a dyn Clock,
 }
 
 impl SurrealValue for TrustLevel {
@@ -38,9 +47,31 @@ impl SurrealValue for TrustLevel {
     }
 }
 
+impl SurrealValue for SyncPolicy {
+    fn kind_of() -> surrealdb_types::Kind {
+        surrealdb_types::Kind::Number
+    }
+
+    fn into_value(self) -> surrealdb_types::Value {
+        self.bits().into_value()
+    }
+
+    fn from_value(value: surrealdb_types::Value) -> Result<Self, surrealdb::Error>
+    where
+        Self: Sized,
+    {
+        // Flags from a newer version are dropped instead of failing the read
+        Ok(SyncPolicy::from_bits_truncate(u8::from_value(value)?))
+    }
+}
+
 impl<'a> UserRepository<'a> {
-    pub fn new(db: &'a Surreal<Db>) -> UserRepository<'a> {
-        UserRepository { db }
+    pub fn new(
+        db: &'a Surreal<Db>,
+        changes: &'a DataChanges,
+        clock: &'a dyn Clock,
+    ) -> UserRepository<'a> {
+        UserRepository { db, changes, clock }
     }
 }
 
@@ -48,7 +79,7 @@ impl<'a> UserRepository<'a> {
     pub async fn upsert_user(&self, user: User) -> Result<(), DatabaseError> {
         let transaction = self.db.clone().begin().await?;
 
-        let timestamp = Timestamp::now();
+        let timestamp = self.clock.now();
 
         let event = Event {
             timestamp,
@@ -62,13 +93,15 @@ impl<'a> UserRepository<'a> {
 
         transaction.commit().await?;
 
+        self.changes.notify(DataKind::Users);
+
         Ok(())
     }
 
     pub async fn upsert_users(&self, users: Vec<User>) -> Result<(), DatabaseError> {
         let transaction = self.db.clone().begin().await?;
 
-        let timestamp = Timestamp::now();
+        let timestamp = self.clock.now();
 
         let events = users
             .iter()
@@ -85,6 +118,8 @@ impl<'a> UserRepository<'a> {
 
         transaction.commit().await?;
 
+        self.changes.notify(DataKind::Users);
+
         Ok(())
     }
 
@@ -150,9 +185,128 @@ impl<'a> UserRepository<'a> {
         Ok(results)
     }
 
-    pub async fn get_all_users(&self) -> Vec<User> {
-        let results: Vec<User> = self.db.select("users").await.unwrap();
-        results
+    /// Up to `take` users at or above `min_trust` that weren't synced with
+    /// during the last `cooldown`, those synced longest ago first
+    pub async fn get_exchange_targets(
+        &self,
+        min_trust: TrustLevel,
+        take: usize,
+        cooldown: Timestamp,
+    ) -> Result<Vec<User>, DatabaseError> {
+        const QUERY: &'static str = "SELECT * FROM users WHERE trust >= $min_trust";
+
+        let users: Vec<User> = self
+            .db
+            .query(QUERY)
+            .bind(("min_trust", min_trust))
+            .await?
+            .take(0)?;
+
+        let stats: Vec<PeerStats> = self.db.select(PeerStats::TABLE_NAME).await?;
+        let last_exchange: HashMap<PublicKey, Timestamp> = stats
+            .into_iter()
+            .filter_map(|s| Some((s.pub_key, s.last_exchange?)))
+            .collect();
+
+        let cutoff = self.clock.now() - cooldown;
+        let mut targets: Vec<(Option<Timestamp>, User)> = users
+            .into_iter()
+            .map(|u| (last_exchange.get(u.pub_key()).copied(), u))
+            .filter(|(last, _)| last.is_none_or(|last| last < cutoff))
+            .collect();
+        // Never synced sorts first
+        targets.sort_by_key(|(last, _)| *last);
+
+        Ok(targets.into_iter().take(take).map(|(_, u)| u).collect())
+    }
+
+    /// Users at or above `min_trust` that use `name`, used to detect someone
+    /// impersonating a known user
+    pub async fn get_users_by_name(
+        &self,
+        name: &str,
+        min_trust: TrustLevel,
+    ) -> Result<Vec<User>, DatabaseError> {
+        const QUERY: &'static str =
+            "SELECT * FROM users WHERE name = $name AND trust >= $min_trust";
+
+        let results: Vec<User> = self
+            .db
+            .query(QUERY)
+            .bind(("name", name.to_string()))
+            .bind(("min_trust", min_trust))
+            .await?
+            .take(0)?;
+
+        Ok(results)
+    }
+
+    pub async fn get_keys_with_trust(
+        &self,
+        min_trust: TrustLevel,
+    ) -> Result<HashSet<PublicKey>, DatabaseError> {
+        const QUERY: &'static str = "SELECT * FROM users WHERE trust >= $min_trust";
+
+        let results: Vec<User> = self
+            .db
+            .query(QUERY)
+            .bind(("min_trust", min_trust))
+            .await?
+            .take(0)?;
+
+        Ok(results.into_iter().map(User::into_pub_key).collect())
+    }
+
+    pub async fn get_all_users(&self) -> Result<Vec<User>, DatabaseError> {
+        let results: Vec<User> = self.db.select(User::TABLE_NAME).await?;
+        Ok(results)
+    }
+
+    /// Users passing `filter` along with their peer stats, ordered by `sort`
+    pub async fn get_user_list(
+        &self,
+        sort: UserSort,
+        filter: UserFilter,
+    ) -> Result<Vec<(User, Option<PeerStats>)>, DatabaseError> {
+        let query = match filter {
+            UserFilter::All => self.db.query("SELECT * FROM users"),
+            UserFilter::Trusted => self
+                .db
+                .query("SELECT * FROM users WHERE trust >= $trust")
+                .bind(("trust", TrustLevel::Trusted)),
+            UserFilter::Blocked => self
+                .db
+                .query("SELECT * FROM users WHERE trust = $trust")
+                .bind(("trust", TrustLevel::Ignore)),
+        };
+        let users: Vec<User> = query.await?.take(0)?;
+
+        let stats: Vec<PeerStats> = self.db.select(PeerStats::TABLE_NAME).await?;
+        let mut stats: HashMap<PublicKey, PeerStats> =
+            stats.into_iter().map(|s| (s.pub_key.clone(), s)).collect();
+
+        let mut list: Vec<(User, Option<PeerStats>)> = users
+            .into_iter()
+            .map(|u| {
+                let stats = stats.remove(u.pub_key());
+                (u, stats)
+            })
+            .collect();
+
+        list.sort_by_cached_key(|(u, _)| u.name().to_lowercase());
+        // Stable, so ties stay ordered by name
+        match sort {
+            UserSort::Name => {}
+            UserSort::Trust => list.sort_by_key(|(u, _)| std::cmp::Reverse(*u.trust())),
+            UserSort::LastSeen => {
+                list.sort_by_key(|(_, s)| std::cmp::Reverse(s.as_ref().and_then(|s| s.last_seen)))
+            }
+            UserSort::LastExchange => list
+                .sort_by_key(|(_, s)| std::cmp::Reverse(s.as_ref().and_then(|s| s.last_exchange))),
+            UserSort::Address => list.sort_by_key(|(u, _)| u.address().inner().is_empty()),
+        }
+
+        Ok(list)
     }
 
     pub async fn get_user(&self, pub_key: &PublicKey) -> Result<Option<User>, DatabaseError> {
@@ -160,4 +314,782 @@ impl<'a> UserRepository<'a> {
 
         Ok(results)
     }
+
+    // ==================== Trust ====================
+
+    /// Sets the trust of `user` and stores it. A change from what we had is
+    /// written to the audit log along with `reason`
+    pub async fn change_trust(
+        &self,
+        mut user: User,
+        trust: TrustLevel,
+        reason: &str,
+    ) -> Result<User, DatabaseError> {
+        let before = self.get_user(user.pub_key()).await?.map(|u| *u.trust());
+        user.set_trust(trust);
+        self.upsert_user(user.clone()).await?;
+
+        if before != Some(trust) {
+            let detail = match before {
+                Some(before) => format!("{} to {}", before, trust),
+                None => format!("Added as {}", trust),
+            };
+            self.audit(AuditEntry::new(
+                AuditKind::Trust,
+                user.pub_key().to_base64(),
+                user.name().to_string(),
+                detail,
+                reason,
+            ))
+            .await?;
+        }
+
+        Ok(user)
+    }
+
+    async fn audit(&self, mut entry: AuditEntry) -> Result<(), DatabaseError> {
+        entry.at = self.clock.now();
+        append_audit(self.db, entry).await?;
+        self.changes.notify(DataKind::AuditLog);
+
+        Ok(())
+    }
+
+    // ==================== Petnames ====================
+
+    /// Sets the local nickname for `pub_key`, an empty name removes it
+    pub async fn set_petname(&self, pub_key: PublicKey, name: String) -> Result<(), DatabaseError> {
+        let id = RecordId::new(Petname::TABLE_NAME, pub_key.to_base64());
+
+        if name.trim().is_empty() {
+            let _: Option<Value> = self.db.delete(id).await?;
+        } else {
+            let _: Option<Value> = self
+                .db
+                .upsert(id)
+                .content(Petname {
+                    pub_key,
+                    name: name.trim().to_string(),
+                })
+                .await?;
+        }
+
+        self.changes.notify(DataKind::Users);
+
+        Ok(())
+    }
+
+    pub async fn get_petname(&self, pub_key: &PublicKey) -> Result<Option<String>, DatabaseError> {
+        let petname: Option<Petname> = self
+            .db
+            .select((Petname::TABLE_NAME, pub_key.to_base64()))
+            .await?;
+
+        Ok(petname.map(|p| p.name))
+    }
+
+    pub async fn get_petnames(&self) -> Result<HashMap<PublicKey, String>, DatabaseError> {
+        let petnames: Vec<Petname> = self.db.select(Petname::TABLE_NAME).await?;
+
+        Ok(petnames.into_iter().map(|p| (p.pub_key, p.name)).collect())
+    }
+
+    /// Name that should be shown for `pub_key`, the petname if we gave it one,
+    /// otherwise the name it signed its user with followed by its fingerprint
+    pub async fn get_display_name(&self, pub_key: &PublicKey) -> Result<String, DatabaseError> {
+        if let Some(petname) = self.get_petname(pub_key).await? {
+            return Ok(petname);
+        }
+
+        Ok(match self.get_user(pub_key).await? {
+            Some(user) => format!("{} ({})", user.name(), pub_key.fingerprint()),
+            None => pub_key.fingerprint(),
+        })
+    }
+
+    /// Users the `handles` of post mentions point at. Petnames are matched
+    /// ignoring case and win over key fingerprints, handles matching neither
+    /// are left out
+    pub async fn resolve_mentions(
+        &self,
+        handles: &[String],
+    ) -> Result<HashMap<String, User>, DatabaseError> {
+        let mut resolved = HashMap::new();
+        if handles.is_empty() {
+            return Ok(resolved);
+        }
+
+        let petnames = self.get_petnames().await?;
+        let users = self.get_all_users().await?;
+
+        for handle in handles {
+            let by_petname = petnames
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(handle))
+                .and_then(|(key, _)| users.iter().find(|u| u.pub_key() == key));
+            let user = by_petname.or_else(|| {
+                users
+                    .iter()
+                    .find(|u| u.pub_key().fingerprint().eq_ignore_ascii_case(handle))
+            });
+
+            if let Some(user) = user {
+                resolved.insert(handle.clone(), user.clone());
+            }
+        }
+
+        Ok(resolved)
+    }
+
+    // ==================== Trust Lists ====================
+
+    /// Every trust decision we made, except about `own`
+    pub async fn export_trust(&self, own: &PublicKey) -> Result<Vec<TrustEntry>, DatabaseError> {
+        let mut petnames = self.get_petnames().await?;
+
+        Ok(self
+            .get_all_users()
+            .await?
+            .into_iter()
+            .filter(|u| u.pub_key() != own)
+            .map(|user| TrustEntry {
+                trust: *user.trust(),
+                petname: petnames.remove(user.pub_key()),
+                user,
+            })
+            .filter(TrustEntry::is_decision)
+            .collect())
+    }
+
+    /// Lines `list` up with our own decisions, nothing is written
+    pub async fn merge_trust_list(
+        &self,
+        list: &TrustList,
+        own: &PublicKey,
+    ) -> Result<TrustMerge, DatabaseError> {
+        let mut merge = TrustMerge::default();
+
+        for theirs in &list.entries {
+            if theirs.user.pub_key() == own {
+                continue;
+            }
+            if !theirs.user.verify() {
+                merge.invalid += 1;
+                continue;
+            }
+
+            let ours = match self.get_user(theirs.user.pub_key()).await? {
+                Some(user) => TrustEntry {
+                    trust: *user.trust(),
+                    petname: self.get_petname(user.pub_key()).await?,
+                    user,
+                },
+                None => {
+                    merge.new.push(theirs.clone());
+                    continue;
+                }
+            };
+
+            if !ours.is_decision() {
+                merge.new.push(theirs.clone());
+            } else if ours.trust == theirs.trust && ours.petname == theirs.petname {
+                merge.unchanged += 1;
+            } else {
+                merge.conflicts.push(TrustConflict {
+                    ours,
+                    theirs: theirs.clone(),
+                });
+            }
+        }
+
+        Ok(merge)
+    }
+
+    /// Takes on the trust and petname of each entry. Users we already know
+    /// keep the record we have
+    pub async fn apply_trust_entries(&self, entries: Vec<TrustEntry>) -> Result<(), DatabaseError> {
+        for entry in entries {
+            let user = match self.get_user(entry.user.pub_key()).await? {
+                Some(user) => user,
+                None => entry.user,
+            };
+
+            let pub_key = user.pub_key().clone();
+            self.change_trust(user, entry.trust, "Imported from a trust list")
+                .await?;
+            self.set_petname(pub_key, entry.petname.unwrap_or_default())
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // ==================== Attestations ====================
+
+    /// Stores `attestation` unless it fails its signature or we already hold
+    /// one as recent from the same voucher about the same subject, which it
+    /// replaces otherwise. Returns whether it was stored
+    pub async fn add_attestation(&self, attestation: Attestation) -> Result<bool, DatabaseError> {
+        if !attestation.verify() {
+            return Ok(false);
+        }
+
+        const QUERY: &'static str =
+            "SELECT * FROM attestations WHERE voucher = $voucher AND subject = $subject";
+
+        let existing: Vec<Attestation> = self
+            .db
+            .query(QUERY)
+            .bind(("voucher", attestation.voucher.clone()))
+            .bind(("subject", attestation.subject.clone()))
+            .await?
+            .take(0)?;
+        if existing
+            .iter()
+            .any(|a| a.timestamp >= attestation.timestamp)
+        {
+            return Ok(false);
+        }
+
+        self.db
+            .query("DELETE FROM attestations WHERE voucher = $voucher AND subject = $subject")
+            .bind(("voucher", attestation.voucher.clone()))
+            .bind(("subject", attestation.subject.clone()))
+            .await?
+            .check()?;
+
+        let id = RecordId::new(Attestation::TABLE_NAME, attestation.signature.as_base64());
+        let _: Option<Value> = self.db.upsert(id).content(attestation).await?;
+
+        self.changes.notify(DataKind::Users);
+
+        Ok(true)
+    }
+
+    /// Attestations signed by `voucher`, what we serve when it's our key
+    pub async fn get_attestations_by(
+        &self,
+        voucher: &PublicKey,
+    ) -> Result<Vec<Attestation>, DatabaseError> {
+        const QUERY: &'static str = "SELECT * FROM attestations WHERE voucher = $voucher";
+
+        let attestations: Vec<Attestation> = self
+            .db
+            .query(QUERY)
+            .bind(("voucher", voucher.clone()))
+            .await?
+            .take(0)?;
+
+        Ok(attestations)
+    }
+
+    /// Attestations about `subject`, newest first
+    pub async fn get_attestations_for(
+        &self,
+        subject: &PublicKey,
+    ) -> Result<Vec<Attestation>, DatabaseError> {
+        const QUERY: &'static str =
+            "SELECT * FROM attestations WHERE subject = $subject ORDER BY timestamp DESC";
+
+        let attestations: Vec<Attestation> = self
+            .db
+            .query(QUERY)
+            .bind(("subject", subject.clone()))
+            .await?
+            .take(0)?;
+
+        Ok(attestations)
+    }
+
+    /// Raises users vouched for by at least `threshold` of our fully trusted
+    /// users to [`TrustLevel::Trusted`]. Only users left at the trust they got
+    /// by default are raised, and a withdrawn vouch doesn't lower them back. A
+    /// `threshold` of 0 turns it off. Returns who was raised and by how many
+    pub async fn apply_vouching(
+        &self,
+        threshold: usize,
+    ) -> Result<Vec<(User, usize)>, DatabaseError> {
+        if threshold == 0 {
+            return Ok(vec![]);
+        }
+
+        let vouchers = self.get_keys_with_trust(TrustLevel::FullTrust).await?;
+        let attestations: Vec<Attestation> = self.db.select(Attestation::TABLE_NAME).await?;
+
+        let mut counts: HashMap<PublicKey, usize> = HashMap::new();
+        for attestation in attestations
+            .into_iter()
+            .filter(|a| a.vouches() && vouchers.contains(&a.voucher))
+        {
+            *counts.entry(attestation.subject).or_default() += 1;
+        }
+
+        let mut raised = vec![];
+        for (subject, count) in counts.into_iter().filter(|(_, c)| *c >= threshold) {
+            let Some(user) = self.get_user(&subject).await? else {
+                continue;
+            };
+            if !matches!(user.trust(), TrustLevel::Unverified | TrustLevel::Untrusted) {
+                continue;
+            }
+
+            let reason = format!("Vouched for by {} of your fully trusted users", count);
+            let user = self
+                .change_trust(user, TrustLevel::Trusted, &reason)
+                .await?;
+            raised.push((user, count));
+        }
+
+        Ok(raised)
+    }
+
+    // ==================== Sync Policies ====================
+
+    pub async fn set_sync_policy(
+        &self,
+        pub_key: PublicKey,
+        policy: SyncPolicy,
+    ) -> Result<(), DatabaseError> {
+        let id = RecordId::new(PeerSyncPolicy::TABLE_NAME, pub_key.to_base64());
+
+        if policy == SyncPolicy::default() {
+            let _: Option<Value> = self.db.delete(id).await?;
+        } else {
+            let _: Option<Value> = self
+                .db
+                .upsert(id)
+                .content(PeerSyncPolicy { pub_key, policy })
+                .await?;
+        }
+
+        self.changes.notify(DataKind::Users);
+
+        Ok(())
+    }
+
+    pub async fn get_sync_policy(&self, pub_key: &PublicKey) -> Result<SyncPolicy, DatabaseError> {
+        let policy: Option<PeerSyncPolicy> = self
+            .db
+            .select((PeerSyncPolicy::TABLE_NAME, pub_key.to_base64()))
+            .await?;
+
+        Ok(policy.map(|p| p.policy).unwrap_or_default())
+    }
+
+    /// Policy for whoever is connecting from `address`. Unverified users can
+    /// claim any address, so if several do only what all of them allow is
+    /// kept
+    pub async fn get_sync_policy_by_address(
+        &self,
+        address: &I2PAddress,
+    ) -> Result<SyncPolicy, DatabaseError> {
+        const QUERY: &'static str = "SELECT * FROM users WHERE address = $address";
+
+        let users: Vec<User> = self
+            .db
+            .query(QUERY)
+            .bind(("address", address.clone()))
+            .await?
+            .take(0)?;
+
+        let mut policy = SyncPolicy::default();
+        for user in users {
+            policy &= self.get_sync_policy(user.pub_key()).await?;
+        }
+
+        Ok(policy)
+    }
+
+    // ==================== Muted Uploaders ====================
+
+    /// Mutes or unmutes `pub_key`, writing it to the audit log along with
+    /// `reason`
+    pub async fn set_muted(
+        &self,
+        pub_key: PublicKey,
+        muted: bool,
+        reason: &str,
+    ) -> Result<(), DatabaseError> {
+        let id = RecordId::new(MutedUploader::TABLE_NAME, pub_key.to_base64());
+        let entry = AuditEntry::new(
+            if muted {
+                AuditKind::Mute
+            } else {
+                AuditKind::Unmute
+            },
+            pub_key.to_base64(),
+            self.get_display_name(&pub_key).await?,
+            String::new(),
+            reason,
+        );
+
+        if muted {
+            let _: Option<Value> = self
+                .db
+                .upsert(id)
+                .content(MutedUploader {
+                    pub_key,
+                    muted_at: self.clock.now(),
+                })
+                .await?;
+        } else {
+            let _: Option<Value> = self.db.delete(id).await?;
+        }
+        self.audit(entry).await?;
+
+        // What the library shows changes with it
+        for kind in [DataKind::Users, DataKind::Indexes, DataKind::Contents] {
+            self.changes.notify(kind);
+        }
+
+        Ok(())
+    }
+
+    pub async fn is_muted(&self, pub_key: &PublicKey) -> Result<bool, DatabaseError> {
+        let muted: Option<MutedUploader> = self
+            .db
+            .select((MutedUploader::TABLE_NAME, pub_key.to_base64()))
+            .await?;
+
+        Ok(muted.is_some())
+    }
+
+    pub async fn get_muted(&self) -> Result<HashSet<PublicKey>, DatabaseError> {
+        let muted: Vec<MutedUploader> = self.db.select(MutedUploader::TABLE_NAME).await?;
+
+        Ok(muted.into_iter().map(|m| m.pub_key).collect())
+    }
+
+    // ==================== Peer Stats ====================
+
+    /// Records the result of pinging `pub_key`, `None` if it didn't answer
+    pub async fn record_ping(
+        &self,
+        pub_key: PublicKey,
+        rtt: Option<std::time::Duration>,
+    ) -> Result<PeerStats, DatabaseError> {
+        self.update_peer_stats(pub_key, |stats| match rtt {
+            Some(rtt) => {
+                stats.last_rtt_ms = Some(rtt.as_millis() as u64);
+                stats.seen(self.clock.now());
+                stats.successes += 1;
+            }
+            None => stats.failures += 1,
+        })
+        .await
+    }
+
+    /// Records a completed sync with every user behind `address`
+    pub async fn record_exchange(&self, address: &I2PAddress) -> Result<(), DatabaseError> {
+        let now = self.clock.now();
+        for user in self.get_users_at(address).await? {
+            self.update_peer_stats(user.into_pub_key(), |stats| {
+                stats.last_exchange = Some(now);
+                stats.seen(now);
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Records that we received the record of `pub_key`
+    pub async fn record_seen(&self, pub_key: PublicKey) -> Result<(), DatabaseError> {
+        let now = self.clock.now();
+        self.update_peer_stats(pub_key, |stats| stats.seen(now))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Records that a peer connected from `address`, for every user behind it
+    pub async fn record_seen_at(&self, address: &I2PAddress) -> Result<(), DatabaseError> {
+        let now = self.clock.now();
+        for user in self.get_users_at(address).await? {
+            self.update_peer_stats(user.into_pub_key(), |stats| stats.seen(now))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get_users_at(&self, address: &I2PAddress) -> Result<Vec<User>, DatabaseError> {
+        const QUERY: &'static str = "SELECT * FROM users WHERE address = $address";
+
+        let users: Vec<User> = self
+            .db
+            .query(QUERY)
+            .bind(("address", address.clone()))
+            .await?
+            .take(0)?;
+
+        Ok(users)
+    }
+
+    async fn update_peer_stats(
+        &self,
+        pub_key: PublicKey,
+        update: impl FnOnce(&mut PeerStats),
+    ) -> Result<PeerStats, DatabaseError> {
+        let mut stats = self
+            .get_peer_stats(&pub_key)
+            .await?
+            .unwrap_or_else(|| PeerStats::new(pub_key.clone()));
+
+        update(&mut stats);
+
+        let _: Option<Value> = self
+            .db
+            .upsert(RecordId::new(PeerStats::TABLE_NAME, pub_key.to_base64()))
+            .content(stats.clone())
+            .await?;
+
+        self.changes.notify(DataKind::Users);
+
+        Ok(stats)
+    }
+
+    pub async fn get_peer_stats(
+        &self,
+        pub_key: &PublicKey,
+    ) -> Result<Option<PeerStats>, DatabaseError> {
+        let stats: Option<PeerStats> = self
+            .db
+            .select((PeerStats::TABLE_NAME, pub_key.to_base64()))
+            .await?;
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        db::{
+            Repositories,
+            user::{
+                Attestation, I2PAddress, TrustEntry, TrustLevel, TrustList, User, UserFilter,
+                UserSort,
+            },
+        },
+        types::{ManualClock, PrivateKey, PublicKey, Timestamp},
+    };
+
+    fn user(name: &str, address: &str) -> User {
+        let mut user = User::new_signed(
+            name.to_string(),
+            Timestamp::now(),
+            &PrivateKey::new(),
+            I2PAddress::new(address),
+        );
+        user.set_trust(TrustLevel::Trusted);
+        user
+    }
+
+    async fn targets(repo: &Repositories, cooldown: i64) -> Vec<PublicKey> {
+        repo.user()
+            .get_exchange_targets(TrustLevel::Trusted, 10, Timestamp::new(cooldown))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(User::into_pub_key)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn recently_synced_peers_are_skipped() {
+        let repo = Repositories::in_memory().await;
+        let (synced, pending) = (user("Synced", "synced.i2p"), user("Pending", "pending.i2p"));
+        repo.user().upsert_user(synced.clone()).await.unwrap();
+        repo.user().upsert_user(pending.clone()).await.unwrap();
+
+        repo.user().record_exchange(synced.address()).await.unwrap();
+
+        assert_eq!(targets(&repo, 60).await, vec![pending.pub_key().clone()]);
+        assert_eq!(
+            targets(&repo, 0).await,
+            vec![pending.pub_key().clone(), synced.pub_key().clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn cooldown_follows_the_clock() {
+        let clock = Arc::new(ManualClock::new(Timestamp::new(1_000)));
+        let repo = Repositories::in_memory().await.with_clock(clock.clone());
+        let synced = user("Synced", "synced.i2p");
+        repo.user().upsert_user(synced.clone()).await.unwrap();
+
+        repo.user().record_exchange(synced.address()).await.unwrap();
+
+        clock.advance(60);
+        assert!(targets(&repo, 60).await.is_empty());
+        clock.advance(1);
+        assert_eq!(targets(&repo, 60).await, vec![synced.pub_key().clone()]);
+    }
+
+    #[tokio::test]
+    async fn user_list_filters_and_sorts() {
+        let repo = Repositories::in_memory().await;
+        let (seen, unseen) = (user("Bea", "bea.i2p"), user("Abe", "abe.i2p"));
+        let mut blocked = user("Cid", "cid.i2p");
+        blocked.set_trust(TrustLevel::Ignore);
+        for u in [&seen, &unseen, &blocked] {
+            repo.user().upsert_user(u.clone()).await.unwrap();
+        }
+
+        repo.user().record_exchange(seen.address()).await.unwrap();
+
+        let keys = |list: Vec<(User, Option<_>)>| -> Vec<PublicKey> {
+            list.into_iter().map(|(u, _)| u.into_pub_key()).collect()
+        };
+        let trusted = repo
+            .user()
+            .get_user_list(UserSort::Name, UserFilter::Trusted)
+            .await
+            .unwrap();
+        assert_eq!(
+            keys(trusted),
+            vec![unseen.pub_key().clone(), seen.pub_key().clone()]
+        );
+
+        let by_seen = repo
+            .user()
+            .get_user_list(UserSort::LastSeen, UserFilter::Trusted)
+            .await
+            .unwrap();
+        assert_eq!(
+            keys(by_seen),
+            vec![seen.pub_key().clone(), unseen.pub_key().clone()]
+        );
+
+        let blocked_only = repo
+            .user()
+            .get_user_list(UserSort::Name, UserFilter::Blocked)
+            .await
+            .unwrap();
+        assert_eq!(keys(blocked_only), vec![blocked.pub_key().clone()]);
+    }
+
+    #[tokio::test]
+    async fn first_seen_stays_put() {
+        let repo = Repositories::in_memory().await;
+        let peer = user("Peer", "peer.i2p");
+        repo.user().upsert_user(peer.clone()).await.unwrap();
+
+        repo.user()
+            .record_seen(peer.pub_key().clone())
+            .await
+            .unwrap();
+        let first = repo
+            .user()
+            .get_peer_stats(peer.pub_key())
+            .await
+            .unwrap()
+            .unwrap();
+
+        repo.user().record_seen_at(peer.address()).await.unwrap();
+        let second = repo
+            .user()
+            .get_peer_stats(peer.pub_key())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(first.first_seen, first.last_seen);
+        assert_eq!(second.first_seen, first.first_seen);
+        assert!(second.last_seen >= first.last_seen);
+    }
+
+    #[tokio::test]
+    async fn trust_lists_merge_with_conflicts() {
+        let repo = Repositories::in_memory().await;
+        let own = PrivateKey::new();
+        let (agreed, disputed, unknown) = (
+            user("Agreed", "agreed.i2p"),
+            user("Disputed", "disputed.i2p"),
+            user("Unknown", "unknown.i2p"),
+        );
+        repo.user().upsert_user(agreed.clone()).await.unwrap();
+        repo.user().upsert_user(disputed.clone()).await.unwrap();
+
+        let entry = |user: &User, trust| TrustEntry {
+            user: user.clone(),
+            trust,
+            petname: None,
+        };
+        let list = TrustList::new_signed(
+            vec![
+                entry(&agreed, TrustLevel::Trusted),
+                entry(&disputed, TrustLevel::Ignore),
+                entry(&unknown, TrustLevel::FullTrust),
+            ],
+            &own,
+        );
+        assert!(list.verify());
+
+        let merge = repo
+            .user()
+            .merge_trust_list(&list, &own.public_key())
+            .await
+            .unwrap();
+        assert_eq!(merge.unchanged, 1);
+        assert_eq!(merge.new, vec![entry(&unknown, TrustLevel::FullTrust)]);
+        assert_eq!(merge.conflicts.len(), 1);
+        assert_eq!(merge.conflicts[0].theirs.trust, TrustLevel::Ignore);
+
+        repo.user().apply_trust_entries(merge.new).await.unwrap();
+        let added = repo.user().get_user(unknown.pub_key()).await.unwrap();
+        assert_eq!(added.map(|u| *u.trust()), Some(TrustLevel::FullTrust));
+    }
+
+    #[tokio::test]
+    async fn vouched_users_are_raised() {
+        let repo = Repositories::in_memory().await;
+        let (first, second) = (PrivateKey::new(), PrivateKey::new());
+        for (key, trust) in [
+            (&first, TrustLevel::FullTrust),
+            (&second, TrustLevel::Trusted),
+        ] {
+            let mut voucher = User::new_signed(
+                "Voucher".to_string(),
+                Timestamp::now(),
+                key,
+                I2PAddress::new("voucher.i2p"),
+            );
+            voucher.set_trust(trust);
+            repo.user().upsert_user(voucher).await.unwrap();
+        }
+        let mut subject = user("Subject", "subject.i2p");
+        subject.set_trust(TrustLevel::Untrusted);
+        repo.user().upsert_user(subject.clone()).await.unwrap();
+
+        for key in [&first, &second] {
+            let attestation =
+                Attestation::new_signed(subject.pub_key().clone(), TrustLevel::Trusted, key);
+            assert!(repo.user().add_attestation(attestation).await.unwrap());
+        }
+        let mut forged =
+            Attestation::new_signed(subject.pub_key().clone(), TrustLevel::Trusted, &first);
+        forged.level = TrustLevel::FullTrust;
+        assert!(!repo.user().add_attestation(forged).await.unwrap());
+
+        // Only the fully trusted voucher counts
+        assert!(repo.user().apply_vouching(2).await.unwrap().is_empty());
+        let raised = repo.user().apply_vouching(1).await.unwrap();
+        assert_eq!(raised.len(), 1);
+        assert_eq!(raised[0].1, 1);
+
+        let stored = repo.user().get_user(subject.pub_key()).await.unwrap();
+        assert_eq!(stored.map(|u| *u.trust()), Some(TrustLevel::Trusted));
+        assert_eq!(
+            repo.user()
+                .get_attestations_for(subject.pub_key())
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
 }
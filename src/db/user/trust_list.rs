@@ -0,0 +1,95 @@
+//! Trust decisions exported to a file, to carry them over to another node of
+//! ours or hand them to a friend setting up theirs.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{
+        ToBytes,
+        user::{TrustLevel, User},
+    },
+    types::{PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+/// What we decided about a single user. The user record is carried along,
+/// signed by its owner, so it can be added on a node that never heard of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TrustEntry {
+    pub user: User,
+    pub trust: TrustLevel,
+    pub petname: Option<String>,
+}
+
+impl TrustEntry {
+    /// Whether there's anything in it worth carrying over, users left at the
+    /// trust they got by default and without a petname aren't
+    pub fn is_decision(&self) -> bool {
+        !matches!(self.trust, TrustLevel::Unverified | TrustLevel::Untrusted)
+            || self.petname.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustList {
+    pub exported_at: Timestamp,
+    pub entries: Vec<TrustEntry>,
+    /// Who vouches for the list, unsigned lists are taken as is
+    pub signer: Option<(PublicKey, Signature)>,
+}
+
+impl TrustList {
+    pub fn new(entries: Vec<TrustEntry>) -> Self {
+        Self {
+            exported_at: Timestamp::now(),
+            entries,
+            signer: None,
+        }
+    }
+
+    pub fn new_signed(entries: Vec<TrustEntry>, priv_key: &PrivateKey) -> Self {
+        let mut list = Self::new(entries);
+        let signature = priv_key.sign(&list.sign_bytes());
+        list.signer = Some((priv_key.public_key(), signature));
+        list
+    }
+
+    fn sign_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.exported_at.to_bytes();
+        for entry in &self.entries {
+            bytes.extend(entry.user.pub_key().as_bytes());
+            bytes.push(entry.trust.into());
+            if let Some(petname) = &entry.petname {
+                bytes.extend(petname.as_bytes());
+            }
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// Unsigned lists verify, there's nothing to check
+    pub fn verify(&self) -> bool {
+        match &self.signer {
+            Some((pub_key, signature)) => pub_key.verify(&self.sign_bytes(), signature),
+            None => true,
+        }
+    }
+}
+
+/// A user we both have an opinion on, and the opinions differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustConflict {
+    pub ours: TrustEntry,
+    pub theirs: TrustEntry,
+}
+
+/// How an imported list lines up with what we have, nothing is written until
+/// the entries picked out of it are applied
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustMerge {
+    /// Users we don't know or have made no decision on
+    pub new: Vec<TrustEntry>,
+    pub unchanged: usize,
+    pub conflicts: Vec<TrustConflict>,
+    /// Entries whose user record failed its signature
+    pub invalid: usize,
+}
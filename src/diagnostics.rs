@@ -0,0 +1,239 @@
+//! Checks of what the node needs to run: SAM, its destination, the database,
+//! the data folder and the clock. They run at startup and from the settings,
+//! each failed one says what to do about it.
+
+use std::{path::Path, time::Duration};
+
+use tokio::net::TcpStream;
+
+use crate::{
+    build_info,
+    config::AkarekoConfig,
+    db::{DATABASE_PATH, Repositories},
+    helpers::b32_from_pub_b64,
+    types::Timestamp,
+};
+
+/// Under this much free space downloads will soon fail
+pub const LOW_DISK_SPACE: u64 = 1024 * 1024 * 1024;
+/// Under this much free space even the database can't be written
+pub const CRITICAL_DISK_SPACE: u64 = 64 * 1024 * 1024;
+
+/// How far behind the build date the clock can be before it's called wrong,
+/// builds from another timezone can look slightly in the future
+const CLOCK_SLACK: i64 = 24 * 60 * 60;
+
+const SAM_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    /// Works for now, but something will break
+    Warning(String),
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What to do when it didn't pass
+    pub hint: &'static str,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, hint: &'static str) -> Self {
+        Self { name, status, hint }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.status == CheckStatus::Passed
+    }
+}
+
+/// Runs every check, `repositories` is `None` while the database isn't
+/// opened yet
+pub async fn run_diagnostics(
+    config: &AkarekoConfig,
+    repositories: Option<&Repositories>,
+) -> Vec<Check> {
+    let data_dir = Path::new(&config.storage().data_dir);
+
+    vec![
+        check_sam(config.sam_tcp_port()).await,
+        check_destination(config),
+        check_database(repositories).await,
+        check_writable("Data folder", data_dir),
+        check_disk_space(data_dir),
+        check_clock(Timestamp::now(), build_info::built_at()),
+    ]
+}
+
+async fn check_sam(port: u16) -> Check {
+    let status =
+        match tokio::time::timeout(SAM_TIMEOUT, TcpStream::connect(("127.0.0.1", port))).await {
+            Ok(Ok(_)) => CheckStatus::Passed,
+            Ok(Err(e)) => CheckStatus::Failed(format!("Nothing answers on port {}: {}", port, e)),
+            Err(_) => CheckStatus::Failed(format!("Port {} didn't answer in time", port)),
+        };
+
+    Check::new(
+        "SAM reachable",
+        status,
+        "The router may still be starting, check again in a minute. If it keeps failing, \
+         another program may hold the SAM port, pick another one above and restart.",
+    )
+}
+
+fn check_destination(config: &AkarekoConfig) -> Check {
+    let key = config.eepsite_key().expose();
+    let address = config.eepsite_address().inner();
+
+    let status = if key.is_empty() {
+        CheckStatus::Failed("No destination was created yet".to_string())
+    } else if b32_from_pub_b64(key).is_err() {
+        CheckStatus::Failed("The destination key isn't valid base64".to_string())
+    } else if !is_b32_address(address) {
+        CheckStatus::Failed(format!("{} isn't a .b32.i2p address", address))
+    } else {
+        CheckStatus::Passed
+    };
+
+    Check::new(
+        "Destination valid",
+        status,
+        "It's created the first time SAM is reachable. If the config was edited by hand, \
+         restore eepsite_key and eepsite_address from a backup, or clear both to get a new \
+         address.",
+    )
+}
+
+fn is_b32_address(address: &str) -> bool {
+    address.strip_suffix(".b32.i2p").is_some_and(|name| {
+        name.len() == 52
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || ('2'..='7').contains(&c))
+    })
+}
+
+async fn check_database(repositories: Option<&Repositories>) -> Check {
+    let hint = "Only one Akareko can use the database at a time, close any other one. \
+                Otherwise the database folder may be read-only or on a full disk.";
+
+    let Some(repositories) = repositories else {
+        let folder = Path::new(DATABASE_PATH).parent().unwrap_or(Path::new("."));
+        let mut check = check_writable("Database opens", folder);
+        if check.passed() {
+            check.status = CheckStatus::Warning("Not opened yet".to_string());
+        }
+        check.hint = hint;
+        return check;
+    };
+
+    let status = match repositories.health().await {
+        Ok(()) => CheckStatus::Passed,
+        Err(e) => CheckStatus::Failed(e.to_string()),
+    };
+
+    Check::new("Database opens", status, hint)
+}
+
+fn check_writable(name: &'static str, dir: &Path) -> Check {
+    let probe = dir.join(".akareko-probe");
+    let written = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"probe"))
+        .and_then(|_| std::fs::remove_file(&probe));
+
+    let status = match written {
+        Ok(()) => CheckStatus::Passed,
+        Err(e) => CheckStatus::Failed(format!("Can't write to {}: {}", dir.display(), e)),
+    };
+
+    Check::new(
+        name,
+        status,
+        "Make sure the folder exists and belongs to the user running Akareko, or point the \
+         data folder somewhere else in the config.",
+    )
+}
+
+fn check_disk_space(dir: &Path) -> Check {
+    let status = match fs4::available_space(dir) {
+        Ok(free) => disk_space_status(free),
+        Err(e) => CheckStatus::Warning(format!("Couldn't read the free space: {}", e)),
+    };
+
+    Check::new(
+        "Disk space",
+        status,
+        "Free up space on the disk holding the data folder, or move the data folder to a \
+         bigger one.",
+    )
+}
+
+fn disk_space_status(free: u64) -> CheckStatus {
+    let message = format!("Only {} MiB left", free / (1024 * 1024));
+    if free < CRITICAL_DISK_SPACE {
+        CheckStatus::Failed(message)
+    } else if free < LOW_DISK_SPACE {
+        CheckStatus::Warning(message)
+    } else {
+        CheckStatus::Passed
+    }
+}
+
+fn check_clock(now: Timestamp, built_at: Timestamp) -> Check {
+    // Builds without the date have it at 0, nothing to compare to
+    let status = if built_at.as_secs() > 0 && now.as_secs() + CLOCK_SLACK < built_at.as_secs() {
+        CheckStatus::Failed(format!(
+            "It's {}, before this version was built on {}",
+            now.format_date(),
+            built_at.format_date()
+        ))
+    } else {
+        CheckStatus::Passed
+    };
+
+    Check::new(
+        "Clock",
+        status,
+        "Peers reject records from the future and sync by timestamps, set the system clock \
+         to the right time or turn on automatic time.",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_behind_the_build_fails() {
+        let built_at = Timestamp::new(1_750_000_000);
+
+        assert!(check_clock(Timestamp::new(1_760_000_000), built_at).passed());
+        assert!(check_clock(Timestamp::new(1_749_990_000), built_at).passed());
+        assert!(!check_clock(Timestamp::new(0), built_at).passed());
+        assert!(check_clock(Timestamp::new(0), Timestamp::new(0)).passed());
+    }
+
+    #[test]
+    fn only_b32_addresses_are_valid() {
+        let name = "a".repeat(52);
+
+        assert!(is_b32_address(&format!("{}.b32.i2p", name)));
+        assert!(!is_b32_address(&format!("{}.i2p", name)));
+        assert!(!is_b32_address("short.b32.i2p"));
+        assert!(!is_b32_address(&format!("{}.b32.i2p", "A".repeat(52))));
+    }
+
+    #[test]
+    fn low_space_warns_before_failing() {
+        assert_eq!(disk_space_status(LOW_DISK_SPACE), CheckStatus::Passed);
+        assert!(matches!(
+            disk_space_status(LOW_DISK_SPACE - 1),
+            CheckStatus::Warning(_)
+        ));
+        assert!(matches!(disk_space_status(0), CheckStatus::Failed(_)));
+    }
+}
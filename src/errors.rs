@@ -29,8 +29,44 @@ error_set::error_set! {
 
     TomlSaveError := TomlError || IoError
 
+    LinkHandlerError := {
+        #[display("Opening links from other apps isn't supported on this system")]
+        Unsupported,
+        #[display("{} failed", command)]
+        CommandFailed {
+            command: &'static str
+        }
+    } || IoError
+
+    ExportError := {
+        SourceNotFound,
+        ZipError(async_zip::error::ZipError)
+    } || IoError
+
     I2PParseError := Base64Error
 
+    DemoDataError := ExportError || DatabaseError
+
+    TrustListError := {
+        #[display("The list's signature doesn't match its contents")]
+        BadSignature,
+        InvalidJson(serde_json::Error)
+    } || IoError || DatabaseError
+
+    EvidenceError := {
+        #[display("The bundle's signature doesn't match its contents")]
+        BadSignature,
+        #[display("We don't know this user")]
+        UnknownUser,
+        InvalidJson(serde_json::Error)
+    } || IoError || DatabaseError
+
+    InviteError := {
+        MissingPrefix,
+        MissingAddress,
+        QrCode(qrcode::types::QrError)
+    } || Base64Error
+
     TorrentError := {
         LtrsError(LtrsError),
         Unknown,
@@ -49,17 +85,84 @@ error_set::error_set! {
     //     DieselError(diesel::result::Error)
     // }
 
-    DatabaseError := {Unknown, NotInitialized} || SurrealError /*||
+    DatabaseError := {
+        #[display("The database is not loaded yet")]
+        NotInitialized,
+        #[display("No record `{}` in `{}`", id, table)]
+        NotFound {
+            table: String,
+            id: String
+        },
+        #[display("Record `{}` already exists in `{}`", id, table)]
+        AlreadyExists {
+            table: String,
+            id: String
+        },
+        #[display("Expected {} record(s) in `{}`, got {}", expected, table, actual)]
+        UnexpectedRecordCount {
+            table: String,
+            expected: usize,
+            actual: usize
+        },
+        #[display("Query on `{}` returned nothing", table)]
+        EmptyResponse {
+            table: String
+        }
+    } || SurrealError || ContentLimitError /*||
 DieselError */
-    ServerError := { RelayNotEnabled } || YosemiteError || IoError
+    ServerError := { RelayNotEnabled } || YosemiteError || IoError || EncodeError || DecodeError || DatabaseError
 
     InvalidSignature := {
         InvalidSignature
     }
 
-    ClientError := { MissingPayload, UnexpectedResponseCode { status:
-AkarekoStatus } } || EncodeError             || DecodeError || YosemiteError
-|| InvalidSignature || DatabaseError
+    PostError := {
+        #[display("Posts can be at most {} characters long, this one has {}", allowed, actual)]
+        TooLong {
+            allowed: usize,
+            actual: usize
+        }
+    }
+
+    ContentLimitError := {
+        #[display("Content lists {} files, at most {} are allowed", actual, allowed)]
+        TooManyEntries {
+            allowed: usize,
+            actual: usize
+        },
+        #[display("Content title is {} characters long, at most {} are allowed", actual, allowed)]
+        TitleTooLong {
+            allowed: usize,
+            actual: usize
+        },
+        #[display("A content path is {} characters long, at most {} are allowed", actual, allowed)]
+        PathTooLong {
+            allowed: usize,
+            actual: usize
+        },
+        #[display("Content takes {} bytes encoded, at most {} are allowed", actual, allowed)]
+        TooLarge {
+            allowed: u64,
+            actual: u64
+        }
+    }
+
+    ClientError := {
+        #[display("The peer stopped answering")]
+        Timeout,
+        MissingPayload,
+        IdentityMismatch,
+        UntrustedRelay,
+        UnexpectedResponseCode { status: AkarekoStatus }
+    } || EncodeError || DecodeError || YosemiteError || InvalidSignature || DatabaseError
+
+    UpdateError := {
+        #[display("Updates are enabled but no maintainer key is pinned")]
+        NotConfigured,
+        #[display("The eepsite didn't answer with a manifest")]
+        BadHttpResponse,
+        InvalidManifest(serde_json::Error)
+    } || ClientError
 
     EncodeError := {
         InvalidData,
@@ -75,7 +178,16 @@ AkarekoStatus } } || EncodeError             || DecodeError || YosemiteError
             enum_name: &'static str
         },
         InvalidData,
-        FromUtf8Error(FromUtf8Error)
+        FromUtf8Error(FromUtf8Error),
+        #[display("Frame of {} bytes is over the {} allowed", actual, allowed)]
+        FrameTooLarge {
+            allowed: u32,
+            actual: u32
+        },
+        #[display("Payload is over the {} bytes allowed", allowed)]
+        PayloadTooLarge {
+            allowed: u64
+        }
     } || IoError
 }
 
@@ -0,0 +1,329 @@
+//! Signed records made entirely from a seed: the same seed always gives the
+//! same users, indexes, contents and posts down to their signatures. Meant for
+//! tests that need a populated database and for demo data, never for anything
+//! shared with real peers since the keys can be derived by anyone.
+
+use std::path::Path;
+
+use async_zip::{Compression, ZipEntryBuilder, tokio::write::ZipFileWriter};
+use data_encoding::BASE32_NOPAD;
+
+use crate::{
+    db::{
+        Magnet, Repositories,
+        comments::Post,
+        index::{
+            Index, IndexLinks,
+            content::Content,
+            tags::{MangaChapter, MangaTag},
+        },
+        user::{I2PAddress, TrustLevel, User},
+    },
+    errors::{DatabaseError, DemoDataError},
+    helpers::Language,
+    types::{Hash, PrivateKey, Timestamp, Topic},
+};
+
+/// Every record in a graph is timestamped from here on, a second apart
+pub const GRAPH_EPOCH: Timestamp = Timestamp::new(1_700_000_000);
+
+const NAMES: [&str; 8] = ["Aoi", "Haru", "Kaede", "Mio", "Ren", "Sora", "Tomo", "Yuki"];
+const WORDS: [&str; 12] = [
+    "Lantern", "Harbor", "Comet", "Garden", "Winter", "Signal", "Orchard", "Tide", "Summit",
+    "Paper", "Echo", "Meadow",
+];
+
+/// Pages of every sample chapter, small enough to bundle
+const SAMPLE_PAGES: [&[u8]; 3] = [
+    include_bytes!("../assets/demo/page-1.png"),
+    include_bytes!("../assets/demo/page-2.png"),
+    include_bytes!("../assets/demo/page-3.png"),
+];
+
+/// Writes the bundled sample pages to `destination` as a .cbz
+pub async fn write_sample_chapter(destination: &Path) -> Result<(), DemoDataError> {
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let file = tokio::fs::File::create(destination).await?;
+    let mut writer = ZipFileWriter::with_tokio(file);
+    for (i, page) in SAMPLE_PAGES.iter().enumerate() {
+        let entry = ZipEntryBuilder::new(format!("{:04}.png", i + 1).into(), Compression::Stored);
+        writer.write_entry_whole(entry, page).await?;
+    }
+    writer.close().await?;
+
+    Ok(())
+}
+
+/// How many records of each kind a [`Graph`] is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphShape {
+    pub users: usize,
+    pub indexes_per_user: usize,
+    pub chapters_per_index: usize,
+    /// Posted by other users on each index
+    pub posts_per_index: usize,
+}
+
+impl Default for GraphShape {
+    fn default() -> Self {
+        Self {
+            users: 3,
+            indexes_per_user: 2,
+            chapters_per_index: 3,
+            posts_per_index: 2,
+        }
+    }
+}
+
+pub struct Graph {
+    pub users: Vec<(User, PrivateKey)>,
+    pub indexes: Vec<Index<MangaTag>>,
+    pub contents: Vec<Content<MangaTag>>,
+    pub posts: Vec<Post>,
+}
+
+/// Picks from `items` by hashing `seed` with `n`
+fn pick<'a>(items: &[&'a str], seed: &str, n: usize) -> &'a str {
+    let hash = Hash::digest(format!("{}:{}", seed, n).as_bytes());
+    items[hash.inner()[0] as usize % items.len()]
+}
+
+/// Key and address of the `n`th user of `seed`
+pub fn seeded_user(seed: &str, n: usize) -> (User, PrivateKey) {
+    let priv_key = PrivateKey::from_seed(&format!("{}/user/{}", seed, n));
+    let address = BASE32_NOPAD
+        .encode(Hash::digest(priv_key.public_key().as_bytes()).inner()[..32].as_ref())
+        .to_lowercase();
+
+    let mut user = User::new_signed(
+        format!("{} {}", pick(&NAMES, seed, n), n + 1),
+        GRAPH_EPOCH,
+        &priv_key,
+        I2PAddress::new(&format!("{}.b32.i2p", address)),
+    );
+    user.set_trust(TrustLevel::Trusted);
+    (user, priv_key)
+}
+
+impl Graph {
+    pub fn from_seed(seed: &str) -> Self {
+        Self::with_shape(seed, GraphShape::default())
+    }
+
+    pub fn with_shape(seed: &str, shape: GraphShape) -> Self {
+        let users: Vec<_> = (0..shape.users).map(|n| seeded_user(seed, n)).collect();
+
+        let mut indexes = vec![];
+        let mut contents = vec![];
+        let mut posts = vec![];
+        let mut tick = 0;
+        let mut next_timestamp = || {
+            tick += 1;
+            GRAPH_EPOCH + tick
+        };
+
+        for (u, (_, priv_key)) in users.iter().enumerate() {
+            for i in 0..shape.indexes_per_user {
+                let n = u * shape.indexes_per_user + i;
+                // Titles are numbered so no two indexes share a hash
+                let title = format!(
+                    "{} {} {}",
+                    pick(&WORDS, seed, 2 * n),
+                    pick(&WORDS, seed, 2 * n + 1),
+                    n + 1
+                );
+                let index = Index::new_signed(
+                    title,
+                    2000 + n as i32,
+                    IndexLinks {
+                        myanimelist: None,
+                        mangadex: None,
+                    },
+                    priv_key,
+                );
+
+                for c in 0..shape.chapters_per_index {
+                    let enumeration = (c + 1) as f32;
+                    contents.push(Content::new_signed(
+                        index.hash().clone(),
+                        next_timestamp(),
+                        Magnet(String::new()),
+                        format!("chapter-{}", c + 1),
+                        format!("Chapter {}", c + 1),
+                        enumeration,
+                        None,
+                        MangaChapter::new(Language::English),
+                        vec![],
+                        None,
+                        priv_key,
+                    ));
+                }
+
+                for p in 0..shape.posts_per_index {
+                    let (author, author_key) = &users[(u + p + 1) % users.len()];
+                    posts.push(
+                        Post::new_signed(
+                            format!("{} read chapter {}", author.name(), p + 1),
+                            next_timestamp(),
+                            Topic::from_index(&index),
+                            author_key,
+                        )
+                        .expect("Short posts always fit"),
+                    );
+                }
+
+                indexes.push(index);
+            }
+        }
+
+        Self {
+            users,
+            indexes,
+            contents,
+            posts,
+        }
+    }
+
+    /// Stores every record, as if they were received from their publishers
+    pub async fn store(&self, repositories: &Repositories) -> Result<(), DatabaseError> {
+        repositories
+            .user()
+            .upsert_users(self.users.iter().map(|(u, _)| u.clone()).collect())
+            .await?;
+        for index in &self.indexes {
+            repositories.index().add_index(index.clone()).await?;
+        }
+        for content in &self.contents {
+            repositories.index().add_content(content.clone()).await?;
+        }
+        for post in &self.posts {
+            repositories.add_post(post.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// Stores the graph to look around the UI with. Chapters are stored local
+    /// only, pointing at sample archives written to `dir`, so they open
+    /// without a network and never leave this machine. Users, indexes and
+    /// posts are stored like any others, a throwaway database is best.
+    pub async fn store_demo(
+        &self,
+        repositories: &Repositories,
+        dir: &Path,
+    ) -> Result<(), DemoDataError> {
+        repositories
+            .user()
+            .upsert_users(self.users.iter().map(|(u, _)| u.clone()).collect())
+            .await?;
+        for index in &self.indexes {
+            repositories.index().add_index(index.clone()).await?;
+        }
+        for post in &self.posts {
+            repositories.add_post(post.clone()).await?;
+        }
+
+        for content in &self.contents {
+            let archive = dir.join(format!("{}.cbz", content.source()));
+            if !archive.exists() {
+                write_sample_chapter(&archive).await?;
+            }
+
+            let Some((_, priv_key)) = self
+                .users
+                .iter()
+                .find(|(u, _)| u.pub_key() == content.poster())
+            else {
+                continue;
+            };
+            // The source is part of what's signed, so the chapter is signed
+            // again with the archive's path
+            let local = Content::new_signed(
+                content.index_hash().clone(),
+                content.timestamp,
+                Magnet(String::new()),
+                archive.to_string_lossy().into_owned(),
+                content.title().to_string(),
+                content.enumeration(),
+                None,
+                content.extra_metadata().clone(),
+                vec![],
+                None,
+                priv_key,
+            );
+            repositories.index().add_local_content(local).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_signatures() {
+        let (a, b) = (Graph::from_seed("demo"), Graph::from_seed("demo"));
+        let other = Graph::from_seed("other");
+
+        assert_eq!(PrivateKey::from_seed("demo"), PrivateKey::from_seed("demo"));
+        assert_eq!(a.indexes, b.indexes);
+        assert!(
+            a.contents
+                .iter()
+                .zip(&b.contents)
+                .all(|(a, b)| a.signature() == b.signature())
+        );
+        assert_eq!(a.posts, b.posts);
+        assert_ne!(a.users[0].0.pub_key(), other.users[0].0.pub_key());
+    }
+
+    #[tokio::test]
+    async fn graph_is_valid_and_storable() {
+        let graph = Graph::from_seed("demo");
+        let repositories = Repositories::in_memory().await;
+
+        assert!(graph.users.iter().all(|(u, _)| u.verify()));
+        assert!(graph.contents.iter().all(|c| c.verify()));
+        graph.store(&repositories).await.unwrap();
+
+        let indexes = repositories
+            .index()
+            .get_all_indexes::<MangaTag>(None, None)
+            .await
+            .unwrap();
+        assert_eq!(indexes.len(), graph.indexes.len());
+    }
+
+    #[tokio::test]
+    async fn demo_chapters_point_at_sample_archives() {
+        let graph = Graph::with_shape(
+            "demo",
+            GraphShape {
+                users: 1,
+                indexes_per_user: 1,
+                chapters_per_index: 2,
+                posts_per_index: 0,
+            },
+        );
+        let repositories = Repositories::in_memory().await;
+        let dir = std::env::temp_dir().join(format!("akareko-demo-{}", std::process::id()));
+
+        graph.store_demo(&repositories, &dir).await.unwrap();
+
+        let contents = repositories
+            .index()
+            .get_index_contents::<MangaTag>(graph.indexes[0].hash().clone())
+            .await
+            .unwrap();
+        assert_eq!(contents.len(), 2);
+        for content in contents {
+            assert!(content.is_local_only());
+            assert!(Path::new(content.source()).exists());
+        }
+        tokio::fs::remove_dir_all(dir).await.unwrap();
+    }
+}
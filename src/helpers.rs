@@ -6,14 +6,19 @@ use skerry::skerry;
 use surrealdb_types::SurrealValue;
 use unicode_normalization::UnicodeNormalization;
 
-use crate::db::user::I2PAddress;
+use crate::{db::user::I2PAddress, errors::DecodeError};
 
 mod byteable;
-pub use byteable::{AkarekoRead, AkarekoWrite};
+pub mod cbz;
+#[cfg(test)]
+pub(crate) use byteable::assert_round_trip;
+pub use byteable::{AkarekoRead, AkarekoWrite, decode_limited};
 
 mod lifo;
+mod lru;
 mod serde_byteable;
 pub use lifo::LiFo;
+pub use lru::Lru;
 
 #[derive(Debug, Clone)]
 pub struct SanitizedString(String);
@@ -42,14 +47,43 @@ impl SanitizedString {
     // }
 }
 
-#[derive(Debug, Clone, SurrealValue, Serialize, Deserialize)]
+/// Goes over the wire as its `u16` discriminant, the same as the signed bytes
+/// of [`MangaChapter`](crate::db::index::tags::MangaChapter)
+#[derive(Debug, Clone, PartialEq, SurrealValue, Serialize, Deserialize)]
+#[serde(into = "u16", try_from = "u16")]
 #[repr(u16)]
 pub enum Language {
-    Japanese,
-    English,
-    French,
-    Portuguese,
-    Unknown,
+    Japanese = 0,
+    English = 1,
+    French = 2,
+    Portuguese = 3,
+    Unknown = 4,
+}
+
+impl From<Language> for u16 {
+    fn from(language: Language) -> Self {
+        language as u16
+    }
+}
+
+impl TryFrom<u16> for Language {
+    type Error = DecodeError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Language::Japanese,
+            1 => Language::English,
+            2 => Language::French,
+            3 => Language::Portuguese,
+            4 => Language::Unknown,
+            _ => {
+                return Err(DecodeError::InvalidEnumVariant {
+                    variant_value: value.to_string(),
+                    enum_name: "Language",
+                });
+            }
+        })
+    }
 }
 
 fn i2p_b64_fix(s: &str) -> String {
@@ -69,3 +103,35 @@ pub fn b32_from_pub_b64(pub_b64: &str) -> Result<I2PAddress, e![InvalidBase64]>
     let b32_52 = b32.chars().take(52).collect::<String>();
     Ok(I2PAddress::new(format!("{}.b32.i2p", b32_52)))
 }
+
+/// `1536` as `2KB`, units of 1024, two decimals from GB up
+pub fn format_bytes(bytes: i64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+
+    let float_bytes = bytes as f64;
+
+    let unit_index = (float_bytes.log2() / 10.0).floor() as usize;
+    let unit_index = unit_index.min(UNITS.len() - 1);
+
+    let divisor = 1024.0f64.powf(unit_index as f64);
+
+    let value = float_bytes / divisor;
+
+    if unit_index > 2 {
+        format!("{:.2}{}", value, UNITS[unit_index])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit_index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Language, assert_round_trip};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn language_is_encoded_as_its_discriminant() {
+        assert_eq!(u16::from(Language::Portuguese), 3);
+        assert!(Language::try_from(5).is_err());
+        assert_round_trip(Language::French).await;
+    }
+}
@@ -1,6 +1,6 @@
 use postcard::{Deserializer, de_flavors::io::io::IOReader};
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio_util::io::SyncIoBridge;
 
 use crate::errors::{DecodeError, EncodeError};
@@ -31,8 +31,8 @@ where
         &self,
         writer: &mut W,
     ) -> Result<(), EncodeError> {
-        let bytes = postcard::to_allocvec(&self).unwrap();
-        writer.write_all(&bytes);
+        let bytes = postcard::to_allocvec(&self).map_err(|_| EncodeError::InvalidData)?;
+        writer.write_all(&bytes).await?;
         Ok(())
     }
 }
@@ -50,11 +50,29 @@ where
         let mut deserializer: Deserializer<'_, IOReader<'_, SyncIoBridge<&mut R>>> =
             postcard::Deserializer::from_flavor(IOReader::new(bridge, &mut buffer));
 
-        let val = T::deserialize(&mut deserializer).unwrap();
+        // The bridge blocks on the reader, which tokio only allows off the
+        // async worker
+        let val = tokio::task::block_in_place(|| T::deserialize(&mut deserializer))
+            .map_err(|_| DecodeError::InvalidData)?;
         Ok(val)
     }
 }
 
+/// Decodes a `T` from at most `max` bytes of `reader`. Lengths in the
+/// encoding are trusted by the decoder, this keeps a peer from making it read
+/// and allocate without end.
+pub async fn decode_limited<T: AkarekoRead, R: AsyncRead + Unpin + Send>(
+    reader: &mut R,
+    max: u64,
+) -> Result<T, DecodeError> {
+    let mut limited = reader.take(max);
+    match T::decode(&mut limited).await {
+        Ok(value) => Ok(value),
+        Err(_) if limited.limit() == 0 => Err(DecodeError::PayloadTooLarge { allowed: max }),
+        Err(e) => Err(e),
+    }
+}
+
 // pub trait Byteable {
 //     fn encode<W: AsyncWrite + Unpin + Send>(
 //         &self,
@@ -382,3 +400,52 @@ where
 // Result<Self, DecodeError> {         Ok(reader.read_i64().await?)
 //     }
 // }
+
+/// Encodes `value` and decodes it back, for checking that hand written
+/// implementations read what they write
+#[cfg(test)]
+pub(crate) async fn assert_round_trip<T>(value: T)
+where
+    T: AkarekoRead + AkarekoWrite + PartialEq + std::fmt::Debug,
+{
+    let mut bytes = Vec::new();
+    value.encode(&mut bytes).await.unwrap();
+
+    let mut reader = bytes.as_slice();
+    let decoded = T::decode(&mut reader).await.unwrap();
+
+    assert_eq!(decoded, value);
+    assert!(reader.is_empty(), "{} bytes left unread", reader.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_round_trip, decode_limited};
+    use crate::{errors::DecodeError, helpers::AkarekoWrite as _};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serde_types_round_trip() {
+        assert_round_trip(42u16).await;
+        assert_round_trip("ページ".to_string()).await;
+        assert_round_trip(vec![(1u64, Some("a".to_string())), (2, None)]).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn payloads_over_the_limit_are_refused() {
+        let mut bytes = Vec::new();
+        vec![7u8; 100].encode(&mut bytes).await.unwrap();
+
+        let mut reader = bytes.as_slice();
+        assert!(matches!(
+            decode_limited::<Vec<u8>, _>(&mut reader, 50).await,
+            Err(DecodeError::PayloadTooLarge { allowed: 50 })
+        ));
+
+        let mut reader = bytes.as_slice();
+        let decoded: Vec<u8> = decode_limited(&mut reader, bytes.len() as u64)
+            .await
+            .unwrap();
+        assert_eq!(decoded, vec![7u8; 100]);
+        assert!(reader.is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use async_zip::{Compression, ZipEntryBuilder, tokio::write::ZipFileWriter};
+use tokio::fs::File;
+
+use crate::errors::ExportError;
+
+const IMAGE_EXTENSIONS: [&str; 6] = ["jpg", "jpeg", "png", "webp", "gif", "avif"];
+
+/// Packages a downloaded chapter into a CBZ archive at `destination`.
+///
+/// `source` can either be a folder of images, which are stored in name order,
+/// or an existing `.cbz`, which is copied as is. Returns the number of pages
+/// written.
+pub async fn export_cbz(source: &Path, destination: &Path) -> Result<usize, ExportError> {
+    if !source.exists() {
+        return Err(ExportError::SourceNotFound);
+    }
+
+    if let Some(parent) = destination.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    if source.is_file() {
+        if !is_cbz(source) {
+            return Err(ExportError::SourceNotFound);
+        }
+        tokio::fs::copy(source, destination).await?;
+        let file = tokio::io::BufReader::new(File::open(destination).await?);
+        let zip = async_zip::tokio::read::seek::ZipFileReader::with_tokio(file).await?;
+        return Ok(zip.file().entries().len());
+    }
+
+    let pages = collect_pages(source).await?;
+    let mut writer = ZipFileWriter::with_tokio(File::create(destination).await?);
+
+    for (i, page) in pages.iter().enumerate() {
+        let data = tokio::fs::read(page).await?;
+        let name = page_name(i, page);
+        // Images are already compressed, deflating them again only costs time
+        let entry = ZipEntryBuilder::new(name.into(), Compression::Stored);
+        writer.write_entry_whole(entry, &data).await?;
+    }
+
+    writer.close().await?;
+
+    Ok(pages.len())
+}
+
+/// Pages of a downloaded chapter, images in a folder or entries of a `.cbz`
+pub async fn count_pages(source: &Path) -> Result<usize, ExportError> {
+    if source.is_dir() {
+        return Ok(collect_pages(source).await?.len());
+    }
+    if !is_cbz(source) {
+        return Err(ExportError::SourceNotFound);
+    }
+
+    let file = tokio::io::BufReader::new(File::open(source).await?);
+    let zip = async_zip::tokio::read::seek::ZipFileReader::with_tokio(file).await?;
+    Ok(zip.file().entries().len())
+}
+
+fn is_cbz(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("cbz"))
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+async fn collect_pages(dir: &Path) -> Result<Vec<PathBuf>, ExportError> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut pages = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_type().await?.is_file() && is_image(&path) {
+            pages.push(path);
+        }
+    }
+    pages.sort();
+    Ok(pages)
+}
+
+/// Zero padded so readers that sort by name keep the page order
+fn page_name(index: usize, path: &Path) -> String {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("jpg")
+        .to_ascii_lowercase();
+    format!("{:04}.{}", index + 1, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_names_keep_order() {
+        assert_eq!(page_name(0, Path::new("a/page1.PNG")), "0001.png");
+        assert_eq!(page_name(11, Path::new("a/12.jpg")), "0012.jpg");
+        assert!(page_name(1, Path::new("b")) < page_name(10, Path::new("a")));
+    }
+
+    #[test]
+    fn only_images_are_pages() {
+        assert!(is_image(Path::new("01.JPG")));
+        assert!(is_image(Path::new("02.webp")));
+        assert!(!is_image(Path::new("info.txt")));
+        assert!(!is_image(Path::new("noext")));
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::VecDeque;
+
+/// Tracks the `N` most recently used keys, handing back the ones that fall
+/// out so the caller can free whatever they point to.
+#[derive(Debug, Clone)]
+pub struct Lru<K: PartialEq, const N: usize> {
+    order: VecDeque<K>,
+}
+
+impl<K: PartialEq, const N: usize> Lru<K, N> {
+    pub fn new() -> Self {
+        Self {
+            order: VecDeque::with_capacity(N),
+        }
+    }
+
+    /// Marks `key` as the most recently used, returns the evicted key if the
+    /// tracker was full.
+    pub fn touch(&mut self, key: K) -> Option<K> {
+        if let Some(pos) = self.order.iter().position(|k| *k == key) {
+            self.order.remove(pos);
+            self.order.push_back(key);
+            return None;
+        }
+
+        self.order.push_back(key);
+        if self.order.len() > N {
+            self.order.pop_front()
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.order.contains(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = Lru::<u32, 2>::new();
+        assert_eq!(lru.touch(1), None);
+        assert_eq!(lru.touch(2), None);
+        assert_eq!(lru.touch(1), None);
+        assert_eq!(lru.touch(3), Some(2));
+        assert!(lru.contains(&1));
+        assert!(!lru.contains(&2));
+    }
+}
@@ -1,10 +1,15 @@
 #![feature(negative_impls)]
 #![feature(auto_traits)]
 
+pub mod build_info;
 pub mod config;
 pub mod db;
+pub mod diagnostics;
 pub mod errors;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 pub mod helpers;
 pub mod server;
+pub mod storage;
 pub mod types;
 pub mod ui;
@@ -1,7 +1,7 @@
 #![feature(negative_impls)]
 #![feature(auto_traits)]
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use freya::{
@@ -19,14 +19,20 @@ use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::Subs
 use crate::ui::{
     AkarekoApp, AppChannel, AppState, AppWindowType, RouteContext,
     app_manager::{AppManager, Event},
+    link_handler::{self, HANDOFF_FILE},
 };
 
+mod build_info;
 mod clients;
 mod config;
 mod db;
+mod diagnostics;
 mod errors;
+#[cfg(any(test, feature = "fixtures"))]
+mod fixtures;
 mod helpers;
 mod server;
+mod storage;
 mod types;
 mod ui;
 
@@ -36,10 +42,18 @@ struct CliArgs {
     ///   Start the application in minimized state.
     #[arg(long)]
     minimized: bool,
+    ///   Folder holding the config, used when started by the OS to open a link.
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    ///   akareko:// or magnet link to open.
+    link: Option<String>,
 }
 
 fn main() -> Result<(), ()> {
     let args = CliArgs::parse();
+    if let Some(dir) = &args.dir {
+        std::env::set_current_dir(dir).map_err(|_| ())?;
+    }
 
     // ==================== Tracing ====================
     let borrowed_format_items =
@@ -71,6 +85,14 @@ fn main() -> Result<(), ()> {
     // Enter the Tokio context so its APIs (channels, timers, etc.) work.
     let _rt = rt.enter();
 
+    // Already running, the link is opened there
+    if let Some(link) = &args.link
+        && rt.block_on(link_handler::hand_off(Path::new(HANDOFF_FILE), link))
+    {
+        info!("Link handed to the running instance");
+        return Ok(());
+    }
+
     let tray_icon = || {
         const ICON: &'static [u8] = include_bytes!("../assets/tray_icon.ico");
         let tray_menu = Menu::new();
@@ -91,6 +113,7 @@ fn main() -> Result<(), ()> {
     };
 
     let mut app_state = AppState::new();
+    app_state.pending_link = args.link.as_deref().and_then(|l| l.parse().ok());
     if !args.minimized {
         app_state.windows_state.try_add_window(AppWindowType::Main);
     }
@@ -99,6 +122,10 @@ fn main() -> Result<(), ()> {
     let router = RouteContext::create_global();
 
     let (manager, manager_tx) = AppManager::new(radio_station);
+    rt.spawn(link_handler::listen(
+        PathBuf::from(HANDOFF_FILE),
+        manager_tx.clone(),
+    ));
     let app = AkarekoApp::new(radio_station, router);
 
     let manager_tx_tray = manager_tx.clone();
@@ -133,9 +160,10 @@ fn main() -> Result<(), ()> {
             }
         }
         TrayEvent::Menu(MenuEvent { id }) if id == "quit" => {
-            match &radio_station.peek().torrent_client {
-                ui::ResourceState::Loaded(client) => {
-                    let _ = block_on(client.save(PathBuf::from("./data/torrents")));
+            let station = radio_station.peek();
+            match (&station.torrent_client, &station.config) {
+                (ui::ResourceState::Loaded(client), ui::ResourceState::Loaded(config)) => {
+                    let _ = block_on(client.save(config.storage().torrents_dir()));
                 }
                 _ => {}
             };
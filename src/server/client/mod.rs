@@ -1,62 +1,158 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
 use fastbloom::BloomFilter;
+use futures::StreamExt as _;
 use rclite::Arc;
 use tokio::sync::Mutex;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use yosemite::{Session, SessionOptions, Stream, style};
 
 use crate::{
-    config::AkarekoConfig,
+    config::{AkarekoConfig, ContentLimitsConfig},
     db::{
         Repositories,
+        catalog::{Catalog, PendingCatalog},
         comments::Post,
+        content_source::{ContentSource, SourceKind},
         event::{EventType, make_event_filter},
+        group::Group,
         index::{
             Index, IndexRepository,
             content::Content,
             tags::{IndexTag, MangaTag},
         },
-        user::{I2PAddress, TrustLevel, User},
+        user::{I2PAddress, SyncPolicy, TrustLevel, User},
     },
     errors::ClientError,
     server::{
         handler::{
             self, AkarekoProtocolCommandRequest,
             events::SyncEventsRequest,
-            index::{GetAllIndexesRequest, GetContents, GetContentsRequest},
-            users::{get_users::GetUsersRequest, who::WhoRequest},
+            group::{
+                GetGroupContents, GetGroupContentsRequest, GroupChallenge, GroupChallengeRequest,
+                ProveGroup, ProveGroupRequest,
+            },
+            index::{
+                Announce, AnnounceRequest, GetAllIndexesRequest, GetCatalogSnapshot,
+                GetCatalogSnapshotRequest, GetContents, GetContentsRequest, GetIndexes,
+                GetIndexesRequest, GetRevocations, GetRevocationsRequest, HaveContent,
+                HaveContentRequest, MAX_HAVE_SIGNATURES, MAX_SUBSCRIBED_INDEXES, SearchIndexes,
+                SearchIndexesRequest, StreamAllIndexes, Subscribe, SubscribeRequest,
+            },
+            meta::{Ping, get_node_info::GetNodeInfoRequest, ping::PingRequest},
+            post::{GetPostsRequest, MAX_POSTS_PER_PAGE},
+            users::{
+                AuthChallenge, Authenticate,
+                auth_challenge::AuthChallengeRequest,
+                authenticate::AuthenticateRequest,
+                get_attestations::GetAttestationsRequest,
+                get_peers::{GetPeersRequest, MAX_PEERS},
+                get_users::GetUsersRequest,
+                who::WhoRequest,
+            },
+        },
+        protocol::{
+            AkarekoProtocolVersion, ChunkedStream, DEFAULT_MAX_RESPONSE_SIZE, PeerStream,
+            StreamDecode,
         },
-        protocol::StreamDecode,
+        simulator::{NetworkSimulation, SimulatedStream},
     },
-    types::{Hash, PublicKey, Timestamp},
+    types::{Hash, PrivateKey, PublicKey, Signature, Timestamp, Topic},
 };
 
+pub use crate::server::handler::meta::get_node_info::{NodeInfo, NodeLimits};
+
 pub const TIME_OFFSET: i64 = 60;
+/// Window [`Post::MAX_PER_HOUR`] is counted over
+const POST_RATE_WINDOW: i64 = 60 * 60;
+/// Followed indexes whose posts are asked for in a routine exchange
+const MAX_EXCHANGED_TOPICS: usize = 200;
 
 pub mod pool;
+pub mod update;
+
+#[derive(Debug, Clone)]
+pub struct WhoReport {
+    pub user: User,
+    /// The peer signed our address with the key it claims to have, so the key
+    /// is really behind the destination we connected to
+    pub address_signature_valid: bool,
+    /// The user record itself is signed by its key
+    pub user_signature_valid: bool,
+    /// The address inside the signed user record is the one we connected to
+    pub address_matches: bool,
+}
+
+impl WhoReport {
+    pub fn is_verified(&self) -> bool {
+        self.address_signature_valid && self.user_signature_valid && self.address_matches
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingReport {
+    /// Time from sending the request to having the response, without opening
+    /// the stream
+    pub rtt: Duration,
+    pub server_time: Timestamp,
+    pub protocol_version: AkarekoProtocolVersion,
+}
 
 #[derive(Clone)]
 pub struct AkarekoClient {
     host_address: I2PAddress,
+    /// Signs auth challenges, peers then know which of their users we are
+    priv_key: PrivateKey,
     session: Arc<Mutex<Session<style::Stream>>>,
+    simulation: Option<NetworkSimulation>,
+    /// Given to each response, and each item streamed after it
+    timeout: Duration,
+    /// Content past these is dropped as it's received
+    content_limits: ContentLimitsConfig,
+    /// Version each peer was found to speak, see [`AkarekoClient::get_stream`]
+    versions: Arc<Mutex<HashMap<I2PAddress, AkarekoProtocolVersion>>>,
+}
+
+/// Fails with [`ClientError::Timeout`] if `fut` takes longer than `limit`, a
+/// peer that stops answering would otherwise hold the stream forever
+async fn timed<T, E: Into<ClientError>>(
+    limit: Duration,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, ClientError> {
+    match tokio::time::timeout(limit, fut).await {
+        Ok(res) => res.map_err(Into::into),
+        Err(_) => Err(ClientError::Timeout),
+    }
 }
 
 macro_rules! impl_get_content {
     ($tag:ty, $id:ident) => {
         paste::paste! {
+            /// Takes the contents of `index_hash` created since we last took
+            /// them from `url`, every one the first time
             pub async fn [<get_ $id _content>](
                 &mut self,
                 url: &I2PAddress,
-                db: IndexRepository<'_>,
+                repo: &Repositories,
                 index_hash: Hash,
-                timestamp: Option<Timestamp>,
-                filter: Option<BloomFilter>,
             ) -> Result<(), ClientError> {
+                let db = repo.index();
+                let started = repo.now();
+                let since = repo
+                    .get_content_synced_at(&index_hash, url)
+                    .await?
+                    .map(|t| t - TIME_OFFSET);
+                let filter = db.make_filter::<$tag>(&index_hash, since).await?;
+
                 let mut stream = self.get_stream(url).await?;
 
-                let mut res = GetContents::<$tag>::request(
-                    GetContentsRequest::new(index_hash, timestamp, filter),
+                let mut res = timed(self.timeout, GetContents::<$tag>::send(
+                    GetContentsRequest::new(index_hash.clone(), since, Some(filter)),
                     &mut stream,
-                )
+                ))
                 .await?;
 
                 if !res.status().is_ok() {
@@ -65,11 +161,23 @@ macro_rules! impl_get_content {
                     });
                 }
 
-                while let Ok(Some(content)) = res.data().next(&mut stream).await {
+                res.data().limit_items(self.content_limits.max_encoded_bytes);
+                // A stream cut short isn't recorded, the next exchange asks
+                // from the same point again
+                while let Some(mut content) = timed(self.timeout, stream.next_item(res.data())).await? {
                     if !content.verify() {
                         error!("Invalid content signature");
                         continue;
                     }
+                    if let Err(e) = content.check_limits(&self.content_limits) {
+                        warn!("Dropping content: {}", e);
+                        continue;
+                    }
+
+                    if !content.verify_relay_trail() {
+                        warn!("Invalid relay trail, dropping it");
+                        content.clear_relay_trail();
+                    }
 
                     match db.add_content(content).await {
                         Ok(_) => {}
@@ -79,12 +187,140 @@ macro_rules! impl_get_content {
                     }
                 }
 
+                repo.record_content_sync(&index_hash, url, started).await?;
+                Ok(())
+            }
+
+            /// Tells `url` which contents of `index_hash` we have, returns the
+            /// ones it's missing
+            pub async fn [<announce_ $id _content>](
+                &mut self,
+                url: &I2PAddress,
+                db: IndexRepository<'_>,
+                index_hash: Hash,
+            ) -> Result<Vec<Signature>, ClientError> {
+                let signatures: Vec<Signature> = db
+                    .get_filtered_index_contents::<$tag>(index_hash.clone(), None, None)
+                    .await?
+                    .into_iter()
+                    .map(|c| c.signature().clone())
+                    .take(MAX_HAVE_SIGNATURES)
+                    .collect();
+
+                let mut stream = self.get_stream(url).await?;
+
+                let res = timed(self.timeout, HaveContent::<$tag>::send(
+                    HaveContentRequest::new(index_hash, signatures),
+                    &mut stream,
+                ))
+                .await?;
+
+                Ok(res.payload_if_ok()?.missing)
+            }
+
+            /// Proves to `url` that we are part of `group` and fetches what was
+            /// shared in it for `index_hash`, all on the same connection
+            pub async fn [<get_group_ $id _content>](
+                &mut self,
+                url: &I2PAddress,
+                db: IndexRepository<'_>,
+                group: &Group,
+                index_hash: Hash,
+            ) -> Result<(), ClientError> {
+                let mut stream = self.get_stream(url).await?;
+
+                let challenge =
+                    timed(self.timeout, GroupChallenge::send(GroupChallengeRequest {}, &mut stream))
+                        .await?
+                        .payload_if_ok()?;
+
+                let proof = group.prove(&challenge.nonce, &self.host_address);
+                timed(self.timeout, ProveGroup::send(ProveGroupRequest::new(group.id().clone(), proof), &mut stream))
+                    .await?
+                    .payload_if_ok()?;
+
+                let mut res = timed(self.timeout, GetGroupContents::<$tag>::send(
+                    GetGroupContentsRequest::new(group.id().clone(), index_hash),
+                    &mut stream,
+                ))
+                .await?;
+
+                if !res.status().is_ok() {
+                    return Err(ClientError::UnexpectedResponseCode {
+                        status: res.status().clone(),
+                    });
+                }
+
+                res.data().limit_items(self.content_limits.max_encoded_bytes);
+                while let Ok(Some(content)) = timed(self.timeout, stream.next_item(res.data())).await {
+                    if !content.verify() {
+                        error!("Invalid content signature");
+                        continue;
+                    }
+                    if let Err(e) = content.check_limits(&self.content_limits) {
+                        warn!("Dropping content: {}", e);
+                        continue;
+                    }
+
+                    if let Err(e) = db.add_group_content(content, group.id().clone()).await {
+                        error!("Failed to add group content: {}", e);
+                    }
+                }
+
                 Ok(())
             }
         }
     };
 }
 
+/// Stores posts received from a peer, dropping the invalid ones and those of
+/// sources over [`Post::MAX_PER_HOUR`]
+struct PostIntake<'a> {
+    repo: &'a Repositories,
+    /// Posts taken per source, counting what was already stored for the last
+    /// hour and everything taken since the intake started
+    taken: HashMap<PublicKey, usize>,
+    stored: usize,
+}
+
+impl<'a> PostIntake<'a> {
+    fn new(repo: &'a Repositories) -> Self {
+        Self {
+            repo,
+            taken: HashMap::new(),
+            stored: 0,
+        }
+    }
+
+    async fn take(&mut self, post: Post) -> Result<(), ClientError> {
+        if !post.verify() {
+            error!("Invalid post signature");
+            return Ok(());
+        }
+        if !post.is_within_limits() {
+            warn!("Dropping post over the length limit");
+            return Ok(());
+        }
+
+        let count = match self.taken.get(&post.source) {
+            Some(count) => *count,
+            None => {
+                let since = self.repo.now() - POST_RATE_WINDOW;
+                self.repo.count_posts_by_source(&post.source, since).await?
+            }
+        };
+        if count >= Post::MAX_PER_HOUR {
+            warn!("{} is over its post rate, dropping", post.source);
+            return Ok(());
+        }
+        self.taken.insert(post.source.clone(), count + 1);
+
+        self.repo.add_post(post).await?;
+        self.stored += 1;
+        Ok(())
+    }
+}
+
 impl AkarekoClient {
     impl_get_content!(MangaTag, manga);
 
@@ -92,31 +328,108 @@ impl AkarekoClient {
         Self {
             session: Arc::new(Mutex::new(sam_session)),
             host_address: config.eepsite_address().clone(),
+            priv_key: config.private_key().clone(),
+            simulation: config.network_simulation(),
+            timeout: config.timeouts().request(),
+            content_limits: config.content_limits().clone(),
+            versions: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    async fn get_stream(&mut self, url: &I2PAddress) -> Result<Stream, ClientError> {
+    /// Plain stream to `url`, for anything else than the protocol
+    async fn connect(&mut self, url: &I2PAddress) -> Result<SimulatedStream<Stream>, ClientError> {
         let session = self.session.clone();
-        let stream = session.lock().await.connect(url.inner()).await?;
+        let stream = timed(self.timeout, session.lock().await.connect(url.inner())).await?;
+        Ok(SimulatedStream::new(stream, self.simulation.clone()))
+    }
+
+    /// Stream to `url` speaking the latest version it knows. The first time,
+    /// the peer is pinged over V1 to learn it, peers from before the ping drop
+    /// the connection and are spoken to in V1 from then on.
+    async fn get_stream(
+        &mut self,
+        url: &I2PAddress,
+    ) -> Result<PeerStream<SimulatedStream<Stream>>, ClientError> {
+        let known = self.versions.lock().await.get(url).copied();
+        if let Some(version) = known {
+            return Ok(PeerStream::new(self.connect(url).await?, version));
+        }
+
+        let mut stream = PeerStream::new(self.connect(url).await?, AkarekoProtocolVersion::V1);
+        let version = match timed(self.timeout, Ping::send(PingRequest {}, &mut stream)).await {
+            Ok(res) => res.payload_if_ok()?.protocol_version,
+            // Nothing is known of a peer that didn't answer
+            Err(ClientError::Timeout) => return Err(ClientError::Timeout),
+            Err(e) => {
+                info!("{} doesn't know ping, speaking V1 to it: {}", url, e);
+                stream = PeerStream::new(self.connect(url).await?, AkarekoProtocolVersion::V1);
+                AkarekoProtocolVersion::V1
+            }
+        };
+
+        self.versions.lock().await.insert(url.clone(), version);
+        stream.set_version(version);
         Ok(stream)
     }
 
+    /// Stream to `url` on which we proved who we are, so the peer applies
+    /// what it agreed with us rather than with everyone at our address.
+    /// Peers that don't know how get a plain stream.
+    async fn get_authenticated_stream(
+        &mut self,
+        url: &I2PAddress,
+    ) -> Result<PeerStream<SimulatedStream<Stream>>, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+        match self.authenticate(&mut stream).await {
+            Ok(()) => Ok(stream),
+            Err(e) => {
+                // The stream may be left anywhere in a failed exchange
+                warn!("Failed to authenticate to {}: {}", url, e);
+                self.get_stream(url).await
+            }
+        }
+    }
+
+    async fn authenticate(
+        &self,
+        stream: &mut PeerStream<SimulatedStream<Stream>>,
+    ) -> Result<(), ClientError> {
+        let challenge = timed(
+            self.timeout,
+            AuthChallenge::send(AuthChallengeRequest {}, stream),
+        )
+        .await?
+        .payload_if_ok()?;
+
+        let req =
+            AuthenticateRequest::new_signed(&challenge.nonce, &self.host_address, &self.priv_key);
+        timed(self.timeout, Authenticate::send(req, stream))
+            .await?
+            .payload_if_ok()?;
+
+        Ok(())
+    }
+
     pub async fn sync_events(
         &mut self,
         url: &I2PAddress,
         timestamp: Timestamp,
         repo: &Repositories,
     ) -> Result<Timestamp, ClientError> {
-        let mut stream = self.get_stream(url).await?;
+        let mut stream = self.get_authenticated_stream(url).await?;
 
         let filter = make_event_filter(timestamp - TIME_OFFSET, &repo.db).await?;
+        let policy = repo.user().get_sync_policy_by_address(url).await?;
 
-        let res = handler::events::SyncEvents::request(
-            SyncEventsRequest {
-                timestamp,
-                filter: Some(filter),
-            },
-            &mut stream,
+        let res = timed(
+            self.timeout,
+            handler::events::SyncEvents::send(
+                SyncEventsRequest {
+                    timestamp,
+                    filter: Some(filter),
+                },
+                &mut stream,
+            ),
         )
         .await?;
 
@@ -138,50 +451,144 @@ impl AkarekoClient {
                 }
                 EventType::User => {
                     let mut stream_decode = StreamDecode::<User>::new_receiver(len);
-                    while let Some(user) = stream_decode.next(&mut stream).await? {
+                    while let Some(user) =
+                        timed(self.timeout, stream.next_item(&mut stream_decode)).await?
+                    {
                         if !user.verify() {
                             error!("Invalid user signature");
                             continue;
                         }
+
+                        let namesakes = repo
+                            .user()
+                            .get_users_by_name(user.name(), TrustLevel::Trusted)
+                            .await?;
+                        if namesakes.iter().any(|u| u.pub_key() != user.pub_key()) {
+                            warn!(
+                                "Received user {} ({}) shares its name with a trusted user",
+                                user.name(),
+                                user.pub_key().fingerprint()
+                            );
+                        }
+
+                        let pub_key = user.pub_key().clone();
                         repo.user().upsert_user(user).await?;
+                        repo.user().record_seen(pub_key).await?;
                     }
                 }
                 EventType::Manga => {
                     let mut stream_decode = StreamDecode::<Index<MangaTag>>::new_receiver(len);
-                    while let Some(index) = stream_decode.next(&mut stream).await? {
+                    while let Some(index) =
+                        timed(self.timeout, stream.next_item(&mut stream_decode)).await?
+                    {
                         if !index.verify() {
                             error!("Invalid index signature");
                             continue;
                         }
+                        if !policy.contains(SyncPolicy::ACCEPT_INDEXES) {
+                            continue;
+                        }
                         repo.index().add_index(index).await?;
                     }
                 }
                 EventType::MangaContent => {
                     let mut stream_decode = StreamDecode::<Content<MangaTag>>::new_receiver(len);
-                    while let Some(content) = stream_decode.next(&mut stream).await? {
+                    stream_decode.limit_items(self.content_limits.max_encoded_bytes);
+                    while let Some(mut content) =
+                        timed(self.timeout, stream.next_item(&mut stream_decode)).await?
+                    {
                         if !content.verify() {
                             error!("Invalid content signature");
                             continue;
                         }
+                        if let Err(e) = content.check_limits(&self.content_limits) {
+                            warn!("Dropping content: {}", e);
+                            continue;
+                        }
+
+                        if !content.verify_relay_trail() {
+                            warn!("Invalid relay trail, dropping it");
+                            content.clear_relay_trail();
+                        }
+
+                        if !policy.contains(SyncPolicy::ACCEPT_CONTENT) {
+                            continue;
+                        }
+
+                        let source =
+                            ContentSource::from_content(&content, url.clone(), SourceKind::Sent);
                         repo.index().add_content(content).await?;
+                        repo.add_content_source(source).await?;
                     }
                 }
                 EventType::Post => {
+                    let mut intake = PostIntake::new(repo);
                     let mut stream_decode = StreamDecode::<Post>::new_receiver(len);
-                    while let Some(post) = stream_decode.next(&mut stream).await? {
-                        if !post.verify() {
-                            error!("Invalid post signature");
+                    while let Some(post) =
+                        timed(self.timeout, stream.next_item(&mut stream_decode)).await?
+                    {
+                        if !policy.contains(SyncPolicy::ACCEPT_POSTS) {
                             continue;
                         }
-                        repo.add_post(post).await?;
+                        intake.take(post).await?;
                     }
                 }
             }
         }
 
+        self.fetch_attestations(url, repo).await?;
+        repo.user().record_exchange(url).await?;
+
         Ok(payload.timestamp)
     }
 
+    /// Takes the attestations the peer signed, those from users we don't
+    /// fully trust are dropped as they would never count
+    pub async fn fetch_attestations(
+        &mut self,
+        url: &I2PAddress,
+        repo: &Repositories,
+    ) -> Result<usize, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let res = timed(
+            self.timeout,
+            handler::users::GetAttestations::send(GetAttestationsRequest {}, &mut stream),
+        )
+        .await?;
+
+        if !res.status().is_ok() {
+            return Err(ClientError::UnexpectedResponseCode {
+                status: res.status().clone(),
+            });
+        }
+
+        let Some(payload) = res.payload() else {
+            return Err(ClientError::MissingPayload);
+        };
+
+        let vouchers = repo
+            .user()
+            .get_keys_with_trust(TrustLevel::FullTrust)
+            .await?;
+
+        let mut stored = 0;
+        for attestation in payload.attestations {
+            if !vouchers.contains(&attestation.voucher) {
+                continue;
+            }
+            if !attestation.verify() {
+                error!("Invalid attestation signature");
+                continue;
+            }
+            if repo.user().add_attestation(attestation).await? {
+                stored += 1;
+            }
+        }
+
+        Ok(stored)
+    }
+
     // ╔===========================================================================╗
     // ║                                   Index                                   ║
     // ╚===========================================================================╝
@@ -193,11 +600,43 @@ impl AkarekoClient {
         timestamp: Option<Timestamp>,
         filter: Option<BloomFilter>,
     ) -> Result<(), ClientError> {
+        let indexes = self.stream_indexes::<T>(url, timestamp, filter).await?;
+        let mut indexes = std::pin::pin!(indexes);
+
+        while let Some(index) = indexes.next().await {
+            if !index.verify() {
+                error!("Invalid index signature");
+                continue;
+            }
+
+            match db.add_index::<T>(index).await {
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Failed to add index: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every index `url` has past `timestamp` and not in `filter`, read chunk
+    /// by chunk as they come. Signatures aren't checked. The stream ends early
+    /// if the peer stops answering or sends something it can't decode.
+    pub async fn stream_indexes<T: IndexTag>(
+        &mut self,
+        url: &I2PAddress,
+        timestamp: Option<Timestamp>,
+        filter: Option<BloomFilter>,
+    ) -> Result<impl futures::Stream<Item = Index<T>> + use<T>, ClientError> {
         let mut stream = self.get_stream(url).await?;
 
-        let mut res = handler::index::GetAllIndexes::request(
-            GetAllIndexesRequest::new::<T>(timestamp, filter),
-            &mut stream,
+        let res = timed(
+            self.timeout,
+            StreamAllIndexes::<MangaTag>::send(
+                GetAllIndexesRequest::new::<T>(timestamp, filter),
+                &mut stream,
+            ),
         )
         .await?;
 
@@ -207,7 +646,84 @@ impl AkarekoClient {
             });
         }
 
-        while let Ok(Some(index)) = res.data().next(&mut stream).await {
+        stream.end_response();
+        let chunks = ChunkedStream::<Index<MangaTag>>::for_version(
+            stream.version(),
+            DEFAULT_MAX_RESPONSE_SIZE,
+        );
+        let timeout = self.timeout;
+        Ok(futures::stream::unfold(
+            (stream, chunks),
+            move |(mut stream, mut chunks)| async move {
+                match timed(timeout, chunks.next(&mut stream)).await {
+                    Ok(Some(index)) => Some((index.transmute::<T>(), (stream, chunks))),
+                    Ok(None) => None,
+                    Err(e) => {
+                        warn!("Indexes stopped coming: {}", e);
+                        None
+                    }
+                }
+            },
+        ))
+    }
+
+    /// Asks `url` for the indexes whose title contains `query`. Nothing is
+    /// stored, the results are for the user to pick from.
+    pub async fn search<T: IndexTag>(
+        &mut self,
+        url: &I2PAddress,
+        query: &str,
+    ) -> Result<Vec<Index<T>>, ClientError> {
+        let Some(req) = SearchIndexesRequest::new::<T>(query) else {
+            return Ok(vec![]);
+        };
+
+        let mut stream = self.get_stream(url).await?;
+        let mut res = timed(self.timeout, SearchIndexes::send(req, &mut stream)).await?;
+
+        if !res.status().is_ok() {
+            return Err(ClientError::UnexpectedResponseCode {
+                status: res.status().clone(),
+            });
+        }
+
+        let mut indexes = vec![];
+        while let Some(index) = timed(self.timeout, stream.next_item(res.data())).await? {
+            let index: Index<T> = index.transmute();
+
+            if !index.verify() {
+                error!("Invalid index signature");
+                continue;
+            }
+            indexes.push(index);
+        }
+
+        Ok(indexes)
+    }
+
+    /// Asks `url` for the indexes in `hashes`, returns how many were stored
+    pub async fn fetch_indexes<T: IndexTag>(
+        &mut self,
+        url: &I2PAddress,
+        db: IndexRepository<'_>,
+        hashes: Vec<Hash>,
+    ) -> Result<usize, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let mut res = timed(
+            self.timeout,
+            GetIndexes::send(GetIndexesRequest::new(hashes), &mut stream),
+        )
+        .await?;
+
+        if !res.status().is_ok() {
+            return Err(ClientError::UnexpectedResponseCode {
+                status: res.status().clone(),
+            });
+        }
+
+        let mut stored = 0;
+        while let Ok(Some(index)) = timed(self.timeout, stream.next_item(res.data())).await {
             let index: Index<T> = index.transmute();
 
             if !index.verify() {
@@ -216,127 +732,446 @@ impl AkarekoClient {
             }
 
             match db.add_index::<T>(index).await {
-                Ok(_) => {}
+                Ok(_) => stored += 1,
+                Err(e) => error!("Failed to add index: {}", e),
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// Fetches the catalog snapshot of `relay` in one request. Everything in
+    /// it is taken at once, so the relay has to be trusted. After the first
+    /// import only the changes since the last generation imported are asked.
+    /// Nothing is stored, the catalog is reviewed and then handed to
+    /// [`Repositories::land_catalog`].
+    pub async fn fetch_catalog<T: IndexTag>(
+        &mut self,
+        relay: &User,
+        repo: &Repositories,
+    ) -> Result<PendingCatalog<T>, ClientError> {
+        if *relay.trust() < TrustLevel::Trusted {
+            return Err(ClientError::UntrustedRelay);
+        }
+
+        let since = repo
+            .get_catalog_follow(relay.pub_key())
+            .await?
+            .map(|f| f.generation);
+
+        let mut stream = self.get_stream(relay.address()).await?;
+
+        let res = timed(
+            self.timeout,
+            GetCatalogSnapshot::send(GetCatalogSnapshotRequest { since }, &mut stream),
+        )
+        .await?;
+
+        if !res.status().is_ok() {
+            return Err(ClientError::UnexpectedResponseCode {
+                status: res.status().clone(),
+            });
+        }
+
+        let Some(snapshot) = res.payload() else {
+            return Err(ClientError::MissingPayload);
+        };
+
+        if snapshot.relay != *relay.pub_key() {
+            return Err(ClientError::IdentityMismatch);
+        }
+        if !snapshot.verify() {
+            return Err(ClientError::InvalidSignature);
+        }
+
+        let opened = snapshot.open::<T>()?;
+        let policy = repo.user().get_sync_policy(relay.pub_key()).await?;
+        let mut catalog = Catalog {
+            indexes: vec![],
+            contents: vec![],
+        };
+
+        if policy.contains(SyncPolicy::ACCEPT_INDEXES) {
+            for index in opened.indexes {
+                if !index.verify() {
+                    error!("Invalid index signature");
+                    continue;
+                }
+                catalog.indexes.push(index);
+            }
+        }
+
+        if policy.contains(SyncPolicy::ACCEPT_CONTENT) {
+            for mut content in opened.contents {
+                if !content.verify() {
+                    error!("Invalid content signature");
+                    continue;
+                }
+                if let Err(e) = content.check_limits(&self.content_limits) {
+                    warn!("Dropping content: {}", e);
+                    continue;
+                }
+
+                if !content.verify_relay_trail() {
+                    warn!("Invalid relay trail, dropping it");
+                    content.clear_relay_trail();
+                }
+
+                catalog.contents.push(content);
+            }
+        }
+
+        info!(
+            "Fetched {} indexes and {} contents from {}",
+            catalog.indexes.len(),
+            catalog.contents.len(),
+            relay
+        );
+
+        Ok(PendingCatalog {
+            relay: relay.pub_key().clone(),
+            relay_address: relay.address().clone(),
+            generation: snapshot.generation,
+            since: snapshot.since,
+            sources: repo.catalog_sources(&catalog).await?,
+            catalog,
+        })
+    }
+
+    // ╔===========================================================================╗
+    // ║                                 Exchange                                  ║
+    // ╚===========================================================================╝
+
+    /// Syncs events with the peer at `url` since `timestamp`, then asks it for
+    /// the posts of the indexes we follow, catching up on topics we had
+    /// missed. Returns the time of the peer to sync from next time.
+    pub async fn routine_exchange(
+        &mut self,
+        url: &I2PAddress,
+        timestamp: Timestamp,
+        repo: &Repositories,
+    ) -> Result<Timestamp, ClientError> {
+        let server_timestamp = self.sync_events(url, timestamp, repo).await?;
+
+        match self
+            .fetch_revocations(url, timestamp - TIME_OFFSET, repo)
+            .await
+        {
+            Ok(0) => {}
+            Ok(applied) => info!("Applied {} revocations from {}", applied, url),
+            Err(e) => warn!("Failed to get revocations from {}: {}", url, e),
+        }
+
+        let followed = repo
+            .index_follow()
+            .get_followed_indexes::<MangaTag>(MAX_EXCHANGED_TOPICS, 0)
+            .await?;
+        let hashes: Vec<Hash> = followed.iter().map(|(_, i)| i.hash().clone()).collect();
+        for (_, index) in followed {
+            match self
+                .fetch_posts(url, Topic::from_index(&index), None, repo)
+                .await
+            {
+                Ok(0) => {}
+                Ok(taken) => info!("Took {} posts on {} from {}", taken, index.title(), url),
+                // Most likely a peer without the command, no use asking again
                 Err(e) => {
-                    error!("Failed to add index: {}", e);
+                    warn!("Failed to fetch posts from {}: {}", url, e);
+                    break;
+                }
+            }
+        }
+
+        // Only relays serve contents by index, each one picks up where the
+        // last exchange with the peer left off
+        let policy = repo.user().get_sync_policy_by_address(url).await?;
+        if policy.contains(SyncPolicy::ACCEPT_CONTENT) {
+            for hash in &hashes {
+                if let Err(e) = self.get_manga_content(url, repo, hash.clone()).await {
+                    warn!("Failed to get contents from {}: {}", url, e);
+                    break;
+                }
+            }
+        }
+
+        match self.fetch_peers(url, repo).await {
+            Ok(0) => {}
+            Ok(added) => info!("Learned {} new users from {}", added, url),
+            Err(e) => warn!("Failed to get peers from {}: {}", url, e),
+        }
+
+        // Renewed on every exchange, the peer forgets subscriptions that aren't
+        if !hashes.is_empty() {
+            match self.subscribe(url, hashes).await {
+                Ok(accepted) if !accepted.is_empty() => {
+                    info!("Subscribed to {} indexes of {}", accepted.len(), url)
                 }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to subscribe to {}: {}", url, e),
+            }
+        }
+
+        Ok(server_timestamp)
+    }
+
+    /// Takes the revocations `url` stored since `since`, on its clock, and
+    /// deletes what they point to. Returns how many were applied, those
+    /// not signed by the target's publisher aren't.
+    pub async fn fetch_revocations(
+        &mut self,
+        url: &I2PAddress,
+        since: Timestamp,
+        repo: &Repositories,
+    ) -> Result<usize, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let res = timed(
+            self.timeout,
+            GetRevocations::<MangaTag>::send(GetRevocationsRequest { since }, &mut stream),
+        )
+        .await?;
+
+        let mut applied = 0;
+        for revocation in res.payload_if_ok()?.revocations {
+            if !revocation.verify() {
+                warn!("Invalid revocation signature from {}", url);
+                continue;
+            }
+            if repo.revoke::<MangaTag>(revocation).await? {
+                applied += 1;
+            }
+        }
+
+        Ok(applied)
+    }
+
+    /// Asks `url` for a sample of the users it knows and stores the ones we
+    /// didn't, as unverified until we reach them ourselves. Users we know
+    /// are left alone, their trust is ours. Returns how many were added.
+    pub async fn fetch_peers(
+        &mut self,
+        url: &I2PAddress,
+        repo: &Repositories,
+    ) -> Result<usize, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let res = timed(
+            self.timeout,
+            handler::users::GetPeers::send(GetPeersRequest { take: MAX_PEERS }, &mut stream),
+        )
+        .await?;
+
+        let mut users = res.payload_if_ok()?.users;
+        // The signature covers the address, a peer can't hand out a user
+        // pointing somewhere else
+        users.retain(|u| {
+            let valid = u.verify();
+            if !valid {
+                warn!("Invalid user signature from {}", url);
             }
+            valid
+        });
+        users.truncate(MAX_PEERS);
+
+        let pub_keys = users.iter().map(|u| u.pub_key().clone()).collect();
+        let known: HashSet<PublicKey> = repo
+            .user()
+            .get_users(pub_keys)
+            .await?
+            .into_iter()
+            .map(|u| u.into_pub_key())
+            .collect();
+
+        let new: Vec<User> = users
+            .into_iter()
+            .filter(|u| !known.contains(u.pub_key()))
+            .map(|mut u| {
+                u.set_trust(TrustLevel::Unverified);
+                u
+            })
+            .collect();
+        let added = new.len();
+        if added > 0 {
+            repo.user().upsert_users(new).await?;
         }
 
+        Ok(added)
+    }
+
+    /// Asks `url` to push the new contents of `indexes` to us as it publishes
+    /// them. Returns the indexes it took, the ones it published.
+    pub async fn subscribe(
+        &mut self,
+        url: &I2PAddress,
+        indexes: Vec<Hash>,
+    ) -> Result<Vec<Hash>, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let res = timed(
+            self.timeout,
+            Subscribe::<MangaTag>::send(
+                SubscribeRequest {
+                    indexes: indexes.into_iter().take(MAX_SUBSCRIBED_INDEXES).collect(),
+                },
+                &mut stream,
+            ),
+        )
+        .await?;
+
+        Ok(res.payload_if_ok()?.accepted)
+    }
+
+    /// Pushes a content we just published to a peer that subscribed to its
+    /// index
+    pub async fn push_content(
+        &mut self,
+        url: &I2PAddress,
+        content: Content<MangaTag>,
+    ) -> Result<(), ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let res = timed(
+            self.timeout,
+            Announce::<MangaTag>::send(AnnounceRequest { content }, &mut stream),
+        )
+        .await?;
+
+        res.payload_if_ok()?;
         Ok(())
     }
 
+    /// Takes the posts of `topic` made since `after` that we don't have yet,
+    /// a page at a time. Returns how many were stored.
+    pub async fn fetch_posts(
+        &mut self,
+        url: &I2PAddress,
+        topic: Topic,
+        after: Option<Timestamp>,
+        repo: &Repositories,
+    ) -> Result<usize, ClientError> {
+        let policy = repo.user().get_sync_policy_by_address(url).await?;
+        if !policy.contains(SyncPolicy::ACCEPT_POSTS) {
+            return Ok(0);
+        }
+
+        let filter = repo.make_posts_filter(topic.clone(), after).await?;
+        let mut stream = self.get_authenticated_stream(url).await?;
+        let mut intake = PostIntake::new(repo);
+        let mut skip = 0;
+
+        loop {
+            let mut res = timed(
+                self.timeout,
+                handler::post::GetPosts::send(
+                    GetPostsRequest {
+                        topic: topic.clone(),
+                        after,
+                        filter: Some(filter.clone()),
+                        skip,
+                        take: MAX_POSTS_PER_PAGE,
+                    },
+                    &mut stream,
+                ),
+            )
+            .await?;
+
+            if !res.status().is_ok() {
+                return Err(ClientError::UnexpectedResponseCode {
+                    status: res.status().clone(),
+                });
+            }
+
+            while let Some(post) = timed(self.timeout, stream.next_item(res.data())).await? {
+                if post.topic != topic {
+                    warn!("Peer sent a post of another topic, dropping");
+                    continue;
+                }
+                intake.take(post).await?;
+            }
+
+            let Some(payload) = res.payload() else {
+                return Err(ClientError::MissingPayload);
+            };
+            match payload.next {
+                Some(next) if next > skip => skip = next,
+                _ => break,
+            }
+        }
+
+        Ok(intake.stored)
+    }
+
     // ╔===========================================================================╗
-    // ║                                 Exchange                                  ║
+    // ║                                   Meta                                    ║
     // ╚===========================================================================╝
 
-    // pub async fn routine_exchange(&mut self, url: &I2PAddress) -> Result<(),
-    // ClientError> {     let mut stream = self.get_stream(url).await?;
-
-    //     let who = self.who_internal(&mut stream).await?;
-
-    //     self.repositories.user().await.upsert_user(who).await?;
-
-    //     let response = handler::index::ExchangeContent::request(
-    //         ExchangeContentRequest { count: 10 },
-    //         &mut stream,
-    //     )
-    //     .await?;
-
-    //     let contents = response.payload_if_ok()?.contents;
-
-    //     let mut existing_indexes: HashSet<Hash> = HashSet::new();
-    //     let mut missing_indexes: Vec<Hash> = Vec::new();
-
-    //     for content in contents.iter() {
-    //         match content {
-    //             TaggedContent::Manga(content) => {
-    //                 match self
-    //                     .repositories
-    //                     .index()
-    //                     .await
-    //                     .get_index::<MangaTag>(content.index_hash())
-    //                     .await
-    //                 {
-    //                     Ok(i) => match i {
-    //                         Some(_) => {
-    //
-    // existing_indexes.insert(content.index_hash().clone());
-    // }                         None => {
-    //
-    // missing_indexes.push(content.index_hash().clone());
-    // }                     },
-    //                     Err(e) => {
-    //                         error!("Failed to get index: {}", e);
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     }
-
-    //     let mut response = handler::index::GetIndexes::request(
-    //         GetIndexesRequest::new(missing_indexes),
-    //         &mut stream,
-    //     )
-    //     .await?;
-
-    //     if !response.status().is_ok() {
-    //         return Err(ClientError::UnexpectedResponseCode {
-    //             status: response.status().clone(),
-    //         });
-    //     }
-
-    //     while let Ok(Some(index)) = response.data().next(&mut stream).await {
-    //         let index: Index<T> = index.make_tagged();
-
-    //         if !index.verify() {
-    //             error!("Invalid index signature");
-    //             continue;
-    //         }
-
-    //         match index {
-    //             self.repositories.index().await.add_index(index).await {
-    //                 Ok(i) => {
-    //                     existing_indexes.insert(i.hash().clone());
-    //                 }
-    //                 Err(e) => {
-    //                     error!("Failed to add index: {}", e);
-    //                 }
-    //             }
-    //         }
-    //     }
-
-    //     for content in contents.into_iter() {
-    //         if !existing_indexes.contains(content.index_hash()) {
-    //             continue;
-    //         }
-
-    //         if !content.verify() {
-    //             error!("Invalid content signature");
-    //             continue;
-    //         }
-    //         match content {
-    //             TaggedContent::Manga(content) => {
-    //                 match
-    // self.repositories.index().await.add_content(content).await {
-    // Ok(_) => {}                     Err(e) => {
-    //                         error!("Failed to add content: {}", e);
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //     }
-
-    //     Ok(())
-    // }
+    pub async fn ping(&mut self, url: &I2PAddress) -> Result<PingReport, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let start = Instant::now();
+        let res = timed(self.timeout, Ping::send(PingRequest {}, &mut stream)).await?;
+        let rtt = start.elapsed();
+
+        if !res.status().is_ok() {
+            return Err(ClientError::UnexpectedResponseCode {
+                status: res.status().clone(),
+            });
+        }
+
+        let Some(payload) = res.payload() else {
+            return Err(ClientError::MissingPayload);
+        };
+
+        Ok(PingReport {
+            rtt,
+            server_time: payload.timestamp,
+            protocol_version: payload.protocol_version,
+        })
+    }
+
+    /// Asks `url` what it supports, fails if the descriptor isn't signed by the
+    /// key it claims
+    pub async fn node_info(&mut self, url: &I2PAddress) -> Result<NodeInfo, ClientError> {
+        let mut stream = self.get_stream(url).await?;
+
+        let res = timed(
+            self.timeout,
+            handler::meta::GetNodeInfo::send(GetNodeInfoRequest {}, &mut stream),
+        )
+        .await?;
+
+        if !res.status().is_ok() {
+            return Err(ClientError::UnexpectedResponseCode {
+                status: res.status().clone(),
+            });
+        }
+
+        let Some(payload) = res.payload() else {
+            return Err(ClientError::MissingPayload);
+        };
+
+        if !payload.verify() {
+            return Err(ClientError::InvalidSignature);
+        }
+
+        Ok(payload)
+    }
 
     // ╔===========================================================================╗
     // ║                                   User                                    ║
     // ╚===========================================================================╝
 
     /// Who function without creating a new stream
-    async fn who_internal(&self, stream: &mut Stream) -> Result<User, ClientError> {
-        let res = handler::users::Who::request(WhoRequest {}, stream).await?;
+    async fn who_internal(
+        &self,
+        url: &I2PAddress,
+        stream: &mut PeerStream<SimulatedStream<Stream>>,
+    ) -> Result<WhoReport, ClientError> {
+        let res = timed(
+            self.timeout,
+            handler::users::Who::send(WhoRequest {}, stream),
+        )
+        .await?;
 
         if !res.status().is_ok() {
             return Err(ClientError::UnexpectedResponseCode {
@@ -348,23 +1183,40 @@ impl AkarekoClient {
             return Err(ClientError::MissingPayload);
         };
 
-        if !payload.verify(&self.host_address) {
-            return Err(ClientError::InvalidSignature);
-        }
+        let address_signature_valid = payload.verify(&self.host_address);
+        let user_signature_valid = payload.user.verify();
+        let address_matches = payload.user.address() == url;
 
         let mut user = payload.user;
-        if !user.verify() {
-            return Err(ClientError::InvalidSignature);
+        if address_signature_valid && user_signature_valid && address_matches {
+            user.set_trust(TrustLevel::Untrusted);
         }
 
-        user.set_trust(TrustLevel::Untrusted);
-
-        Ok(user)
+        Ok(WhoReport {
+            user,
+            address_signature_valid,
+            user_signature_valid,
+            address_matches,
+        })
     }
 
+    /// Asks `url` who it is and only returns the user if every check in
+    /// [`WhoReport`] passed
     pub async fn who(&mut self, url: &I2PAddress) -> Result<User, ClientError> {
+        let report = self.who_report(url).await?;
+
+        if !report.is_verified() {
+            return Err(ClientError::InvalidSignature);
+        }
+
+        Ok(report.user)
+    }
+
+    /// Same as [`AkarekoClient::who`] but returns the result of each check
+    /// instead of failing, used to show the user what went wrong
+    pub async fn who_report(&mut self, url: &I2PAddress) -> Result<WhoReport, ClientError> {
         let mut stream = self.get_stream(url).await?;
-        self.who_internal(&mut stream).await
+        self.who_internal(url, &mut stream).await
     }
 
     pub async fn request_users(
@@ -374,8 +1226,11 @@ impl AkarekoClient {
     ) -> Result<Vec<User>, ClientError> {
         let mut stream = self.get_stream(url).await?;
 
-        let res =
-            handler::users::GetUsers::request(GetUsersRequest { pub_keys }, &mut stream).await?;
+        let res = timed(
+            self.timeout,
+            handler::users::GetUsers::send(GetUsersRequest { pub_keys }, &mut stream),
+        )
+        .await?;
 
         if !res.status().is_ok() {
             return Err(ClientError::UnexpectedResponseCode {
@@ -404,3 +1259,22 @@ impl std::fmt::Debug for AkarekoClient {
         f.debug_struct("AkarekoClient").finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn silent_peers_time_out() {
+        // The other end is kept open but never answers
+        let (mut stream, _peer) = tokio::io::duplex(1024);
+
+        let res = timed(
+            Duration::from_millis(50),
+            Ping::request(PingRequest {}, &mut stream),
+        )
+        .await;
+
+        assert!(matches!(res, Err(ClientError::Timeout)));
+    }
+}
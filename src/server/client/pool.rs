@@ -6,6 +6,7 @@ use crate::server::client::AkarekoClient;
 pub struct ClientPool {
     client: AkarekoClient,
     permits: std::sync::Arc<Semaphore>,
+    size: u16,
 }
 
 impl ClientPool {
@@ -13,9 +14,15 @@ impl ClientPool {
         Self {
             client,
             permits: std::sync::Arc::new(Semaphore::new(size as usize)),
+            size,
         }
     }
 
+    /// Clients taken from the pool right now, one per exchange in progress
+    pub fn in_use(&self) -> usize {
+        self.size as usize - self.permits.available_permits()
+    }
+
     pub async fn get_client(self) -> PooledClient {
         PooledClient {
             client: self.client,
@@ -0,0 +1,186 @@
+//! Checks an eepsite for a newer release. Nothing is downloaded or installed,
+//! the user is only told about it.
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    build_info,
+    db::{ToBytes, user::I2PAddress},
+    errors::UpdateError,
+    server::client::AkarekoClient,
+    types::{PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+/// Where the manifest is expected on the eepsite
+pub const RELEASE_MANIFEST_PATH: &str = "/release.json";
+
+/// Manifests are tiny, anything bigger isn't one
+const MAX_MANIFEST_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct UpdateConfig {
+    pub enabled: bool,
+    /// Eepsite serving the manifest at [`RELEASE_MANIFEST_PATH`]
+    pub eepsite: String,
+    /// Manifests not signed by this key are refused, the check fails while
+    /// it's not set
+    pub maintainer_key: Option<PublicKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ReleaseManifest {
+    pub version: String,
+    pub released_at: Timestamp,
+    pub notes: String,
+    /// Where to get it, shown to the user as is
+    pub download: String,
+    signature: Signature,
+}
+
+impl ReleaseManifest {
+    fn sign_bytes(version: &str, released_at: &Timestamp, notes: &str, download: &str) -> Vec<u8> {
+        let mut bytes = version.as_bytes().to_vec();
+        bytes.extend(released_at.to_bytes());
+        bytes.extend(notes.as_bytes());
+        bytes.extend(download.as_bytes());
+        bytes
+    }
+
+    pub fn new_signed(
+        version: String,
+        released_at: Timestamp,
+        notes: String,
+        download: String,
+        priv_key: &PrivateKey,
+    ) -> Self {
+        let signature = priv_key.sign(&Self::sign_bytes(&version, &released_at, &notes, &download));
+
+        Self {
+            version,
+            released_at,
+            notes,
+            download,
+            signature,
+        }
+    }
+
+    pub fn verify(&self, maintainer_key: &PublicKey) -> bool {
+        let bytes = Self::sign_bytes(
+            &self.version,
+            &self.released_at,
+            &self.notes,
+            &self.download,
+        );
+        maintainer_key.verify(&bytes, &self.signature)
+    }
+
+    /// Whether the manifest is for a later version than the one running
+    pub fn is_newer(&self) -> bool {
+        is_newer_version(&self.version, build_info::VERSION)
+    }
+}
+
+/// Compares dotted versions number by number, parts that aren't numbers count
+/// as 0
+fn is_newer_version(candidate: &str, current: &str) -> bool {
+    let parse = |v: &str| -> Vec<u64> {
+        v.trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    };
+
+    let (candidate, current) = (parse(candidate), parse(current));
+    for i in 0..candidate.len().max(current.len()) {
+        let a = candidate.get(i).copied().unwrap_or(0);
+        let b = current.get(i).copied().unwrap_or(0);
+        if a != b {
+            return a > b;
+        }
+    }
+
+    false
+}
+
+impl AkarekoClient {
+    /// Fetches the manifest from the configured eepsite, `None` if updates are
+    /// off or there is nothing newer
+    pub async fn check_for_update(
+        &mut self,
+        config: &UpdateConfig,
+    ) -> Result<Option<ReleaseManifest>, UpdateError> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let Some(maintainer_key) = &config.maintainer_key else {
+            return Err(UpdateError::NotConfigured);
+        };
+
+        let mut stream = self.connect(&I2PAddress::new(&config.eepsite)).await?;
+
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            RELEASE_MANIFEST_PATH, config.eepsite
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut response = Vec::new();
+        (&mut stream)
+            .take(MAX_MANIFEST_SIZE)
+            .read_to_end(&mut response)
+            .await?;
+
+        let Some(split) = response.windows(4).position(|w| w == b"\r\n\r\n") else {
+            return Err(UpdateError::BadHttpResponse);
+        };
+        let (head, body) = (&response[..split], &response[split + 4..]);
+
+        let status_line = head.split(|b| *b == b'\n').next().unwrap_or_default();
+        if !status_line.split(|b| *b == b' ').any(|part| part == b"200") {
+            return Err(UpdateError::BadHttpResponse);
+        }
+
+        let manifest: ReleaseManifest = serde_json::from_slice(body)?;
+
+        if !manifest.verify(maintainer_key) {
+            return Err(UpdateError::InvalidSignature);
+        }
+
+        Ok(manifest.is_newer().then_some(manifest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReleaseManifest, is_newer_version};
+    use crate::types::{PrivateKey, Timestamp};
+
+    #[test]
+    fn versions_compare_by_number() {
+        assert!(is_newer_version("0.1.10", "0.1.9"));
+        assert!(is_newer_version("v1.0", "0.9.9"));
+        assert!(is_newer_version("0.2", "0.1.5"));
+        assert!(!is_newer_version("0.1.0", "0.1"));
+        assert!(!is_newer_version("0.1.0", "0.1.1"));
+    }
+
+    #[test]
+    fn manifest_is_checked_against_the_pinned_key() {
+        let maintainer = PrivateKey::new();
+        let mut manifest = ReleaseManifest::new_signed(
+            "9.9.9".to_string(),
+            Timestamp::new(0),
+            "Security fixes".to_string(),
+            "http://example.i2p/akareko-9.9.9.tar.gz".to_string(),
+            &maintainer,
+        );
+
+        assert!(manifest.verify(&maintainer.public_key()));
+        assert!(!manifest.verify(&PrivateKey::new().public_key()));
+
+        manifest.download = "http://evil.i2p/akareko.tar.gz".to_string();
+        assert!(!manifest.verify(&maintainer.public_key()));
+    }
+}
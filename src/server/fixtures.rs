@@ -0,0 +1,107 @@
+//! Records and server states to test handlers against, handlers are called
+//! directly with them instead of going through a stream.
+
+use uuid::Uuid;
+
+use crate::{
+    config::AkarekoConfig,
+    db::{
+        Magnet, Repositories,
+        index::{
+            Index, IndexLinks,
+            content::Content,
+            tags::{MangaChapter, MangaTag},
+        },
+        user::{I2PAddress, SyncPolicy, TrustLevel, User},
+    },
+    helpers::Language,
+    server::{ConnectionContext, ServerState},
+    types::{PrivateKey, Timestamp},
+};
+
+/// Address of the peer on the other end of [`peer`]
+pub const PEER_ADDRESS: &str = "peer.b32.i2p";
+
+/// Trusted user with a key seeded from its name
+pub fn user(name: &str, address: &str) -> (User, PrivateKey) {
+    let priv_key = PrivateKey::from_seed(name);
+    let mut user = User::new_signed(
+        name.to_string(),
+        Timestamp::now(),
+        &priv_key,
+        I2PAddress::new(address),
+    );
+    user.set_trust(TrustLevel::Trusted);
+    (user, priv_key)
+}
+
+pub fn index(title: &str, priv_key: &PrivateKey) -> Index<MangaTag> {
+    Index::new_signed(
+        title.to_string(),
+        0,
+        IndexLinks {
+            myanimelist: None,
+            mangadex: Some(Uuid::parse_str("410d499a-f438-4a56-9ad4-eb90a4de5b39").unwrap()),
+        },
+        priv_key,
+    )
+}
+
+pub fn content(
+    index: &Index<MangaTag>,
+    enumeration: f32,
+    priv_key: &PrivateKey,
+) -> Content<MangaTag> {
+    Content::new_signed(
+        index.hash().clone(),
+        Timestamp::now(),
+        Magnet(String::new()),
+        String::new(),
+        format!("Chapter {}", enumeration),
+        enumeration,
+        None,
+        MangaChapter::new(Language::Unknown),
+        vec![],
+        None,
+        priv_key,
+    )
+}
+
+/// In memory state of a node whose own user is stored, like after setup
+pub async fn state() -> ServerState {
+    let config = AkarekoConfig::default();
+    let repositories = Repositories::in_memory().await;
+
+    let own = User::new_signed(
+        "Node".to_string(),
+        Timestamp::now(),
+        config.private_key(),
+        I2PAddress::new("node.b32.i2p"),
+    );
+    repositories.user().upsert_user(own).await.unwrap();
+
+    ServerState::for_tests(repositories, config)
+}
+
+/// Stores the user at [`PEER_ADDRESS`] with a policy that doesn't share what
+/// we published
+pub async fn withhold_published(state: &ServerState) {
+    let (peer, _) = user("Peer", PEER_ADDRESS);
+    let pub_key = peer.pub_key().clone();
+    let users = state.repositories.user();
+    users.upsert_user(peer).await.unwrap();
+    users
+        .set_sync_policy(pub_key, SyncPolicy::all() - SyncPolicy::SEND_PUBLISHED)
+        .await
+        .unwrap();
+}
+
+/// State of a node whose database can't be reached
+pub fn broken_state() -> ServerState {
+    ServerState::for_tests(Repositories::disconnected(), AkarekoConfig::default())
+}
+
+/// Fresh connection from [`PEER_ADDRESS`]
+pub fn peer() -> ConnectionContext {
+    ConnectionContext::new(I2PAddress::new(PEER_ADDRESS))
+}
@@ -1,20 +1,22 @@
 use fastbloom::BloomFilter;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     db::{
         event::{EventType, filter_events},
         index::tags::MangaTag,
-        user::I2PAddress,
+        user::SyncPolicy,
     },
-    helpers::{AkarekoRead as _, AkarekoWrite as _},
+    errors::ServerError,
+    helpers::{AkarekoRead as _, AkarekoWrite as _, decode_limited},
     server::{
-        ServerState,
+        ConnectionContext, ServerState,
         handler::{
             AkarekoProtocolCommandHandler, AkarekoProtocolCommandMetadata,
-            AkarekoProtocolCommandRequest,
+            AkarekoProtocolCommandRequest, exchange_frame,
         },
-        protocol::AkarekoProtocolResponse,
+        protocol::{AkarekoProtocolResponse, Frame, MAX_REQUEST_FRAME},
     },
     types::{Hash, PublicKey, Signature, Timestamp},
 };
@@ -33,23 +35,44 @@ impl AkarekoProtocolCommandRequest<SyncEventsRequest, AkarekoProtocolResponse<Sy
         let res = AkarekoProtocolResponse::<SyncEventsResponse>::decode(stream).await?;
         Ok(res)
     }
+
+    async fn request_framed<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send>(
+        payload: SyncEventsRequest,
+        stream: &mut S,
+    ) -> Result<(AkarekoProtocolResponse<SyncEventsResponse>, Frame), crate::errors::ClientError>
+    {
+        let mut frame = exchange_frame::<SyncEvents, _, _>(&payload, stream).await?;
+        let res = AkarekoProtocolResponse::<SyncEventsResponse>::decode(&mut frame).await?;
+        Ok((res, frame))
+    }
 }
 impl AkarekoProtocolCommandHandler for SyncEvents {
     async fn handle<S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send>(
         stream: &mut S,
         state: &ServerState,
-        _: &I2PAddress,
-    ) {
-        let req = SyncEventsRequest::decode(stream).await.unwrap();
+        ctx: &mut ConnectionContext,
+    ) -> Result<(), ServerError> {
+        // Carries a bloom filter of the events the peer has
+        let req: SyncEventsRequest = decode_limited(stream, MAX_REQUEST_FRAME as u64).await?;
+
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::SEND_PUBLISHED)
+        {
+            AkarekoProtocolResponse::<(), ()>::forbidden("Not sharing with you".into())
+                .encode(stream)
+                .await?;
+            return Ok(());
+        }
 
         let events = match filter_events(req.timestamp, req.filter, &state.repositories.db).await {
             Ok(events) => events,
             Err(_) => {
                 AkarekoProtocolResponse::<(), ()>::internal_error("Database Error".into())
                     .encode(stream)
-                    .await
-                    .unwrap();
-                return;
+                    .await?;
+                return Ok(());
             }
         };
 
@@ -66,11 +89,10 @@ impl AkarekoProtocolCommandHandler for SyncEvents {
 
         AkarekoProtocolResponse::<SyncEventsResponse>::ok(SyncEventsResponse {
             decode_streams,
-            timestamp: Timestamp::now(),
+            timestamp: state.now(),
         })
         .encode(stream)
-        .await
-        .unwrap();
+        .await?;
 
         // SAFETY: Our DB should be verified anyway and the client will check it
         // later too. The only problem would be losing trust from a bad DB state.
@@ -89,9 +111,9 @@ impl AkarekoProtocolCommandHandler for SyncEvents {
                         })
                         .collect();
 
-                    let users = state.repositories.user().get_users(keys).await.unwrap();
+                    let users = state.repositories.user().get_users(keys).await?;
                     for user in users {
-                        user.encode(stream).await.unwrap();
+                        user.encode(stream).await?;
                     }
                 }
                 EventType::Manga => {
@@ -104,12 +126,13 @@ impl AkarekoProtocolCommandHandler for SyncEvents {
                         .repositories
                         .index()
                         .get_indexes::<MangaTag>(&hashes)
-                        .await
-                        .unwrap();
+                        .await?;
 
                     for index in indexes {
-                        index.encode(stream).await.unwrap();
+                        index.encode(stream).await?;
                     }
+
+                    touch_relayed(state, &hashes, &[]).await;
                 }
                 EventType::MangaContent => {
                     let signatures = topics
@@ -121,12 +144,15 @@ impl AkarekoProtocolCommandHandler for SyncEvents {
                         .repositories
                         .index()
                         .get_contents::<MangaTag>(&signatures)
-                        .await
-                        .unwrap();
+                        .await?;
 
-                    for content in contents {
-                        content.encode(stream).await.unwrap();
+                    let priv_key = state.config.read().await.private_key().clone();
+                    for mut content in contents {
+                        content.append_relay_hop(&priv_key);
+                        content.encode(stream).await?;
                     }
+
+                    touch_relayed(state, &[], &signatures).await;
                 }
                 EventType::Post => {
                     let signatures = topics
@@ -134,14 +160,28 @@ impl AkarekoProtocolCommandHandler for SyncEvents {
                         .map(|v| unsafe { Signature::from_bytes_unchecked(v.to_inner()) })
                         .collect::<Vec<_>>();
 
-                    let posts = state.repositories.get_posts(&signatures).await.unwrap();
+                    let posts = state.repositories.get_posts(&signatures).await?;
 
                     for post in posts {
-                        post.encode(stream).await.unwrap();
+                        post.encode(stream).await?;
                     }
                 }
             }
         }
+
+        Ok(())
+    }
+}
+
+/// Serving a record counts as exchanging it for a relay-only node
+async fn touch_relayed(state: &ServerState, indexes: &[Hash], contents: &[Signature]) {
+    if let Err(e) = state
+        .repositories
+        .index()
+        .touch_relayed::<MangaTag>(indexes, contents)
+        .await
+    {
+        warn!("Failed to mark relayed records as exchanged: {}", e);
     }
 }
 
@@ -157,3 +197,92 @@ pub struct SyncEventsResponse {
     pub timestamp: Timestamp,
     pub decode_streams: Vec<(EventType, u64)>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{index::Index, user::User},
+        server::{
+            fixtures::{broken_state, index, peer, state, withhold_published},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    /// Runs the handler against a request sent through an in-memory stream
+    /// and hands back the client end to read the answer from
+    async fn sync(state: &ServerState) -> tokio::io::DuplexStream {
+        let (mut client, mut server) = tokio::io::duplex(1 << 20);
+        SyncEventsRequest {
+            timestamp: Timestamp::new(0),
+            filter: None,
+        }
+        .encode(&mut client)
+        .await
+        .unwrap();
+
+        SyncEvents::handle(&mut server, state, &mut peer())
+            .await
+            .unwrap();
+        client
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn streams_records_of_every_event() {
+        let state = state().await;
+        let index = index("Title", &PrivateKey::new());
+        state
+            .repositories
+            .index()
+            .add_index(index.clone())
+            .await
+            .unwrap();
+
+        let mut client = sync(&state).await;
+        let res = AkarekoProtocolResponse::<SyncEventsResponse>::decode(&mut client)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+
+        let mut indexes = vec![];
+        for (event_type, count) in res.payload().unwrap().decode_streams {
+            for _ in 0..count {
+                match event_type {
+                    EventType::User => {
+                        User::decode(&mut client).await.unwrap();
+                    }
+                    EventType::Manga => {
+                        indexes.push(Index::<MangaTag>::decode(&mut client).await.unwrap())
+                    }
+                    other => panic!("No {:?} events were created", other),
+                }
+            }
+        }
+        assert_eq!(indexes, vec![index]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn withheld_peer_is_forbidden() {
+        let state = state().await;
+        withhold_published(&state).await;
+
+        let mut client = sync(&state).await;
+        let res = AkarekoProtocolResponse::<SyncEventsResponse>::decode(&mut client)
+            .await
+            .unwrap();
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+
+    /// The sync policy can't be read either, so nothing is shared
+    #[tokio::test(flavor = "multi_thread")]
+    async fn database_failure_is_forbidden() {
+        let mut client = sync(&broken_state()).await;
+        let res = AkarekoProtocolResponse::<SyncEventsResponse>::decode(&mut client)
+            .await
+            .unwrap();
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+}
@@ -0,0 +1,123 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::index::{content::Content, tags::IndexTag},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+    types::Hash,
+};
+
+/// Contents of an index shared in a group, only for peers that proved they are
+/// part of it on this connection
+pub struct GetGroupContents<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for GetGroupContents<I> {
+    type RequestPayload = GetGroupContentsRequest;
+    type ResponsePayload = GetGroupContentsResponse;
+    type ResponseData = Content<I>;
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        if !ctx.groups.contains(&req.group) {
+            return AkarekoProtocolResponse::forbidden("Not proven to be in the group".to_string());
+        }
+
+        let contents = match state
+            .repositories
+            .index()
+            .get_group_contents::<I>(req.group, req.index)
+            .await
+        {
+            Ok(c) => c,
+            Err(_) => {
+                return AkarekoProtocolResponse::internal_error(format!("Database error"));
+            }
+        };
+
+        AkarekoProtocolResponse::ok_with_data(GetGroupContentsResponse {}, contents)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetGroupContentsRequest {
+    group: Hash,
+    index: Hash,
+}
+
+impl GetGroupContentsRequest {
+    pub fn new(group: Hash, index: Hash) -> Self {
+        Self { group, index }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetGroupContentsResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{group::Group, index::tags::MangaTag},
+        server::{
+            fixtures::{broken_state, content, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn members_get_the_group_contents() {
+        let state = state().await;
+        let group = Group::generate("Group".to_string());
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let content = content(&index, 1.0, &priv_key);
+        state
+            .repositories
+            .index()
+            .add_group_content(content.clone(), group.id().clone())
+            .await
+            .unwrap();
+        let mut ctx = peer();
+        ctx.groups.insert(group.id().clone());
+
+        let req = GetGroupContentsRequest::new(group.id().clone(), index.hash().clone());
+        let mut res = GetGroupContents::<MangaTag>::process(req, &state, &mut ctx).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let sent = res.data().sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signature(), content.signature());
+    }
+
+    #[tokio::test]
+    async fn unproven_peer_is_forbidden() {
+        let group = Group::generate("Group".to_string());
+        let index = index("Title", &PrivateKey::new());
+
+        let req = GetGroupContentsRequest::new(group.id().clone(), index.hash().clone());
+        let res = GetGroupContents::<MangaTag>::process(req, &state().await, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let group = Group::generate("Group".to_string());
+        let index = index("Title", &PrivateKey::new());
+        let mut ctx = peer();
+        ctx.groups.insert(group.id().clone());
+
+        let req = GetGroupContentsRequest::new(group.id().clone(), index.hash().clone());
+        let res = GetGroupContents::<MangaTag>::process(req, &broken_state(), &mut ctx).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
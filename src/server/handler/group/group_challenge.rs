@@ -0,0 +1,68 @@
+use rand::{RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+    protocol::AkarekoProtocolResponse,
+};
+
+/// First half of joining a group on this connection, hands out the nonce that
+/// [`ProveGroup`](super::ProveGroup) has to answer
+pub struct GroupChallenge;
+
+impl AkarekoProtocolCommand for GroupChallenge {
+    type RequestPayload = GroupChallengeRequest;
+    type ResponsePayload = GroupChallengeResponse;
+    type ResponseData = ();
+
+    async fn process(
+        _: Self::RequestPayload,
+        _: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+
+        // A new challenge replaces the previous one, each nonce is only good
+        // for one proof
+        ctx.group_challenge = Some(nonce);
+
+        AkarekoProtocolResponse::ok(GroupChallengeResponse { nonce })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupChallengeRequest {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupChallengeResponse {
+    pub nonce: [u8; 32],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        fixtures::{broken_state, peer},
+        protocol::AkarekoStatus,
+    };
+
+    #[tokio::test]
+    async fn each_challenge_replaces_the_last() {
+        let state = broken_state();
+        let mut ctx = peer();
+
+        let first = GroupChallenge::process(GroupChallengeRequest {}, &state, &mut ctx).await;
+        assert_eq!(first.status(), &AkarekoStatus::Ok);
+        let first = first.payload().unwrap().nonce;
+        assert_eq!(ctx.group_challenge, Some(first));
+
+        let second = GroupChallenge::process(GroupChallengeRequest {}, &state, &mut ctx)
+            .await
+            .payload()
+            .unwrap()
+            .nonce;
+        assert_ne!(first, second);
+        assert_eq!(ctx.group_challenge, Some(second));
+    }
+}
@@ -0,0 +1,10 @@
+mod get_group_contents;
+mod group_challenge;
+mod prove_group;
+
+#[allow(unused_imports)]
+pub use get_group_contents::{GetGroupContents, GetGroupContentsRequest, GetGroupContentsResponse};
+#[allow(unused_imports)]
+pub use group_challenge::{GroupChallenge, GroupChallengeRequest, GroupChallengeResponse};
+#[allow(unused_imports)]
+pub use prove_group::{ProveGroup, ProveGroupRequest, ProveGroupResponse};
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+    types::Hash,
+};
+
+/// Second half of joining a group, the peer answers the last challenge with an
+/// HMAC of it made with the group secret. Once accepted the group's content is
+/// served for the rest of the connection.
+pub struct ProveGroup;
+
+impl AkarekoProtocolCommand for ProveGroup {
+    type RequestPayload = ProveGroupRequest;
+    type ResponsePayload = ProveGroupResponse;
+    type ResponseData = ();
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let Some(nonce) = ctx.group_challenge.take() else {
+            return AkarekoProtocolResponse::invalid_argument("No challenge was asked".to_string());
+        };
+
+        // Unknown groups get the same answer as a wrong proof, so peers can't
+        // probe which groups we are in
+        let group = match state.repositories.get_group(&req.group).await {
+            Ok(group) => group,
+            Err(_) => return AkarekoProtocolResponse::internal_error("Database error".to_string()),
+        };
+
+        match group {
+            Some(group) if group.verify(&nonce, &ctx.address, &req.proof) => {
+                ctx.groups.insert(req.group);
+                AkarekoProtocolResponse::ok(ProveGroupResponse {})
+            }
+            _ => AkarekoProtocolResponse::forbidden("Invalid proof".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProveGroupRequest {
+    group: Hash,
+    #[serde(with = "serde_bytes")]
+    proof: Vec<u8>,
+}
+
+impl ProveGroupRequest {
+    pub fn new(group: Hash, proof: Vec<u8>) -> Self {
+        Self { group, proof }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProveGroupResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::group::Group,
+        server::{
+            fixtures::{broken_state, peer, state},
+            protocol::AkarekoStatus,
+        },
+    };
+
+    const NONCE: [u8; 32] = [7; 32];
+
+    fn group() -> Group {
+        Group::generate("Group".to_string())
+    }
+
+    #[tokio::test]
+    async fn valid_proof_joins_the_group() {
+        let state = state().await;
+        let group = group();
+        state.repositories.add_group(group.clone()).await.unwrap();
+        let mut ctx = peer();
+        ctx.group_challenge = Some(NONCE);
+
+        let req = ProveGroupRequest::new(group.id().clone(), group.prove(&NONCE, &ctx.address));
+        let res = ProveGroup::process(req, &state, &mut ctx).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert!(ctx.groups.contains(group.id()));
+        assert_eq!(ctx.group_challenge, None);
+    }
+
+    #[tokio::test]
+    async fn wrong_proof_and_unknown_group_look_the_same() {
+        let state = state().await;
+        let group = group();
+        state.repositories.add_group(group.clone()).await.unwrap();
+        let unknown = Group::generate("Unknown".to_string());
+
+        for (id, proof) in [
+            (group.id().clone(), vec![0; 32]),
+            (unknown.id().clone(), unknown.prove(&NONCE, &peer().address)),
+        ] {
+            let mut ctx = peer();
+            ctx.group_challenge = Some(NONCE);
+            let res =
+                ProveGroup::process(ProveGroupRequest::new(id, proof), &state, &mut ctx).await;
+
+            assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+            assert!(ctx.groups.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn proof_without_challenge_is_invalid() {
+        let group = group();
+        let req = ProveGroupRequest::new(group.id().clone(), group.prove(&NONCE, &peer().address));
+        let res = ProveGroup::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InvalidArgument(_)));
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let group = group();
+        let mut ctx = peer();
+        ctx.group_challenge = Some(NONCE);
+
+        let req = ProveGroupRequest::new(group.id().clone(), group.prove(&NONCE, &ctx.address));
+        let res = ProveGroup::process(req, &broken_state(), &mut ctx).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -0,0 +1,150 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    db::{
+        content_source::{ContentSource, SourceKind},
+        index::{content::Content, tags::IndexTag},
+        user::SyncPolicy,
+    },
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
+    },
+};
+
+/// New content pushed by the publisher of an index we
+/// [`Subscribe`](super::Subscribe)d to. Only taken for indexes we follow, the
+/// rest still arrives through the usual exchanges.
+pub struct Announce<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for Announce<I> {
+    type RequestPayload = AnnounceRequest<I>;
+    type ResponsePayload = AnnounceResponse;
+    type ResponseData = ();
+
+    /// Content can be up to its limits in size, see
+    /// [`ContentLimitsConfig`](crate::config::ContentLimitsConfig)
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let content = req.content;
+        if !content.verify() {
+            return AkarekoProtocolResponse::invalid_argument("Signature is not valid".to_string());
+        }
+
+        let limits = state.config.read().await.content_limits().clone();
+        if let Err(e) = content.check_limits(&limits) {
+            return AkarekoProtocolResponse::invalid_argument(e.to_string());
+        }
+
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::ACCEPT_CONTENT)
+        {
+            return AkarekoProtocolResponse::forbidden(
+                "Content from you isn't accepted".to_string(),
+            );
+        }
+
+        match state
+            .repositories
+            .index_follow()
+            .get_index_follow::<I>(content.index_hash().clone())
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                return AkarekoProtocolResponse::not_found("Not following this index".to_string());
+            }
+            Err(_) => return AkarekoProtocolResponse::internal_error("Database error".to_string()),
+        }
+
+        let source = ContentSource::from_content(&content, ctx.address.clone(), SourceKind::Sent);
+        if state
+            .repositories
+            .index()
+            .add_content(content)
+            .await
+            .is_err()
+        {
+            return AkarekoProtocolResponse::internal_error("Database error".to_string());
+        }
+
+        if let Err(e) = state.repositories.add_content_source(source).await {
+            warn!("Failed to record content source: {}", e);
+        }
+
+        AkarekoProtocolResponse::ok(AnnounceResponse {})
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct AnnounceRequest<I: IndexTag> {
+    pub content: Content<I>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AnnounceResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{
+            follow_index::{IndexFollow, NotificationPreference},
+            index::tags::MangaTag,
+        },
+        server::{
+            fixtures::{content, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::{PrivateKey, Timestamp},
+    };
+
+    #[tokio::test]
+    async fn takes_content_of_followed_indexes_only() {
+        let state = state().await;
+        let publisher = PrivateKey::new();
+        let followed = index("Followed", &publisher);
+        let other = index("Other", &publisher);
+        let repositories = &state.repositories;
+        repositories
+            .index_follow()
+            .add_index_follow(IndexFollow::<MangaTag>::new(
+                followed.hash().clone(),
+                NotificationPreference::Toast,
+                Timestamp::new(0),
+            ))
+            .await
+            .unwrap();
+
+        let pushed = content(&followed, 1.0, &publisher);
+        let req = AnnounceRequest {
+            content: pushed.clone(),
+        };
+        let res = Announce::<MangaTag>::process(req, &state, &mut peer()).await;
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let stored = repositories
+            .index()
+            .get_contents::<MangaTag>(std::slice::from_ref(pushed.signature()))
+            .await
+            .unwrap();
+        assert_eq!(stored, vec![pushed]);
+
+        let req = AnnounceRequest {
+            content: content(&other, 1.0, &publisher),
+        };
+        let res = Announce::<MangaTag>::process(req, &state, &mut peer()).await;
+        assert!(matches!(res.status(), AkarekoStatus::NotFound(_)));
+    }
+}
@@ -2,11 +2,12 @@ use fastbloom::BloomFilter;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    db::{
-        index::{Index, tags::IndexTag},
-        user::I2PAddress,
+    db::index::{Index, tags::IndexTag},
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
     },
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
     types::Timestamp,
 };
 
@@ -17,10 +18,13 @@ impl<I: IndexTag> AkarekoProtocolCommand for GetAllIndexes<I> {
     type ResponsePayload = GetAllIndexesResponse;
     type ResponseData = Index<I>;
 
+    /// Carries a bloom filter of the indexes the peer has
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        _: &I2PAddress,
+        _: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
         let indexes = match state
             .repositories
@@ -42,8 +46,8 @@ impl<I: IndexTag> AkarekoProtocolCommand for GetAllIndexes<I> {
 pub struct GetAllIndexesRequest {
     tag: String,
     /// Get indexes created_updated after this timestamp
-    timestamp: Option<Timestamp>,
-    filter: Option<BloomFilter>,
+    pub(super) timestamp: Option<Timestamp>,
+    pub(super) filter: Option<BloomFilter>,
 }
 
 impl GetAllIndexesRequest {
@@ -58,3 +62,42 @@ impl GetAllIndexesRequest {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetAllIndexesResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn streams_every_index() {
+        let state = state().await;
+        let index = index("Title", &PrivateKey::new());
+        state
+            .repositories
+            .index()
+            .add_index(index.clone())
+            .await
+            .unwrap();
+
+        let req = GetAllIndexesRequest::new::<MangaTag>(None, None);
+        let mut res = GetAllIndexes::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(res.data().sent(), &[index]);
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = GetAllIndexesRequest::new::<MangaTag>(None, None);
+        let res = GetAllIndexes::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
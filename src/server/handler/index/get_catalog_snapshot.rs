@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{catalog::CatalogSnapshot, index::tags::IndexTag, user::SyncPolicy},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+};
+
+/// Every index and the recent content signed by this relay, or only what
+/// changed since a generation the client already has
+pub struct GetCatalogSnapshot<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for GetCatalogSnapshot<I> {
+    type RequestPayload = GetCatalogSnapshotRequest;
+    type ResponsePayload = CatalogSnapshot;
+    type ResponseData = ();
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::SEND_PUBLISHED)
+        {
+            return AkarekoProtocolResponse::forbidden("Not sharing with you".to_string());
+        }
+
+        let repositories = &state.repositories;
+        let Ok(generation) = repositories.current_catalog_generation().await else {
+            return AkarekoProtocolResponse::internal_error(format!("Database error"));
+        };
+
+        // A generation we don't know about gets a full snapshot
+        let since = match req.since {
+            Some(since) => match repositories.get_catalog_generation(since).await {
+                Ok(since) => since,
+                Err(_) => {
+                    return AkarekoProtocolResponse::internal_error(format!("Database error"));
+                }
+            },
+            None => None,
+        };
+
+        let Ok(mut catalog) = repositories.build_catalog::<I>(since.as_ref()).await else {
+            return AkarekoProtocolResponse::internal_error(format!("Database error"));
+        };
+
+        let priv_key = state.config.read().await.private_key().clone();
+        for content in catalog.contents.iter_mut() {
+            content.append_relay_hop(&priv_key);
+        }
+
+        match CatalogSnapshot::new_signed(
+            &catalog,
+            generation.generation,
+            since.map(|s| s.generation),
+            &priv_key,
+        ) {
+            Ok(snapshot) => AkarekoProtocolResponse::ok(snapshot),
+            Err(_) => AkarekoProtocolResponse::internal_error(format!("Failed to pack catalog")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetCatalogSnapshotRequest {
+    /// Generation already imported, `None` for a full snapshot
+    pub since: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, index, peer, state, withhold_published},
+            protocol::AkarekoStatus,
+        },
+    };
+
+    #[tokio::test]
+    async fn full_snapshot_is_signed_by_us() {
+        let state = state().await;
+        let priv_key = state.config.read().await.private_key().clone();
+        state
+            .repositories
+            .index()
+            .add_index(index("Title", &priv_key))
+            .await
+            .unwrap();
+
+        let req = GetCatalogSnapshotRequest { since: None };
+        let res = GetCatalogSnapshot::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let snapshot = res.payload().unwrap();
+        assert!(snapshot.verify());
+        assert_eq!(snapshot.since, None);
+    }
+
+    #[tokio::test]
+    async fn withheld_peer_is_forbidden() {
+        let state = state().await;
+        withhold_published(&state).await;
+
+        let req = GetCatalogSnapshotRequest { since: None };
+        let res = GetCatalogSnapshot::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+
+    /// The sync policy can't be read either, so nothing is shared
+    #[tokio::test]
+    async fn database_failure_is_forbidden() {
+        let req = GetCatalogSnapshotRequest { since: None };
+        let res = GetCatalogSnapshot::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+}
@@ -2,13 +2,18 @@ use std::marker::PhantomData;
 
 use fastbloom::BloomFilter;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     db::{
         index::{content::Content, tags::IndexTag},
-        user::I2PAddress,
+        user::SyncPolicy,
+    },
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
     },
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
     types::{Hash, Timestamp},
 };
 
@@ -19,15 +24,26 @@ impl<I: IndexTag> AkarekoProtocolCommand for GetContents<I> {
     type ResponsePayload = GetContentsResponse;
     type ResponseData = Content<I>;
 
+    /// Carries a bloom filter of the contents the peer has
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        _: &I2PAddress,
+        ctx: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
-        let contents = match state
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::SEND_PUBLISHED)
+        {
+            return AkarekoProtocolResponse::forbidden("Not sharing with you".to_string());
+        }
+
+        let mut contents = match state
             .repositories
             .index()
-            .get_filtered_index_contents::<I>(req.index, req.after, req.filter)
+            .get_filtered_index_contents::<I>(req.index.clone(), req.after, req.filter)
             .await
         {
             Ok(c) => c,
@@ -36,6 +52,21 @@ impl<I: IndexTag> AkarekoProtocolCommand for GetContents<I> {
             }
         };
 
+        let signatures: Vec<_> = contents.iter().map(|c| c.signature().clone()).collect();
+        if let Err(e) = state
+            .repositories
+            .index()
+            .touch_relayed::<I>(&[req.index], &signatures)
+            .await
+        {
+            warn!("Failed to mark relayed records as exchanged: {}", e);
+        }
+
+        let priv_key = state.config.read().await.private_key().clone();
+        for content in contents.iter_mut() {
+            content.append_relay_hop(&priv_key);
+        }
+
         AkarekoProtocolResponse::ok_with_data(GetContentsResponse {}, contents)
     }
 }
@@ -60,3 +91,85 @@ impl GetContentsRequest {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetContentsResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, content, index, peer, state, withhold_published},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn streams_contents_with_our_hop() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let content = content(&index, 1.0, &priv_key);
+        let repositories = &state.repositories;
+        repositories.index().add_index(index.clone()).await.unwrap();
+        repositories
+            .index()
+            .add_content(content.clone())
+            .await
+            .unwrap();
+
+        let req = GetContentsRequest::new(index.hash().clone(), None, None);
+        let mut res = GetContents::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let sent = res.data().sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].signature(), content.signature());
+    }
+
+    #[tokio::test]
+    async fn only_contents_created_after_are_sent() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let content = content(&index, 1.0, &priv_key);
+        let repositories = &state.repositories;
+        repositories.index().add_index(index.clone()).await.unwrap();
+        repositories.index().add_content(content).await.unwrap();
+
+        let after = Some(Timestamp::now() + Timestamp::new(60));
+        let req = GetContentsRequest::new(index.hash().clone(), after, None);
+        let mut res = GetContents::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert!(res.data().sent().is_empty());
+    }
+
+    #[tokio::test]
+    async fn withheld_peer_is_forbidden() {
+        let state = state().await;
+        withhold_published(&state).await;
+
+        let req = GetContentsRequest::new(
+            index("Title", &PrivateKey::new()).hash().clone(),
+            None,
+            None,
+        );
+        let res = GetContents::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+
+    /// The sync policy can't be read either, so nothing is shared
+    #[tokio::test]
+    async fn database_failure_is_forbidden() {
+        let req = GetContentsRequest::new(
+            index("Title", &PrivateKey::new()).hash().clone(),
+            None,
+            None,
+        );
+        let res = GetContents::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+}
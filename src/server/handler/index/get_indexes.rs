@@ -1,11 +1,12 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    db::{
-        index::{Index, tags::IndexTag},
-        user::I2PAddress,
+    db::index::{Index, tags::IndexTag},
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
     },
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
     types::Hash,
 };
 
@@ -16,10 +17,13 @@ impl<I: IndexTag> AkarekoProtocolCommand for GetIndexes<I> {
     type ResponsePayload = GetIndexesResponse;
     type ResponseData = Index<I>;
 
+    /// Carries the hash of every index asked for
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        _: &I2PAddress,
+        _: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
         let indexes = match state
             .repositories
@@ -50,3 +54,44 @@ impl GetIndexesRequest {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetIndexesResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn unknown_hashes_are_left_out() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let stored = index("Stored", &priv_key);
+        let unknown = index("Unknown", &priv_key);
+        state
+            .repositories
+            .index()
+            .add_index(stored.clone())
+            .await
+            .unwrap();
+
+        let req = GetIndexesRequest::new(vec![stored.hash().clone(), unknown.hash().clone()]);
+        let mut res = GetIndexes::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(res.data().sent(), &[stored]);
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = GetIndexesRequest::new(vec![index("Title", &PrivateKey::new()).hash().clone()]);
+        let res = GetIndexes::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -0,0 +1,101 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{index::tags::IndexTag, revocation::Revocation},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+    types::Timestamp,
+};
+
+/// Most revocations sent back in one response, ask again from the last one's
+/// timestamp for more
+pub const MAX_REVOCATIONS: usize = 200;
+
+/// The [`Revocation`]s we stored since the peer last exchanged with us, so
+/// retracted uploads are deleted across the network and don't come back
+pub struct GetRevocations<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for GetRevocations<I> {
+    type RequestPayload = GetRevocationsRequest;
+    type ResponsePayload = GetRevocationsResponse;
+    type ResponseData = ();
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        match state
+            .repositories
+            .get_revocations_since(req.since, MAX_REVOCATIONS)
+            .await
+        {
+            Ok(revocations) => AkarekoProtocolResponse::ok(GetRevocationsResponse { revocations }),
+            Err(_) => AkarekoProtocolResponse::internal_error("Database error".to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetRevocationsRequest {
+    /// On our clock, like the timestamps of
+    /// [`SyncEvents`](crate::server::handler::events::SyncEvents)
+    pub since: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetRevocationsResponse {
+    pub revocations: Vec<Revocation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, content, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn sends_stored_revocations() {
+        let state = state().await;
+        let publisher = PrivateKey::new();
+        let revocation = Revocation::for_content(
+            &content(&index("Revoked", &publisher), 1.0, &publisher),
+            &publisher,
+        );
+        state
+            .repositories
+            .revoke::<MangaTag>(revocation.clone())
+            .await
+            .unwrap();
+
+        let req = GetRevocationsRequest {
+            since: Timestamp::new(0),
+        };
+        let res = GetRevocations::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let revocations = res.payload().unwrap().revocations;
+        assert_eq!(revocations.len(), 1);
+        assert_eq!(revocations[0].signature, revocation.signature);
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = GetRevocationsRequest {
+            since: Timestamp::new(0),
+        };
+        let res = GetRevocations::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashSet, marker::PhantomData};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    db::{
+        content_source::{ContentSource, SourceKind},
+        index::tags::IndexTag,
+    },
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
+    },
+    types::{Hash, Signature},
+};
+
+/// Most signatures taken in a single announcement
+pub const MAX_HAVE_SIGNATURES: usize = 4096;
+
+/// Lets a peer tell us which contents of an index it has without sending them,
+/// we answer with the ones we are missing
+pub struct HaveContent<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for HaveContent<I> {
+    type RequestPayload = HaveContentRequest;
+    type ResponsePayload = HaveContentResponse;
+    type ResponseData = ();
+
+    /// Carries the signature of every content asked about
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        if req.signatures.len() > MAX_HAVE_SIGNATURES {
+            return AkarekoProtocolResponse::invalid_argument(format!(
+                "At most {} signatures per announcement",
+                MAX_HAVE_SIGNATURES
+            ));
+        }
+
+        let owned: HashSet<Signature> = match state
+            .repositories
+            .index()
+            .get_contents::<I>(&req.signatures)
+            .await
+        {
+            Ok(c) => c.into_iter().map(|c| c.signature().clone()).collect(),
+            Err(_) => {
+                return AkarekoProtocolResponse::internal_error(format!("Database error"));
+            }
+        };
+
+        // Only what we have is worth asking this peer for later
+        let sources = req
+            .signatures
+            .iter()
+            .filter(|s| owned.contains(*s))
+            .map(|s| {
+                ContentSource::new(
+                    s.clone(),
+                    req.index.clone(),
+                    ctx.address.clone(),
+                    SourceKind::Announced,
+                )
+            })
+            .collect();
+        if let Err(e) = state.repositories.add_content_sources(sources).await {
+            warn!("Failed to record content sources: {}", e);
+        }
+
+        let missing = req
+            .signatures
+            .into_iter()
+            .filter(|s| !owned.contains(s))
+            .collect();
+
+        AkarekoProtocolResponse::ok(HaveContentResponse { missing })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HaveContentRequest {
+    index: Hash,
+    signatures: Vec<Signature>,
+}
+
+impl HaveContentRequest {
+    pub fn new(index: Hash, signatures: Vec<Signature>) -> Self {
+        Self { index, signatures }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HaveContentResponse {
+    /// Announced contents we don't have yet
+    pub missing: Vec<Signature>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, content, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn answers_with_missing_contents() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let owned = content(&index, 1.0, &priv_key);
+        let missing = content(&index, 2.0, &priv_key);
+        state
+            .repositories
+            .index()
+            .add_content(owned.clone())
+            .await
+            .unwrap();
+
+        let req = HaveContentRequest::new(
+            index.hash().clone(),
+            vec![owned.signature().clone(), missing.signature().clone()],
+        );
+        let res = HaveContent::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(
+            res.payload().unwrap().missing,
+            vec![missing.signature().clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_owned_contents_get_a_source() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let owned = content(&index, 1.0, &priv_key);
+        let unknown = content(&index, 2.0, &priv_key);
+        state
+            .repositories
+            .index()
+            .add_content(owned.clone())
+            .await
+            .unwrap();
+
+        let req = HaveContentRequest::new(
+            index.hash().clone(),
+            vec![owned.signature().clone(), unknown.signature().clone()],
+        );
+        HaveContent::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        let sources = &state.repositories;
+        let owned = sources.get_content_sources(owned.signature()).await;
+        assert_eq!(owned.unwrap().len(), 1);
+        let unknown = sources.get_content_sources(unknown.signature()).await;
+        assert!(unknown.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn too_many_signatures_are_invalid() {
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let signature = content(&index, 1.0, &priv_key).signature().clone();
+
+        let req = HaveContentRequest::new(
+            index.hash().clone(),
+            vec![signature; MAX_HAVE_SIGNATURES + 1],
+        );
+        let res = HaveContent::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InvalidArgument(_)));
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let priv_key = PrivateKey::new();
+        let index = index("Title", &priv_key);
+        let signature = content(&index, 1.0, &priv_key).signature().clone();
+
+        let req = HaveContentRequest::new(index.hash().clone(), vec![signature]);
+        let res = HaveContent::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
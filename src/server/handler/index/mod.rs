@@ -1,10 +1,36 @@
+mod announce;
 mod get_all_indexes;
+mod get_catalog_snapshot;
 mod get_contents;
 mod get_indexes;
+mod get_revocations;
+mod have_content;
+mod search_indexes;
+mod stream_all_indexes;
+mod subscribe;
 
+#[allow(unused_imports)]
+pub use announce::{Announce, AnnounceRequest, AnnounceResponse};
 #[allow(unused_imports)]
 pub use get_all_indexes::{GetAllIndexes, GetAllIndexesRequest, GetAllIndexesResponse};
 #[allow(unused_imports)]
+pub use get_catalog_snapshot::{GetCatalogSnapshot, GetCatalogSnapshotRequest};
+#[allow(unused_imports)]
 pub use get_contents::{GetContents, GetContentsRequest, GetContentsResponse};
 #[allow(unused_imports)]
 pub use get_indexes::{GetIndexes, GetIndexesRequest, GetIndexesResponse};
+#[allow(unused_imports)]
+pub use get_revocations::{
+    GetRevocations, GetRevocationsRequest, GetRevocationsResponse, MAX_REVOCATIONS,
+};
+#[allow(unused_imports)]
+pub use have_content::{HaveContent, HaveContentRequest, HaveContentResponse, MAX_HAVE_SIGNATURES};
+#[allow(unused_imports)]
+pub use search_indexes::{
+    MAX_SEARCH_QUERY_CHARS, MAX_SEARCH_RESULTS, SearchIndexes, SearchIndexesRequest,
+    SearchIndexesResponse, sanitize_query,
+};
+#[allow(unused_imports)]
+pub use stream_all_indexes::StreamAllIndexes;
+#[allow(unused_imports)]
+pub use subscribe::{MAX_SUBSCRIBED_INDEXES, Subscribe, SubscribeRequest, SubscribeResponse};
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::index::{Index, tags::IndexTag},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+};
+
+/// Longest query searched for, longer ones are cut
+pub const MAX_SEARCH_QUERY_CHARS: usize = 64;
+/// Most indexes sent back for a search
+pub const MAX_SEARCH_RESULTS: usize = 50;
+
+/// Lowercases `query`, drops control characters and collapses whitespace,
+/// none if nothing is left to search for
+pub fn sanitize_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .map(|w| {
+            w.chars()
+                .filter(|c| !c.is_control())
+                .flat_map(char::to_lowercase)
+                .collect()
+        })
+        .filter(|w: &String| !w.is_empty())
+        .collect();
+
+    let query: String = words
+        .join(" ")
+        .chars()
+        .take(MAX_SEARCH_QUERY_CHARS)
+        .collect();
+    let query = query.trim_end();
+    (!query.is_empty()).then(|| query.to_string())
+}
+
+/// Indexes whose title contains a query, so peers can search the network for
+/// what they don't have
+pub struct SearchIndexes<I: IndexTag>(std::marker::PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for SearchIndexes<I> {
+    type RequestPayload = SearchIndexesRequest;
+    type ResponsePayload = SearchIndexesResponse;
+    type ResponseData = Index<I>;
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let Some(query) = sanitize_query(&req.query) else {
+            return AkarekoProtocolResponse::invalid_argument("Empty query".to_string());
+        };
+
+        let indexes = match state
+            .repositories
+            .index()
+            .search_indexes::<I>(&query, MAX_SEARCH_RESULTS)
+            .await
+        {
+            Ok(indexes) => indexes,
+            Err(_) => {
+                return AkarekoProtocolResponse::internal_error(format!("Database error"));
+            }
+        };
+
+        AkarekoProtocolResponse::ok_with_data(SearchIndexesResponse {}, indexes)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchIndexesRequest {
+    tag: String,
+    query: String,
+}
+
+impl SearchIndexesRequest {
+    /// None if `query` is empty once sanitized
+    pub fn new<T: IndexTag>(query: &str) -> Option<Self> {
+        Some(Self {
+            tag: T::TAG.to_string(),
+            query: sanitize_query(query)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SearchIndexesResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[test]
+    fn queries_are_sanitized() {
+        assert_eq!(
+            sanitize_query("  Blue\u{0}  PERIOD\n"),
+            Some("blue period".to_string())
+        );
+        assert_eq!(sanitize_query(" \t\u{7}"), None);
+        assert_eq!(
+            sanitize_query(&"a".repeat(200)).unwrap().len(),
+            MAX_SEARCH_QUERY_CHARS
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_titles_ignoring_case() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let found = index("Blue Period", &priv_key);
+        for index in [found.clone(), index("Red Garden", &priv_key)] {
+            state.repositories.index().add_index(index).await.unwrap();
+        }
+
+        let req = SearchIndexesRequest::new::<MangaTag>("BLUE").unwrap();
+        let mut res = SearchIndexes::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(res.data().sent(), &[found]);
+    }
+
+    #[tokio::test]
+    async fn empty_query_is_refused() {
+        let req = SearchIndexesRequest {
+            tag: MangaTag::TAG.to_string(),
+            query: "  ".to_string(),
+        };
+        let res = SearchIndexes::<MangaTag>::process(req, &state().await, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InvalidArgument(_)));
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = SearchIndexesRequest::new::<MangaTag>("blue").unwrap();
+        let res = SearchIndexes::<MangaTag>::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -0,0 +1,283 @@
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    db::index::{Index, tags::IndexTag},
+    errors::{ClientError, EncodeError, ServerError},
+    helpers::{AkarekoRead as _, AkarekoWrite as _, decode_limited},
+    server::{
+        ConnectionContext, ServerState,
+        handler::{
+            AkarekoProtocolCommandHandler, AkarekoProtocolCommandMetadata,
+            AkarekoProtocolCommandRequest, error_status, exchange_frame,
+            index::GetAllIndexesRequest,
+        },
+        protocol::{
+            AkarekoProtocolResponse, Frame, MAX_CHUNK_ITEMS, MAX_REQUEST_FRAME, encode_chunk,
+            end_chunks, write_frame,
+        },
+    },
+};
+
+/// [`super::GetAllIndexes`] sent in chunks as the table is walked, so neither
+/// side holds the whole catalog at once. The indexes follow the response in
+/// chunks, read them with [`crate::server::protocol::ChunkedStream`]. Over
+/// [V2](crate::server::protocol::AkarekoProtocolVersion::V2) each chunk is a
+/// frame of its own.
+pub struct StreamAllIndexes<I: IndexTag>(std::marker::PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommandRequest<GetAllIndexesRequest, AkarekoProtocolResponse<()>>
+    for StreamAllIndexes<I>
+where
+    Self: AkarekoProtocolCommandMetadata,
+{
+    async fn request<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        payload: GetAllIndexesRequest,
+        stream: &mut S,
+    ) -> Result<AkarekoProtocolResponse<()>, ClientError> {
+        Self::encode_request(stream).await?;
+        payload.encode(stream).await?;
+        let res = AkarekoProtocolResponse::<()>::decode(stream).await?;
+        Ok(res)
+    }
+
+    async fn request_framed<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        payload: GetAllIndexesRequest,
+        stream: &mut S,
+    ) -> Result<(AkarekoProtocolResponse<()>, Frame), ClientError> {
+        let mut frame = exchange_frame::<Self, _, _>(&payload, stream).await?;
+        let res = AkarekoProtocolResponse::<()>::decode(&mut frame).await?;
+        Ok((res, frame))
+    }
+}
+
+impl<I: IndexTag> AkarekoProtocolCommandHandler for StreamAllIndexes<I> {
+    async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        stream: &mut S,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> Result<(), ServerError> {
+        // Carries a bloom filter of the indexes the peer has
+        let req: GetAllIndexesRequest = decode_limited(stream, MAX_REQUEST_FRAME as u64).await?;
+        Self::respond(req, stream, state, false).await
+    }
+}
+
+impl<I: IndexTag> StreamAllIndexes<I> {
+    /// Over [V2](crate::server::protocol::AkarekoProtocolVersion::V2) the
+    /// response and every chunk are written to `stream` in frames of their
+    /// own as they're read, instead of in the single frame other commands
+    /// answer with. `request` is what's left of the request frame after the
+    /// command.
+    pub(in crate::server) async fn handle_framed<S: AsyncWrite + Unpin + Send>(
+        request: &mut Frame,
+        stream: &mut S,
+        state: &ServerState,
+    ) -> Result<(), ServerError> {
+        let req: GetAllIndexesRequest =
+            match decode_limited(request, MAX_REQUEST_FRAME as u64).await {
+                Ok(req) => req,
+                Err(e) => {
+                    let mut body = vec![];
+                    error_status(&e.into()).encode(&mut body).await?;
+                    write_frame(stream, &body).await?;
+                    return Ok(());
+                }
+            };
+        Self::respond(req, stream, state, true).await
+    }
+
+    async fn respond<W: AsyncWrite + Unpin + Send>(
+        req: GetAllIndexesRequest,
+        writer: &mut W,
+        state: &ServerState,
+        framed: bool,
+    ) -> Result<(), ServerError> {
+        let index = state.repositories.index();
+        let page_size = MAX_CHUNK_ITEMS as usize;
+        let mut out = vec![];
+
+        let mut page = match index
+            .get_all_indexes_page::<I>(req.timestamp, 0, page_size)
+            .await
+        {
+            Ok(page) => page,
+            Err(_) => {
+                AkarekoProtocolResponse::<()>::internal_error("Database error".into())
+                    .encode(&mut out)
+                    .await?;
+                send(writer, &mut out, framed).await?;
+                return Ok(());
+            }
+        };
+
+        AkarekoProtocolResponse::ok(()).encode(&mut out).await?;
+        send(writer, &mut out, framed).await?;
+
+        let mut start = 0;
+        loop {
+            let last = page.len() < page_size;
+            start += page.len();
+
+            let page: Vec<Index<I>> = match &req.filter {
+                Some(filter) => page.into_iter().filter(|i| !filter.contains(i)).collect(),
+                None => page,
+            };
+            encode_chunk(&page, &mut out).await?;
+            send(writer, &mut out, framed).await?;
+
+            if last {
+                break;
+            }
+            // Failing halfway drops the connection, the peer keeps what it
+            // got so far
+            page = index
+                .get_all_indexes_page::<I>(req.timestamp, start, page_size)
+                .await?;
+        }
+
+        end_chunks(&mut out).await?;
+        send(writer, &mut out, framed).await?;
+        Ok(())
+    }
+}
+
+/// Writes what was encoded to `out` and clears it, in a frame of its own if
+/// `framed`
+async fn send<W: AsyncWrite + Unpin + Send>(
+    writer: &mut W,
+    out: &mut Vec<u8>,
+    framed: bool,
+) -> Result<(), EncodeError> {
+    // An empty chunk isn't written at all, nor is its frame
+    if out.is_empty() {
+        return Ok(());
+    }
+    if framed {
+        write_frame(writer, out).await?;
+    } else {
+        writer.write_all(out).await?;
+    }
+    out.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{broken_state, index, peer, state},
+            handler::{
+                V2,
+                meta::{Ping, ping::PingRequest},
+            },
+            protocol::{
+                AkarekoProtocolVersion, AkarekoStatus, ChunkedStream, DEFAULT_MAX_RESPONSE_SIZE,
+            },
+        },
+        types::PrivateKey,
+    };
+
+    async fn stream_all(state: &ServerState) -> tokio::io::DuplexStream {
+        let (mut client, mut server) = tokio::io::duplex(1 << 22);
+        GetAllIndexesRequest::new::<MangaTag>(None, None)
+            .encode(&mut client)
+            .await
+            .unwrap();
+
+        StreamAllIndexes::<MangaTag>::handle(&mut server, state, &mut peer())
+            .await
+            .unwrap();
+        client
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn streams_every_index_across_chunks() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        for i in 0..MAX_CHUNK_ITEMS + 3 {
+            state
+                .repositories
+                .index()
+                .add_index(index(&format!("Title {}", i), &priv_key))
+                .await
+                .unwrap();
+        }
+
+        let mut client = stream_all(&state).await;
+        let res = AkarekoProtocolResponse::<()>::decode(&mut client)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+
+        let mut chunks = ChunkedStream::<Index<MangaTag>>::new(DEFAULT_MAX_RESPONSE_SIZE);
+        let mut count = 0;
+        while let Some(index) = chunks.next(&mut client).await.unwrap() {
+            assert!(index.verify());
+            count += 1;
+        }
+        assert_eq!(count, MAX_CHUNK_ITEMS + 3);
+    }
+
+    /// Each chunk comes in a frame of its own and the connection goes on
+    /// after the last one
+    #[tokio::test(flavor = "multi_thread")]
+    async fn v2_sends_chunks_in_their_own_frames() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        for i in 0..MAX_CHUNK_ITEMS + 3 {
+            state
+                .repositories
+                .index()
+                .add_index(index(&format!("Title {}", i), &priv_key))
+                .await
+                .unwrap();
+        }
+        let mut ctx = peer();
+        let (mut client, mut server) = tokio::io::duplex(1 << 16);
+
+        let (res, count) = tokio::join!(
+            async {
+                let request = GetAllIndexesRequest::new::<MangaTag>(None, None);
+                let (res, frame) =
+                    StreamAllIndexes::<MangaTag>::request_framed(request, &mut client)
+                        .await
+                        .unwrap();
+                assert_eq!(frame.remaining(), 0);
+
+                let mut chunks = ChunkedStream::<Index<MangaTag>>::for_version(
+                    AkarekoProtocolVersion::V2,
+                    DEFAULT_MAX_RESPONSE_SIZE,
+                );
+                let mut count = 0;
+                while chunks.next(&mut client).await.unwrap().is_some() {
+                    count += 1;
+                }
+                (res, count)
+            },
+            async {
+                AkarekoProtocolVersion::decode(&mut server).await.unwrap();
+                V2::handle(&mut server, &state, &mut ctx).await.unwrap();
+            }
+        );
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(count, MAX_CHUNK_ITEMS + 3);
+
+        let (res, ()) = tokio::join!(Ping::request_framed(PingRequest {}, &mut client), async {
+            AkarekoProtocolVersion::decode(&mut server).await.unwrap();
+            V2::handle(&mut server, &state, &mut ctx).await.unwrap();
+        });
+        assert_eq!(res.unwrap().0.status(), &AkarekoStatus::Ok);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn database_failure_is_internal() {
+        let mut client = stream_all(&broken_state()).await;
+        let res = AkarekoProtocolResponse::<()>::decode(&mut client)
+            .await
+            .unwrap();
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -0,0 +1,136 @@
+use std::marker::PhantomData;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{index::tags::IndexTag, user::SyncPolicy},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+    types::Hash,
+};
+
+/// Most indexes a peer subscribes to in one request
+pub const MAX_SUBSCRIBED_INDEXES: usize = 200;
+
+/// Asks to be pushed the new contents of some of our indexes with
+/// [`Announce`](super::Announce) as soon as we publish them. Only indexes we
+/// published are kept, they're pushed to the address the request came from.
+pub struct Subscribe<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> AkarekoProtocolCommand for Subscribe<I> {
+    type RequestPayload = SubscribeRequest;
+    type ResponsePayload = SubscribeResponse;
+    type ResponseData = ();
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        if req.indexes.len() > MAX_SUBSCRIBED_INDEXES {
+            return AkarekoProtocolResponse::invalid_argument(format!(
+                "At most {} indexes per request",
+                MAX_SUBSCRIBED_INDEXES
+            ));
+        }
+
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::SEND_PUBLISHED)
+        {
+            return AkarekoProtocolResponse::forbidden("Not sharing with you".to_string());
+        }
+
+        let own_key = state.config.read().await.public_key().clone();
+        let published = match state
+            .repositories
+            .index()
+            .get_published_index_hashes::<I>(&own_key)
+            .await
+        {
+            Ok(hashes) => hashes,
+            Err(_) => return AkarekoProtocolResponse::internal_error("Database error".to_string()),
+        };
+
+        let accepted: Vec<Hash> = req
+            .indexes
+            .into_iter()
+            .filter(|hash| published.contains(hash))
+            .collect();
+
+        if state
+            .repositories
+            .subscribe(&accepted, &ctx.address)
+            .await
+            .is_err()
+        {
+            return AkarekoProtocolResponse::internal_error("Database error".to_string());
+        }
+
+        AkarekoProtocolResponse::ok(SubscribeResponse { accepted })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubscribeRequest {
+    pub indexes: Vec<Hash>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubscribeResponse {
+    /// The indexes of the request we publish, the rest aren't ours to push
+    pub accepted: Vec<Hash>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::index::tags::MangaTag,
+        server::{
+            fixtures::{PEER_ADDRESS, index, peer, state, withhold_published},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn keeps_only_our_indexes() {
+        let state = state().await;
+        let own_key = state.config.read().await.private_key().clone();
+        let ours = index("Ours", &own_key);
+        let theirs = index("Theirs", &PrivateKey::new());
+        for index in [&ours, &theirs] {
+            state
+                .repositories
+                .index()
+                .add_index(index.clone())
+                .await
+                .unwrap();
+        }
+
+        let req = SubscribeRequest {
+            indexes: vec![ours.hash().clone(), theirs.hash().clone()],
+        };
+        let res = Subscribe::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(res.payload().unwrap().accepted, vec![ours.hash().clone()]);
+        let subscribers = state.repositories.get_subscribers(ours.hash()).await;
+        assert_eq!(subscribers.unwrap()[0].inner(), PEER_ADDRESS);
+    }
+
+    #[tokio::test]
+    async fn withheld_peer_is_forbidden() {
+        let state = state().await;
+        withhold_published(&state).await;
+
+        let req = SubscribeRequest { indexes: vec![] };
+        let res = Subscribe::<MangaTag>::process(req, &state, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+}
@@ -1,24 +1,76 @@
+/// Declares the commands of a protocol version and routes them to their
+/// handlers:
+///
+/// ```ignore
+/// handler!(V1, {
+///     GetContents = 4 ("manga/get_contents", RelayMiddleware) => index::GetContents<MangaTag>,
+///     Ping = 7 ("meta/ping") => meta::Ping,
+/// });
+/// ```
+///
+/// The number is what goes on the wire for the command, never change or
+/// reuse one that was released, a new command takes the next free number.
 #[macro_export]
 macro_rules! handler {
     (
         $version:ident,
         {
             $(
-                $command:ident ($cmd_discriminant:literal $(, $middleware:ident)?) => $handler:path
+                $command:ident = $id:literal ($cmd_discriminant:literal $(, $middleware:ident)?) => $handler:path
             ),* $(,)?
         }
     ) => {
         paste::paste! {
             pub struct $version;
 
-            #[repr(u8)]
-            #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
             pub enum [<Commands $version>] {
                 $(
                     $command,
                 )*
             }
             impl CommandEnum for [<Commands $version>] {}
+
+            impl [<Commands $version>] {
+                /// Number the command is sent as
+                pub const fn id(self) -> u32 {
+                    match self {
+                        $(
+                            [<Commands $version>]::$command => $id,
+                        )*
+                    }
+                }
+
+                pub const fn from_id(id: u32) -> Option<Self> {
+                    match id {
+                        $(
+                            $id => Some([<Commands $version>]::$command),
+                        )*
+                        _ => None,
+                    }
+                }
+            }
+
+            // Sent as a u32 so the first commands keep the bytes they had as
+            // the variant index of a derived enum
+            impl serde::Serialize for [<Commands $version>] {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_u32(self.id())
+                }
+            }
+
+            impl<'de> serde::Deserialize<'de> for [<Commands $version>] {
+                fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    let id = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+                    Self::from_id(id).ok_or_else(|| {
+                        <D::Error as serde::de::Error>::custom(format!(
+                            "unknown {} command {}",
+                            stringify!($version),
+                            id
+                        ))
+                    })
+                }
+            }
             // impl Byteable for [<Commands $version>] {
             //     async fn encode<W: AsyncWrite + Unpin + Send>(
             //         &self,
@@ -54,19 +106,25 @@ macro_rules! handler {
                 }
             )*
 
+            const _: () = assert!(
+                !$crate::server::handler::has_duplicate_ids(&[$($id),*]),
+                concat!("handler!(", stringify!($version), ") gives two commands the same number")
+            );
+
             impl $version {
-                pub async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(stream: &mut S, state: &ServerState, address: &I2PAddress) {
-                    let command = [<Commands $version>]::decode(stream)
-                        .await
-                        .unwrap();
+                /// Name of every command this version understands
+                pub const COMMANDS: &'static [&'static str] = &[$($cmd_discriminant),*];
+
+                pub async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(stream: &mut S, state: &ServerState, ctx: &mut ConnectionContext) -> Result<(), ServerError> {
+                    let command = [<Commands $version>]::decode(stream).await?;
 
                     match command {
                         $(
                             [<Commands $version>]::$command => {
                                 $(
-                                    <$middleware as AkarekoMiddleware>::apply_middleware(state, address).await.unwrap();
+                                    <$middleware as AkarekoMiddleware>::apply_middleware(state, ctx).await?;
                                 )*
-                                <$handler as AkarekoProtocolCommandHandler>::handle(stream, state, address).await;
+                                <$handler as AkarekoProtocolCommandHandler>::handle(stream, state, ctx).await
                             }
                         )*
                     }
@@ -74,4 +132,9 @@ macro_rules! handler {
             }
         }
     };
+    ($($rest:tt)*) => {
+        compile_error!(
+            "expected `handler!(Version, { Command = 0 (\"group/name\" [, Middleware]) => path::Handler, ... })`"
+        );
+    };
 }
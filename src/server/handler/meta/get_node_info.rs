@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build_info,
+    db::{
+        ToBytes,
+        index::{relay_trail::MAX_RELAY_HOPS, tags::IndexTag, tags::MangaTag},
+    },
+    server::{
+        ConnectionContext, ServerState,
+        handler::{AkarekoProtocolCommand, V1},
+        protocol::{AkarekoProtocolResponse, AkarekoProtocolVersion},
+    },
+    types::{PrivateKey, PublicKey, Signature, Timestamp},
+};
+
+/// Describes what the node does so clients can adapt to it
+pub struct GetNodeInfo;
+
+impl AkarekoProtocolCommand for GetNodeInfo {
+    type RequestPayload = GetNodeInfoRequest;
+    type ResponsePayload = NodeInfo;
+    type ResponseData = ();
+
+    async fn process(
+        _: Self::RequestPayload,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let config = state.config.read().await;
+
+        AkarekoProtocolResponse::ok(NodeInfo::new_signed(
+            config.is_relay(),
+            state.now(),
+            config.private_key(),
+        ))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetNodeInfoRequest {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeLimits {
+    /// Content with a longer relay trail is not accepted
+    pub max_relay_hops: u16,
+}
+
+impl NodeLimits {
+    pub fn current() -> Self {
+        Self {
+            max_relay_hops: MAX_RELAY_HOPS as u16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub pub_key: PublicKey,
+    pub is_relay: bool,
+    /// [`IndexTag::TAG`] of every kind of content served
+    pub tags: Vec<String>,
+    pub protocol_versions: Vec<AkarekoProtocolVersion>,
+    /// Names of the commands it answers to
+    pub commands: Vec<String>,
+    pub software_version: String,
+    /// Commit the node was built from, see [`build_info::GIT_HASH`]
+    pub git_hash: String,
+    pub built_at: Timestamp,
+    pub limits: NodeLimits,
+    pub timestamp: Timestamp,
+    pub signature: Signature,
+}
+
+impl NodeInfo {
+    pub fn new_signed(is_relay: bool, timestamp: Timestamp, priv_key: &PrivateKey) -> Self {
+        let mut info = Self {
+            pub_key: priv_key.public_key(),
+            is_relay,
+            tags: vec![MangaTag::TAG.to_string()],
+            protocol_versions: vec![AkarekoProtocolVersion::V1, AkarekoProtocolVersion::V2],
+            commands: V1::COMMANDS.iter().map(|c| c.to_string()).collect(),
+            software_version: build_info::VERSION.to_string(),
+            git_hash: build_info::GIT_HASH.to_string(),
+            built_at: build_info::built_at(),
+            limits: NodeLimits::current(),
+            timestamp,
+            signature: Signature::empty(),
+        };
+
+        info.signature = priv_key.sign(&info.sign_bytes());
+        info
+    }
+
+    fn sign_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.pub_key.as_bytes().to_vec();
+        bytes.push(self.is_relay as u8);
+        for tag in &self.tags {
+            bytes.extend(tag.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend(self.protocol_versions.iter().map(|v| *v as u8));
+        for command in &self.commands {
+            bytes.extend(command.as_bytes());
+            bytes.push(0);
+        }
+        bytes.extend(self.software_version.as_bytes());
+        bytes.push(0);
+        bytes.extend(self.git_hash.as_bytes());
+        bytes.extend(self.built_at.to_bytes());
+        bytes.extend(self.limits.max_relay_hops.to_le_bytes());
+        bytes.extend(self.timestamp.to_bytes());
+        bytes
+    }
+
+    pub fn verify(&self) -> bool {
+        self.pub_key.verify(&self.sign_bytes(), &self.signature)
+    }
+
+    pub fn serves<I: IndexTag>(&self) -> bool {
+        self.tags.iter().any(|t| t == I::TAG)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        fixtures::{broken_state, peer},
+        protocol::AkarekoStatus,
+    };
+
+    #[test]
+    fn signed_node_info_verifies() {
+        let priv_key = PrivateKey::new();
+        let info = NodeInfo::new_signed(true, Timestamp::now(), &priv_key);
+
+        assert!(info.verify());
+        assert!(info.serves::<MangaTag>());
+        assert!(info.commands.iter().any(|c| c == "meta/get_node_info"));
+    }
+
+    #[tokio::test]
+    async fn answers_signed_by_the_node() {
+        let state = broken_state();
+        let res = GetNodeInfo::process(GetNodeInfoRequest {}, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let info = res.payload().unwrap();
+        assert!(info.verify());
+        assert_eq!(&info.pub_key, state.config.read().await.public_key());
+    }
+
+    #[test]
+    fn tampered_node_info_fails() {
+        let priv_key = PrivateKey::new();
+        let mut info = NodeInfo::new_signed(false, Timestamp::now(), &priv_key);
+        info.is_relay = true;
+
+        assert!(!info.verify());
+    }
+}
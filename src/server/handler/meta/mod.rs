@@ -0,0 +1,4 @@
+pub mod get_node_info;
+pub mod ping;
+pub use get_node_info::GetNodeInfo;
+pub use ping::Ping;
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, AkarekoProtocolVersion},
+    },
+    types::Timestamp,
+};
+
+/// Cheapest possible round trip, used to check a peer is reachable and how long
+/// it takes to answer
+pub struct Ping;
+
+impl AkarekoProtocolCommand for Ping {
+    type RequestPayload = PingRequest;
+    type ResponsePayload = PingResponse;
+    type ResponseData = ();
+
+    async fn process(
+        _: Self::RequestPayload,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        AkarekoProtocolResponse::ok(PingResponse {
+            timestamp: state.now(),
+            protocol_version: AkarekoProtocolVersion::LATEST,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingRequest {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingResponse {
+    /// Server time, lets the client notice clocks that are way off
+    pub timestamp: Timestamp,
+    /// Latest version the server speaks, clients use it once they know
+    pub protocol_version: AkarekoProtocolVersion,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::AkarekoConfig,
+        db::Repositories,
+        server::{
+            fixtures::{broken_state, peer},
+            protocol::AkarekoStatus,
+        },
+        types::ManualClock,
+    };
+
+    #[tokio::test]
+    async fn answers_with_the_server_time() {
+        let now = Timestamp::new(1_000);
+        let repositories = Repositories::disconnected().with_clock(ManualClock::new(now));
+        let state = ServerState::for_tests(repositories, AkarekoConfig::default());
+
+        let res = Ping::process(PingRequest {}, &state, &mut peer()).await;
+
+        assert_eq!(res.payload().unwrap().timestamp, now);
+    }
+
+    #[tokio::test]
+    async fn answers_without_the_database() {
+        let res = Ping::process(PingRequest {}, &broken_state(), &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let pong = res.payload().unwrap();
+        assert_eq!(pong.protocol_version, AkarekoProtocolVersion::LATEST);
+    }
+}
@@ -1,23 +1,34 @@
+use std::time::Instant;
+
 use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::{debug, warn};
 
 use crate::{
-    db::{index::tags::MangaTag, user::I2PAddress},
-    errors::{ClientError, EncodeError, ServerError},
-    helpers::{AkarekoRead, AkarekoWrite},
+    db::index::tags::MangaTag,
+    errors::{ClientError, DecodeError, EncodeError, ServerError},
+    helpers::{AkarekoRead, AkarekoWrite, decode_limited},
     server::{
-        ServerState,
-        protocol::{AkarekoProtocolRequest, AkarekoProtocolResponse, AkarekoProtocolVersion},
+        ConnectionContext, ServerState,
+        protocol::{
+            AkarekoProtocolRequest, AkarekoProtocolResponse, AkarekoProtocolVersion, AkarekoStatus,
+            DEFAULT_MAX_REQUEST_SIZE, DEFAULT_MAX_RESPONSE_SIZE, Frame, MAX_REQUEST_FRAME,
+            MAX_RESPONSE_FRAME, PeerStream, read_frame, with_wire_version, write_frame,
+        },
     },
 };
 
+pub mod group;
 pub mod index;
 mod macros;
+pub mod meta;
 pub mod events {
     mod sync_events;
     pub use sync_events::{SyncEvents, SyncEventsRequest};
 }
 pub mod post {
+    mod get_posts;
     mod get_posts_by_topic;
+    pub use get_posts::{GetPosts, GetPostsRequest, MAX_POSTS_PER_PAGE};
     pub use get_posts_by_topic::{
         GetPostsByTopic,
         // GetPostsByTopicRequest, GetPostsByTopicResponse,
@@ -40,10 +51,16 @@ pub(super) trait AkarekoProtocolCommand: Sized {
     type ResponsePayload: AkarekoRead + AkarekoWrite;
     type ResponseData: AkarekoRead + AkarekoWrite;
 
+    /// Largest request payload the server decodes, in bytes
+    const MAX_REQUEST_SIZE: u64 = DEFAULT_MAX_REQUEST_SIZE;
+    /// Largest response payload, and item of response data, the client
+    /// decodes, in bytes
+    const MAX_RESPONSE_SIZE: u64 = DEFAULT_MAX_RESPONSE_SIZE;
+
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        address: &I2PAddress,
+        ctx: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData>;
 }
 
@@ -53,6 +70,50 @@ pub trait AkarekoProtocolCommandRequest<P, R> {
         payload: P,
         stream: &mut S,
     ) -> Result<R, ClientError>;
+
+    /// Same as [`Self::request`] over [`AkarekoProtocolVersion::V2`], the
+    /// response data is read from the returned frame instead of the stream
+    async fn request_framed<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        payload: P,
+        stream: &mut S,
+    ) -> Result<(R, Frame), ClientError>;
+
+    /// Sends the request in the version agreed with the peer, the response
+    /// data is then read from `stream` whichever it is
+    async fn send<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        payload: P,
+        stream: &mut PeerStream<S>,
+    ) -> Result<R, ClientError> {
+        let version = stream.version();
+        with_wire_version(version, async {
+            match version {
+                AkarekoProtocolVersion::V1 => Self::request(payload, stream.start_request()).await,
+                AkarekoProtocolVersion::V2 => {
+                    let (res, frame) =
+                        Self::request_framed(payload, stream.start_request()).await?;
+                    stream.set_response_frame(frame);
+                    Ok(res)
+                }
+            }
+        })
+        .await
+    }
+}
+
+/// Sends `payload` for `C` as a V2 frame and reads the response frame back
+async fn exchange_frame<C, P, S>(payload: &P, stream: &mut S) -> Result<Frame, ClientError>
+where
+    C: AkarekoProtocolCommandMetadata,
+    P: AkarekoWrite,
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let mut body = vec![];
+    C::COMMAND.encode(&mut body).await?;
+    payload.encode(&mut body).await?;
+
+    AkarekoProtocolVersion::V2.encode(stream).await?;
+    write_frame(stream, &body).await?;
+    Ok(Frame::new(read_frame(stream, MAX_RESPONSE_FRAME).await?))
 }
 
 impl<T: AkarekoProtocolCommand + AkarekoProtocolCommandMetadata>
@@ -67,29 +128,52 @@ impl<T: AkarekoProtocolCommand + AkarekoProtocolCommandMetadata>
     ) -> Result<AkarekoProtocolResponse<T::ResponsePayload, T::ResponseData>, ClientError> {
         let req = AkarekoProtocolRequest::<Self> { payload };
         req.encode(stream).await?;
-        let res =
-            AkarekoProtocolResponse::<T::ResponsePayload, T::ResponseData>::decode(stream).await?;
+        let res = AkarekoProtocolResponse::<T::ResponsePayload, T::ResponseData>::decode_limited(
+            stream,
+            T::MAX_RESPONSE_SIZE,
+        )
+        .await?;
         Ok(res)
     }
+
+    async fn request_framed<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        payload: T::RequestPayload,
+        stream: &mut S,
+    ) -> Result<
+        (
+            AkarekoProtocolResponse<T::ResponsePayload, T::ResponseData>,
+            Frame,
+        ),
+        ClientError,
+    > {
+        let mut frame = exchange_frame::<T, _, _>(&payload, stream).await?;
+        let res = AkarekoProtocolResponse::<T::ResponsePayload, T::ResponseData>::decode_limited(
+            &mut frame,
+            T::MAX_RESPONSE_SIZE,
+        )
+        .await?;
+        Ok((res, frame))
+    }
 }
 
 trait AkarekoProtocolCommandHandler {
     async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
         stream: &mut S,
         state: &ServerState,
-        address: &I2PAddress,
-    );
+        ctx: &mut ConnectionContext,
+    ) -> Result<(), ServerError>;
 }
 
 impl<T: AkarekoProtocolCommand> AkarekoProtocolCommandHandler for T {
     async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
         stream: &mut S,
         state: &ServerState,
-        address: &I2PAddress,
-    ) {
-        let req = T::RequestPayload::decode(stream).await.unwrap();
-        let res = T::process(req, state, address).await;
-        res.encode(stream).await.unwrap();
+        ctx: &mut ConnectionContext,
+    ) -> Result<(), ServerError> {
+        let req = decode_limited(stream, T::MAX_REQUEST_SIZE).await?;
+        let res = T::process(req, state, ctx).await;
+        res.encode(stream).await?;
+        Ok(())
     }
 }
 
@@ -111,15 +195,32 @@ pub trait AkarekoProtocolCommandMetadata {
 pub trait AkarekoMiddleware {
     fn apply_middleware(
         state: &ServerState,
-        address: &I2PAddress,
+        ctx: &mut ConnectionContext,
     ) -> impl Future<Output = Result<(), ServerError>>;
 }
 
+/// Used by the handler macro to refuse two commands sent as the same number
+/// at compile time
+pub const fn has_duplicate_ids(ids: &[u32]) -> bool {
+    let mut i = 0;
+    while i < ids.len() {
+        let mut j = i + 1;
+        while j < ids.len() {
+            if ids[i] == ids[j] {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
 struct RelayMiddleware;
 impl AkarekoMiddleware for RelayMiddleware {
     async fn apply_middleware(
         state: &ServerState,
-        _address: &I2PAddress,
+        _: &mut ConnectionContext,
     ) -> Result<(), ServerError> {
         if !state.config.read().await.is_relay() {
             return Err(ServerError::RelayNotEnabled);
@@ -129,22 +230,263 @@ impl AkarekoMiddleware for RelayMiddleware {
     }
 }
 
+/// Status sent back for a V2 request that failed before it had a response
+fn error_status(e: &ServerError) -> AkarekoStatus {
+    match e {
+        ServerError::RelayNotEnabled => AkarekoStatus::Forbidden("Not a relay".to_string()),
+        ServerError::InvalidData
+        | ServerError::InvalidEnumVariant { .. }
+        | ServerError::FromUtf8Error(_)
+        | ServerError::FrameTooLarge { .. }
+        | ServerError::PayloadTooLarge { .. } => AkarekoStatus::InvalidArgument(e.to_string()),
+        _ => AkarekoStatus::InternalError("Failed to answer".to_string()),
+    }
+}
+
+/// The commands of [`V1`], each request and response in a frame. A request
+/// that fails to decode or to be answered gets an error status back and the
+/// connection goes on with the next frame.
+pub struct V2;
+
+impl V2 {
+    pub async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
+        stream: &mut S,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> Result<(), ServerError> {
+        let response = match read_frame(stream, MAX_REQUEST_FRAME).await {
+            Ok(_) if !state.rate_limiter.try_acquire(&ctx.address, Instant::now()) => {
+                debug!("Refused a request from {}: too many requests", ctx.address);
+                let mut body = vec![];
+                AkarekoStatus::TooManyRequests("Too many requests, slow down".to_string())
+                    .encode(&mut body)
+                    .await?;
+                body
+            }
+            Ok(body) => {
+                let command = CommandsV1::decode(&mut body.as_slice()).await;
+                let mut frame = Frame::new(body);
+                // Answered as it's read, it couldn't be held in one frame
+                if let Ok(CommandsV1::StreamAllIndexes) = command {
+                    CommandsV1::decode(&mut frame).await?;
+                    return index::StreamAllIndexes::<MangaTag>::handle_framed(
+                        &mut frame, stream, state,
+                    )
+                    .await;
+                }
+                match V1::handle(&mut frame, state, ctx).await {
+                    Ok(()) => frame.into_output(),
+                    Err(e) => {
+                        warn!("Failed to answer {}: {}", ctx.address, e);
+                        let mut body = vec![];
+                        error_status(&e).encode(&mut body).await?;
+                        body
+                    }
+                }
+            }
+            Err(e @ DecodeError::FrameTooLarge { .. }) => {
+                warn!("Skipped a request from {}: {}", ctx.address, e);
+                let mut body = vec![];
+                error_status(&e.into()).encode(&mut body).await?;
+                body
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        write_frame(stream, &response).await?;
+        Ok(())
+    }
+}
+
 crate::handler!(V1,
 {
-    Who("who") => users::Who,
+    // The first seven are the commands the protocol started with
+    Who = 0 ("who") => users::Who,
+    GetUsers = 1 ("user/get_users") => users::GetUsers,
+    GetAllIndexes = 2 ("manga/get_all_indexes") => index::GetAllIndexes<MangaTag>,
+    GetIndexes = 3 ("manga/get_indexes") => index::GetIndexes<MangaTag>,
+    GetContents = 4 ("manga/get_contents", RelayMiddleware) => index::GetContents<MangaTag>,
+    GetPostsByTopic = 5 ("post/get_posts_by_topic") => post::GetPostsByTopic,
+    SyncEvents = 6 ("event/sync_events") => events::SyncEvents,
 
-    // ==================== User ====================
-    GetUsers("user/get_users") => users::GetUsers,
+    // New commands go at the end with the next number
+    Ping = 7 ("meta/ping") => meta::Ping,
+    GetNodeInfo = 8 ("meta/get_node_info") => meta::GetNodeInfo,
+    HaveContent = 9 ("manga/have_content") => index::HaveContent<MangaTag>,
+    GroupChallenge = 10 ("group/challenge") => group::GroupChallenge,
+    ProveGroup = 11 ("group/prove") => group::ProveGroup,
+    GetGroupContents = 12 ("manga/get_group_contents") => group::GetGroupContents<MangaTag>,
+    GetCatalogSnapshot = 13 ("manga/get_catalog_snapshot", RelayMiddleware) => index::GetCatalogSnapshot<MangaTag>,
+    GetAttestations = 14 ("user/get_attestations") => users::GetAttestations,
+    GetPosts = 15 ("post/get_posts") => post::GetPosts,
+    SearchIndexes = 16 ("manga/search_indexes") => index::SearchIndexes<MangaTag>,
+    AuthChallenge = 17 ("user/challenge") => users::AuthChallenge,
+    Authenticate = 18 ("user/authenticate") => users::Authenticate,
+    StreamAllIndexes = 19 ("manga/stream_all_indexes") => index::StreamAllIndexes<MangaTag>,
+    Subscribe = 20 ("manga/subscribe") => index::Subscribe<MangaTag>,
+    Announce = 21 ("manga/announce") => index::Announce<MangaTag>,
+    GetPeers = 22 ("user/get_peers") => users::GetPeers,
+    GetRevocations = 23 ("manga/get_revocations") => index::GetRevocations<MangaTag>,
+});
 
-    // ==================== Index ====================
-    GetAllIndexes("manga/get_all_indexes") => index::GetAllIndexes<MangaTag>,
-    GetIndexes("manga/get_indexes") => index::GetIndexes<MangaTag>,
-    GetContents("manga/get_contents", RelayMiddleware) => index::GetContents<MangaTag>,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::RateLimitConfig,
+        server::{
+            fixtures::{peer, state},
+            handler::meta::ping::PingRequest,
+            rate_limit::RateLimiter,
+        },
+    };
 
-    // ==================== Post ====================
-    GetPostsByTopic("post/get_posts_by_topic") => post::GetPostsByTopic,
+    /// Peers that were never upgraded still send these bytes
+    #[test]
+    fn first_commands_keep_their_wire_bytes() {
+        let baseline = [
+            (CommandsV1::Who, 0u8),
+            (CommandsV1::GetUsers, 1),
+            (CommandsV1::GetAllIndexes, 2),
+            (CommandsV1::GetIndexes, 3),
+            (CommandsV1::GetContents, 4),
+            (CommandsV1::GetPostsByTopic, 5),
+            (CommandsV1::SyncEvents, 6),
+        ];
 
-    // ==================== Events ====================
-    SyncEvents("event/sync_events") => events::SyncEvents
+        for (command, byte) in baseline {
+            assert_eq!(postcard::to_allocvec(&command).unwrap(), vec![byte]);
+            assert_eq!(
+                postcard::from_bytes::<CommandsV1>(&[byte]).unwrap(),
+                command
+            );
+        }
+        assert!(postcard::from_bytes::<CommandsV1>(&[100]).is_err());
+    }
 
-});
+    #[test]
+    fn duplicate_command_ids_are_found() {
+        assert!(has_duplicate_ids(&[7, 0, 7]));
+        assert!(!has_duplicate_ids(&[0, 1, 2]));
+    }
+
+    /// A request that can't be decoded is answered with an error and the next
+    /// one on the connection still goes through
+    #[tokio::test(flavor = "multi_thread")]
+    async fn v2_goes_on_after_a_bad_request() {
+        let state = state().await;
+        let mut ctx = peer();
+        let (mut client, mut server) = tokio::io::duplex(1 << 16);
+
+        write_frame(&mut client, &[0xff; 8]).await.unwrap();
+        V2::handle(&mut server, &state, &mut ctx).await.unwrap();
+        let mut answer = Frame::new(read_frame(&mut client, MAX_RESPONSE_FRAME).await.unwrap());
+        assert!(matches!(
+            AkarekoStatus::decode(&mut answer).await.unwrap(),
+            AkarekoStatus::InvalidArgument(_)
+        ));
+
+        let (res, ()) = tokio::join!(
+            meta::Ping::request_framed(PingRequest {}, &mut client),
+            async {
+                AkarekoProtocolVersion::decode(&mut server).await.unwrap();
+                V2::handle(&mut server, &state, &mut ctx).await.unwrap();
+            }
+        );
+        let (res, frame) = res.unwrap();
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(frame.remaining(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn v2_refuses_requests_past_the_rate_limit() {
+        let mut state = state().await;
+        state.rate_limiter = rclite::Arc::new(RateLimiter::new(&RateLimitConfig {
+            burst: 1,
+            per_minute: 1,
+        }));
+        let mut ctx = peer();
+        let (mut client, mut server) = tokio::io::duplex(1 << 16);
+
+        for expected in [
+            AkarekoStatus::Ok,
+            AkarekoStatus::TooManyRequests(String::new()),
+        ] {
+            let (res, ()) = tokio::join!(
+                meta::Ping::request_framed(PingRequest {}, &mut client),
+                async {
+                    AkarekoProtocolVersion::decode(&mut server).await.unwrap();
+                    V2::handle(&mut server, &state, &mut ctx).await.unwrap();
+                }
+            );
+            let (res, _) = res.unwrap();
+            assert_eq!(res.status().code(), expected.code());
+        }
+    }
+
+    /// What `handler!` expands to, on commands that only exist here
+    mod expansion {
+        use super::{super::*, peer, state};
+
+        struct Echo;
+        impl AkarekoProtocolCommandHandler for Echo {
+            async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
+                _: &mut S,
+                _: &ServerState,
+                _: &mut ConnectionContext,
+            ) -> Result<(), ServerError> {
+                Ok(())
+            }
+        }
+
+        struct Relayed;
+        impl AkarekoProtocolCommandHandler for Relayed {
+            async fn handle<S: AsyncRead + AsyncWrite + Unpin + Send>(
+                _: &mut S,
+                _: &ServerState,
+                _: &mut ConnectionContext,
+            ) -> Result<(), ServerError> {
+                Ok(())
+            }
+        }
+
+        crate::handler!(V1, {
+            Relayed = 300 ("test/relayed", RelayMiddleware) => Relayed,
+            Echo = 2 ("test/echo") => Echo,
+        });
+
+        #[test]
+        fn commands_keep_their_declared_ids() {
+            assert_eq!(V1::COMMANDS, &["test/relayed", "test/echo"]);
+            assert_eq!(CommandsV1::Relayed.id(), 300);
+            assert_eq!(CommandsV1::from_id(2), Some(CommandsV1::Echo));
+            assert_eq!(CommandsV1::from_id(0), None);
+            assert_eq!(
+                <Echo as AkarekoProtocolCommandMetadata>::COMMAND,
+                CommandsV1::Echo
+            );
+
+            assert_eq!(
+                postcard::to_allocvec(&CommandsV1::Relayed).unwrap(),
+                vec![0xac, 0x02]
+            );
+            assert!(postcard::from_bytes::<CommandsV1>(&[0]).is_err());
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn middleware_runs_before_its_handler() {
+            let state = state().await;
+            let mut ctx = peer();
+            let (mut client, mut server) = tokio::io::duplex(64);
+
+            CommandsV1::Echo.encode(&mut client).await.unwrap();
+            V1::handle(&mut server, &state, &mut ctx).await.unwrap();
+
+            CommandsV1::Relayed.encode(&mut client).await.unwrap();
+            assert!(matches!(
+                V1::handle(&mut server, &state, &mut ctx).await,
+                Err(ServerError::RelayNotEnabled)
+            ));
+        }
+    }
+}
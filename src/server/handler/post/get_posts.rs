@@ -0,0 +1,172 @@
+use fastbloom::BloomFilter;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::{comments::Post, user::SyncPolicy},
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
+    },
+    types::{Timestamp, Topic},
+};
+
+/// Most posts sent for a single request, asking for more gets this many
+pub const MAX_POSTS_PER_PAGE: usize = 100;
+
+/// Posts of a topic a page at a time, for peers catching up on a topic
+/// without a full event sync
+pub struct GetPosts;
+
+impl AkarekoProtocolCommand for GetPosts {
+    type RequestPayload = GetPostsRequest;
+    type ResponsePayload = GetPostsResponse;
+    type ResponseData = Post;
+
+    /// Carries a bloom filter of the posts the peer has
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::SEND_PUBLISHED)
+        {
+            return AkarekoProtocolResponse::forbidden("Not sharing with you".to_string());
+        }
+
+        let take = req.take.clamp(1, MAX_POSTS_PER_PAGE);
+        let Ok(posts) = state
+            .repositories
+            .get_posts_page(req.topic, req.after, take, req.skip)
+            .await
+        else {
+            return AkarekoProtocolResponse::internal_error("Database error".to_string());
+        };
+
+        // A full page may be followed by more, the filter is applied after
+        // so it doesn't shift the pages
+        let next = (posts.len() == take).then_some(req.skip + take);
+        let posts = match req.filter {
+            Some(filter) => posts.into_iter().filter(|p| !filter.contains(p)).collect(),
+            None => posts,
+        };
+
+        AkarekoProtocolResponse::ok_with_data(GetPostsResponse { next }, posts)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetPostsRequest {
+    pub topic: Topic,
+    /// Only posts made since then
+    pub after: Option<Timestamp>,
+    /// Posts the requester already has
+    pub filter: Option<BloomFilter>,
+    pub skip: usize,
+    /// Capped to [`MAX_POSTS_PER_PAGE`]
+    pub take: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetPostsResponse {
+    /// `skip` of the next page, none once the topic is exhausted
+    pub next: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::BLOOM_FILTER_FALSE_POSITIVE_RATE,
+        server::{
+            fixtures::{broken_state, index, peer, state, withhold_published},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    fn request(topic: Topic, skip: usize, take: usize) -> GetPostsRequest {
+        GetPostsRequest {
+            topic,
+            after: None,
+            filter: None,
+            skip,
+            take,
+        }
+    }
+
+    async fn posted(state: &ServerState, count: i64) -> (Topic, Vec<Post>) {
+        let priv_key = PrivateKey::new();
+        let topic = Topic::from_index(&index("Title", &priv_key));
+        let mut posts = vec![];
+        for i in 0..count {
+            let post = Post::new_signed(
+                format!("Post {}", i),
+                Timestamp::new(1_700_000_000 + i),
+                topic.clone(),
+                &priv_key,
+            )
+            .unwrap();
+            state.repositories.add_post(post.clone()).await.unwrap();
+            posts.push(post);
+        }
+        (topic, posts)
+    }
+
+    #[tokio::test]
+    async fn pages_through_the_topic() {
+        let state = state().await;
+        let (topic, posts) = posted(&state, 3).await;
+
+        let mut first = GetPosts::process(request(topic.clone(), 0, 2), &state, &mut peer()).await;
+        assert_eq!(first.data().sent(), &posts[..2]);
+        assert_eq!(first.payload().unwrap().next, Some(2));
+
+        let mut last = GetPosts::process(request(topic, 2, 2), &state, &mut peer()).await;
+        assert_eq!(last.data().sent(), &posts[2..]);
+        assert_eq!(last.payload().unwrap().next, None);
+    }
+
+    #[tokio::test]
+    async fn filtered_posts_keep_the_page() {
+        let state = state().await;
+        let (topic, posts) = posted(&state, 2).await;
+        let mut filter =
+            BloomFilter::with_false_pos(BLOOM_FILTER_FALSE_POSITIVE_RATE).expected_items(1);
+        filter.insert(&posts[0]);
+
+        let req = GetPostsRequest {
+            filter: Some(filter),
+            ..request(topic, 0, 2)
+        };
+        let mut res = GetPosts::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.data().sent(), &posts[1..]);
+        assert_eq!(res.payload().unwrap().next, Some(2));
+    }
+
+    #[tokio::test]
+    async fn withheld_from_peers_we_dont_share_with() {
+        let state = state().await;
+        withhold_published(&state).await;
+        let (topic, _) = posted(&state, 1).await;
+
+        let res = GetPosts::process(request(topic, 0, 10), &state, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+
+    /// The sync policy can't be read either, so nothing is shared
+    #[tokio::test]
+    async fn database_failure_is_forbidden() {
+        let topic = Topic::from_index(&index("Title", &PrivateKey::new()));
+        let res = GetPosts::process(request(topic, 0, 10), &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+    }
+}
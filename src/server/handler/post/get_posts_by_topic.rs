@@ -2,8 +2,12 @@ use fastbloom::BloomFilter;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    db::{comments::Post, user::I2PAddress},
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
+    db::comments::Post,
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
+    },
     types::{Timestamp, Topic},
 };
 
@@ -14,10 +18,13 @@ impl AkarekoProtocolCommand for GetPostsByTopic {
     type ResponsePayload = GetPostsByTopicResponse;
     type ResponseData = Post;
 
+    /// Carries a bloom filter of the posts the peer has
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        _address: &I2PAddress,
+        _: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
         let Ok(posts) = state
             .repositories
@@ -40,3 +47,52 @@ pub struct GetPostsByTopicRequest {
 
 #[derive(Serialize, Deserialize)]
 pub struct GetPostsByTopicResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{
+            fixtures::{broken_state, index, peer, state},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn streams_posts_of_the_topic_only() {
+        let state = state().await;
+        let priv_key = PrivateKey::new();
+        let topic = Topic::from_index(&index("Title", &priv_key));
+        let other = Topic::from_index(&index("Other", &priv_key));
+        let post =
+            Post::new_signed("Hi".to_string(), Timestamp::now(), topic.clone(), &priv_key).unwrap();
+        let elsewhere =
+            Post::new_signed("Hi".to_string(), Timestamp::now(), other, &priv_key).unwrap();
+        for post in [post.clone(), elsewhere] {
+            state.repositories.add_post(post).await.unwrap();
+        }
+
+        let req = GetPostsByTopicRequest {
+            topic,
+            timestamp: None,
+            filter: None,
+        };
+        let mut res = GetPostsByTopic::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(res.data().sent(), &[post]);
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = GetPostsByTopicRequest {
+            topic: Topic::from_index(&index("Title", &PrivateKey::new())),
+            timestamp: None,
+            filter: None,
+        };
+        let res = GetPostsByTopic::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
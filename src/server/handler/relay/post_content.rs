@@ -1,13 +1,18 @@
 use std::marker::PhantomData;
 
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 use crate::{
     db::{
+        content_source::{ContentSource, SourceKind},
         index::{content::Content, tags::IndexTag},
-        user::I2PAddress,
+        user::SyncPolicy,
+    },
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
     },
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
 };
 
 pub struct SendContent<I: IndexTag>(PhantomData<I>);
@@ -20,17 +25,39 @@ impl<I: IndexTag + 'static> AkarekoProtocolCommand for SendContent<I> {
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        _: &I2PAddress,
+        ctx: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
         if !req.content.verify() {
             return AkarekoProtocolResponse::invalid_argument("Signature is not valid".to_string());
         }
 
+        let limits = state.config.read().await.content_limits().clone();
+        if let Err(e) = req.content.check_limits(&limits) {
+            return AkarekoProtocolResponse::invalid_argument(e.to_string());
+        }
+
+        if !state
+            .sync_policy(ctx)
+            .await
+            .contains(SyncPolicy::ACCEPT_CONTENT)
+        {
+            return AkarekoProtocolResponse::forbidden(
+                "Content from you isn't accepted".to_string(),
+            );
+        }
+
+        let source =
+            ContentSource::from_content(&req.content, ctx.address.clone(), SourceKind::Sent);
+
         match state.repositories.index().add_content(req.content).await {
             Ok(_) => {}
             Err(_) => return AkarekoProtocolResponse::internal_error("Database error".to_string()),
         };
 
+        if let Err(e) = state.repositories.add_content_source(source).await {
+            warn!("Failed to record content source: {}", e);
+        }
+
         AkarekoProtocolResponse::ok(PostContentResponse {})
     }
 }
@@ -0,0 +1,65 @@
+use rand::{RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::server::{
+    ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+    protocol::AkarekoProtocolResponse,
+};
+
+/// First half of telling who we are on this connection, hands out the nonce
+/// that [`Authenticate`](super::Authenticate) has to sign
+pub struct AuthChallenge;
+
+impl AkarekoProtocolCommand for AuthChallenge {
+    type RequestPayload = AuthChallengeRequest;
+    type ResponsePayload = AuthChallengeResponse;
+    type ResponseData = ();
+
+    async fn process(
+        _: Self::RequestPayload,
+        _: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let mut nonce = [0u8; 32];
+        OsRng.fill_bytes(&mut nonce);
+
+        ctx.auth_challenge = Some(nonce);
+
+        AkarekoProtocolResponse::ok(AuthChallengeResponse { nonce })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthChallengeRequest {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthChallengeResponse {
+    pub nonce: [u8; 32],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        fixtures::{broken_state, peer},
+        protocol::AkarekoStatus,
+    };
+
+    #[tokio::test]
+    async fn each_challenge_replaces_the_last() {
+        let state = broken_state();
+        let mut ctx = peer();
+
+        let first = AuthChallenge::process(AuthChallengeRequest {}, &state, &mut ctx).await;
+        assert_eq!(first.status(), &AkarekoStatus::Ok);
+        let first = first.payload().unwrap().nonce;
+
+        let second = AuthChallenge::process(AuthChallengeRequest {}, &state, &mut ctx)
+            .await
+            .payload()
+            .unwrap()
+            .nonce;
+        assert_ne!(first, second);
+        assert_eq!(ctx.auth_challenge, Some(second));
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::user::I2PAddress,
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+    types::{PrivateKey, PublicKey, Signature},
+};
+
+/// Second half of telling who we are, the peer signs the last challenge with
+/// its key. Once accepted the connection gets the sync policy of that key,
+/// whatever other users claim the same address, as long as it's a user we
+/// confirmed at that address.
+pub struct Authenticate;
+
+impl AkarekoProtocolCommand for Authenticate {
+    type RequestPayload = AuthenticateRequest;
+    type ResponsePayload = AuthenticateResponse;
+    type ResponseData = ();
+
+    async fn process(
+        req: Self::RequestPayload,
+        _: &ServerState,
+        ctx: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let Some(nonce) = ctx.auth_challenge.take() else {
+            return AkarekoProtocolResponse::invalid_argument("No challenge was asked".to_string());
+        };
+
+        if !req.verify(&nonce, &ctx.address) {
+            return AkarekoProtocolResponse::forbidden("Invalid signature".to_string());
+        }
+
+        ctx.peer = Some(req.pub_key);
+
+        AkarekoProtocolResponse::ok(AuthenticateResponse {})
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticateRequest {
+    pub_key: PublicKey,
+    signature: Signature,
+}
+
+impl AuthenticateRequest {
+    /// What's signed: the nonce and the address the requester connects from,
+    /// so a challenge forwarded from another connection can't be answered
+    fn signed_bytes(nonce: &[u8; 32], request_address: &I2PAddress) -> Vec<u8> {
+        let mut bytes = b"akareko/authenticate".to_vec();
+        bytes.extend(nonce);
+        bytes.extend(request_address.to_string().as_bytes());
+        bytes
+    }
+
+    pub fn new_signed(
+        nonce: &[u8; 32],
+        request_address: &I2PAddress,
+        priv_key: &PrivateKey,
+    ) -> Self {
+        Self {
+            pub_key: priv_key.public_key(),
+            signature: priv_key.sign(&Self::signed_bytes(nonce, request_address)),
+        }
+    }
+
+    pub fn verify(&self, nonce: &[u8; 32], request_address: &I2PAddress) -> bool {
+        self.pub_key
+            .verify(&Self::signed_bytes(nonce, request_address), &self.signature)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticateResponse {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::user::SyncPolicy,
+        server::{
+            fixtures::{PEER_ADDRESS, broken_state, peer, state, user, withhold_published},
+            protocol::AkarekoStatus,
+        },
+    };
+
+    const NONCE: [u8; 32] = [7; 32];
+
+    #[tokio::test]
+    async fn signed_challenge_identifies_the_peer() {
+        let priv_key = PrivateKey::new();
+        let mut ctx = peer();
+        ctx.auth_challenge = Some(NONCE);
+
+        let req =
+            AuthenticateRequest::new_signed(&NONCE, &I2PAddress::new(PEER_ADDRESS), &priv_key);
+        let res = Authenticate::process(req, &broken_state(), &mut ctx).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(ctx.peer, Some(priv_key.public_key()));
+        assert_eq!(ctx.auth_challenge, None);
+    }
+
+    /// Until then the peer only gets what every user at its address is allowed
+    #[tokio::test]
+    async fn authenticated_peer_gets_the_policy_of_its_key() {
+        let state = state().await;
+        withhold_published(&state).await;
+        let (other, priv_key) = user("Other", PEER_ADDRESS);
+        state.repositories.user().upsert_user(other).await.unwrap();
+        let mut ctx = peer();
+        assert!(
+            !state
+                .sync_policy(&ctx)
+                .await
+                .contains(SyncPolicy::SEND_PUBLISHED)
+        );
+
+        ctx.auth_challenge = Some(NONCE);
+        let req = AuthenticateRequest::new_signed(&NONCE, &ctx.address, &priv_key);
+        Authenticate::process(req, &state, &mut ctx).await;
+
+        assert_eq!(state.sync_policy(&ctx).await, SyncPolicy::all());
+    }
+
+    #[tokio::test]
+    async fn unknown_key_keeps_the_policy_of_the_address() {
+        let state = state().await;
+        withhold_published(&state).await;
+        let mut ctx = peer();
+
+        ctx.auth_challenge = Some(NONCE);
+        let req = AuthenticateRequest::new_signed(&NONCE, &ctx.address, &PrivateKey::new());
+        let res = Authenticate::process(req, &state, &mut ctx).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert!(
+            !state
+                .sync_policy(&ctx)
+                .await
+                .contains(SyncPolicy::SEND_PUBLISHED)
+        );
+    }
+
+    #[tokio::test]
+    async fn signature_for_another_address_is_refused() {
+        let mut ctx = peer();
+        ctx.auth_challenge = Some(NONCE);
+
+        let req = AuthenticateRequest::new_signed(
+            &NONCE,
+            &I2PAddress::new("other.b32.i2p"),
+            &PrivateKey::new(),
+        );
+        let res = Authenticate::process(req, &broken_state(), &mut ctx).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::Forbidden(_)));
+        assert_eq!(ctx.peer, None);
+    }
+
+    #[tokio::test]
+    async fn answer_without_challenge_is_invalid() {
+        let req = AuthenticateRequest::new_signed(
+            &NONCE,
+            &I2PAddress::new(PEER_ADDRESS),
+            &PrivateKey::new(),
+        );
+        let res = Authenticate::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InvalidArgument(_)));
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::user::Attestation,
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+};
+
+/// Who we vouch for, only attestations signed by our own key are served
+pub struct GetAttestations;
+
+impl AkarekoProtocolCommand for GetAttestations {
+    type RequestPayload = GetAttestationsRequest;
+    type ResponsePayload = GetAttestationsResponse;
+    type ResponseData = ();
+
+    async fn process(
+        _: Self::RequestPayload,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let pub_key = state.config.read().await.public_key().clone();
+
+        let attestations = match state
+            .repositories
+            .user()
+            .get_attestations_by(&pub_key)
+            .await
+        {
+            Ok(attestations) => attestations,
+            Err(_) => {
+                return AkarekoProtocolResponse::internal_error(
+                    "Failed to get attestations".to_string(),
+                );
+            }
+        };
+
+        AkarekoProtocolResponse::ok(Self::ResponsePayload { attestations })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetAttestationsRequest {}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetAttestationsResponse {
+    pub attestations: Vec<Attestation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::user::TrustLevel,
+        server::{
+            fixtures::{broken_state, peer, state, user},
+            protocol::AkarekoStatus,
+        },
+    };
+
+    #[tokio::test]
+    async fn serves_only_our_attestations() {
+        let state = state().await;
+        let (subject, other_key) = user("Subject", "subject.b32.i2p");
+        let ours = {
+            let config = state.config.read().await;
+            Attestation::new_signed(
+                subject.pub_key().clone(),
+                TrustLevel::Trusted,
+                config.private_key(),
+            )
+        };
+        let theirs = Attestation::new_signed(
+            state.config.read().await.public_key().clone(),
+            TrustLevel::Trusted,
+            &other_key,
+        );
+        for attestation in [ours.clone(), theirs] {
+            state
+                .repositories
+                .user()
+                .add_attestation(attestation)
+                .await
+                .unwrap();
+        }
+
+        let res = GetAttestations::process(GetAttestationsRequest {}, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        assert_eq!(res.payload().unwrap().attestations, vec![ours]);
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let res =
+            GetAttestations::process(GetAttestationsRequest {}, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
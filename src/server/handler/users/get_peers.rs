@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    db::user::{TrustLevel, User},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
+};
+
+/// Most users sent back by [`GetPeers`]
+pub const MAX_PEERS: usize = 50;
+
+/// A random sample of the users we know, so the peer can reach nodes it
+/// didn't know about. Only users whose address we confirmed are shared, each
+/// one carries its own signature over its address.
+pub struct GetPeers;
+
+impl AkarekoProtocolCommand for GetPeers {
+    type RequestPayload = GetPeersRequest;
+    type ResponsePayload = GetPeersResponse;
+    type ResponseData = ();
+
+    async fn process(
+        req: Self::RequestPayload,
+        state: &ServerState,
+        _: &mut ConnectionContext,
+    ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
+        let take = req.take.min(MAX_PEERS);
+        let users = match state
+            .repositories
+            .user()
+            .get_random_users(TrustLevel::Untrusted, take)
+            .await
+        {
+            Ok(users) => users,
+            Err(_) => {
+                return AkarekoProtocolResponse::internal_error("Failed to get users".to_string());
+            }
+        };
+
+        AkarekoProtocolResponse::ok(GetPeersResponse { users })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetPeersRequest {
+    /// Capped at [`MAX_PEERS`]
+    pub take: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetPeersResponse {
+    pub users: Vec<User>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        fixtures::{broken_state, peer, state, user},
+        protocol::AkarekoStatus,
+    };
+
+    #[tokio::test]
+    async fn shares_confirmed_users_only() {
+        let state = state().await;
+        let (confirmed, _) = user("Confirmed", "confirmed.b32.i2p");
+        let (mut unconfirmed, _) = user("Unconfirmed", "unconfirmed.b32.i2p");
+        unconfirmed.set_trust(TrustLevel::Unverified);
+        for user in [confirmed.clone(), unconfirmed] {
+            state.repositories.user().upsert_user(user).await.unwrap();
+        }
+
+        let req = GetPeersRequest { take: 10 };
+        let res = GetPeers::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let users = res.payload().unwrap().users;
+        assert_eq!(users, vec![confirmed]);
+        assert!(users[0].verify());
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = GetPeersRequest { take: 10 };
+        let res = GetPeers::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    db::user::{I2PAddress, User},
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
+    db::user::User,
+    server::{
+        ConnectionContext, ServerState,
+        handler::AkarekoProtocolCommand,
+        protocol::{AkarekoProtocolResponse, MAX_REQUEST_FRAME},
+    },
     types::PublicKey,
 };
 
@@ -13,10 +17,13 @@ impl AkarekoProtocolCommand for GetUsers {
     type ResponsePayload = GetUsersResponse;
     type ResponseData = ();
 
+    /// Carries the keys of every user asked for
+    const MAX_REQUEST_SIZE: u64 = MAX_REQUEST_FRAME as u64;
+
     async fn process(
         req: Self::RequestPayload,
         state: &ServerState,
-        _: &I2PAddress,
+        _: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
         let users = match state.repositories.user().get_users(req.pub_keys).await {
             Ok(users) => users,
@@ -40,3 +47,47 @@ pub struct GetUsersRequest {
 pub struct GetUsersResponse {
     pub users: Vec<User>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        server::{
+            fixtures::{broken_state, peer, state, user},
+            protocol::AkarekoStatus,
+        },
+        types::PrivateKey,
+    };
+
+    #[tokio::test]
+    async fn returns_known_users_only() {
+        let state = state().await;
+        let (known, _) = user("Known", "known.b32.i2p");
+        state
+            .repositories
+            .user()
+            .upsert_user(known.clone())
+            .await
+            .unwrap();
+
+        let req = GetUsersRequest {
+            pub_keys: vec![known.pub_key().clone(), PrivateKey::new().public_key()],
+        };
+        let res = GetUsers::process(req, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let users = res.payload().unwrap().users;
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].pub_key(), known.pub_key());
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let req = GetUsersRequest {
+            pub_keys: vec![PrivateKey::new().public_key()],
+        };
+        let res = GetUsers::process(req, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
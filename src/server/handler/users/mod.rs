@@ -1,4 +1,12 @@
+pub mod auth_challenge;
+pub mod authenticate;
+pub mod get_attestations;
+pub mod get_peers;
 pub mod get_users;
 pub mod who;
+pub use auth_challenge::AuthChallenge;
+pub use authenticate::Authenticate;
+pub use get_attestations::GetAttestations;
+pub use get_peers::GetPeers;
 pub use get_users::GetUsers;
 pub use who::Who;
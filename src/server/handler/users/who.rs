@@ -5,7 +5,10 @@ use crate::{
         ToBytes,
         user::{I2PAddress, User},
     },
-    server::{ServerState, handler::AkarekoProtocolCommand, protocol::AkarekoProtocolResponse},
+    server::{
+        ConnectionContext, ServerState, handler::AkarekoProtocolCommand,
+        protocol::AkarekoProtocolResponse,
+    },
     types::{PrivateKey, Signature, Timestamp},
 };
 
@@ -20,21 +23,25 @@ impl AkarekoProtocolCommand for Who {
     async fn process(
         _: Self::RequestPayload,
         state: &ServerState,
-        address: &I2PAddress,
+        ctx: &mut ConnectionContext,
     ) -> AkarekoProtocolResponse<Self::ResponsePayload, Self::ResponseData> {
         let response: Option<WhoResponse> = {
             let config = state.config.read().await;
             let user_pub_key = config.public_key();
             let priv_key = config.private_key();
-            match state
-                .repositories
-                .user()
-                .get_user(user_pub_key)
-                .await
-                .unwrap()
-            {
-                Some(user) => Some(WhoResponse::new_signed(user, &address, priv_key)),
-                None => None,
+            match state.repositories.user().get_user(user_pub_key).await {
+                Ok(Some(user)) => Some(WhoResponse::new_signed(
+                    user,
+                    state.now(),
+                    &ctx.address,
+                    priv_key,
+                )),
+                Ok(None) => None,
+                Err(_) => {
+                    return AkarekoProtocolResponse::internal_error(
+                        "Failed to get user".to_string(),
+                    );
+                }
             }
         };
 
@@ -63,10 +70,15 @@ impl WhoResponse {
         bytes
     }
 
-    pub fn new_signed(user: User, request_address: &I2PAddress, priv_key: &PrivateKey) -> Self {
+    pub fn new_signed(
+        user: User,
+        timestamp: Timestamp,
+        request_address: &I2PAddress,
+        priv_key: &PrivateKey,
+    ) -> Self {
         let mut response = Self {
             user: user.into(),
-            timestamp: Timestamp::now(),
+            timestamp,
             signature: Signature::empty(),
         };
 
@@ -81,3 +93,43 @@ impl WhoResponse {
         self.user.pub_key().verify(&bytes, &self.signature)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::AkarekoConfig,
+        db::Repositories,
+        server::{
+            fixtures::{PEER_ADDRESS, broken_state, peer, state},
+            protocol::AkarekoStatus,
+        },
+    };
+
+    #[tokio::test]
+    async fn signs_for_the_asking_address() {
+        let state = state().await;
+        let res = Who::process(WhoRequest {}, &state, &mut peer()).await;
+
+        assert_eq!(res.status(), &AkarekoStatus::Ok);
+        let who = res.payload().unwrap();
+        assert!(who.verify(&I2PAddress::new(PEER_ADDRESS)));
+        assert!(!who.verify(&I2PAddress::new("other.b32.i2p")));
+    }
+
+    #[tokio::test]
+    async fn missing_own_user_is_not_found() {
+        let state =
+            ServerState::for_tests(Repositories::in_memory().await, AkarekoConfig::default());
+        let res = Who::process(WhoRequest {}, &state, &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::NotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn database_failure_is_internal() {
+        let res = Who::process(WhoRequest {}, &broken_state(), &mut peer()).await;
+
+        assert!(matches!(res.status(), AkarekoStatus::InternalError(_)));
+    }
+}
@@ -1,34 +1,185 @@
-use std::io;
+use std::{
+    collections::HashSet,
+    io,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Instant,
+};
 
 use rclite::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info};
+use tokio::{
+    io::AsyncWriteExt as _,
+    sync::{RwLock, Semaphore},
+    time::timeout,
+};
+use tracing::{debug, error, info, warn};
 use yosemite::{Session, SessionOptions, style};
 
 use crate::{
     config::AkarekoConfig,
-    db::Repositories,
+    db::{
+        Repositories,
+        user::{I2PAddress, SyncPolicy, TrustLevel},
+    },
     errors::{DecodeError, ServerError},
-    helpers::{AkarekoRead as _, b32_from_pub_b64},
-    server::protocol::AkarekoProtocolVersion,
+    helpers::{AkarekoRead as _, AkarekoWrite as _, b32_from_pub_b64},
+    server::{
+        protocol::{AkarekoProtocolVersion, AkarekoStatus, with_wire_version, write_frame},
+        rate_limit::RateLimiter,
+        simulator::SimulatedStream,
+    },
+    types::{Hash, PublicKey, Timestamp},
 };
 
 pub mod client;
+#[cfg(test)]
+pub(crate) mod fixtures;
 mod handler;
+pub mod opds;
 pub mod protocol;
 pub mod proxy;
+mod rate_limit;
+pub mod simulator;
+
+pub struct AkarekoServer {
+    metrics: ServerMetrics,
+}
+
+/// Load of the server, shared with the UI
+#[derive(Debug, Clone, Default)]
+pub struct ServerMetrics {
+    active: std::sync::Arc<AtomicUsize>,
+    max: std::sync::Arc<AtomicUsize>,
+    accepted: std::sync::Arc<AtomicU64>,
+    rejected: std::sync::Arc<AtomicU64>,
+    evicted: std::sync::Arc<AtomicU64>,
+}
+
+impl ServerMetrics {
+    /// Peers connected right now
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn max(&self) -> usize {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    pub fn accepted(&self) -> u64 {
+        self.accepted.load(Ordering::Relaxed)
+    }
+
+    /// Peers turned away because every slot was taken
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
 
-pub struct AkarekoServer {}
+    /// Relayed records dropped to keep the database under its size limit
+    pub fn evicted(&self) -> u64 {
+        self.evicted.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_evicted(&self, count: usize) {
+        self.evicted.fetch_add(count as u64, Ordering::Relaxed);
+    }
+}
 
 #[derive(Clone)]
 struct ServerState {
     pub config: Arc<RwLock<AkarekoConfig>>,
     pub repositories: Repositories,
+    pub rate_limiter: Arc<RateLimiter>,
+}
+
+impl ServerState {
+    /// State handlers can be called with directly, without a server around
+    #[cfg(test)]
+    pub(crate) fn for_tests(repositories: Repositories, config: AkarekoConfig) -> Self {
+        Self {
+            rate_limiter: Arc::new(RateLimiter::new(config.rate_limit())),
+            config: Arc::new(RwLock::new(config)),
+            repositories,
+        }
+    }
+
+    /// Time according to the repositories' clock, so handlers and the
+    /// database agree on it
+    fn now(&self) -> Timestamp {
+        self.repositories.now()
+    }
+
+    /// What we agreed to exchange with the peer on the other end of `ctx`,
+    /// nothing if it can't be read. Once the peer authenticated as a user
+    /// whose address we confirmed to be this one it's the policy of its key,
+    /// otherwise of every user claiming its address. A key we don't know
+    /// can't be used to get around the policy of the address.
+    async fn sync_policy(&self, ctx: &ConnectionContext) -> SyncPolicy {
+        let users = self.repositories.user();
+        let policy = match &ctx.peer {
+            Some(pub_key) => match users.get_user(pub_key).await {
+                Ok(Some(user))
+                    if user.address() == &ctx.address && *user.trust() >= TrustLevel::Untrusted =>
+                {
+                    users.get_sync_policy(pub_key).await
+                }
+                Ok(_) => users.get_sync_policy_by_address(&ctx.address).await,
+                Err(e) => Err(e),
+            },
+            None => users.get_sync_policy_by_address(&ctx.address).await,
+        };
+
+        match policy {
+            Ok(policy) => policy,
+            Err(e) => {
+                warn!(
+                    "Failed to read sync policy of {}: {}",
+                    ctx.address.inner(),
+                    e
+                );
+                SyncPolicy::empty()
+            }
+        }
+    }
+}
+
+/// Lives as long as a peer stays connected and is shared by every command it
+/// sends, for anything that has to be remembered between them
+pub(crate) struct ConnectionContext {
+    pub address: I2PAddress,
+    pub connected_at: Instant,
+    pub commands_handled: u64,
+    /// Nonce handed out by the last group challenge, waiting for its proof
+    pub group_challenge: Option<[u8; 32]>,
+    /// Groups the peer proved to be part of
+    pub groups: HashSet<Hash>,
+    /// Nonce handed out by the last auth challenge, waiting to be signed
+    pub auth_challenge: Option<[u8; 32]>,
+    /// Key the peer proved to hold, none until it authenticates
+    pub peer: Option<PublicKey>,
+}
+
+impl ConnectionContext {
+    fn new(address: I2PAddress) -> Self {
+        Self {
+            address,
+            connected_at: Instant::now(),
+            commands_handled: 0,
+            group_challenge: None,
+            groups: HashSet::new(),
+            auth_challenge: None,
+            peer: None,
+        }
+    }
 }
 
 impl AkarekoServer {
     pub fn new() -> AkarekoServer {
-        AkarekoServer {}
+        AkarekoServer {
+            metrics: ServerMetrics::default(),
+        }
+    }
+
+    pub fn metrics(&self) -> ServerMetrics {
+        self.metrics.clone()
     }
 
     pub async fn run(
@@ -43,18 +194,73 @@ impl AkarekoServer {
         //     b64_to_b32_i2p(sam_session.destination()).unwrap()
         // );
 
+        let rate_limiter = Arc::new(RateLimiter::new(config.read().await.rate_limit()));
         let state = ServerState {
             config,
             repositories,
+            rate_limiter,
         };
 
+        let max_connections = state.config.read().await.max_server_connections() as usize;
+        let simulation = state.config.read().await.network_simulation();
+        let timeouts = state.config.read().await.timeouts().clone();
+        let slots = std::sync::Arc::new(Semaphore::new(max_connections));
+        self.metrics.max.store(max_connections, Ordering::Relaxed);
+
         while let Ok(mut stream) = sam_session.accept().await {
+            let Ok(permit) = slots.clone().try_acquire_owned() else {
+                self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                let wait = timeouts.request();
+                tokio::spawn(async move {
+                    // The status goes back in the version the peer speaks, a
+                    // V2 peer only reads frames
+                    let Ok(Ok(version)) =
+                        timeout(wait, AkarekoProtocolVersion::decode(&mut stream)).await
+                    else {
+                        return;
+                    };
+                    let busy = AkarekoStatus::Busy("Too many peers connected".to_string());
+                    let sent = match version {
+                        AkarekoProtocolVersion::V1 => busy.encode(&mut stream).await,
+                        AkarekoProtocolVersion::V2 => {
+                            let mut body = vec![];
+                            match busy.encode(&mut body).await {
+                                Ok(()) => write_frame(&mut stream, &body).await,
+                                Err(e) => Err(e),
+                            }
+                        }
+                    };
+                    if sent.is_ok() {
+                        let _ = stream.shutdown().await;
+                    }
+                });
+                continue;
+            };
+
+            self.metrics.accepted.fetch_add(1, Ordering::Relaxed);
+            self.metrics.active.fetch_add(1, Ordering::Relaxed);
+
             let state = state.clone();
+            let metrics = self.metrics.clone();
+            let simulation = simulation.clone();
+            let timeouts = timeouts.clone();
             tokio::spawn(async move {
+                let _permit = permit;
                 let address = b32_from_pub_b64(stream.remote_destination()).unwrap();
+                if let Err(e) = state.repositories.user().record_seen_at(&address).await {
+                    warn!("Failed to record {} as seen: {}", address, e);
+                }
+                let mut stream = SimulatedStream::new(stream, simulation);
+                let mut ctx = ConnectionContext::new(address);
 
                 loop {
-                    let version = match AkarekoProtocolVersion::decode(&mut stream).await {
+                    let decoded =
+                        timeout(timeouts.idle(), AkarekoProtocolVersion::decode(&mut stream)).await;
+                    let Ok(decoded) = decoded else {
+                        debug!("Dropping {}: idle for too long", ctx.address);
+                        break;
+                    };
+                    let version = match decoded {
                         Ok(v) => v,
                         Err(e) => match e {
                             DecodeError::IoError(e) => {
@@ -75,12 +281,53 @@ impl AkarekoServer {
                         },
                     };
 
-                    match version {
+                    let handled = match version {
+                        // The request can't be skipped without reading it,
+                        // so the connection goes with it
+                        AkarekoProtocolVersion::V1
+                            if !state.rate_limiter.try_acquire(&ctx.address, Instant::now()) =>
+                        {
+                            warn!("Dropping {}: too many requests", ctx.address);
+                            let limited = AkarekoStatus::TooManyRequests(
+                                "Too many requests, slow down".to_string(),
+                            );
+                            let _ = limited.encode(&mut stream).await;
+                            break;
+                        }
                         AkarekoProtocolVersion::V1 => {
-                            handler::V1::handle(&mut stream, &state, &address).await;
+                            let handled = with_wire_version(
+                                AkarekoProtocolVersion::V1,
+                                handler::V1::handle(&mut stream, &state, &mut ctx),
+                            );
+                            timeout(timeouts.request(), handled).await
+                        }
+                        AkarekoProtocolVersion::V2 => {
+                            let handled = handler::V2::handle(&mut stream, &state, &mut ctx);
+                            timeout(timeouts.request(), handled).await
                         }
+                    };
+                    // Whatever the peer was sent or sending is cut short, so
+                    // the stream can't be read from again
+                    let Ok(handled) = handled else {
+                        warn!("Dropping {}: request took too long", ctx.address);
+                        break;
+                    };
+                    // Only V2 can go on past a bad request, with V1 we no
+                    // longer know where the next one starts
+                    if let Err(e) = handled {
+                        warn!("Dropping {}: {}", ctx.address, e);
+                        break;
                     }
+                    ctx.commands_handled += 1;
                 }
+
+                debug!(
+                    "{} disconnected after {} commands in {:?}",
+                    ctx.address,
+                    ctx.commands_handled,
+                    ctx.connected_at.elapsed()
+                );
+                metrics.active.fetch_sub(1, Ordering::Relaxed);
             });
         }
 
@@ -0,0 +1,377 @@
+//! Read-only HTTP server exposing downloaded content as an OPDS catalog, so
+//! reader apps can browse it without knowing how `./data` is laid out.
+//!
+//! Only understands plain `GET` requests, everything else is refused.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio::{
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tracing::{error, info};
+
+use crate::{
+    db::{Repositories, torrent_link::TorrentLink},
+    errors::ServerError,
+    storage::sanitize_relative_path,
+};
+
+/// Requests with a bigger head than this are dropped
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+const NAVIGATION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const ACQUISITION_TYPE: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct OpdsConfig {
+    pub enabled: bool,
+    /// Only reachable from this machine by default, set to `0.0.0.0` to allow
+    /// devices on the LAN
+    pub bind_address: String,
+    pub port: u16,
+}
+
+impl Default for OpdsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 8090,
+        }
+    }
+}
+
+pub async fn run_opds_server(
+    config: OpdsConfig,
+    repositories: Repositories,
+) -> Result<(), ServerError> {
+    let listener = TcpListener::bind((config.bind_address.as_str(), config.port)).await?;
+    info!(
+        "OPDS catalog listening on http://{}:{}/opds",
+        config.bind_address, config.port
+    );
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let repositories = repositories.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &repositories).await {
+                error!("OPDS request failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    repositories: &Repositories,
+) -> std::io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || head.len() + read > MAX_REQUEST_HEAD {
+            return Ok(());
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let Some(path) = parse_request_line(head.lines().next().unwrap_or_default()) else {
+        return respond(&mut stream, 405, "text/plain", b"Method not allowed").await;
+    };
+
+    let segments: Vec<String> = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_decode)
+        .collect();
+
+    match segments
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [] | ["opds"] => {
+            let links = repositories.get_torrent_links().await.unwrap_or_default();
+            let feed = root_feed(&links);
+            respond(&mut stream, 200, NAVIGATION_TYPE, feed.as_bytes()).await
+        }
+        ["opds", info_hash] => match repositories.get_torrent_link(info_hash).await {
+            Ok(Some(link)) => {
+                let files = list_files(Path::new(&link.path)).await;
+                let feed = content_feed(&link, &files);
+                respond(&mut stream, 200, ACQUISITION_TYPE, feed.as_bytes()).await
+            }
+            _ => respond(&mut stream, 404, "text/plain", b"Not found").await,
+        },
+        ["files", info_hash, file @ ..] => {
+            let link = repositories
+                .get_torrent_link(info_hash)
+                .await
+                .ok()
+                .flatten();
+            match (link, sanitize_relative_path(file)) {
+                (Some(link), Some(relative)) => {
+                    serve_file(&mut stream, &Path::new(&link.path).join(relative)).await
+                }
+                _ => respond(&mut stream, 404, "text/plain", b"Not found").await,
+            }
+        }
+        _ => respond(&mut stream, 404, "text/plain", b"Not found").await,
+    }
+}
+
+/// Path of a `GET` request line, `None` for any other method
+fn parse_request_line(line: &str) -> Option<&str> {
+    let mut parts = line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+
+    let target = parts.next()?;
+    Some(target.split('?').next().unwrap_or(target))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn rfc3339(secs: i64) -> String {
+    OffsetDateTime::from_unix_timestamp(secs)
+        .ok()
+        .and_then(|t| t.format(&Rfc3339).ok())
+        .unwrap_or_default()
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    match extension.as_deref() {
+        Some("cbz") => "application/vnd.comicbook+zip",
+        Some("cbr") => "application/vnd.comicbook-rar",
+        Some("epub") => "application/epub+zip",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn feed(id: &str, title: &str, self_type: &str, self_href: &str, entries: &[String]) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:opds="http://opds-spec.org/2010/catalog">
+<id>urn:akareko:{}</id>
+<title>{}</title>
+<updated>{}</updated>
+<link rel="self" href="{}" type="{}"/>
+<link rel="start" href="/opds" type="{}"/>
+{}
+</feed>"#,
+        xml_escape(id),
+        xml_escape(title),
+        rfc3339(OffsetDateTime::now_utc().unix_timestamp()),
+        self_href,
+        self_type,
+        NAVIGATION_TYPE,
+        entries.join("\n")
+    )
+}
+
+fn root_feed(links: &[TorrentLink]) -> String {
+    let entries: Vec<String> = links
+        .iter()
+        .map(|link| {
+            format!(
+                r#"<entry><title>{}</title><id>urn:akareko:{}</id><updated>{}</updated><link rel="subsection" href="/opds/{}" type="{}"/></entry>"#,
+                xml_escape(&link.title),
+                xml_escape(&link.info_hash),
+                rfc3339(link.created_at.as_secs()),
+                percent_encode(&link.info_hash),
+                ACQUISITION_TYPE
+            )
+        })
+        .collect();
+
+    feed("library", "Akareko", NAVIGATION_TYPE, "/opds", &entries)
+}
+
+fn content_feed(link: &TorrentLink, files: &[PathBuf]) -> String {
+    let entries: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let href = file
+                .iter()
+                .map(|c| percent_encode(&c.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join("/");
+
+            format!(
+                r#"<entry><title>{}</title><id>urn:akareko:{}/{}</id><updated>{}</updated><link rel="http://opds-spec.org/acquisition" href="/files/{}/{}" type="{}"/></entry>"#,
+                xml_escape(&file.to_string_lossy()),
+                xml_escape(&link.info_hash),
+                xml_escape(&href),
+                rfc3339(link.created_at.as_secs()),
+                percent_encode(&link.info_hash),
+                href,
+                mime_type(file)
+            )
+        })
+        .collect();
+
+    feed(
+        &link.info_hash,
+        &link.title,
+        ACQUISITION_TYPE,
+        &format!("/opds/{}", percent_encode(&link.info_hash)),
+        &entries,
+    )
+}
+
+/// Every file under `root`, relative to it and sorted
+async fn list_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        let Ok(mut dir) = tokio::fs::read_dir(root.join(&relative)).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            let Ok(file_type) = entry.file_type().await else {
+                continue;
+            };
+
+            let path = relative.join(entry.file_name());
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+async fn serve_file(stream: &mut TcpStream, path: &Path) -> std::io::Result<()> {
+    let Ok(mut file) = tokio::fs::File::open(path).await else {
+        return respond(stream, 404, "text/plain", b"Not found").await;
+    };
+    let length = file.metadata().await?.len();
+
+    write_head(stream, 200, mime_type(path), length).await?;
+    tokio::io::copy(&mut file, stream).await?;
+    stream.shutdown().await
+}
+
+async fn respond(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    write_head(stream, status, content_type, body.len() as u64).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
+
+async fn write_head<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: u16,
+    content_type: &str,
+    length: u64,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Error",
+    };
+
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, reason, content_type, length
+    );
+    writer.write_all(head.as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_request_line, percent_decode, percent_encode};
+    use crate::storage::sanitize_relative_path;
+
+    #[test]
+    fn only_get_requests_are_parsed() {
+        assert_eq!(parse_request_line("GET /opds?x=1 HTTP/1.1"), Some("/opds"));
+        assert_eq!(parse_request_line("POST /opds HTTP/1.1"), None);
+        assert_eq!(parse_request_line(""), None);
+    }
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        let name = "Ch. 1 & 2/ページ.png";
+        assert_eq!(percent_decode(&percent_encode(name)), name);
+        // Encoded slashes must still be rejected after decoding
+        assert!(sanitize_relative_path(&[&percent_decode("..%2Fsecret")]).is_none());
+    }
+}
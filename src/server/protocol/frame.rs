@@ -0,0 +1,142 @@
+//! Framing of [`AkarekoProtocolVersion::V2`](super::AkarekoProtocolVersion):
+//! a request or response is encoded as usual into a body, sent after its
+//! length as a big endian u32. The whole body is read before decoding, so a
+//! malformed message fails alone and the next one starts at the right place.
+
+use std::{
+    io::{self, Cursor},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::errors::{DecodeError, EncodeError};
+
+/// Largest request body a server reads, bigger ones are skipped
+pub const MAX_REQUEST_FRAME: u32 = 1 << 20;
+/// Largest response body a client reads, bigger ones are skipped
+pub const MAX_RESPONSE_FRAME: u32 = 64 << 20;
+
+/// Reads the next frame's body. One over `max` is read past and refused, the
+/// stream is left at the start of the following frame.
+pub async fn read_frame<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    max: u32,
+) -> Result<Vec<u8>, DecodeError> {
+    let len = reader.read_u32().await?;
+    if len > max {
+        tokio::io::copy(&mut (&mut *reader).take(len as u64), &mut tokio::io::sink()).await?;
+        return Err(DecodeError::FrameTooLarge {
+            allowed: max,
+            actual: len,
+        });
+    }
+
+    let mut body = vec![0; len as usize];
+    reader.read_exact(&mut body).await?;
+    Ok(body)
+}
+
+pub async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    body: &[u8],
+) -> Result<(), EncodeError> {
+    let len = u32::try_from(body.len()).map_err(|_| EncodeError::TooManyElements {
+        allowed: u32::MAX as usize,
+        actual: body.len(),
+    })?;
+
+    writer.write_u32(len).await?;
+    writer.write_all(body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// A frame's body to decode from, with what's written to it kept aside for
+/// the frame sent back. Lets handlers written for a stream run on a frame.
+pub struct Frame {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(body: Vec<u8>) -> Self {
+        Self {
+            input: Cursor::new(body),
+            output: vec![],
+        }
+    }
+
+    /// Bytes of the body not decoded yet
+    pub fn remaining(&self) -> usize {
+        self.input.get_ref().len() - self.input.position() as usize
+    }
+
+    pub fn into_output(self) -> Vec<u8> {
+        self.output
+    }
+}
+
+impl AsyncRead for Frame {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.input).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Frame {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.output).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn oversized_frames_are_skipped() {
+        let mut bytes = vec![];
+        write_frame(&mut bytes, &[1; 16]).await.unwrap();
+        write_frame(&mut bytes, &[2; 4]).await.unwrap();
+
+        let mut reader = bytes.as_slice();
+        assert!(matches!(
+            read_frame(&mut reader, 8).await,
+            Err(DecodeError::FrameTooLarge {
+                allowed: 8,
+                actual: 16
+            })
+        ));
+        assert_eq!(read_frame(&mut reader, 8).await.unwrap(), vec![2; 4]);
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn frame_reads_its_body_and_keeps_writes() {
+        let mut frame = Frame::new(vec![1, 2, 3]);
+        let mut first = [0; 2];
+        frame.read_exact(&mut first).await.unwrap();
+        frame.write_all(&[9]).await.unwrap();
+
+        assert_eq!(first, [1, 2]);
+        assert_eq!(frame.remaining(), 1);
+        assert_eq!(frame.into_output(), vec![9]);
+    }
+}
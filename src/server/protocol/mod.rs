@@ -3,14 +3,43 @@ use tokio::io::{AsyncRead, AsyncWrite};
 
 use crate::{
     errors::{ClientError, DecodeError, EncodeError},
-    helpers::{AkarekoRead, AkarekoWrite},
+    helpers::{AkarekoRead, AkarekoWrite, decode_limited},
     server::handler::{AkarekoProtocolCommand, AkarekoProtocolCommandMetadata},
 };
 
+mod frame;
+mod peer_stream;
+mod wire_version;
+
+pub use frame::{Frame, MAX_REQUEST_FRAME, MAX_RESPONSE_FRAME, read_frame, write_frame};
+pub use peer_stream::PeerStream;
+pub use wire_version::{since_v2, wire_version, with_wire_version};
+
+/// Largest request payload a command decodes unless it sets its own
+pub const DEFAULT_MAX_REQUEST_SIZE: u64 = 64 << 10;
+/// Largest response payload, and item of response data, a command decodes
+/// unless it sets its own
+pub const DEFAULT_MAX_RESPONSE_SIZE: u64 = 16 << 20;
+
+/// Sent as its serde variant index, 0 for V1, not as its number
 #[repr(u8)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AkarekoProtocolVersion {
+    /// Requests and responses streamed field by field
     V1 = 1,
+    /// The commands of V1, each request and response in a length prefixed
+    /// frame, see [`frame`]
+    V2 = 2,
+}
+
+impl AkarekoProtocolVersion {
+    pub const LATEST: AkarekoProtocolVersion = AkarekoProtocolVersion::V2;
+}
+
+impl std::fmt::Display for AkarekoProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
 }
 
 #[derive(Debug)]
@@ -24,13 +53,22 @@ pub enum AkarekoStatus {
     NotFound(String),
     InvalidArgument(String),
     InternalError(String),
+    /// The peer isn't allowed to do this by our sync policy for it
+    Forbidden(String),
+    /// The node is at capacity, try again later
+    Busy(String),
+    /// The peer sent more requests than its rate limit allows, try again later
+    TooManyRequests(String),
 }
 
 impl AkarekoStatus {
     const OK_CODE: u16 = 200;
     const INTERNAL_ERROR_CODE: u16 = 500;
     const INVALID_ARGUMENT_CODE: u16 = 400;
+    const FORBIDDEN_CODE: u16 = 403;
     const NOT_FOUND_CODE: u16 = 404;
+    const TOO_MANY_REQUESTS_CODE: u16 = 429;
+    const BUSY_CODE: u16 = 503;
 
     pub fn is_ok(&self) -> bool {
         matches!(self, AkarekoStatus::Ok)
@@ -42,6 +80,9 @@ impl AkarekoStatus {
             AkarekoStatus::InvalidArgument(_) => Self::INVALID_ARGUMENT_CODE,
             AkarekoStatus::NotFound(_) => Self::NOT_FOUND_CODE,
             AkarekoStatus::InternalError(_) => Self::INTERNAL_ERROR_CODE,
+            AkarekoStatus::Forbidden(_) => Self::FORBIDDEN_CODE,
+            AkarekoStatus::Busy(_) => Self::BUSY_CODE,
+            AkarekoStatus::TooManyRequests(_) => Self::TOO_MANY_REQUESTS_CODE,
         }
     }
 }
@@ -64,6 +105,15 @@ impl AkarekoWrite for AkarekoStatus {
             AkarekoStatus::InternalError(message) => {
                 message.encode(writer).await?;
             }
+            AkarekoStatus::Forbidden(message) => {
+                message.encode(writer).await?;
+            }
+            AkarekoStatus::Busy(message) => {
+                message.encode(writer).await?;
+            }
+            AkarekoStatus::TooManyRequests(message) => {
+                message.encode(writer).await?;
+            }
         }
 
         Ok(())
@@ -91,6 +141,18 @@ impl AkarekoRead for AkarekoStatus {
                 let message = String::decode(reader).await?;
                 AkarekoStatus::InternalError(message)
             }
+            Self::FORBIDDEN_CODE => {
+                let message = String::decode(reader).await?;
+                AkarekoStatus::Forbidden(message)
+            }
+            Self::BUSY_CODE => {
+                let message = String::decode(reader).await?;
+                AkarekoStatus::Busy(message)
+            }
+            Self::TOO_MANY_REQUESTS_CODE => {
+                let message = String::decode(reader).await?;
+                AkarekoStatus::TooManyRequests(message)
+            }
             _ => {
                 return Err(DecodeError::InvalidEnumVariant {
                     enum_name: "AkarekoStatus",
@@ -111,15 +173,23 @@ enum Either<A, B> {
 // TODO: Later try to change the vec to a stream
 pub(super) struct StreamDecode<D: AkarekoRead + AkarekoWrite> {
     d: Either<Vec<D>, u64>,
+    /// Largest item decoded, in bytes
+    max_item: u64,
 }
 
 impl<D: AkarekoRead + AkarekoWrite> StreamDecode<D> {
     pub fn new(data: Vec<D>) -> Self {
-        Self { d: Either::A(data) }
+        Self {
+            d: Either::A(data),
+            max_item: DEFAULT_MAX_RESPONSE_SIZE,
+        }
     }
 
     pub fn new_receiver(len: u64) -> Self {
-        Self { d: Either::B(len) }
+        Self {
+            d: Either::B(len),
+            max_item: DEFAULT_MAX_RESPONSE_SIZE,
+        }
     }
 
     pub async fn next<R: AsyncRead + Unpin + Send>(
@@ -133,18 +203,33 @@ impl<D: AkarekoRead + AkarekoWrite> StreamDecode<D> {
                     Ok(None)
                 } else {
                     *len -= 1;
-                    Ok(Some(D::decode(reader).await?))
+                    Ok(Some(decode_limited(reader, self.max_item).await?))
                 }
             }
         }
     }
 
+    /// Lowers the size allowed for each item, for records with a tighter
+    /// limit than the command's
+    pub fn limit_items(&mut self, max: u64) {
+        self.max_item = self.max_item.min(max);
+    }
+
     pub fn len(&self) -> usize {
         match &self.d {
             Either::A(vec) => vec.len(),
             Either::B(len) => *len as usize,
         }
     }
+
+    /// What is about to be sent, empty on the receiving side
+    #[cfg(test)]
+    pub fn sent(&self) -> &[D] {
+        match &self.d {
+            Either::A(vec) => vec,
+            Either::B(_) => &[],
+        }
+    }
 }
 
 impl<D: AkarekoRead + AkarekoWrite> AkarekoWrite for StreamDecode<D> {
@@ -170,9 +255,114 @@ impl<D: AkarekoRead + AkarekoWrite> AkarekoWrite for StreamDecode<D> {
 
 impl<D: AkarekoRead + AkarekoWrite> AkarekoRead for StreamDecode<D> {
     async fn decode<R: AsyncRead + Unpin + Send>(reader: &mut R) -> Result<Self, DecodeError> {
-        Ok(StreamDecode {
-            d: Either::B(u64::decode(reader).await?),
-        })
+        Ok(StreamDecode::new_receiver(u64::decode(reader).await?))
+    }
+}
+
+/// Most items in one chunk of a chunked response
+pub const MAX_CHUNK_ITEMS: u32 = 256;
+
+/// Writes `items` as one chunk of a chunked response, a `u32` count followed
+/// by the items. Nothing is written for an empty slice, a zero count ends the
+/// response, see [`end_chunks`].
+pub(super) async fn encode_chunk<D: AkarekoWrite, W: AsyncWrite + Unpin + Send>(
+    items: &[D],
+    writer: &mut W,
+) -> Result<(), EncodeError> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    if items.len() > MAX_CHUNK_ITEMS as usize {
+        return Err(EncodeError::TooManyElements {
+            allowed: MAX_CHUNK_ITEMS as usize,
+            actual: items.len(),
+        });
+    }
+
+    (items.len() as u32).encode(writer).await?;
+    for item in items {
+        item.encode(writer).await?;
+    }
+
+    Ok(())
+}
+
+/// Ends a chunked response
+pub(super) async fn end_chunks<W: AsyncWrite + Unpin + Send>(
+    writer: &mut W,
+) -> Result<(), EncodeError> {
+    0u32.encode(writer).await
+}
+
+/// Receiving side of a response sent in chunks, for result sets too large to
+/// hold at once. Items are read one at a time, the sender doesn't say how
+/// many there are in total.
+pub(super) struct ChunkedStream<D: AkarekoRead> {
+    /// Items left in the chunk being read
+    left: u32,
+    done: bool,
+    /// Largest item decoded, in bytes
+    max_item: u64,
+    /// Over [`AkarekoProtocolVersion::V2`] each chunk comes in a frame of its
+    /// own, this is the one being read
+    frame: Option<Frame>,
+    framed: bool,
+    _data: std::marker::PhantomData<D>,
+}
+
+impl<D: AkarekoRead> ChunkedStream<D> {
+    pub fn new(max_item: u64) -> Self {
+        Self {
+            left: 0,
+            done: false,
+            max_item,
+            frame: None,
+            framed: false,
+            _data: std::marker::PhantomData,
+        }
+    }
+
+    /// Chunks sent in `version`, each in its own frame from
+    /// [`AkarekoProtocolVersion::V2`] on
+    pub fn for_version(version: AkarekoProtocolVersion, max_item: u64) -> Self {
+        Self {
+            framed: version >= AkarekoProtocolVersion::V2,
+            ..Self::new(max_item)
+        }
+    }
+
+    pub async fn next<R: AsyncRead + Unpin + Send>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<Option<D>, DecodeError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        if self.left == 0 {
+            if self.framed {
+                self.frame = Some(Frame::new(read_frame(reader, MAX_RESPONSE_FRAME).await?));
+            }
+            let count = match &mut self.frame {
+                Some(frame) => u32::decode(frame).await?,
+                None => u32::decode(reader).await?,
+            };
+            if count == 0 {
+                self.done = true;
+                return Ok(None);
+            }
+            if count > MAX_CHUNK_ITEMS {
+                return Err(DecodeError::InvalidData);
+            }
+            self.left = count;
+        }
+
+        self.left -= 1;
+        let item = match &mut self.frame {
+            Some(frame) => decode_limited(frame, self.max_item).await?,
+            None => decode_limited(reader, self.max_item).await?,
+        };
+        Ok(Some(item))
     }
 }
 
@@ -232,6 +422,14 @@ impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoProtoc
         }
     }
 
+    pub fn forbidden(message: String) -> Self {
+        Self {
+            status: AkarekoStatus::Forbidden(message),
+            payload: None,
+            data: StreamDecode::new(vec![]),
+        }
+    }
+
     pub fn status(&self) -> &AkarekoStatus {
         &self.status
     }
@@ -282,11 +480,14 @@ impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoWrite
     }
 }
 
-impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoRead
-    for AkarekoProtocolResponse<P, D>
-{
-    async fn decode<R: AsyncRead + Unpin + Send>(reader: &mut R) -> Result<Self, DecodeError> {
-        let status = AkarekoStatus::decode(reader).await?;
+impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoProtocolResponse<P, D> {
+    /// Decodes a response whose payload, and each item of its data, is at
+    /// most `max` bytes
+    pub async fn decode_limited<R: AsyncRead + Unpin + Send>(
+        reader: &mut R,
+        max: u64,
+    ) -> Result<Self, DecodeError> {
+        let status: AkarekoStatus = decode_limited(reader, max).await?;
 
         if !status.is_ok() {
             return Ok(AkarekoProtocolResponse {
@@ -296,8 +497,9 @@ impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoRead
             });
         }
 
-        let response = P::decode(reader).await?;
-        let data = StreamDecode::decode(reader).await?;
+        let response = decode_limited(reader, max).await?;
+        let mut data = StreamDecode::decode(reader).await?;
+        data.max_item = max;
         Ok(AkarekoProtocolResponse {
             status,
             payload: Some(response),
@@ -305,3 +507,134 @@ impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoRead
         })
     }
 }
+
+impl<P: AkarekoRead + AkarekoWrite, D: AkarekoRead + AkarekoWrite> AkarekoRead
+    for AkarekoProtocolResponse<P, D>
+{
+    async fn decode<R: AsyncRead + Unpin + Send>(reader: &mut R) -> Result<Self, DecodeError> {
+        Self::decode_limited(reader, DEFAULT_MAX_RESPONSE_SIZE).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::LazyLock;
+
+    use proptest::{collection::vec, prelude::*};
+    use tokio::runtime::Runtime;
+
+    use super::{
+        AkarekoProtocolVersion, AkarekoStatus, ChunkedStream, DEFAULT_MAX_RESPONSE_SIZE,
+        MAX_CHUNK_ITEMS, StreamDecode, encode_chunk, end_chunks,
+    };
+    use crate::{
+        helpers::{AkarekoRead, AkarekoWrite, assert_round_trip},
+        server::handler::CommandsV1,
+    };
+
+    /// The byte a peer reads the version from, not the enum's number
+    #[test]
+    fn versions_are_sent_as_their_variant_index() {
+        assert_eq!(
+            postcard::to_allocvec(&AkarekoProtocolVersion::V1).unwrap(),
+            vec![0]
+        );
+        assert_eq!(
+            postcard::to_allocvec(&AkarekoProtocolVersion::V2).unwrap(),
+            vec![1]
+        );
+    }
+
+    /// Proptest bodies aren't async and decoding needs a multi thread runtime
+    static RUNTIME: LazyLock<Runtime> = LazyLock::new(|| Runtime::new().unwrap());
+
+    fn status() -> impl Strategy<Value = AkarekoStatus> {
+        prop_oneof![
+            Just(AkarekoStatus::Ok),
+            any::<String>().prop_map(AkarekoStatus::NotFound),
+            any::<String>().prop_map(AkarekoStatus::InvalidArgument),
+            any::<String>().prop_map(AkarekoStatus::InternalError),
+            any::<String>().prop_map(AkarekoStatus::Forbidden),
+            any::<String>().prop_map(AkarekoStatus::Busy),
+            any::<String>().prop_map(AkarekoStatus::TooManyRequests),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn status_round_trips(status in status()) {
+            RUNTIME.block_on(assert_round_trip(status));
+        }
+
+        #[test]
+        fn stream_round_trips(values in vec(any::<u32>(), 0..64)) {
+            let decoded = RUNTIME.block_on(async {
+                let mut bytes = Vec::new();
+                StreamDecode::new(values.clone())
+                    .encode(&mut bytes)
+                    .await
+                    .unwrap();
+
+                let mut reader = bytes.as_slice();
+                let mut stream = StreamDecode::<u32>::decode(&mut reader).await.unwrap();
+                let mut decoded = vec![];
+                while let Some(value) = stream.next(&mut reader).await.unwrap() {
+                    decoded.push(value);
+                }
+                assert!(reader.is_empty());
+                decoded
+            });
+
+            prop_assert_eq!(decoded, values);
+        }
+
+        #[test]
+        fn chunks_round_trip(
+            values in vec(any::<u32>(), 0..MAX_CHUNK_ITEMS as usize * 3),
+            chunk_size in 1..=MAX_CHUNK_ITEMS as usize,
+        ) {
+            let decoded = RUNTIME.block_on(async {
+                let mut bytes = Vec::new();
+                for chunk in values.chunks(chunk_size) {
+                    encode_chunk(chunk, &mut bytes).await.unwrap();
+                }
+                encode_chunk::<u32, _>(&[], &mut bytes).await.unwrap();
+                end_chunks(&mut bytes).await.unwrap();
+
+                let mut reader = bytes.as_slice();
+                let mut stream = ChunkedStream::<u32>::new(DEFAULT_MAX_RESPONSE_SIZE);
+                let mut decoded = vec![];
+                while let Some(value) = stream.next(&mut reader).await.unwrap() {
+                    decoded.push(value);
+                }
+                assert!(reader.is_empty());
+                assert_eq!(stream.next(&mut reader).await.unwrap(), None);
+                decoded
+            });
+
+            prop_assert_eq!(decoded, values);
+        }
+
+        #[test]
+        fn command_ids_round_trip(id in any::<u32>()) {
+            let bytes = postcard::to_allocvec(&id).unwrap();
+            match CommandsV1::from_id(id) {
+                Some(command) => {
+                    prop_assert_eq!(postcard::to_allocvec(&command).unwrap(), bytes.clone());
+                    prop_assert_eq!(postcard::from_bytes::<CommandsV1>(&bytes).unwrap(), command);
+                }
+                None => prop_assert!(postcard::from_bytes::<CommandsV1>(&bytes).is_err()),
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn oversized_chunks_are_refused() {
+        let mut bytes = Vec::new();
+        (MAX_CHUNK_ITEMS + 1).encode(&mut bytes).await.unwrap();
+
+        let mut reader = bytes.as_slice();
+        let mut stream = ChunkedStream::<u32>::new(DEFAULT_MAX_RESPONSE_SIZE);
+        assert!(stream.next(&mut reader).await.is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::{AkarekoProtocolVersion, Frame, StreamDecode, with_wire_version};
+use crate::{
+    errors::DecodeError,
+    helpers::{AkarekoRead, AkarekoWrite},
+};
+
+/// Client end of a connection, requests go out in the version agreed with the
+/// peer. Over [`AkarekoProtocolVersion::V2`] the data of a response is read
+/// from its frame, so callers read from the stream either way.
+pub struct PeerStream<S> {
+    inner: S,
+    version: AkarekoProtocolVersion,
+    /// Response to the last V2 request
+    frame: Option<Frame>,
+}
+
+impl<S> PeerStream<S> {
+    pub fn new(inner: S, version: AkarekoProtocolVersion) -> Self {
+        Self {
+            inner,
+            version,
+            frame: None,
+        }
+    }
+
+    pub fn version(&self) -> AkarekoProtocolVersion {
+        self.version
+    }
+
+    pub(in crate::server) fn set_version(&mut self, version: AkarekoProtocolVersion) {
+        self.version = version;
+    }
+
+    /// Stream to write the next request to, what was left of the last
+    /// response is dropped
+    pub(in crate::server) fn start_request(&mut self) -> &mut S {
+        self.frame = None;
+        &mut self.inner
+    }
+
+    pub(in crate::server) fn set_response_frame(&mut self, frame: Frame) {
+        self.frame = Some(frame);
+    }
+
+    /// Drops the frame of the last response, for data sent after it in
+    /// frames of its own, see [`super::ChunkedStream::for_version`]
+    pub(in crate::server) fn end_response(&mut self) {
+        self.frame = None;
+    }
+
+    /// Reads the next item of a response's data, laid out for the version
+    /// it was sent in
+    pub(in crate::server) async fn next_item<D: AkarekoRead + AkarekoWrite>(
+        &mut self,
+        data: &mut StreamDecode<D>,
+    ) -> Result<Option<D>, DecodeError>
+    where
+        S: AsyncRead + Unpin + Send,
+    {
+        with_wire_version(self.version, data.next(self)).await
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for PeerStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match &mut this.frame {
+            Some(frame) => Pin::new(frame).poll_read(cx, buf),
+            None => Pin::new(&mut this.inner).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for PeerStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,91 @@
+//! Version of the connection a value is encoded for. Records sent since V1
+//! gained fields, a V1 peer still expects the bytes it always got, so those
+//! fields are left out while encoding or decoding in a V1 exchange. See
+//! [`since_v2`].
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::AkarekoProtocolVersion;
+
+tokio::task_local! {
+    static WIRE_VERSION: AkarekoProtocolVersion;
+}
+
+/// Version of the exchange being encoded or decoded, the latest outside of
+/// one, e.g. when writing to a file
+pub fn wire_version() -> AkarekoProtocolVersion {
+    WIRE_VERSION
+        .try_with(|version| *version)
+        .unwrap_or(AkarekoProtocolVersion::LATEST)
+}
+
+/// Runs `fut` with everything it encodes and decodes laid out for `version`
+pub async fn with_wire_version<F: Future>(version: AkarekoProtocolVersion, fut: F) -> F::Output {
+    WIRE_VERSION.scope(version, fut).await
+}
+
+/// Serde `with` for a field added after V1: nothing is written for it in a V1
+/// exchange and it's read back as its default. A V1 peer can't check a
+/// signature covering such a field, records that use it don't verify there.
+pub mod since_v2 {
+    use super::*;
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match wire_version() {
+            AkarekoProtocolVersion::V1 => serializer.serialize_unit(),
+            _ => value.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de> + Default, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        match wire_version() {
+            AkarekoProtocolVersion::V1 => {
+                <()>::deserialize(deserializer)?;
+                Ok(T::default())
+            }
+            _ => T::deserialize(deserializer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        kept: u8,
+        #[serde(with = "since_v2")]
+        added: Vec<u8>,
+    }
+
+    #[tokio::test]
+    async fn added_fields_are_left_out_of_v1() {
+        let record = Record {
+            kept: 7,
+            added: vec![1, 2],
+        };
+
+        let v1 = with_wire_version(AkarekoProtocolVersion::V1, async {
+            postcard::to_allocvec(&record).unwrap()
+        })
+        .await;
+        assert_eq!(v1, vec![7]);
+        let decoded = with_wire_version(AkarekoProtocolVersion::V1, async {
+            postcard::from_bytes::<Record>(&v1).unwrap()
+        })
+        .await;
+        assert_eq!(decoded.added, Vec::<u8>::new());
+
+        let latest = postcard::to_allocvec(&record).unwrap();
+        assert_eq!(latest, vec![7, 2, 1, 2]);
+        assert_eq!(postcard::from_bytes::<Record>(&latest).unwrap(), record);
+    }
+}
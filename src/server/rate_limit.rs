@@ -0,0 +1,99 @@
+//! Token bucket per peer address, kept across connections so reconnecting
+//! doesn't reset it. A request takes a token, tokens come back at a steady
+//! rate up to the burst size.
+
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use crate::{config::RateLimitConfig, db::user::I2PAddress};
+
+/// Peers tracked before the ones with a full bucket are forgotten, they'd
+/// start with a full one anyway
+const PRUNE_ABOVE: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: Mutex<HashMap<I2PAddress, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            burst: config.burst.max(1) as f64,
+            per_second: config.per_minute as f64 / 60.,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token from `address`'s bucket, `false` if it's empty and the
+    /// request should be refused
+    pub fn try_acquire(&self, address: &I2PAddress, now: Instant) -> bool {
+        if self.per_second == 0. {
+            return true;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_ABOVE {
+            buckets.retain(|_, b| self.refilled(b, now) < self.burst);
+        }
+
+        let bucket = buckets.entry(address.clone()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.refilled_at = now;
+
+        if bucket.tokens < 1. {
+            return false;
+        }
+        bucket.tokens -= 1.;
+        true
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn limiter(burst: u32, per_minute: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig { burst, per_minute })
+    }
+
+    #[test]
+    fn refuses_past_the_burst_until_refilled() {
+        let limiter = limiter(2, 60);
+        let peer = I2PAddress::new("peer.b32.i2p");
+        let other = I2PAddress::new("other.b32.i2p");
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire(&peer, now));
+        assert!(limiter.try_acquire(&peer, now));
+        assert!(!limiter.try_acquire(&peer, now));
+        assert!(limiter.try_acquire(&other, now));
+
+        assert!(limiter.try_acquire(&peer, now + Duration::from_secs(1)));
+        assert!(!limiter.try_acquire(&peer, now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn no_rate_means_no_limit() {
+        let limiter = limiter(1, 0);
+        let peer = I2PAddress::new("peer.b32.i2p");
+        let now = Instant::now();
+
+        assert!((0..10).all(|_| limiter.try_acquire(&peer, now)));
+    }
+}
@@ -0,0 +1,166 @@
+//! Dev mode wrapper around peer streams that makes the network worse on
+//! purpose, so timeouts and half finished exchanges can be tried without
+//! waiting for I2P to misbehave.
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::Sleep,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct NetworkSimulation {
+    /// Only used in dev mode
+    pub enabled: bool,
+    /// Added to every read and write
+    pub latency_ms: u64,
+    /// 0 means unlimited
+    pub bytes_per_second: u64,
+    /// Chance from 0 to 1 that any read or write drops the connection
+    pub disconnect_chance: f64,
+}
+
+impl Default for NetworkSimulation {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            latency_ms: 500,
+            bytes_per_second: 32 * 1024,
+            disconnect_chance: 0.001,
+        }
+    }
+}
+
+impl NetworkSimulation {
+    /// How long an operation moving `bytes` has to wait
+    fn delay_for(&self, bytes: usize) -> Duration {
+        let transfer = match self.bytes_per_second {
+            0 => Duration::ZERO,
+            rate => Duration::from_secs_f64(bytes as f64 / rate as f64),
+        };
+
+        Duration::from_millis(self.latency_ms) + transfer
+    }
+
+    fn should_disconnect(&self) -> bool {
+        self.disconnect_chance > 0. && rand::thread_rng().gen_bool(self.disconnect_chance.min(1.))
+    }
+}
+
+/// Passes everything through untouched unless a simulation is set
+pub struct SimulatedStream<S> {
+    inner: S,
+    simulation: Option<NetworkSimulation>,
+    read_delay: Option<Pin<Box<Sleep>>>,
+    write_delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> SimulatedStream<S> {
+    pub fn new(inner: S, simulation: Option<NetworkSimulation>) -> Self {
+        Self {
+            inner,
+            simulation,
+            read_delay: None,
+            write_delay: None,
+        }
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionReset, "simulated disconnect")
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for SimulatedStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(simulation) = &this.simulation else {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        };
+
+        // The delay is paid after the bytes arrive, so it's waited before the
+        // next read
+        if let Some(delay) = &mut this.read_delay {
+            ready!(delay.as_mut().poll(cx));
+            this.read_delay = None;
+        }
+
+        if simulation.should_disconnect() {
+            return Poll::Ready(Err(disconnected()));
+        }
+
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = buf.filled().len() - before;
+
+        this.read_delay = Some(Box::pin(tokio::time::sleep(simulation.delay_for(read))));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for SimulatedStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let Some(simulation) = &this.simulation else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+
+        if let Some(delay) = &mut this.write_delay {
+            ready!(delay.as_mut().poll(cx));
+            this.write_delay = None;
+        }
+
+        if simulation.should_disconnect() {
+            return Poll::Ready(Err(disconnected()));
+        }
+
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+
+        this.write_delay = Some(Box::pin(tokio::time::sleep(simulation.delay_for(written))));
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::NetworkSimulation;
+
+    #[test]
+    fn delay_includes_transfer_time() {
+        let simulation = NetworkSimulation {
+            enabled: true,
+            latency_ms: 100,
+            bytes_per_second: 1000,
+            disconnect_chance: 0.,
+        };
+
+        assert_eq!(simulation.delay_for(500), Duration::from_millis(600));
+        assert!(!simulation.should_disconnect());
+    }
+}
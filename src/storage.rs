@@ -0,0 +1,195 @@
+//! Where downloaded content lives on disk.
+//!
+//! Every folder is built from [`StorageConfig::template`] so the layout can be
+//! changed in one place, and anything that comes from other nodes (content
+//! sources, titles) goes through [`sanitize_source`] before touching the disk.
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::db::index::{
+    content::{Content, ContentType},
+    tags::IndexTag,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub data_dir: String,
+    /// Folder each content is downloaded to. Understands `{data_dir}`, `{tag}`,
+    /// `{index}` and `{signature}`.
+    pub template: String,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: "./data".to_string(),
+            template: "{data_dir}/{tag}/{index}/{signature}".to_string(),
+        }
+    }
+}
+
+impl StorageConfig {
+    /// Folder the torrent of `content` is downloaded to
+    pub fn content_dir<I: IndexTag, S: ContentType<I>>(&self, content: &Content<I, S>) -> PathBuf {
+        render(
+            &self.template,
+            &self.data_dir,
+            I::TAG,
+            &path_safe_base64(&content.index_hash().as_base64()),
+            &path_safe_base64(&content.signature().as_base64()),
+        )
+    }
+
+    /// File of `content` inside its folder, `None` if the signed source tries
+    /// to point outside of it
+    pub fn content_source<I: IndexTag>(&self, content: &Content<I>) -> Option<PathBuf> {
+        Some(
+            self.content_dir(content)
+                .join(sanitize_source(content.source())?),
+        )
+    }
+
+    pub fn torrents_dir(&self) -> PathBuf {
+        Path::new(&self.data_dir).join("torrents")
+    }
+}
+
+fn render(template: &str, data_dir: &str, tag: &str, index: &str, signature: &str) -> PathBuf {
+    template
+        .replace("{data_dir}", data_dir)
+        .replace("{tag}", tag)
+        .replace("{index}", index)
+        .replace("{signature}", signature)
+        .into()
+}
+
+/// Swaps the base64 characters that have a meaning in paths for the url-safe
+/// ones, so a hash is always a single folder
+pub fn path_safe_base64(b64: &str) -> String {
+    b64.replace('/', "_").replace('+', "-")
+}
+
+/// Relative path from a content source, which is chosen by whoever signed the
+/// content. Only plain names are kept, `None` if it tries to leave the content
+/// folder. An empty source is the folder itself.
+pub fn sanitize_source(source: &str) -> Option<PathBuf> {
+    let segments: Vec<&str> = source
+        .split(['/', '\\'])
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if segments.is_empty() {
+        return Some(PathBuf::new());
+    }
+
+    sanitize_relative_path(&segments)
+}
+
+/// Joins the segments if none of them can escape the content folder
+pub fn sanitize_relative_path(segments: &[&str]) -> Option<PathBuf> {
+    if segments.is_empty() {
+        return None;
+    }
+
+    let mut path = PathBuf::new();
+    for segment in segments {
+        if *segment == "." || *segment == ".." || segment.contains(['/', '\\', '\0']) {
+            return None;
+        }
+        // Catches things like `C:` on windows
+        let mut components = Path::new(segment).components();
+        if !matches!(components.next(), Some(Component::Normal(_))) || components.next().is_some() {
+            return None;
+        }
+        path.push(segment);
+    }
+
+    Some(path)
+}
+
+/// Moves a content folder to its new place. Nothing is done if there's nothing
+/// to move or the target is already taken, returns whether it moved.
+pub async fn migrate_dir(from: &Path, to: &Path) -> std::io::Result<bool> {
+    if from == to || !from.exists() || to.exists() {
+        return Ok(false);
+    }
+
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(from, to).await?;
+
+    Ok(true)
+}
+
+/// Total size of the files under `path`
+pub async fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    let mut dirs = vec![path.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn template_is_rendered() {
+        assert_eq!(
+            render(
+                "{data_dir}/{tag}/{index}/{signature}",
+                "./data",
+                "mangas",
+                "idx",
+                "sig"
+            ),
+            PathBuf::from("./data/mangas/idx/sig")
+        );
+        assert_eq!(path_safe_base64("a/b+c=="), "a_b-c==");
+    }
+
+    #[test]
+    fn sources_cannot_escape_the_content_folder() {
+        assert_eq!(
+            sanitize_source("vol 1/ch1.cbz"),
+            Some(PathBuf::from("vol 1").join("ch1.cbz"))
+        );
+        assert_eq!(sanitize_source(""), Some(PathBuf::new()));
+        assert_eq!(
+            sanitize_source("/etc/passwd"),
+            Some(PathBuf::from("etc").join("passwd"))
+        );
+        assert_eq!(sanitize_source("../../config.toml"), None);
+        assert_eq!(sanitize_source("a\\..\\..\\b"), None);
+        assert_eq!(sanitize_source("a/./b"), None);
+    }
+
+    #[test]
+    fn paths_cannot_escape_the_content_folder() {
+        assert_eq!(
+            sanitize_relative_path(&["vol 1", "01.png"]),
+            Some(PathBuf::from("vol 1").join("01.png"))
+        );
+        assert_eq!(sanitize_relative_path(&["..", "config.toml"]), None);
+        assert_eq!(sanitize_relative_path(&["a\\..\\b"]), None);
+        assert_eq!(sanitize_relative_path(&[]), None);
+    }
+}
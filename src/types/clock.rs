@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use crate::types::Timestamp;
+
+/// Where the current time comes from. Repositories and handlers ask their
+/// clock instead of the system so time dependent logic can be tested.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Timestamp;
+}
+
+/// The system time, used everywhere outside of tests
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+}
+
+/// Stays at the time it's set to until moved
+#[derive(Debug, Default)]
+pub struct ManualClock(AtomicI64);
+
+impl ManualClock {
+    pub fn new(now: Timestamp) -> Self {
+        Self(AtomicI64::new(now.as_secs()))
+    }
+
+    pub fn set(&self, now: Timestamp) {
+        self.0.store(now.as_secs(), Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, secs: i64) {
+        self.0.fetch_add(secs, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::new(self.0.load(Ordering::Relaxed))
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for std::sync::Arc<C> {
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
@@ -11,8 +11,9 @@ use surrealdb::types::{SerializationError, SurrealValue};
 use zeroize::ZeroizeOnDrop;
 
 use crate::errors::Base64Error;
+use crate::types::secret::REDACTED;
 
-#[derive(Serialize, Deserialize, Debug, Clone, ZeroizeOnDrop, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, ZeroizeOnDrop, PartialEq)]
 #[serde(transparent)]
 pub struct PrivateKey(#[serde(with = "serde_bytes")] [u8; 32]);
 
@@ -192,6 +193,18 @@ impl SurrealValue for Signature {
     }
 }
 
+impl std::fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PrivateKey({})", REDACTED)
+    }
+}
+
+impl Display for PrivateKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", REDACTED)
+    }
+}
+
 impl PrivateKey {
     pub fn new() -> Self {
         let mut csprng = OsRng;
@@ -200,6 +213,19 @@ impl PrivateKey {
         PrivateKey(signing_key.to_bytes())
     }
 
+    /// Same seed, same key. Anyone who knows the seed has the key, so this is
+    /// only for fixtures and demo data.
+    #[cfg(any(test, feature = "fixtures"))]
+    pub fn from_seed(seed: &str) -> Self {
+        use sha2::Digest;
+
+        let mut bytes = b"akareko-seed:".to_vec();
+        bytes.extend(seed.as_bytes());
+        let hash = sha2::Sha512::digest(&bytes);
+
+        PrivateKey(hash[..32].try_into().unwrap())
+    }
+
     pub fn sign(&self, msg: &[u8]) -> Signature {
         let mut signing_key = ed25519_dalek::SigningKey::from_bytes(&self.0);
         let signature = signing_key.sign(msg);
@@ -266,6 +292,22 @@ impl PublicKey {
     pub unsafe fn from_bytes_unchecked(bytes: [u8; 32]) -> Self {
         PublicKey(bytes)
     }
+
+    /// Whether the bytes are a usable ed25519 key, nothing it signed would
+    /// verify otherwise
+    pub fn is_valid(&self) -> bool {
+        ed25519_dalek::VerifyingKey::from_bytes(&self.0).is_ok()
+    }
+
+    /// Short, human comparable form of the key. Names are not unique so this
+    /// should be shown next to them whenever a user is displayed.
+    pub fn fingerprint(&self) -> String {
+        self.0[..6]
+            .chunks(2)
+            .map(|c| format!("{:02x}{:02x}", c[0], c[1]))
+            .collect::<Vec<_>>()
+            .join("-")
+    }
 }
 
 impl Display for PublicKey {
@@ -0,0 +1,114 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::types::{Hash, PublicKey};
+
+/// Points at a record by its id, written as `akareko://index/<hash>` or
+/// `akareko://user/<public key>` so it can be pasted around, in posts
+/// included, and opened by anyone who has the record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Link {
+    Index(Hash),
+    User(PublicKey),
+}
+
+impl Link {
+    pub const SCHEME: &'static str = "akareko://";
+
+    /// Every link in `text`, in order and without repeats. A link ends at
+    /// the first character that can't be part of an id, so punctuation
+    /// right after one is left out.
+    pub fn find_all(text: &str) -> Vec<Link> {
+        let mut links = vec![];
+        for (start, _) in text.match_indices(Self::SCHEME) {
+            let rest = &text[start..];
+            let after_kind = rest[Self::SCHEME.len()..]
+                .find('/')
+                .map_or(rest.len(), |i| Self::SCHEME.len() + i + 1);
+            let len = rest[after_kind..]
+                .find(|c| !is_id_char(c))
+                .map_or(rest.len(), |i| after_kind + i);
+
+            if let Ok(link) = rest[..len].parse()
+                && !links.contains(&link)
+            {
+                links.push(link);
+            }
+        }
+        links
+    }
+}
+
+/// Ids are base64, url safe for hashes and standard for keys
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '/')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidLink;
+
+impl Display for InvalidLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not an {} link", Link::SCHEME)
+    }
+}
+
+impl FromStr for Link {
+    type Err = InvalidLink;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let path = s.trim().strip_prefix(Self::SCHEME).ok_or(InvalidLink)?;
+
+        match path.split_once('/').ok_or(InvalidLink)? {
+            ("index", id) => Hash::from_base64(id)
+                .map(Link::Index)
+                .map_err(|_| InvalidLink),
+            ("user", id) => PublicKey::from_base64(id)
+                .map(Link::User)
+                .map_err(|_| InvalidLink),
+            _ => Err(InvalidLink),
+        }
+    }
+}
+
+impl Display for Link {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Link::Index(hash) => write!(f, "{}index/{}", Self::SCHEME, hash.as_base64()),
+            Link::User(pub_key) => write!(f, "{}user/{}", Self::SCHEME, pub_key.to_base64()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PrivateKey;
+
+    fn links() -> [Link; 2] {
+        [
+            Link::Index(Hash::digest(b"index")),
+            Link::User(PrivateKey::from_seed("user").public_key()),
+        ]
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        for link in links() {
+            assert_eq!(link.to_string().parse(), Ok(link));
+        }
+        assert_eq!("akareko://index/short".parse::<Link>(), Err(InvalidLink));
+        assert_eq!("akareko://post/abc".parse::<Link>(), Err(InvalidLink));
+        assert_eq!("https://index/abc".parse::<Link>(), Err(InvalidLink));
+    }
+
+    #[test]
+    fn found_in_text_without_trailing_punctuation() {
+        let [index, user] = links();
+        let text = format!(
+            "Read ({}), by {}. Again: {} and akareko://index/broken",
+            index, user, index
+        );
+
+        assert_eq!(Link::find_all(&text), vec![index, user]);
+    }
+}
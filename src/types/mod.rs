@@ -8,11 +8,17 @@ use surrealdb_types::{SerializationError, SurrealValue};
 
 use crate::errors::Base64Error;
 
+mod clock;
 mod keys;
+mod link;
+mod secret;
 mod string;
 mod timestamp;
 mod topic;
+pub use clock::{Clock, ManualClock, SystemClock};
 pub use keys::{PrivateKey, PublicKey, Signable, Signature};
+pub use link::{InvalidLink, Link};
+pub use secret::Secret;
 pub use timestamp::Timestamp;
 pub use topic::Topic;
 
@@ -47,7 +47,7 @@ impl std::ops::Sub<Timestamp> for Timestamp {
 }
 
 impl Timestamp {
-    pub fn new(timestamp: i64) -> Self {
+    pub const fn new(timestamp: i64) -> Self {
         Timestamp(timestamp)
     }
 
@@ -59,6 +59,24 @@ impl Timestamp {
                 .as_secs() as i64,
         )
     }
+
+    /// Seconds since the unix epoch
+    pub fn as_secs(&self) -> i64 {
+        self.0
+    }
+
+    /// `YYYY-MM-DD hh:mm` in UTC, falls back to the raw seconds if out of range
+    pub fn format_date(&self) -> String {
+        let Ok(format) = time::format_description::parse("[year]-[month]-[day] [hour]:[minute]")
+        else {
+            return self.0.to_string();
+        };
+
+        time::OffsetDateTime::from_unix_timestamp(self.0)
+            .ok()
+            .and_then(|t| t.format(&format).ok())
+            .unwrap_or_else(|| self.0.to_string())
+    }
 }
 
 impl ToBytes for Timestamp {
@@ -1,40 +1,775 @@
-use anawt::{TorrentClient, options::AnawtOptions};
+use std::{collections::HashSet, path::Path, time::Duration};
+
+use anawt::{
+    AnawtTorrentStatus, InfoHash, RemoveFlags, SettingsPack, TorrentClient, TorrentState,
+    options::AnawtOptions,
+};
 use emissary_core::{Config, Ntcp2Config, SamConfig, Ssu2Config, TransitConfig, router::Router};
 use emissary_util::{
     reseeder::Reseeder,
     runtime::tokio::Runtime,
     storage::{Storage, StorageBundle},
 };
-use freya::radio::RadioStation;
-use tokio::sync::RwLock;
-use tracing::error;
+use freya::{query::QueriesStorage, radio::RadioStation};
+use tokio::sync::{RwLock, broadcast, watch};
+use tracing::{error, info, warn};
 use yosemite::{RouterApi, Session, style};
 
 use crate::{
-    config::AkarekoConfig,
-    db::{Repositories, user::I2PAddress},
-    helpers::b32_from_pub_b64,
+    config::{AkarekoConfig, RelayOnlyConfig},
+    db::{
+        DATABASE_PATH, Magnet, Repositories,
+        changes::DataKind,
+        follow_index::NotificationPreference,
+        index::{
+            content::{Content, is_valid_web_seed},
+            manifest::{ManifestCheck, verify_manifest},
+            release::Release,
+            tags::{IndexTag, MangaTag},
+        },
+        torrent_link::TorrentLink,
+        user::{I2PAddress, TrustLevel, User},
+    },
+    diagnostics::{CheckStatus, run_diagnostics},
+    errors::DatabaseError,
+    helpers::{b32_from_pub_b64, cbz::count_pages},
     server::{
-        AkarekoServer,
+        AkarekoServer, ServerMetrics,
         client::{AkarekoClient, pool::ClientPool},
+        opds::run_opds_server,
+    },
+    storage::{StorageConfig, dir_size, migrate_dir, sanitize_source},
+    types::{Hash, PublicKey, Timestamp},
+    ui::{
+        AppChannel, AppState, ResourceState,
+        link_handler::ExternalLink,
+        queries::{
+            FetchAuditLog, FetchContentSources, FetchContents, FetchDisplayName, FetchHistory,
+            FetchIndex, FetchIndexes, FetchInfoHashConflicts, FetchLibraryStats, FetchMuted,
+            FetchPeerStats, FetchPetnames, FetchPopularIndexes, FetchPopularity, FetchSuppressions,
+            FetchTorrentLinks, FetchUser, FetchUserList, FetchUsers, FetchVouchers,
+            GetFollowContent, ResolveMentions,
+        },
     },
-    ui::{AppChannel, AppState, ResourceState},
 };
 
 pub enum Event {
     RemoveMainWindow,
+    OpenLink(ExternalLink),
 }
 
 enum LoadEvent {
     LoadedClient(ClientPool),
+    ConnectedSam(SamSessions),
+}
+
+/// The primary session has to outlive the subsessions made from it
+struct SamSessions {
+    primary: Session<style::Primary>,
+    client: Session<style::Stream>,
+    server: Session<style::Stream>,
 }
 
 pub struct AppManager {
     client_thread: Option<tokio::task::JoinHandle<()>>,
+    /// Restarted along with the client, see [`republish_own_content`]
+    republish_task: Option<tokio::task::JoinHandle<()>>,
+    sam_session: Option<Session<style::Primary>>,
     radio_station: RadioStation<AppState, AppChannel>,
     load_tx: tokio::sync::mpsc::UnboundedSender<LoadEvent>,
     load_rx: tokio::sync::mpsc::UnboundedReceiver<LoadEvent>,
     rx: tokio::sync::mpsc::UnboundedReceiver<Event>,
+    /// Posts stored up to here were already looked at for mentions
+    mentions_checked: Timestamp,
+}
+
+/// Waits until the torrent finishes downloading, then records it on its link
+/// and content. `info_hash` is the link's key, see [`Magnet::info_hash`].
+pub async fn watch_torrent_completion(
+    mut watcher: watch::Receiver<AnawtTorrentStatus>,
+    info_hash: String,
+    repositories: Repositories,
+) {
+    loop {
+        let state = watcher.borrow_and_update().state.clone();
+
+        match state {
+            TorrentState::Finished | TorrentState::Seeding => {
+                match repositories.get_torrent_link(&info_hash).await {
+                    Ok(Some(link)) => {
+                        info!("Finished downloading {}", link.title);
+                        finish_download(link, &repositories).await;
+                    }
+                    Ok(None) => {}
+                    Err(e) => error!("Failed to get the link of {}: {}", info_hash, e),
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        if watcher.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// How long a download may sit without receiving anything before the
+/// publisher's web seed is tried
+const WEB_SEED_GRACE: Duration = Duration::from_secs(120);
+
+/// libtorrent's `proxy_type` for a SAM bridge
+const I2P_PROXY: i32 = 6;
+
+/// Session settings of the torrent client. With
+/// [`AkarekoConfig::i2p_only_torrents`] peers are only reached through the
+/// SAM bridge: DHT, local discovery and port mapping are off, nothing listens
+/// on clearnet and I2P torrents don't mix in clearnet peers.
+fn torrent_options(config: &AkarekoConfig) -> AnawtOptions {
+    if !config.i2p_only_torrents() {
+        return AnawtOptions::new();
+    }
+
+    let mut settings_pack = SettingsPack::new();
+    for name in ["enable_dht", "enable_lsd", "enable_upnp", "enable_natpmp"] {
+        settings_pack.set_bool(name, false);
+    }
+    settings_pack.set_bool("allow_i2p_mixed", false);
+    settings_pack.set_bool("anonymous_mode", true);
+    settings_pack.set_str("listen_interfaces", "");
+    settings_pack.set_int("proxy_type", I2P_PROXY);
+    settings_pack.set_str("i2p_hostname", "127.0.0.1");
+    settings_pack.set_int("i2p_port", config.sam_tcp_port() as i32);
+
+    AnawtOptions::new().settings_pack(settings_pack)
+}
+
+/// The magnet as it's handed to the torrent client, without the trackers and
+/// sources outside I2P when `i2p_only` is on
+pub(crate) fn client_magnet(magnet: &Magnet, i2p_only: bool) -> Magnet {
+    match i2p_only {
+        true => magnet.scrub_trackers(&[]).0,
+        false => magnet.clone(),
+    }
+}
+
+/// Hands the content's web seed to the torrent client once it's clear the
+/// swarm has no seeds for it. The torrent is added back with the URL as a
+/// BEP 19 `ws` parameter, keeping whatever was already on disk. With a
+/// `link`, the re-added torrent is watched for completion in place of the
+/// removed one, see [`watch_torrent_completion`].
+pub async fn web_seed_fallback(
+    client: TorrentClient,
+    info_hash: InfoHash,
+    magnet: Magnet,
+    path: String,
+    web_seed: String,
+    link: Option<String>,
+    repositories: Repositories,
+) {
+    if !is_valid_web_seed(&web_seed) {
+        warn!("Ignoring web seed outside I2P: {}", web_seed);
+        return;
+    }
+
+    tokio::time::sleep(WEB_SEED_GRACE).await;
+
+    // Removed in the meantime or the swarm is delivering
+    let Some(status) = client.get_status(info_hash).await else {
+        return;
+    };
+    if status.progress > 0.0
+        || matches!(status.state, TorrentState::Finished | TorrentState::Seeding)
+    {
+        return;
+    }
+
+    info!("No seeds for {}, falling back to {}", status.name, web_seed);
+    if let Err(e) = client.remove_torrent(info_hash, RemoveFlags::empty()).await {
+        error!(
+            "Failed to remove {} to add its web seed: {:?}",
+            status.name, e
+        );
+        return;
+    }
+
+    let encoded: String = url::form_urlencoded::byte_serialize(web_seed.as_bytes()).collect();
+    let magnet = format!("{}&ws={}", magnet.0, encoded);
+    let info_hash = match client.add_magnet(&magnet, &path).await {
+        Ok(info_hash) => info_hash,
+        Err(e) => {
+            error!("Failed to add {} with its web seed: {:?}", status.name, e);
+            return;
+        }
+    };
+
+    // The watcher of the removed torrent stopped along with it
+    if let Some(link) = link
+        && let Some(watcher) = client
+            .subscribe_all()
+            .await
+            .into_iter()
+            .find(|w| w.borrow().info_hash == info_hash)
+    {
+        tokio::spawn(watch_torrent_completion(watcher, link, repositories));
+    }
+}
+
+/// Marks the link as completed and goes through the downloaded files, see
+/// [`check_download`]
+async fn finish_download(mut link: TorrentLink, repositories: &Repositories) {
+    // Already handled on a previous run
+    if link.completed_at.is_some() || link.tag != MangaTag::TAG {
+        return;
+    }
+
+    let content = match repositories
+        .index()
+        .get_contents::<MangaTag>(std::slice::from_ref(&link.content))
+        .await
+    {
+        Ok(contents) => contents.into_iter().next(),
+        Err(e) => {
+            error!("Failed to get content for {}: {}", link.title, e);
+            return;
+        }
+    };
+
+    link.completed_at = Some(repositories.now());
+    if let Some(content) = content {
+        match sanitize_source(content.source()) {
+            Some(source) => {
+                let source = Path::new(&link.path).join(source);
+                check_download(&mut link, &content, &source, repositories).await;
+            }
+            None => error!("{} points outside of its folder", link.title),
+        }
+    }
+
+    if let Err(e) = repositories.upsert_torrent_link(link).await {
+        error!("Failed to save the finished download: {}", e);
+    }
+}
+
+/// Saves the number of pages downloaded for `content` and checks the files
+/// against its manifest
+async fn check_download(
+    link: &mut TorrentLink,
+    content: &Content<MangaTag>,
+    source: &Path,
+    repositories: &Repositories,
+) {
+    // Progress is counted in pages, the reader would otherwise only learn how
+    // many there are once the chapter is opened
+    match count_pages(source).await {
+        Ok(pages) if pages > 0 => {
+            if let Err(e) = repositories
+                .index()
+                .update_content_count::<MangaTag>(content.signature().clone(), pages as u32)
+                .await
+            {
+                error!("Failed to save the page count of {}: {}", link.title, e);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to count the pages of {}: {}", link.title, e),
+    }
+
+    if content.manifest.is_empty() {
+        return;
+    }
+
+    let check = verify_manifest(source, &content.manifest).await;
+    match &check {
+        ManifestCheck::Verified => info!("Verified files of {}", link.title),
+        ManifestCheck::Missing(path) => error!("{} is missing {}", link.title, path),
+        ManifestCheck::Mismatch(path) => {
+            error!("{} of {} doesn't match the manifest", path, link.title)
+        }
+    }
+    link.verified = Some(check.is_verified());
+}
+
+/// Moves downloaded content still in an old layout to the folder the storage
+/// template gives it. Torrents are taken out of the client while their files
+/// move and added back at the new place, where they only need a recheck.
+async fn migrate_content_dirs(
+    storage: &StorageConfig,
+    i2p_only: bool,
+    torrent_client: &TorrentClient,
+    repositories: &Repositories,
+) {
+    let links = match repositories.get_torrent_links().await {
+        Ok(links) => links,
+        Err(e) => {
+            error!("Failed to get torrent links: {}", e);
+            return;
+        }
+    };
+
+    for mut link in links {
+        if link.tag != MangaTag::TAG {
+            continue;
+        }
+
+        let content = match repositories
+            .index()
+            .get_contents::<MangaTag>(std::slice::from_ref(&link.content))
+            .await
+        {
+            Ok(contents) => match contents.into_iter().next() {
+                Some(c) => c,
+                None => continue,
+            },
+            Err(e) => {
+                error!("Failed to get content for {}: {}", link.title, e);
+                continue;
+            }
+        };
+
+        let target = storage.content_dir(&content);
+        let current = std::path::PathBuf::from(&link.path);
+        if current == target || !current.exists() || target.exists() {
+            continue;
+        }
+
+        let info_hash = InfoHash::from_magnet(&link.magnet.0).ok();
+        let mut was_added = false;
+        if let Some(info_hash) = info_hash
+            && torrent_client.get_status(info_hash).await.is_some()
+        {
+            if let Err(e) = torrent_client
+                .remove_torrent(info_hash, RemoveFlags::empty())
+                .await
+            {
+                error!("Failed to pause {} for moving: {:?}", link.title, e);
+                continue;
+            }
+            was_added = true;
+        }
+
+        match migrate_dir(&current, &target).await {
+            Ok(true) => {
+                info!("Moved {} to {}", link.title, target.display());
+                link.path = target.to_string_lossy().to_string();
+                if let Err(e) = repositories.upsert_torrent_link(link.clone()).await {
+                    error!("Failed to update torrent link: {}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => error!("Failed to move {}: {}", link.title, e),
+        }
+
+        let magnet = client_magnet(&link.magnet, i2p_only);
+        if was_added && let Err(e) = torrent_client.add_magnet(&magnet.0, &link.path).await {
+            error!("Failed to add {} back: {:?}", link.title, e);
+        }
+    }
+}
+
+/// Time the router gets to open SAM before the startup diagnostics
+const DIAGNOSTICS_DELAY: Duration = Duration::from_secs(15);
+
+/// Runs the diagnostics once the router had a moment to open SAM, failures are
+/// logged and shown as a toast pointing to the settings
+async fn report_diagnostics(
+    config: AkarekoConfig,
+    repositories: Repositories,
+    radio_station: RadioStation<AppState, AppChannel>,
+) {
+    tokio::time::sleep(DIAGNOSTICS_DELAY).await;
+
+    let checks = run_diagnostics(&config, Some(&repositories)).await;
+
+    let mut failed = Vec::new();
+    for check in &checks {
+        match &check.status {
+            CheckStatus::Passed => {}
+            CheckStatus::Warning(detail) => warn!("{}: {}", check.name, detail),
+            CheckStatus::Failed(detail) => {
+                error!("{}: {}. {}", check.name, detail, check.hint);
+                failed.push(check.name);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        radio_station.write_channel(AppChannel::Toasts).toasts.push(
+            "Something is wrong".to_string(),
+            format!(
+                "{} failed, see Diagnostics in the settings",
+                failed.join(", ")
+            ),
+        );
+    }
+}
+
+/// Logs what the integrity check finds at startup, repairs are left to the
+/// settings page
+async fn report_integrity(repositories: Repositories) {
+    let report = match repositories.check_integrity::<MangaTag>().await {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Failed to check the database: {}", e);
+            return;
+        }
+    };
+
+    if !report.is_clean() {
+        warn!(
+            "Database check found {} contents without their index, {} indexes with a bad signature and {} users with a malformed key",
+            report.orphaned_count(),
+            report.invalid_indexes.len(),
+            report.malformed_users.len()
+        );
+    }
+}
+
+/// Relayed records evicted at a time while the database is over its limit
+const EVICTION_BATCH: usize = 200;
+
+/// Keeps a relay-only node's metadata in check for as long as the app runs.
+/// Relayed records are dropped once they outlive the retention, and the least
+/// recently exchanged ones are evicted while the database is over its limit.
+/// Records from trusted users and our own are never dropped.
+async fn maintain_relay_storage(
+    repositories: Repositories,
+    config: RelayOnlyConfig,
+    own_key: PublicKey,
+    metrics: ServerMetrics,
+) {
+    let interval = Duration::from_secs(config.prune_interval.as_secs().max(60) as u64);
+
+    loop {
+        match kept_publishers(&repositories, &own_key).await {
+            Ok(keep) => {
+                let cutoff = repositories.now() - config.retention;
+                match repositories
+                    .index()
+                    .prune_relayed::<MangaTag>(cutoff, &keep)
+                    .await
+                {
+                    Ok(0) => {}
+                    Ok(pruned) => info!("Pruned {} relayed records", pruned),
+                    Err(e) => error!("Failed to prune relayed records: {}", e),
+                }
+
+                if config.max_database_mb > 0 {
+                    evict_over_quota(&repositories, &config, &keep, &metrics).await;
+                }
+            }
+            Err(e) => error!("Failed to get trusted users: {}", e),
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Publishers whose relayed records stay regardless of age or quota, trusted
+/// users and ourselves
+async fn kept_publishers(
+    repositories: &Repositories,
+    own_key: &PublicKey,
+) -> Result<HashSet<PublicKey>, DatabaseError> {
+    let mut keep = repositories
+        .user()
+        .get_keys_with_trust(TrustLevel::Trusted)
+        .await?;
+    keep.insert(own_key.clone());
+    Ok(keep)
+}
+
+/// Evicts a single batch per pass, the database only shrinks on disk once it
+/// compacts so measuring again right away would evict far more than needed
+async fn evict_over_quota(
+    repositories: &Repositories,
+    config: &RelayOnlyConfig,
+    keep: &HashSet<PublicKey>,
+    metrics: &ServerMetrics,
+) {
+    let size = match dir_size(Path::new(DATABASE_PATH)).await {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to measure the database: {}", e);
+            return;
+        }
+    };
+    if size <= config.max_database_mb * 1024 * 1024 {
+        return;
+    }
+
+    match repositories
+        .index()
+        .evict_relayed::<MangaTag>(EVICTION_BATCH, keep)
+        .await
+    {
+        Ok(0) => warn!(
+            "Database is over its {} MB limit but nothing can be evicted",
+            config.max_database_mb
+        ),
+        Ok(evicted) => {
+            info!("Evicted {} relayed records", evicted);
+            metrics.add_evicted(evicted);
+        }
+        Err(e) => error!("Failed to evict relayed records: {}", e),
+    }
+}
+
+/// Trusted peers our releases are announced to on each pass
+const REPUBLISH_PEERS: usize = 8;
+
+/// Random trusted peers plus our full sync targets, one per address, that
+/// releases of ours get announced to
+pub(crate) async fn announce_targets(
+    repositories: &Repositories,
+    own_key: &PublicKey,
+) -> Result<Vec<User>, DatabaseError> {
+    let mut peers = repositories
+        .user()
+        .get_random_users(TrustLevel::Trusted, REPUBLISH_PEERS)
+        .await?;
+    if let Ok(targets) = repositories.full_sync_addresses().await {
+        let keys = targets.into_iter().map(|t| t.pub_key).collect();
+        match repositories.user().get_users(keys).await {
+            Ok(users) => peers.extend(users),
+            Err(e) => error!("Failed to get full sync targets: {}", e),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    peers.retain(|peer| {
+        peer.pub_key() != own_key
+            && !peer.address().inner().is_empty()
+            && seen.insert(peer.address().clone())
+    });
+    Ok(peers)
+}
+
+/// Stores what a release signed, the index only if it's a new one
+async fn store_release(
+    repositories: &Repositories,
+    release: &Release,
+) -> Result<(), DatabaseError> {
+    if release.new_index {
+        repositories
+            .index()
+            .add_index(release.index.clone())
+            .await?;
+    }
+    for content in &release.contents {
+        repositories.index().add_content(content.clone()).await?;
+    }
+    Ok(())
+}
+
+/// Points the release's torrent at the local files so it checks them and
+/// starts seeding, `false` if there are none or it couldn't be added
+async fn seed_release(
+    torrent_client: &TorrentClient,
+    i2p_only: bool,
+    repositories: &Repositories,
+    release: &Release,
+) -> bool {
+    let (Some(save_path), Some(content)) = (
+        Path::new(&release.files_path).parent(),
+        release.contents.first(),
+    ) else {
+        return false;
+    };
+
+    let magnet = &client_magnet(&content.magnet_link, i2p_only).0;
+    if let Ok(info_hash) = InfoHash::from_magnet(magnet)
+        && torrent_client.get_status(info_hash).await.is_some()
+    {
+        return true;
+    }
+
+    let path = save_path.to_string_lossy().to_string();
+    if let Err(e) = torrent_client.add_magnet(magnet, &path).await {
+        error!("Failed to seed {}: {:?}", release.index.title(), e);
+        return false;
+    }
+
+    // So it's added back after a restart, like a download
+    if let Some(link) = TorrentLink::from_content(content, path)
+        && let Err(e) = repositories.upsert_torrent_link(link).await
+    {
+        error!("Failed to save torrent link: {}", e);
+    }
+    true
+}
+
+/// Tells [`announce_targets`] about the contents we have for `index_hash`
+async fn announce_release(
+    client: ClientPool,
+    repositories: Repositories,
+    own_key: PublicKey,
+    index_hash: Hash,
+) {
+    let peers = match announce_targets(&repositories, &own_key).await {
+        Ok(peers) => peers,
+        Err(e) => {
+            error!("Failed to get peers to announce to: {}", e);
+            return;
+        }
+    };
+
+    let mut announced = 0;
+    for peer in peers {
+        match client
+            .clone()
+            .get_client()
+            .await
+            .announce_manga_content(peer.address(), repositories.index(), index_hash.clone())
+            .await
+        {
+            Ok(_) => announced += 1,
+            Err(e) => warn!("Failed to announce to {}: {}", peer.name(), e),
+        }
+    }
+    info!("Announced release to {} peers", announced);
+}
+
+/// Pushes contents we just published to the peers that subscribed to their
+/// index, so followers get them without waiting for an exchange
+pub(crate) async fn push_to_subscribers(
+    client: ClientPool,
+    repositories: Repositories,
+    index_hash: Hash,
+    contents: Vec<Content<MangaTag>>,
+) {
+    let subscribers = match repositories.get_subscribers(&index_hash).await {
+        Ok(subscribers) => subscribers,
+        Err(e) => {
+            error!("Failed to get subscribers: {}", e);
+            return;
+        }
+    };
+
+    let mut pushed = 0;
+    for address in subscribers {
+        let mut client = client.clone().get_client().await;
+        for content in contents.iter() {
+            if let Err(e) = client.push_content(&address, content.clone()).await {
+                warn!("Failed to push to {}: {}", address, e);
+                break;
+            }
+        }
+        pushed += 1;
+    }
+    if pushed > 0 {
+        info!("Pushed release to {} subscribers", pushed);
+    }
+}
+
+/// Announces the content of what we published to trusted peers and full sync
+/// targets every `interval`, so those that dropped it since, relays in
+/// particular, see we still have it and can ask for it again
+async fn republish_own_content(
+    client: ClientPool,
+    repositories: Repositories,
+    own_key: PublicKey,
+    interval: Timestamp,
+) {
+    let interval = Duration::from_secs(interval.as_secs().max(60) as u64);
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let hashes = match repositories
+            .index()
+            .get_published_index_hashes::<MangaTag>(&own_key)
+            .await
+        {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                error!("Failed to get published indexes: {}", e);
+                continue;
+            }
+        };
+        if hashes.is_empty() {
+            continue;
+        }
+
+        let peers = match announce_targets(&repositories, &own_key).await {
+            Ok(peers) => peers,
+            Err(e) => {
+                error!("Failed to get peers to republish to: {}", e);
+                continue;
+            }
+        };
+
+        for peer in peers {
+            let mut client = client.clone().get_client().await;
+            for hash in hashes.iter() {
+                match client
+                    .announce_manga_content(peer.address(), repositories.index(), hash.clone())
+                    .await
+                {
+                    Ok(missing) if !missing.is_empty() => {
+                        info!(
+                            "{} was missing {} of our contents",
+                            peer.name(),
+                            missing.len()
+                        )
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!("Failed to republish to {}: {}", peer.name(), e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Invalidates the queries that show `kind`, so open pages refetch
+async fn refresh_queries(kind: DataKind) {
+    match kind {
+        DataKind::Users => {
+            QueriesStorage::<FetchUsers>::invalidate_all().await;
+            QueriesStorage::<FetchUser>::invalidate_all().await;
+            QueriesStorage::<FetchUserList>::invalidate_all().await;
+            QueriesStorage::<FetchPetnames>::invalidate_all().await;
+            QueriesStorage::<FetchDisplayName>::invalidate_all().await;
+            QueriesStorage::<FetchPeerStats>::invalidate_all().await;
+            QueriesStorage::<FetchMuted>::invalidate_all().await;
+            QueriesStorage::<ResolveMentions>::invalidate_all().await;
+            QueriesStorage::<FetchVouchers>::invalidate_all().await;
+        }
+        DataKind::Indexes => {
+            QueriesStorage::<FetchIndexes<MangaTag>>::invalidate_all().await;
+            QueriesStorage::<FetchIndex<MangaTag>>::invalidate_all().await;
+            QueriesStorage::<FetchLibraryStats>::invalidate_all().await;
+            QueriesStorage::<FetchPopularIndexes>::invalidate_all().await;
+        }
+        DataKind::Contents => {
+            QueriesStorage::<FetchContents<MangaTag>>::invalidate_all().await;
+            QueriesStorage::<FetchInfoHashConflicts<MangaTag>>::invalidate_all().await;
+            QueriesStorage::<FetchLibraryStats>::invalidate_all().await;
+            QueriesStorage::<FetchPopularity>::invalidate_all().await;
+            QueriesStorage::<FetchPopularIndexes>::invalidate_all().await;
+            QueriesStorage::<FetchContentSources>::invalidate_all().await;
+        }
+        DataKind::Follows => {
+            QueriesStorage::<GetFollowContent<MangaTag>>::invalidate_all().await;
+        }
+        DataKind::Posts => {}
+        DataKind::TorrentLinks => {
+            QueriesStorage::<FetchTorrentLinks>::invalidate_all().await;
+            QueriesStorage::<FetchLibraryStats>::invalidate_all().await;
+        }
+        DataKind::Suppressions => {
+            QueriesStorage::<FetchSuppressions>::invalidate_all().await;
+        }
+        DataKind::History => {
+            QueriesStorage::<FetchHistory>::invalidate_all().await;
+        }
+        DataKind::AuditLog => {
+            QueriesStorage::<FetchAuditLog>::invalidate_all().await;
+        }
+    }
 }
 
 pub async fn init_router(sam_tcp_port: u16, sam_udp_port: u16) -> Router<Runtime> {
@@ -131,6 +866,75 @@ pub async fn init_router(sam_tcp_port: u16, sam_udp_port: u16) -> Router<Runtime
     router
 }
 
+const SAM_RETRY_MIN: Duration = Duration::from_secs(2);
+const SAM_RETRY_MAX: Duration = Duration::from_secs(60);
+
+async fn open_sam_sessions(config: &mut AkarekoConfig) -> Result<SamSessions, yosemite::Error> {
+    if config.eepsite_key().expose().is_empty() {
+        let (destination, private_key) = RouterApi::new(config.sam_tcp_port())
+            .generate_destination()
+            .await?;
+        config.set_eepsite_data(b32_from_pub_b64(&destination).unwrap(), private_key);
+    }
+
+    let mut primary = Session::<style::Primary>::new(yosemite::SessionOptions {
+        nickname: "Akareko".to_string(),
+        samv3_tcp_port: config.sam_tcp_port(),
+        samv3_udp_port: config.sam_udp_port(),
+        destination: yosemite::DestinationKind::Persistent {
+            private_key: config.eepsite_key().expose().clone(),
+        },
+        ..Default::default()
+    })
+    .await?;
+    tracing::info!("Loaded SAM session");
+
+    let client = primary
+        .create_subsession::<style::Stream>(yosemite::SessionOptions {
+            nickname: "AkarekoClient".to_string(),
+            ..Default::default()
+        })
+        .await?;
+    tracing::info!("Loaded client SAM session");
+
+    let server = primary
+        .create_subsession::<style::Stream>(yosemite::SessionOptions {
+            nickname: "AkarekoServer".to_string(),
+            ..Default::default()
+        })
+        .await?;
+    tracing::info!("Loaded server session");
+
+    Ok(SamSessions {
+        primary,
+        client,
+        server,
+    })
+}
+
+/// Keeps trying until the router answers, marking the server and the client as
+/// unavailable in the meantime
+async fn connect_sam(
+    config: &mut AkarekoConfig,
+    mut radio_station: RadioStation<AppState, AppChannel>,
+) -> SamSessions {
+    let mut delay = SAM_RETRY_MIN;
+
+    loop {
+        match open_sam_sessions(config).await {
+            Ok(sessions) => return sessions,
+            Err(e) => {
+                warn!("Could not reach SAM, retrying in {:?}: {}", delay, e);
+                radio_station.write_channel(AppChannel::Server).server = ResourceState::Error(());
+                radio_station.write_channel(AppChannel::Client).client = ResourceState::Error(());
+            }
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(SAM_RETRY_MAX);
+    }
+}
+
 impl AppManager {
     pub async fn run_manager(mut self) {
         self.radio_station.write_channel(AppChannel::Config).config = ResourceState::Loading;
@@ -138,66 +942,23 @@ impl AppManager {
         self.radio_station.write_channel(AppChannel::Config).config =
             ResourceState::Loaded(config.clone());
 
+        // The database doesn't need the network, so the library can be browsed
+        // while the router starts. A new config has to wait for its address.
+        let mut repos = None;
+        if !config.eepsite_key().expose().is_empty() {
+            repos = Some(self.load_repositories(&config).await);
+        }
+
         let router = init_router(config.sam_tcp_port(), config.sam_udp_port()).await;
 
         tokio::spawn(router);
         tracing::info!("Initialized I2P router");
 
-        if config.eepsite_key().is_empty() {
-            let (destination, private_key) = RouterApi::new(config.sam_tcp_port())
-                .generate_destination()
-                .await
-                .unwrap();
-            config.set_eepsite_data(b32_from_pub_b64(&destination).unwrap(), private_key);
-        }
-
-        let mut sam_session = Session::<style::Primary>::new(yosemite::SessionOptions {
-            nickname: "Akareko".to_string(),
-            samv3_tcp_port: config.sam_tcp_port(),
-            samv3_udp_port: config.sam_udp_port(),
-            destination: yosemite::DestinationKind::Persistent {
-                private_key: config.eepsite_key().clone(),
-            },
-            ..Default::default()
-        })
-        .await
-        .unwrap();
-
-        tracing::info!("Loaded SAM session");
-
-        let client_sam_session = sam_session
-            .create_subsession::<style::Stream>(yosemite::SessionOptions {
-                nickname: "AkarekoClient".to_string(),
-                // samv3_tcp_port: config.sam_tcp_port(),
-                // samv3_udp_port: config.sam_udp_port(),
-                // destination: yosemite::DestinationKind::Persistent {
-                //     private_key: config.eepsite_key().clone(),
-                // },
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-
-        tracing::info!("Loaded client SAM session");
-        let server_sam_session = sam_session
-            .create_subsession::<style::Stream>(yosemite::SessionOptions {
-                nickname: "AkarekoServer".to_string(),
-                // samv3_tcp_port: config.sam_tcp_port(),
-                // samv3_udp_port: config.sam_udp_port(),
-                // destination: yosemite::DestinationKind::Persistent {
-                //     private_key: config.eepsite_key().clone(),
-                // },
-                ..Default::default()
-            })
-            .await
-            .unwrap();
-
-        tracing::info!("Loaded server session");
         self.radio_station
             .write_channel(AppChannel::TorrentClient)
             .torrent_client = ResourceState::Loading;
-        let torrent_client = TorrentClient::create(AnawtOptions::new());
-        match torrent_client.load("./data/torrents".into()).await {
+        let torrent_client = TorrentClient::create(torrent_options(&config));
+        match torrent_client.load(config.storage().torrents_dir()).await {
             Ok(_) => {}
             Err(e) => {
                 error!("Failed to load torrents: {}", e);
@@ -205,30 +966,123 @@ impl AppManager {
         }
         self.radio_station
             .write_channel(AppChannel::TorrentClient)
-            .torrent_client = ResourceState::Loaded(torrent_client);
-
-        self.radio_station
-            .write_channel(AppChannel::Repository)
-            .repositories = ResourceState::Loading;
-        let repos = Repositories::initialize(&config).await;
-        self.radio_station
-            .write_channel(AppChannel::Repository)
-            .repositories = ResourceState::Loaded(repos.clone());
+            .torrent_client = ResourceState::Loaded(torrent_client.clone());
 
         self.radio_station.write_channel(AppChannel::Server).server = ResourceState::Loading;
+        self.radio_station.write_channel(AppChannel::Client).client = ResourceState::Loading;
+
+        // Without an address there's no user to load, so only a new config waits
+        // for SAM here, everything else works offline until it connects
+        let sessions = match repos {
+            Some(_) => None,
+            None => Some(connect_sam(&mut config, self.radio_station).await),
+        };
+
+        let repos = match repos {
+            Some(repos) => repos,
+            None => self.load_repositories(&config).await,
+        };
+        let changes_rx = repos.changes().subscribe();
+
+        migrate_content_dirs(
+            config.storage(),
+            config.i2p_only_torrents(),
+            &torrent_client,
+            &repos,
+        )
+        .await;
+        tokio::spawn(report_integrity(repos.clone()));
+        tokio::spawn(report_diagnostics(
+            config.clone(),
+            repos.clone(),
+            self.radio_station,
+        ));
+
+        // Torrents are only known by their info hash in the client, each is
+        // paired once with the key of its link
+        let links: Vec<(InfoHash, String)> = repos
+            .get_torrent_links()
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|l| Some((InfoHash::from_magnet(&l.magnet.0).ok()?, l.info_hash)))
+            .collect();
+        for watcher in torrent_client.subscribe_all().await {
+            let info_hash = watcher.borrow().info_hash;
+            if let Some((_, key)) = links.iter().find(|(h, _)| *h == info_hash) {
+                tokio::spawn(watch_torrent_completion(
+                    watcher,
+                    key.clone(),
+                    repos.clone(),
+                ));
+            }
+        }
+
+        if config.opds().enabled {
+            let opds_config = config.opds().clone();
+            let repositories = repos.clone();
+            tokio::spawn(async move {
+                if let Err(e) = run_opds_server(opds_config, repositories).await {
+                    error!("OPDS server stopped: {}", e);
+                }
+            });
+        }
+
+        match sessions {
+            Some(sessions) => self.start_networking(sessions, repos, &config),
+            None => {
+                let load_tx = self.load_tx.clone();
+                let radio_station = self.radio_station;
+                tokio::spawn(async move {
+                    let sessions = connect_sam(&mut config, radio_station).await;
+                    load_tx.send(LoadEvent::ConnectedSam(sessions)).unwrap();
+                });
+            }
+        }
+
+        self.process_events(changes_rx).await;
+    }
+
+    /// Starts the server and the client once SAM is up
+    fn start_networking(
+        &mut self,
+        sessions: SamSessions,
+        repos: Repositories,
+        config: &AkarekoConfig,
+    ) {
         let server = AkarekoServer::new();
+        let metrics = server.metrics();
+        if config.relay_only().enabled {
+            tokio::spawn(maintain_relay_storage(
+                repos.clone(),
+                config.relay_only().clone(),
+                config.public_key().clone(),
+                metrics.clone(),
+            ));
+        }
         let server_conf = rclite::Arc::new(RwLock::new(config.clone()));
         tokio::spawn(async move {
-            server
-                .run(server_conf, repos, server_sam_session)
-                .await
-                .unwrap();
+            if let Err(e) = server.run(server_conf, repos, sessions.server).await {
+                error!("Server stopped: {}", e);
+            }
         });
-        self.radio_station.write_channel(AppChannel::Server).server = ResourceState::Loaded(());
+        self.radio_station.write_channel(AppChannel::Server).server =
+            ResourceState::Loaded(metrics);
 
-        self.start_client_thread(client_sam_session);
+        self.start_client_thread(sessions.client);
+        self.sam_session = Some(sessions.primary);
+    }
 
-        self.process_events().await;
+    async fn load_repositories(&mut self, config: &AkarekoConfig) -> Repositories {
+        self.radio_station
+            .write_channel(AppChannel::Repository)
+            .repositories = ResourceState::Loading;
+        let repos = Repositories::initialize(config).await;
+        self.radio_station
+            .write_channel(AppChannel::Repository)
+            .repositories = ResourceState::Loaded(repos.clone());
+
+        repos
     }
 
     pub fn new(
@@ -240,10 +1094,13 @@ impl AppManager {
 
         let manager = AppManager {
             client_thread: None,
+            republish_task: None,
+            sam_session: None,
             radio_station,
             load_tx,
             load_rx,
             rx,
+            mentions_checked: Timestamp::now(),
         };
 
         (manager, tx)
@@ -272,23 +1129,304 @@ impl AppManager {
         }));
     }
 
-    pub async fn process_events(&mut self) {
+    fn start_republishing(&mut self, client: ClientPool) {
+        if let Some(t) = self.republish_task.take() {
+            t.abort();
+        }
+
+        let (repositories, config) = {
+            let state = self.radio_station.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return,
+            }
+        };
+
+        let interval = config.scheduler_config().republish_interval;
+        if interval.as_secs() == 0 {
+            return;
+        }
+
+        self.republish_task = Some(tokio::spawn(republish_own_content(
+            client,
+            repositories,
+            config.public_key().clone(),
+            interval,
+        )));
+    }
+
+    /// Tells about new chapters of followed titles the way each follow asks
+    async fn announce_arrivals(&mut self) {
+        let repositories = match &self.radio_station.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return,
+        };
+
+        let arrivals = match repositories.take_arrivals::<MangaTag>().await {
+            Ok(arrivals) => arrivals,
+            Err(e) => {
+                error!("Failed to check followed titles: {}", e);
+                return;
+            }
+        };
+
+        for arrival in arrivals {
+            let title = arrival.index.title().clone();
+            let body = match arrival.contents.as_slice() {
+                [content] => format!("Ch. {}: {}", content.enumeration(), content.title()),
+                contents => format!("{} new chapters", contents.len()),
+            };
+
+            match arrival.notify {
+                NotificationPreference::Toast => {
+                    self.radio_station
+                        .write_channel(AppChannel::Toasts)
+                        .toasts
+                        .push(title, body);
+                }
+                NotificationPreference::Desktop => {
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(e) = notify_rust::Notification::new()
+                            .appname("Akareko")
+                            .summary(&title)
+                            .body(&body)
+                            .show()
+                        {
+                            warn!("Failed to show desktop notification: {}", e);
+                        }
+                    });
+                }
+                NotificationPreference::Silent => {}
+            }
+        }
+    }
+
+    /// Toasts posts synced from others that mention our key, if asked to
+    async fn announce_mentions(&mut self) {
+        let (repositories, config) = {
+            let state = self.radio_station.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return,
+            }
+        };
+
+        let (posts, checked) = match repositories
+            .posts_mentioning(config.public_key(), self.mentions_checked)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                error!("Failed to check for mentions: {}", e);
+                return;
+            }
+        };
+        self.mentions_checked = checked;
+
+        if !config.notify_mentions() {
+            return;
+        }
+
+        for post in posts {
+            let author = match repositories.user().get_display_name(&post.source).await {
+                Ok(name) => name,
+                Err(_) => post.source.fingerprint(),
+            };
+
+            self.radio_station
+                .write_channel(AppChannel::Toasts)
+                .toasts
+                .push(format!("{} mentioned you", author), post.content);
+        }
+    }
+
+    /// Raises users vouched for by enough of our fully trusted users, as set
+    /// in the config, and tells about each
+    async fn apply_vouching(&mut self) {
+        let (repositories, config) = {
+            let state = self.radio_station.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return,
+            }
+        };
+
+        let raised = match repositories
+            .user()
+            .apply_vouching(config.vouch_threshold() as usize)
+            .await
+        {
+            Ok(raised) => raised,
+            Err(e) => {
+                error!("Failed to apply vouching: {}", e);
+                return;
+            }
+        };
+
+        for (user, vouchers) in raised {
+            let name = match repositories.user().get_display_name(user.pub_key()).await {
+                Ok(name) => name,
+                Err(_) => user.pub_key().fingerprint(),
+            };
+
+            self.radio_station
+                .write_channel(AppChannel::Toasts)
+                .toasts
+                .push(
+                    format!("{} is now trusted", name),
+                    format!("Vouched for by {} of your fully trusted users", vouchers),
+                );
+        }
+    }
+
+    /// Stores, seeds and announces the staged releases whose undo window is
+    /// over, see [`StagedReleases`](crate::ui::components::StagedReleases)
+    async fn publish_due(&mut self) {
+        if self.radio_station.read().staged.iter().next().is_none() {
+            return;
+        }
+
+        let due = self
+            .radio_station
+            .write_channel(AppChannel::Staged)
+            .staged
+            .take_due(Timestamp::now());
+
+        for release in due {
+            let (repositories, config, torrent_client, client) = {
+                let state = self.radio_station.read();
+                let (ResourceState::Loaded(r), ResourceState::Loaded(c)) =
+                    (&state.repositories, &state.config)
+                else {
+                    error!("Dropped {} before it was stored", release.index.title());
+                    continue;
+                };
+                let torrent_client = match &state.torrent_client {
+                    ResourceState::Loaded(t) => Some(t.clone()),
+                    _ => None,
+                };
+                let client = match &state.client {
+                    ResourceState::Loaded(p) => Some(p.clone()),
+                    _ => None,
+                };
+                (r.clone(), c.clone(), torrent_client, client)
+            };
+
+            let title = release.index.title().clone();
+            if let Err(e) = store_release(&repositories, &release).await {
+                error!("Failed to store {}: {}", title, e);
+                self.radio_station
+                    .write_channel(AppChannel::Toasts)
+                    .toasts
+                    .push(format!("Failed to publish {}", title), e.to_string());
+                continue;
+            }
+
+            let seeding = match &torrent_client {
+                Some(t) => {
+                    seed_release(t, config.i2p_only_torrents(), &repositories, &release).await
+                }
+                None => false,
+            };
+            if let Some(client) = client {
+                tokio::spawn(push_to_subscribers(
+                    client.clone(),
+                    repositories.clone(),
+                    release.index.hash().clone(),
+                    release.contents.clone(),
+                ));
+                tokio::spawn(announce_release(
+                    client,
+                    repositories,
+                    config.public_key().clone(),
+                    release.index.hash().clone(),
+                ));
+            }
+
+            self.radio_station
+                .write_channel(AppChannel::Toasts)
+                .toasts
+                .push(
+                    format!("Published {}", title),
+                    format!(
+                        "{} chapters stored, {}",
+                        release.contents.len(),
+                        match seeding {
+                            true => "seeding the torrent",
+                            false => "not seeding the torrent",
+                        }
+                    ),
+                );
+        }
+    }
+
+    pub async fn process_events(&mut self, mut changes_rx: broadcast::Receiver<DataKind>) {
+        let mut staging = tokio::time::interval(Duration::from_secs(1));
         loop {
             tokio::select! {
+                _ = staging.tick() => {
+                    self.publish_due().await;
+                }
+                val = changes_rx.recv() => {
+                    let kinds = match val {
+                        Ok(kind) => vec![kind],
+                        // We missed some, refresh everything rather than guess
+                        Err(broadcast::error::RecvError::Lagged(_)) => DataKind::ALL.to_vec(),
+                        Err(broadcast::error::RecvError::Closed) => vec![],
+                    };
+
+                    if kinds.contains(&DataKind::Contents) {
+                        self.announce_arrivals().await;
+                    }
+
+                    if kinds.contains(&DataKind::Posts) {
+                        self.announce_mentions().await;
+                    }
+
+                    // Raising someone changes users again, but nobody is
+                    // raised twice so it settles
+                    if kinds.contains(&DataKind::Users) {
+                        self.apply_vouching().await;
+                    }
+
+                    for kind in kinds {
+                        refresh_queries(kind).await;
+                        self.radio_station
+                            .write_channel(AppChannel::Data)
+                            .data_versions
+                            .bump(kind);
+                    }
+                }
                 val = self.rx.recv() => {
                     match val.unwrap() {
                         Event::RemoveMainWindow => {
                             self.radio_station.write_channel(AppChannel::Window).windows_state.remove_main_window();
                         },
+                        Event::OpenLink(link) => {
+                            self.radio_station.write_channel(AppChannel::Links).pending_link = Some(link);
+                        },
                     }
                 }
                 val = self.load_rx.recv() => {
                     match val.unwrap() {
                         LoadEvent::LoadedClient(client) => {
+                            self.start_republishing(client.clone());
                             self.radio_station.write_channel(AppChannel::Client).client =
                                 ResourceState::Loaded(client);
                             self.client_thread = None;
                         }
+                        LoadEvent::ConnectedSam(sessions) => {
+                            let state = self.radio_station.read();
+                            let (ResourceState::Loaded(repos), ResourceState::Loaded(config)) =
+                                (&state.repositories, &state.config)
+                            else {
+                                continue;
+                            };
+                            let (repos, config) = (repos.clone(), config.clone());
+                            drop(state);
+
+                            self.start_networking(sessions, repos, &config);
+                        }
                     }
                 }
             }
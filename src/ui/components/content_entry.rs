@@ -1,18 +1,26 @@
-use std::time::Duration;
-
-use anawt::InfoHash;
-use freya::{prelude::*, query::*};
+use anawt::{AnawtTorrentStatus, InfoHash, TorrentState};
+use freya::{prelude::*, query::*, radio::use_radio, sdk::use_track_watcher};
+use tokio::sync::watch;
 
 use crate::{
-    db::index::{
-        content::{Content, ContentType, ExternalContent, InternalContent},
-        tags::{IndexTag, MangaTag},
+    db::{
+        index::{
+            content::{Content, ContentType, ExternalContent, InternalContent},
+            relay_trail::RelayHop,
+            tags::{IndexTag, MangaTag},
+        },
+        suppression::Suppression,
+        torrent_link::TorrentLink,
     },
+    types::{Signature, Topic},
     ui::{
-        DEFAULT_CORNER_RADIUS, Route, RouteContext,
-        components::{Spacer, no_reaction_button, svg_button},
+        AppChannel, DEFAULT_CORNER_RADIUS, ResourceState, Route, RouteContext,
+        components::{Spacer, copy_button, no_reaction_button, svg_button},
         icons::{self},
-        queries::{AddTorrent, FetchTorrentStatus, UpdateContentProgress},
+        queries::{
+            AddTorrent, DeleteContent, FetchContentSources, FetchDisplayName, FetchTorrentLinks,
+            FetchTorrentWatcher, RevokeContent, UpdateContentProgress,
+        },
     },
 };
 
@@ -22,19 +30,51 @@ mod sealed {
 
 pub struct ContentEntry<I: IndexTag + VisualizeRoute<I, S>, S: ContentType<I>> {
     content: Content<I, S>,
+    /// Posts under the chapter's topic, shown next to the posts button
+    posts: usize,
 }
 
 impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component
     for ContentEntry<I, InternalContent>
 {
     fn render(&self) -> impl IntoElement {
+        if self.content.is_local_only() {
+            return LocalContentEntry {
+                content: self.content.clone(),
+            }
+            .into_element();
+        }
+
         let info_hash = InfoHash::from_magnet(&self.content.magnet_link.0).unwrap();
-        let torrent_status = use_query(
-            Query::new(info_hash, FetchTorrentStatus).interval_time(Duration::from_millis(500)),
-        );
+        let torrent_watcher = use_query(Query::new(info_hash, FetchTorrentWatcher));
 
         let seen_mutation = use_mutation(Mutation::new(UpdateContentProgress::<I>::new()));
         let download_mutation = use_mutation(Mutation::new(AddTorrent));
+        let config = use_radio(AppChannel::Config);
+        let links_query = use_query(Query::new((), FetchTorrentLinks));
+
+        let verified = match &*links_query.read().state() {
+            QueryStateData::Settled { res: Ok(links), .. } => links
+                .iter()
+                .find(|l| l.content == *self.content.signature())
+                .and_then(|l| l.verified),
+            _ => None,
+        };
+        let verification_badge = verified.map(|verified| {
+            let (mark, color, description) = match verified {
+                true => (
+                    "✔",
+                    Color::from_rgb(46, 125, 50),
+                    "Files match what the uploader signed",
+                ),
+                false => (
+                    "✘",
+                    Color::from_rgb(198, 40, 40),
+                    "Files differ from what the uploader signed",
+                ),
+            };
+            TooltipContainer::new(Tooltip::new(description)).child(label().text(mark).color(color))
+        });
 
         let watch_icon = {
             let content = self.content.clone();
@@ -57,58 +97,61 @@ impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component
         let (torrent_status_icon, on_press_title): (
             Element,
             Option<EventHandler<Event<PressEventData>>>,
-        ) = match &*torrent_status.read().state() {
+        ) = match &*torrent_watcher.read().state() {
             QueryStateData::Settled {
-                res: Ok(status), ..
+                res: Ok(watcher), ..
             }
             | QueryStateData::Loading {
-                res: Some(Ok(status)),
-            } => {
-                let content = self.content.clone();
-                let open_file = move |_| {
-                    RouteContext::get().push(I::visualize_route(content.clone()));
-                };
-
-                match status {
-                    Some(s) => match &s.state {
-                        anawt::TorrentState::CheckingFiles => (rect().into_element(), None),
-                        anawt::TorrentState::DownloadingMetadata => (rect().into_element(), None),
-                        anawt::TorrentState::Downloading => (
-                            ProgressBar::new(s.progress as f32 * 100.0).into_element(),
-                            None,
-                        ),
-                        anawt::TorrentState::Finished => (
-                            svg_button(icons::CHECK_CIRCLE_ICON, 24., Color::WHITE).into_element(),
-                            Some(open_file.into()),
-                        ),
-                        anawt::TorrentState::Seeding => (
-                            svg_button(icons::CHECK_CIRCLE_ICON, 24., Color::WHITE).into_element(),
-                            Some(open_file.into()),
-                        ),
-                        anawt::TorrentState::CheckingResumeData => (rect().into_element(), None),
-                    },
-                    None => {
-                        let keys = (
-                            self.content.magnet_link.clone(),
-                            format!("./data/{}/{}", I::TAG, self.content.signature().as_base64()),
-                        );
-                        let download_torrent: EventHandler<Event<PressEventData>> = (move |_| {
-                            download_mutation.mutate(keys.clone());
-                        })
-                        .into();
-                        (
-                            Button::new()
-                                .child(
-                                    svg(icons::DOWNLOAD_ICON)
-                                        .on_press(download_torrent.clone())
-                                        .color(Color::WHITE),
-                                )
-                                .into_element(),
-                            Some(download_torrent),
-                        )
-                    }
+                res: Some(Ok(watcher)),
+            } => match watcher {
+                Some(w) => {
+                    let content = self.content.clone();
+                    let open_file: EventHandler<Event<PressEventData>> = (move |_| {
+                        RouteContext::get().push(I::visualize_route(content.clone()));
+                    })
+                    .into();
+
+                    let on_press_title = is_complete(&w.borrow()).then(|| open_file.clone());
+                    (
+                        TorrentStatusIcon {
+                            watcher: w.clone(),
+                            open_file,
+                        }
+                        .into_element(),
+                        on_press_title,
+                    )
                 }
-            }
+                None => {
+                    let path = config
+                        .read()
+                        .config
+                        .unwrap_ref()
+                        .storage()
+                        .content_dir(&self.content)
+                        .to_string_lossy()
+                        .to_string();
+                    let keys = (
+                        self.content.magnet_link.clone(),
+                        path.clone(),
+                        TorrentLink::from_content(&self.content, path),
+                        self.content.web_seed.clone(),
+                    );
+                    let download_torrent: EventHandler<Event<PressEventData>> = (move |_| {
+                        download_mutation.mutate(keys.clone());
+                    })
+                    .into();
+                    (
+                        Button::new()
+                            .child(
+                                svg(icons::DOWNLOAD_ICON)
+                                    .on_press(download_torrent.clone())
+                                    .color(Color::WHITE),
+                            )
+                            .into_element(),
+                        Some(download_torrent),
+                    )
+                }
+            },
             QueryStateData::Pending { .. } | QueryStateData::Loading { .. } => {
                 (CircularLoader::new().into_element(), None)
             }
@@ -120,7 +163,63 @@ impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component
             ),
         };
 
-        let post_icon = svg_button(icons::CHAT_ICON, 24., Color::WHITE);
+        let published = config.read().config.unwrap_ref().public_key() == self.content.poster();
+        let swarm_health = match &*torrent_watcher.read().state() {
+            _ if !published => None,
+            QueryStateData::Settled {
+                res: Ok(Some(watcher)),
+                ..
+            } => Some(
+                SwarmHealth {
+                    watcher: watcher.clone(),
+                    content: self.content.signature().clone(),
+                }
+                .into_element(),
+            ),
+            QueryStateData::Settled { res: Ok(None), .. } => Some(
+                label()
+                    .text("You published this but aren't seeding it")
+                    .color(Color::from_rgb(198, 40, 40))
+                    .font_size(14)
+                    .into_element(),
+            ),
+            _ => None,
+        };
+
+        let uploader_query = use_query(Query::new(self.content.poster().clone(), FetchDisplayName));
+        let uploader = match &*uploader_query.read().state() {
+            QueryStateData::Settled { res: Ok(name), .. } => name.clone(),
+            _ => self.content.poster().fingerprint(),
+        };
+
+        let post_icon = {
+            let topic = Topic::from_content(&self.content);
+            let title = format!(
+                "Ch. {}: {}",
+                self.content.enumeration(),
+                self.content.title()
+            );
+
+            rect()
+                .horizontal()
+                .cross_align(Alignment::Center)
+                .child(
+                    svg_button(icons::CHAT_ICON, 24., Color::WHITE).on_press(move |_| {
+                        RouteContext::get().push(Route::Posts {
+                            topic: topic.clone(),
+                            title: title.clone(),
+                        });
+                    }),
+                )
+                .maybe(self.posts > 0, |r| {
+                    r.child(
+                        label()
+                            .text(self.posts.to_string())
+                            .font_size(12)
+                            .color(Color::WHITE),
+                    )
+                })
+        };
 
         let progress = self.content.calculate_progress();
 
@@ -148,9 +247,19 @@ impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component
                     }),
             )
             .child(Spacer::horizontal_fill())
+            .child(copy_button(
+                self.content.magnet_link.0.clone(),
+                Color::WHITE,
+            ))
+            .maybe(verification_badge.is_some(), |r| {
+                r.child(verification_badge.unwrap())
+            })
             .child(watch_icon)
             .child(torrent_status_icon)
-            .child(post_icon);
+            .child(post_icon)
+            .child(DeleteContentButton {
+                content: self.content.clone(),
+            });
 
         rect()
             .width(Size::Fill)
@@ -159,9 +268,245 @@ impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component
                 rect()
                     .width(Size::Fill)
                     .background(Color::GRAY)
+                    .child(
+                        TooltipContainer::new(Tooltip::new(relay_trail_description(
+                            self.content.relay_trail(),
+                        )))
+                        .child(
+                            label()
+                                .text(format!("Uploader: {}", uploader))
+                                .color(Color::WHITE)
+                                .font_size(14),
+                        ),
+                    )
+                    .padding((0., 5.)),
+            )
+            .maybe(swarm_health.is_some(), |r| {
+                r.child(rect().padding((0., 5.)).child(swarm_health.unwrap()))
+            })
+            .child(
+                ProgressBar::new(progress)
+                    .show_progress(false)
+                    .width(Size::Fill)
+                    .height(10.),
+            )
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::DARK_GRAY)
+            .into_element()
+    }
+}
+
+/// Swarm of a chapter we published, and when a peer last told us it has it
+struct SwarmHealth {
+    watcher: watch::Receiver<AnawtTorrentStatus>,
+    content: Signature,
+}
+
+impl PartialEq for SwarmHealth {
+    fn eq(&self, other: &Self) -> bool {
+        self.watcher.same_channel(&other.watcher) && self.content == other.content
+    }
+}
+
+impl Component for SwarmHealth {
+    fn render(&self) -> impl IntoElement {
+        use_track_watcher(&self.watcher);
+        let status = self.watcher.borrow().clone();
+        let sources_query = use_query(Query::new(self.content.clone(), FetchContentSources));
+
+        let last_seen = match &*sources_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(sources), ..
+            } => sources
+                .first()
+                .map(|s| s.last_seen.format_date())
+                .unwrap_or_else(|| "never".to_string()),
+            _ => "-".to_string(),
+        };
+
+        let (warning, color) = match status.num_seeds {
+            0 => (", no other seeders", Color::from_rgb(198, 40, 40)),
+            _ => ("", Color::LIGHT_GRAY),
+        };
+
+        label()
+            .text(format!(
+                "{} seeds, {} peers{}. Last had by a peer: {}",
+                status.num_seeds, status.num_peers, warning, last_seen
+            ))
+            .color(color)
+            .font_size(14)
+    }
+}
+
+/// Asks before deleting, and whether the content should be kept from coming
+/// back with the next exchange
+struct DeleteContentButton<I: IndexTag> {
+    content: Content<I>,
+}
+
+impl<I: IndexTag> PartialEq for DeleteContentButton<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.content == other.content
+    }
+}
+
+impl<I: IndexTag> Component for DeleteContentButton<I> {
+    fn render(&self) -> impl IntoElement {
+        let mut confirming = use_state(|| false);
+        let delete_mutation = use_mutation(Mutation::new(DeleteContent::<I>::new()));
+        let revoke_mutation = use_mutation(Mutation::new(RevokeContent::<I>::new()));
+        let config = use_radio(AppChannel::Config);
+
+        if matches!(
+            &*delete_mutation.read().state(),
+            MutationStateData::Loading { .. }
+        ) || matches!(
+            &*revoke_mutation.read().state(),
+            MutationStateData::Loading { .. }
+        ) {
+            return CircularLoader::new().into_element();
+        }
+
+        // Only what we published can be taken back from the other nodes
+        let ours = match &config.read().config {
+            ResourceState::Loaded(c) => c.public_key() == self.content.poster(),
+            _ => false,
+        };
+
+        if !*confirming.read() {
+            return svg_button(icons::TRASH_ICON, 20., Color::WHITE)
+                .on_press(move |_| confirming.set(true))
+                .hover_background(Color::TRANSPARENT)
+                .into_element();
+        }
+
+        let keys = (
+            self.content.signature().clone(),
+            self.content.index_hash().clone(),
+            None,
+        );
+        let suppressed_keys = (
+            keys.0.clone(),
+            keys.1.clone(),
+            Some(Suppression::from_content(&self.content)),
+        );
+        let revoke_keys = (keys.0.clone(), keys.1.clone());
+
+        rect()
+            .horizontal()
+            .spacing(5.)
+            .cross_align(Alignment::Center)
+            .child(label().text("Delete?").color(Color::WHITE))
+            .child(
+                Button::new()
+                    .child("Delete")
+                    .on_press(move |_| delete_mutation.mutate(keys.clone())),
+            )
+            .child(
+                Button::new()
+                    .child("Delete, don't fetch again")
+                    .on_press(move |_| delete_mutation.mutate(suppressed_keys.clone())),
+            )
+            .maybe(ours, |r| {
+                r.child(
+                    Button::new()
+                        .child("Revoke for everyone")
+                        .on_press(move |_| revoke_mutation.mutate(revoke_keys.clone())),
+                )
+            })
+            .child(
+                Button::new()
+                    .child("Cancel")
+                    .on_press(move |_| confirming.set(false)),
+            )
+            .into_element()
+    }
+}
+
+/// Entry imported from local files, there's no torrent to download or
+/// uploader to show
+struct LocalContentEntry<I: IndexTag> {
+    content: Content<I>,
+}
+
+impl<I: IndexTag> PartialEq for LocalContentEntry<I> {
+    fn eq(&self, other: &Self) -> bool {
+        self.content == other.content
+            && self.content.progress == other.content.progress
+            && self.content.count == other.content.count
+    }
+}
+
+impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component for LocalContentEntry<I> {
+    fn render(&self) -> impl IntoElement {
+        let seen_mutation = use_mutation(Mutation::new(UpdateContentProgress::<I>::new()));
+
+        let watch_icon = {
+            let content = self.content.clone();
+            let seen = content.progress >= content.count;
+
+            svg_button(
+                if seen {
+                    icons::EYE_SLASH_ICON
+                } else {
+                    icons::EYE_ICON
+                },
+                20.,
+                if seen {
+                    Color::LIGHT_GRAY
+                } else {
+                    Color::WHITE
+                },
+            )
+            .on_press(move |_| {
+                let progress = if seen { 0 } else { content.count };
+                seen_mutation.mutate((content.signature().clone(), progress));
+            })
+            .hover_background(Color::TRANSPARENT)
+        };
+
+        let progress = self.content.calculate_progress();
+
+        let content = self.content.clone();
+        let on_press_title = move |_| {
+            RouteContext::get().push(I::visualize_route(content.clone()));
+        };
+
+        let first_line = rect()
+            .horizontal()
+            .content(freya::prelude::Content::Flex)
+            .cross_align(Alignment::Center)
+            .child(
+                no_reaction_button()
                     .child(
                         label()
-                            .text("Group: Anon")
+                            .text(format!(
+                                "Ch. {}: {}",
+                                self.content.enumeration(),
+                                self.content.title()
+                            ))
+                            .text_decoration(TextDecoration::Underline)
+                            .maybe(progress >= 100.0, |el| {
+                                el.text_decoration(TextDecoration::LineThrough)
+                            })
+                            .color(Color::WHITE),
+                    )
+                    .on_press(on_press_title),
+            )
+            .child(Spacer::horizontal_fill())
+            .child(watch_icon);
+
+        rect()
+            .width(Size::Fill)
+            .child(first_line.padding(5.))
+            .child(
+                rect()
+                    .width(Size::Fill)
+                    .background(Color::GRAY)
+                    .child(
+                        label()
+                            .text(format!("Local only: {}", self.content.source()))
                             .color(Color::WHITE)
                             .font_size(14),
                     )
@@ -177,6 +522,45 @@ impl<I: IndexTag + VisualizeRoute<I, InternalContent>> Component
             .background(Color::DARK_GRAY)
     }
 }
+
+fn is_complete(status: &AnawtTorrentStatus) -> bool {
+    matches!(status.state, TorrentState::Finished | TorrentState::Seeding)
+}
+
+/// Follows the torrent watcher so only this icon re-renders when the torrent
+/// makes progress
+struct TorrentStatusIcon {
+    watcher: watch::Receiver<AnawtTorrentStatus>,
+    open_file: EventHandler<Event<PressEventData>>,
+}
+
+impl PartialEq for TorrentStatusIcon {
+    fn eq(&self, other: &Self) -> bool {
+        self.watcher.same_channel(&other.watcher)
+    }
+}
+
+impl Component for TorrentStatusIcon {
+    fn render(&self) -> impl IntoElement {
+        use_track_watcher(&self.watcher);
+        let status = self.watcher.borrow().clone();
+
+        match status.state {
+            TorrentState::Downloading => {
+                ProgressBar::new(status.progress as f32 * 100.0).into_element()
+            }
+            TorrentState::Finished | TorrentState::Seeding => {
+                svg_button(icons::CHECK_CIRCLE_ICON, 24., Color::WHITE)
+                    .on_press(self.open_file.clone())
+                    .into_element()
+            }
+            TorrentState::CheckingFiles
+            | TorrentState::DownloadingMetadata
+            | TorrentState::CheckingResumeData => rect().into_element(),
+        }
+    }
+}
+
 impl<I: IndexTag + VisualizeRoute<I, ExternalContent>> Component
     for ContentEntry<I, ExternalContent>
 {
@@ -275,6 +659,12 @@ impl<I: IndexTag + VisualizeRoute<I, ExternalContent>> Component
         //     ),
         // };
 
+        let uploader_query = use_query(Query::new(self.content.poster().clone(), FetchDisplayName));
+        let uploader = match &*uploader_query.read().state() {
+            QueryStateData::Settled { res: Ok(name), .. } => name.clone(),
+            _ => self.content.poster().fingerprint(),
+        };
+
         let post_icon = svg_button(icons::CHAT_ICON, 24., Color::WHITE);
 
         let progress = self.content.calculate_progress();
@@ -318,10 +708,15 @@ impl<I: IndexTag + VisualizeRoute<I, ExternalContent>> Component
                     .width(Size::Fill)
                     .background(Color::GRAY)
                     .child(
-                        label()
-                            .text("Group: Anon")
-                            .color(Color::WHITE)
-                            .font_size(14),
+                        TooltipContainer::new(Tooltip::new(relay_trail_description(
+                            self.content.relay_trail(),
+                        )))
+                        .child(
+                            label()
+                                .text(format!("Uploader: {}", uploader))
+                                .color(Color::WHITE)
+                                .font_size(14),
+                        ),
                     )
                     .padding((0., 5.)),
             )
@@ -336,9 +731,23 @@ impl<I: IndexTag + VisualizeRoute<I, ExternalContent>> Component
     }
 }
 
+fn relay_trail_description(trail: &[RelayHop]) -> String {
+    if trail.is_empty() {
+        return "Received directly from the uploader".to_string();
+    }
+
+    let hops: Vec<String> = trail.iter().map(|h| h.relay.fingerprint()).collect();
+    format!("Relayed through {}", hops.join(" → "))
+}
+
 impl<I: IndexTag + VisualizeRoute<I, S>, S: ContentType<I>> ContentEntry<I, S> {
     pub fn new(content: Content<I, S>) -> Self {
-        Self { content }
+        Self { content, posts: 0 }
+    }
+
+    pub fn posts(mut self, posts: usize) -> Self {
+        self.posts = posts;
+        self
     }
 }
 
@@ -360,7 +769,7 @@ impl VisualizeRoute<MangaTag, ExternalContent> for MangaTag {
 }
 
 impl<I: IndexTag + VisualizeRoute<I, S>, S: ContentType<I>> PartialEq for ContentEntry<I, S> {
-    fn eq(&self, _other: &Self) -> bool {
-        true
+    fn eq(&self, other: &Self) -> bool {
+        self.posts == other.posts
     }
 }
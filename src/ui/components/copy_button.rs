@@ -0,0 +1,17 @@
+use freya::{clipboard::Clipboard, prelude::*};
+use tracing::warn;
+
+use crate::ui::{components::svg_button, icons};
+
+/// Small icon button that puts `text` in the system clipboard when pressed.
+pub fn copy_button(text: impl Into<String>, color: Color) -> Button {
+    let text: String = text.into();
+
+    svg_button(icons::COPY_ICON, 18., color)
+        .on_press(move |_| {
+            if let Err(e) = Clipboard::set(text.clone()) {
+                warn!("Failed to copy to clipboard: {:?}", e);
+            }
+        })
+        .hover_background(Color::TRANSPARENT)
+}
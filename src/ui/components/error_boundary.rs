@@ -0,0 +1,67 @@
+use std::{
+    any::Any,
+    cell::RefCell,
+    panic::{AssertUnwindSafe, catch_unwind},
+    rc::Rc,
+};
+
+use freya::prelude::*;
+use tracing::error;
+
+use crate::ui::{DEFAULT_PAGE_PADDING, Route, RouteContext};
+
+/// Renders `C` in its own scope and shows an error panel instead if it
+/// panics, so one broken page doesn't take the app and the node with it.
+///
+/// `C` is rendered directly, its hooks live in the boundary. A boundary that
+/// caught a panic never renders `C` again, its hooks may have been left half
+/// registered. Going to another page starts a new one.
+#[derive(PartialEq)]
+pub struct ErrorBoundary<C: Component + PartialEq + 'static>(pub C);
+
+impl<C: Component + PartialEq + 'static> Component for ErrorBoundary<C> {
+    fn render(&self) -> impl IntoElement {
+        let failure = use_hook(|| Rc::new(RefCell::new(None::<String>)));
+
+        if let Some(message) = failure.borrow().clone() {
+            return error_panel(message);
+        }
+
+        match catch_unwind(AssertUnwindSafe(|| self.0.render().into_element())) {
+            Ok(element) => element,
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                error!(
+                    "{} panicked while rendering: {}",
+                    std::any::type_name::<C>(),
+                    message
+                );
+                *failure.borrow_mut() = Some(message.clone());
+                error_panel(message)
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown error".to_string()
+    }
+}
+
+fn error_panel(message: String) -> Element {
+    rect()
+        .padding(DEFAULT_PAGE_PADDING)
+        .spacing(15.)
+        .child(label().text("This page broke").font_size(32))
+        .child(format!("It failed with: {}", message))
+        .child("The rest of the app keeps running, the failure was logged.")
+        .child(Button::new().child("Go home").on_press(|_| {
+            RouteContext::get().push(Route::Home);
+        }))
+        .into_element()
+}
@@ -0,0 +1,62 @@
+use freya::prelude::*;
+
+use crate::{
+    types::Link,
+    ui::{DEFAULT_CORNER_RADIUS, Route, RouteContext},
+};
+
+/// Box to paste a [`Link`] into and open what it points at
+#[derive(PartialEq)]
+pub struct GoTo;
+
+impl Component for GoTo {
+    fn render(&self) -> impl IntoElement {
+        let mut text = use_state(String::new);
+        let mut error = use_state(|| None::<String>);
+
+        let go = move |_| {
+            let parsed = text.read().parse::<Link>();
+            match parsed {
+                Ok(link) => {
+                    error.set(None);
+                    text.set(String::new());
+                    RouteContext::get().push(Route::from(link));
+                }
+                Err(e) => error.set(Some(e.to_string())),
+            }
+        };
+
+        rect()
+            .width(Size::Fill)
+            .padding(5.)
+            .spacing(5.)
+            .child(
+                rect()
+                    .horizontal()
+                    .width(Size::Fill)
+                    .content(Content::Flex)
+                    .spacing(5.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Input::new(text)
+                            .placeholder(Link::SCHEME)
+                            .corner_radius(DEFAULT_CORNER_RADIUS)
+                            .width(Size::flex(1.)),
+                    )
+                    .child(
+                        Button::new()
+                            .child("Go")
+                            .enabled(!text.read().trim().is_empty())
+                            .on_press(go),
+                    ),
+            )
+            .maybe(error.read().is_some(), |r| {
+                r.child(
+                    label()
+                        .text(error.read().clone().unwrap_or_default())
+                        .color(Color::RED)
+                        .font_size(12),
+                )
+            })
+    }
+}
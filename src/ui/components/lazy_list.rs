@@ -0,0 +1,34 @@
+use std::rc::Rc;
+
+use freya::prelude::*;
+
+/// Gap left under every row, counted inside `item_size`
+const ROW_GAP: f32 = 10.;
+
+/// Scrollable list that only builds the rows currently in view, so libraries
+/// with thousands of entries don't construct thousands of elements.
+///
+/// Rows are laid out with a fixed `item_size` (gap included) so the visible
+/// range can be known without measuring them, anything taller gets clipped.
+pub fn lazy_list<T: 'static>(
+    items: Vec<T>,
+    item_size: f32,
+    builder: impl Fn(&T) -> Element + 'static,
+) -> VirtualScrollView {
+    let length = items.len();
+    let items = Rc::new(items);
+
+    VirtualScrollView::new(move |i, _| {
+        rect()
+            .width(Size::Fill)
+            .height(Size::px(item_size))
+            .padding((0., 0., ROW_GAP, 0.))
+            .overflow(Overflow::Clip)
+            .child(builder(&items[i]))
+            .into_element()
+    })
+    .length(length)
+    .item_size(item_size)
+    .width(Size::Fill)
+    .height(Size::Fill)
+}
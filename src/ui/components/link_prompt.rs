@@ -0,0 +1,134 @@
+use freya::{
+    prelude::*,
+    query::{Query, QueryStateData, use_query},
+    radio::use_radio,
+};
+
+use crate::{
+    db::index::tags::MangaTag,
+    ui::{
+        AppChannel, DEFAULT_CORNER_RADIUS, Route, RouteContext, components::AkLayers,
+        link_handler::ExternalLink, queries::FetchContentsByInfoHash,
+    },
+};
+
+/// Asks before opening a link handed over by another app, nothing is looked
+/// up on the network until the user agrees
+#[derive(PartialEq)]
+pub struct LinkPrompt;
+
+impl Component for LinkPrompt {
+    fn render(&self) -> impl IntoElement {
+        let radio = use_radio(AppChannel::Links);
+        let pending = radio.read().pending_link.clone();
+
+        let Some(pending) = pending else {
+            return rect().into_element();
+        };
+
+        rect()
+            .layer(AkLayers::Frame)
+            .position(Position::new_absolute().left(20.).top(20.))
+            .width(Size::px(360.))
+            .padding(10.)
+            .spacing(5.)
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::from_rgb(40, 40, 40))
+            .child(
+                label()
+                    .text("Another app sent a link")
+                    .font_weight(FontWeight::BOLD)
+                    .color(Color::WHITE),
+            )
+            .child(match pending {
+                ExternalLink::Link(link) => Confirm {
+                    text: format!("Open {}?", link),
+                    color: Color::WHITE,
+                    route: Some(Route::from(link)),
+                }
+                .into_element(),
+                ExternalLink::Magnet(info_hash) => MagnetConfirm { info_hash }.into_element(),
+            })
+            .into_element()
+    }
+}
+
+/// What the link opens, and where to if there's anywhere to go
+#[derive(PartialEq)]
+struct Confirm {
+    text: String,
+    color: Color,
+    route: Option<Route>,
+}
+
+impl Component for Confirm {
+    fn render(&self) -> impl IntoElement {
+        let mut radio = use_radio(AppChannel::Links);
+        let route = self.route.clone();
+
+        rect()
+            .width(Size::Fill)
+            .spacing(5.)
+            .child(label().text(self.text.clone()).color(self.color))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(5.)
+                    .maybe(route.is_some(), |r| {
+                        r.child(Button::new().child("Open").on_press(move |_| {
+                            radio.write().pending_link = None;
+                            if let Some(route) = route.clone() {
+                                RouteContext::get().push(route);
+                            }
+                        }))
+                    })
+                    .child(Button::new().child("Ignore").on_press(move |_| {
+                        radio.write().pending_link = None;
+                    })),
+            )
+    }
+}
+
+/// Magnets are opened at the release that has them, if we know one
+#[derive(PartialEq)]
+struct MagnetConfirm {
+    info_hash: String,
+}
+
+impl Component for MagnetConfirm {
+    fn render(&self) -> impl IntoElement {
+        let contents_query = use_query(Query::new(
+            self.info_hash.clone(),
+            FetchContentsByInfoHash::<MangaTag>::new(),
+        ));
+
+        let (text, color, route) = match &*contents_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(contents), ..
+            }
+            | QueryStateData::Loading {
+                res: Some(Ok(contents)),
+            } => match contents.first() {
+                Some(content) => (
+                    format!("Open the release of {}?", content.title()),
+                    Color::WHITE,
+                    Some(Route::Manga {
+                        hash: content.index_hash().clone(),
+                    }),
+                ),
+                None => (
+                    "No release we know has this torrent".to_string(),
+                    Color::WHITE,
+                    None,
+                ),
+            },
+            QueryStateData::Settled { res: Err(e), .. }
+            | QueryStateData::Loading { res: Some(Err(e)) } => (e.to_string(), Color::RED, None),
+            QueryStateData::Pending { .. } | QueryStateData::Loading { .. } => {
+                ("Looking for the release...".to_string(), Color::WHITE, None)
+            }
+        };
+
+        Confirm { text, color, route }
+    }
+}
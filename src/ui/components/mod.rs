@@ -2,10 +2,26 @@ use freya::prelude::*;
 
 mod circular_progress_bar;
 mod content_entry;
+mod copy_button;
+mod error_boundary;
+mod go_to;
 mod layout_button;
+mod lazy_list;
+mod link_prompt;
+mod staged;
+mod status_bar;
+mod toast;
 
 pub use content_entry::ContentEntry;
+pub use copy_button::copy_button;
+pub use error_boundary::ErrorBoundary;
+pub use go_to::GoTo;
 pub use layout_button::layout_button;
+pub use lazy_list::lazy_list;
+pub use link_prompt::LinkPrompt;
+pub use staged::{StageState, StagedArea, StagedReleases, UNDO_WINDOW};
+pub use status_bar::StatusBar;
+pub use toast::{ToastArea, Toasts};
 
 pub enum AkLayers {
     Frame,
@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use freya::{prelude::*, radio::use_radio};
+
+use crate::{
+    db::index::release::Release,
+    types::Timestamp,
+    ui::{AppChannel, DEFAULT_CORNER_RADIUS, components::AkLayers},
+};
+
+/// How long a release waits before it's stored and announced. Once announced
+/// the signatures are out and taking it back is up to every peer that got it.
+pub const UNDO_WINDOW: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagedRelease {
+    id: u64,
+    pub release: Release,
+    pub publish_at: Timestamp,
+}
+
+impl StagedRelease {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageState {
+    Staged,
+    Undone,
+    Sent,
+}
+
+/// Releases signed and published in the UI that haven't left this machine
+/// yet, taken by the app manager once their window is over
+#[derive(Default)]
+pub struct StagedReleases {
+    next_id: u64,
+    staged: Vec<StagedRelease>,
+    undone: Vec<u64>,
+}
+
+impl StagedReleases {
+    pub fn stage(&mut self, release: Release) -> u64 {
+        let id = self.next_id;
+        self.staged.push(StagedRelease {
+            id,
+            release,
+            publish_at: Timestamp::now() + UNDO_WINDOW.as_secs() as i64,
+        });
+        self.next_id += 1;
+        id
+    }
+
+    /// Drops the release if it's still waiting, `false` if it already went out
+    pub fn undo(&mut self, id: u64) -> bool {
+        let Some(i) = self.staged.iter().position(|s| s.id == id) else {
+            return false;
+        };
+        self.staged.remove(i);
+        self.undone.push(id);
+        true
+    }
+
+    /// Takes out the releases whose window is over
+    pub fn take_due(&mut self, now: Timestamp) -> Vec<Release> {
+        let (due, waiting) = std::mem::take(&mut self.staged)
+            .into_iter()
+            .partition(|s| s.publish_at <= now);
+        self.staged = waiting;
+        due.into_iter().map(|s: StagedRelease| s.release).collect()
+    }
+
+    pub fn state(&self, id: u64) -> StageState {
+        if self.staged.iter().any(|s| s.id == id) {
+            StageState::Staged
+        } else if self.undone.contains(&id) {
+            StageState::Undone
+        } else {
+            StageState::Sent
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StagedRelease> {
+        self.staged.iter()
+    }
+}
+
+/// Stacks the releases waiting to go out in the bottom left corner, each with
+/// a way to take it back
+#[derive(PartialEq)]
+pub struct StagedArea;
+
+impl Component for StagedArea {
+    fn render(&self) -> impl IntoElement {
+        let mut radio = use_radio(AppChannel::Staged);
+        let staged: Vec<StagedRelease> = radio.read().staged.iter().cloned().collect();
+
+        // Re-rendered every second to count down the time left
+        let mut tick = use_state(|| 0u64);
+        use_hook(move || {
+            spawn(async move {
+                loop {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    *tick.write() += 1;
+                }
+            })
+        });
+        let _ = tick.read();
+
+        let now = Timestamp::now();
+        rect()
+            .layer(AkLayers::Frame)
+            .position(Position::new_absolute().left(20.).bottom(20.))
+            .width(Size::px(320.))
+            .spacing(10.)
+            .children(staged.into_iter().map(|staged| {
+                let id = staged.id();
+                let left = (staged.publish_at.as_secs() - now.as_secs()).max(0);
+
+                rect()
+                    .width(Size::Fill)
+                    .padding(10.)
+                    .spacing(5.)
+                    .corner_radius(DEFAULT_CORNER_RADIUS)
+                    .background(Color::from_rgb(40, 40, 40))
+                    .child(
+                        label()
+                            .text(format!("Publishing {}", staged.release.index.title()))
+                            .font_weight(FontWeight::BOLD)
+                            .color(Color::WHITE),
+                    )
+                    .child(
+                        label()
+                            .text(format!(
+                                "{} chapters go out in {}s",
+                                staged.release.contents.len(),
+                                left
+                            ))
+                            .color(Color::WHITE),
+                    )
+                    .child(Button::new().child("Undo").on_press(move |_| {
+                        radio.write().staged.undo(id);
+                    }))
+                    .into()
+            }))
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use anawt::TorrentState;
+use freya::{
+    prelude::*,
+    query::{Query, QueryStateData, use_query},
+    radio::use_radio,
+};
+
+use crate::{
+    db::changes::DataKind,
+    helpers::format_bytes,
+    ui::{
+        AppChannel, ResourceState,
+        hooks::{use_data_changed, use_view_task},
+        icons,
+        queries::FetchTorrentWatchers,
+    },
+};
+
+/// Exchange counters and download rates aren't pushed, they're read again
+/// this often
+const STATUS_REFRESH: Duration = Duration::from_secs(2);
+
+/// Bar under every page with whether I2P is up, the exchanges in progress,
+/// the downloads and the toasts still shown
+#[derive(PartialEq)]
+pub struct StatusBar;
+
+impl Component for StatusBar {
+    fn render(&self) -> impl IntoElement {
+        let radio = use_radio(AppChannel::Status);
+        let toasts = use_radio(AppChannel::Toasts);
+        let links_version = use_data_changed(DataKind::TorrentLinks);
+        let watchers_query = use_query(Query::new(links_version, FetchTorrentWatchers));
+
+        let mut tick = use_state(|| 0u64);
+        let ticker = use_view_task();
+        use_hook(move || {
+            ticker.spawn(async move {
+                loop {
+                    tokio::time::sleep(STATUS_REFRESH).await;
+                    *tick.write() += 1;
+                }
+            })
+        });
+        let _ = tick.read();
+
+        let (i2p, i2p_color) = match (&radio.read().server, &radio.read().client) {
+            (ResourceState::Loaded(_), ResourceState::Loaded(_)) => ("I2P connected", Color::GREEN),
+            (ResourceState::Error(_), _) | (_, ResourceState::Error(_)) => {
+                ("I2P unreachable", Color::RED)
+            }
+            (ResourceState::Pending, ResourceState::Pending) => {
+                ("I2P not started", Color::LIGHT_GRAY)
+            }
+            _ => ("Connecting to I2P", Color::YELLOW),
+        };
+
+        let incoming = match &radio.read().server {
+            ResourceState::Loaded(metrics) => metrics.active(),
+            _ => 0,
+        };
+        let outgoing = match &radio.read().client {
+            ResourceState::Loaded(pool) => pool.in_use(),
+            _ => 0,
+        };
+
+        let (downloading, rate) = match &*watchers_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(watchers), ..
+            } => watchers
+                .iter()
+                .map(|w| {
+                    let status = w.borrow();
+                    let downloading = status.state == TorrentState::Downloading;
+                    (downloading, status.download_rate as i64)
+                })
+                .filter(|(downloading, _)| *downloading)
+                .fold((0, 0), |(count, total), (_, rate)| {
+                    (count + 1, total + rate)
+                }),
+            _ => (0, 0),
+        };
+        let downloads = match downloading {
+            0 => "No downloads".to_string(),
+            count => format!("{} downloading at {}/s", count, format_bytes(rate)),
+        };
+
+        let shown_toasts = toasts.read().toasts.iter().count();
+
+        rect()
+            .horizontal()
+            .width(Size::Fill)
+            .padding(5.)
+            .spacing(20.)
+            .cross_align(Alignment::Center)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(5.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        svg(icons::CIRCLE)
+                            .fill(i2p_color)
+                            .stroke_width(12.)
+                            .stroke(Color::BLACK)
+                            .height(Size::px(10.)),
+                    )
+                    .child(label().text(i2p).font_size(12.)),
+            )
+            .child(
+                label()
+                    .text(format!(
+                        "{} exchanges ({} in, {} out)",
+                        incoming + outgoing,
+                        incoming,
+                        outgoing
+                    ))
+                    .font_size(12.),
+            )
+            .child(label().text(downloads).font_size(12.))
+            .child(
+                label()
+                    .text(format!("{} notifications", shown_toasts))
+                    .font_size(12.),
+            )
+    }
+}
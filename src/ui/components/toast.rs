@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use freya::{
+    animation::{AnimNum, Ease, Function, OnCreation, use_animation},
+    prelude::*,
+    radio::use_radio,
+};
+
+use crate::{
+    config::{ToastConfig, ToastCorner},
+    ui::{
+        AppChannel, DEFAULT_CORNER_RADIUS, ResourceState, components::AkLayers,
+        hooks::use_view_task,
+    },
+};
+
+/// How long a toast stays up unless closed earlier
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+/// How long a toast takes to slide in or out
+const TOAST_ANIMATION: Duration = Duration::from_millis(250);
+/// How far a toast slides in from the side of the window
+const TOAST_SLIDE: f32 = 40.;
+/// Space between the toasts and the corner of the window
+const TOAST_MARGIN: f32 = 20.;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    id: u64,
+    pub title: String,
+    pub body: String,
+}
+
+/// Toasts currently shown over the app, newest last
+#[derive(Default)]
+pub struct Toasts {
+    next_id: u64,
+    shown: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, title: String, body: String) {
+        self.shown.push(Toast {
+            id: self.next_id,
+            title,
+            body,
+        });
+        self.next_id += 1;
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.shown.retain(|t| t.id != id);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.shown.iter()
+    }
+}
+
+/// Stacks the toasts in the corner picked in the settings. Past the most
+/// shown at once, the rest wait behind a counter.
+#[derive(PartialEq)]
+pub struct ToastArea;
+
+impl Component for ToastArea {
+    fn render(&self) -> impl IntoElement {
+        let radio = use_radio(AppChannel::Toasts);
+        let config_radio = use_radio(AppChannel::Config);
+        let config = match &config_radio.read().config {
+            ResourceState::Loaded(c) => c.toasts().clone(),
+            _ => ToastConfig::default(),
+        };
+        let toasts: Vec<Toast> = radio.read().toasts.iter().cloned().collect();
+
+        let max_visible = (config.max_visible as usize).max(1);
+        let waiting = toasts.len().saturating_sub(max_visible);
+        let corner = config.corner;
+
+        let position = match corner {
+            ToastCorner::TopLeft => Position::new_absolute()
+                .left(TOAST_MARGIN)
+                .top(TOAST_MARGIN),
+            ToastCorner::TopRight => Position::new_absolute()
+                .right(TOAST_MARGIN)
+                .top(TOAST_MARGIN),
+            ToastCorner::BottomLeft => Position::new_absolute()
+                .left(TOAST_MARGIN)
+                .bottom(TOAST_MARGIN),
+            ToastCorner::BottomRight => Position::new_absolute()
+                .right(TOAST_MARGIN)
+                .bottom(TOAST_MARGIN),
+        };
+
+        rect()
+            .layer(AkLayers::Frame)
+            .position(position)
+            .width(Size::px(320.))
+            .spacing(10.)
+            .children(
+                toasts
+                    .into_iter()
+                    .take(max_visible)
+                    .map(|toast| ToastCard { toast, corner }.into_element()),
+            )
+            .maybe(waiting > 0, |r| {
+                r.child(
+                    rect()
+                        .padding(5.)
+                        .corner_radius(DEFAULT_CORNER_RADIUS)
+                        .background(Color::from_rgb(40, 40, 40))
+                        .child(
+                            label()
+                                .text(format!("+{} more", waiting))
+                                .color(Color::WHITE),
+                        ),
+                )
+            })
+    }
+}
+
+#[derive(PartialEq)]
+struct ToastCard {
+    toast: Toast,
+    /// Toasts slide in from the side of the window they're on
+    corner: ToastCorner,
+}
+
+impl Component for ToastCard {
+    fn render(&self) -> impl IntoElement {
+        let mut radio = use_radio(AppChannel::Toasts);
+        let id = self.toast.id;
+
+        let mut animation = use_animation(|conf| {
+            conf.on_creation(OnCreation::Run);
+            AnimNum::new(0., 1.)
+                .time(TOAST_ANIMATION.as_millis() as u64)
+                .ease(Ease::Out)
+                .function(Function::Cubic)
+        });
+
+        // Plays the animation backwards before the toast is removed
+        let mut closing = use_state(|| false);
+        let close = move || {
+            if *closing.read() {
+                return;
+            }
+            closing.set(true);
+            animation.reverse();
+            spawn(async move {
+                tokio::time::sleep(TOAST_ANIMATION).await;
+                radio.write().toasts.dismiss(id);
+            });
+        };
+
+        // Cancelled when the toast is closed by hand and goes away first
+        let timer = use_view_task();
+        use_hook(move || {
+            timer.spawn(async move {
+                tokio::time::sleep(TOAST_DURATION).await;
+                close();
+            })
+        });
+
+        let shown = animation.get().value();
+        let side = match self.corner.is_left() {
+            true => -1.,
+            false => 1.,
+        };
+
+        rect()
+            .width(Size::Fill)
+            .padding(10.)
+            .spacing(5.)
+            .opacity(shown)
+            .offset_x((1. - shown) * TOAST_SLIDE * side)
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::from_rgb(40, 40, 40))
+            .child(
+                rect()
+                    .horizontal()
+                    .width(Size::Fill)
+                    .content(Content::Flex)
+                    .child(
+                        label()
+                            .text(self.toast.title.clone())
+                            .font_weight(FontWeight::BOLD)
+                            .color(Color::WHITE)
+                            .width(Size::flex(1.)),
+                    )
+                    .child(Button::new().child("✕").on_press(move |_| close())),
+            )
+            .child(label().text(self.toast.body.clone()).color(Color::WHITE))
+    }
+}
@@ -0,0 +1,45 @@
+use std::{cell::RefCell, rc::Rc};
+
+use freya::{prelude::*, radio::use_radio};
+
+use crate::{db::changes::DataKind, ui::AppChannel};
+
+/// Re-renders the component whenever data of `kind` is written, returning how
+/// many writes happened since the app started. Handy as a query key or to tell
+/// apart what's new since the view was opened.
+pub fn use_data_changed(kind: DataKind) -> u64 {
+    let radio = use_radio(AppChannel::Data);
+    radio.read().data_versions.get(kind)
+}
+
+/// Task of a view that shouldn't outlive it, see [`use_view_task`]
+#[derive(Clone, Default)]
+pub struct ViewTask(Rc<RefCell<Option<TaskHandle>>>);
+
+impl ViewTask {
+    /// Cancels the task still running, if any, and starts `task` instead
+    pub fn spawn(&self, task: impl Future<Output = ()> + 'static) {
+        self.replace(Some(spawn(task)));
+    }
+
+    pub fn cancel(&self) {
+        self.replace(None);
+    }
+
+    fn replace(&self, task: Option<TaskHandle>) {
+        if let Some(old) = self.0.replace(task) {
+            old.try_cancel();
+        }
+    }
+}
+
+/// Holds one task at a time for the component. Spawning another cancels the
+/// one still running so a slow result never lands after a newer one, and the
+/// last one is cancelled when the component is dropped, so a view that was
+/// left never gets written to.
+pub fn use_view_task() -> ViewTask {
+    let task = use_hook(ViewTask::default);
+    let dropped = task.clone();
+    use_drop(move || dropped.cancel());
+    task
+}
@@ -32,4 +32,6 @@ icon!(
     "../../assets/icons/arrow-circle-up.svg"
 );
 icon!(PLUS_ICON, "../../assets/icons/plus.svg");
+icon!(COPY_ICON, "../../assets/icons/copy.svg");
 icon!(CIRCLE, "../../assets/icons/circle-fill.svg");
+icon!(TRASH_ICON, "../../assets/icons/trash.svg");
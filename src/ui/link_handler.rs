@@ -0,0 +1,251 @@
+//! Links opened from other apps. The OS starts a new process with the link,
+//! which hands it to the instance already running through a loopback socket
+//! whose port is kept in [`HANDOFF_FILE`], or opens it itself if there's none.
+//! Nothing is done with a link until the user confirms it.
+
+use std::{
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::UnboundedSender,
+};
+use tracing::{info, warn};
+
+use crate::{
+    db::Magnet,
+    errors::LinkHandlerError,
+    types::{InvalidLink, Link},
+    ui::app_manager::Event,
+};
+
+/// Next to the config, holds the port the running instance takes links on
+pub const HANDOFF_FILE: &str = "handoff.port";
+/// Longest line read from the socket, links are far shorter
+const MAX_HANDOFF_LEN: u64 = 4096;
+
+/// Something another app asked us to open
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternalLink {
+    Link(Link),
+    /// Info hash of a magnet, opened at the release that has it
+    Magnet(String),
+}
+
+impl FromStr for ExternalLink {
+    type Err = InvalidLink;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with("magnet:") {
+            return Magnet(s.to_string())
+                .info_hash()
+                .map(ExternalLink::Magnet)
+                .ok_or(InvalidLink);
+        }
+        s.parse().map(ExternalLink::Link)
+    }
+}
+
+/// Gives `link` to the instance listening through `handoff_file`, `false` if
+/// there's none and this one has to open it
+pub async fn hand_off(handoff_file: &Path, link: &str) -> bool {
+    let Ok(port) = tokio::fs::read_to_string(handoff_file).await else {
+        return false;
+    };
+    let Ok(port) = port.trim().parse::<u16>() else {
+        return false;
+    };
+    // Left over by an instance that didn't exit cleanly if nothing answers
+    let Ok(mut stream) = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await else {
+        return false;
+    };
+
+    stream
+        .write_all(format!("{}\n", link.trim()).as_bytes())
+        .await
+        .is_ok()
+}
+
+/// Takes links handed off by later instances and sends them to the app
+/// manager, for as long as the app runs
+pub async fn listen(handoff_file: PathBuf, tx: UnboundedSender<Event>) {
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Failed to listen for links: {}", e);
+            return;
+        }
+    };
+    let port = match listener.local_addr() {
+        Ok(address) => address.port(),
+        Err(e) => {
+            warn!("Failed to listen for links: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = tokio::fs::write(&handoff_file, port.to_string()).await {
+        warn!("Failed to write {}: {}", handoff_file.display(), e);
+        return;
+    }
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to take a link: {}", e);
+                continue;
+            }
+        };
+
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut line = String::new();
+            let mut reader = BufReader::new(stream.take(MAX_HANDOFF_LEN));
+            if reader.read_line(&mut line).await.is_err() {
+                return;
+            }
+
+            match line.parse::<ExternalLink>() {
+                Ok(link) => {
+                    let _ = tx.send(Event::OpenLink(link));
+                }
+                Err(_) => warn!("Handed off something that isn't a link"),
+            }
+        });
+    }
+}
+
+/// Makes the OS open `akareko://` links with this executable, and magnets
+/// too if `magnets`. The current folder is passed along so the config is
+/// found wherever the OS starts us from.
+pub fn register(magnets: bool) -> Result<(), LinkHandlerError> {
+    let exe = std::env::current_exe()?;
+    let dir = std::env::current_dir()?;
+    let mut schemes = vec!["akareko"];
+    if magnets {
+        schemes.push("magnet");
+    }
+
+    register_schemes(&exe, &dir, &schemes)?;
+    info!("Registered as the handler of {}", schemes.join(", "));
+    Ok(())
+}
+
+fn run(command: &'static str, args: &[&str]) -> Result<(), LinkHandlerError> {
+    if Command::new(command).args(args).status()?.success() {
+        Ok(())
+    } else {
+        Err(LinkHandlerError::CommandFailed { command })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn register_schemes(exe: &Path, dir: &Path, schemes: &[&str]) -> Result<(), LinkHandlerError> {
+    const DESKTOP_FILE: &str = "akareko-links.desktop";
+
+    let applications = dirs::data_dir()
+        .ok_or(LinkHandlerError::Unsupported)?
+        .join("applications");
+    std::fs::create_dir_all(&applications)?;
+
+    let mime_types: String = schemes
+        .iter()
+        .map(|s| format!("x-scheme-handler/{};", s))
+        .collect();
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=Akareko\nExec=\"{}\" --dir \"{}\" %u\nNoDisplay=true\nMimeType={}\n",
+        exe.display(),
+        dir.display(),
+        mime_types
+    );
+    std::fs::write(applications.join(DESKTOP_FILE), entry)?;
+
+    for scheme in schemes {
+        run(
+            "xdg-mime",
+            &[
+                "default",
+                DESKTOP_FILE,
+                &format!("x-scheme-handler/{}", scheme),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+fn register_schemes(exe: &Path, dir: &Path, schemes: &[&str]) -> Result<(), LinkHandlerError> {
+    let command = format!("\"{}\" --dir \"{}\" \"%1\"", exe.display(), dir.display());
+
+    for scheme in schemes {
+        let key = format!("HKCU\\Software\\Classes\\{}", scheme);
+        let name = format!("URL:{}", scheme);
+        let open = format!("{}\\shell\\open\\command", key);
+        run("reg", &["add", &key, "/ve", "/d", &name, "/f"])?;
+        run("reg", &["add", &key, "/v", "URL Protocol", "/d", "", "/f"])?;
+        run("reg", &["add", &open, "/ve", "/d", &command, "/f"])?;
+    }
+    Ok(())
+}
+
+/// Schemes are declared in the app bundle there, nothing to do at runtime
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn register_schemes(_: &Path, _: &Path, _: &[&str]) -> Result<(), LinkHandlerError> {
+    Err(LinkHandlerError::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Hash;
+
+    #[test]
+    fn magnets_are_opened_by_info_hash() {
+        let link = Link::Index(Hash::digest(b"index"));
+        let hash = "c12fe1c06bba254a9dc9f519b335aa7c1367a88a";
+
+        assert_eq!(
+            link.to_string().parse(),
+            Ok(ExternalLink::Link(link.clone()))
+        );
+        assert_eq!(
+            format!("magnet:?xt=urn:btih:{}&dn=Chapter", hash).parse(),
+            Ok(ExternalLink::Magnet(hash.to_string()))
+        );
+        assert_eq!(
+            "magnet:?dn=Chapter".parse::<ExternalLink>(),
+            Err(InvalidLink)
+        );
+    }
+
+    #[tokio::test]
+    async fn links_are_handed_to_the_running_instance() {
+        let handoff_file =
+            std::env::temp_dir().join(format!("akareko-handoff-{}", std::process::id()));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(listen(handoff_file.clone(), tx));
+
+        let link = Link::Index(Hash::digest(b"index"));
+        let mut handed_off = false;
+        for _ in 0..50 {
+            handed_off = hand_off(&handoff_file, &link.to_string()).await;
+            if handed_off {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        assert!(handed_off);
+        assert!(matches!(
+            rx.recv().await,
+            Some(Event::OpenLink(ExternalLink::Link(l))) if l == link
+        ));
+        std::fs::remove_file(handoff_file).unwrap();
+    }
+}
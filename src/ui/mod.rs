@@ -1,26 +1,36 @@
+use std::collections::HashMap;
+
 use anawt::TorrentClient;
 use freya::{
     prelude::*,
-    radio::{RadioChannel, RadioStation, use_share_radio},
+    radio::{RadioChannel, RadioStation, use_radio, use_share_radio},
 };
 
 use crate::{
     config::AkarekoConfig,
     db::{
         Repositories,
+        changes::DataKind,
         index::{Index, tags::IndexTag},
     },
-    server::client::pool::ClientPool,
+    server::{ServerMetrics, client::pool::ClientPool},
+    types::Topic,
     ui::{
-        components::{layout_button, no_reaction_button},
+        components::{
+            GoTo, LinkPrompt, StagedArea, StagedReleases, StatusBar, ToastArea, Toasts,
+            layout_button, no_reaction_button,
+        },
         icons::ARROW_LEFT_ICON,
+        link_handler::ExternalLink,
         router::RouteComponent,
     },
 };
 
 pub mod app_manager;
 mod components;
+mod hooks;
 mod icons;
+pub mod link_handler;
 mod queries;
 mod router;
 mod theme;
@@ -36,20 +46,25 @@ const UNKNOWN_COVER: (&'static str, Bytes) = (
 #[derive(Clone)]
 struct IndexComponent<I: IndexTag + 'static> {
     index: Index<I>,
+    /// Posts under the index's own topic
+    posts: usize,
+    /// Peers that had the index, see
+    /// [`Popularity`](crate::db::content_source::Popularity)
+    peers: usize,
 }
 impl<'a, I: IndexTag> PartialEq for IndexComponent<I> {
     fn eq(&self, other: &Self) -> bool {
         self.index.hash() == other.index.hash()
+            && self.posts == other.posts
+            && self.peers == other.peers
     }
 }
 
 impl<I: IndexTag + 'static> Component for IndexComponent<I> {
     fn render(&self) -> impl IntoElement {
-        let i = self.index.clone();
+        let hash = self.index.hash().clone();
         let on_press = move |_| {
-            RouteContext::get().push(Route::Manga {
-                index: i.clone().transmute(),
-            });
+            RouteContext::get().push(Route::Manga { hash: hash.clone() });
         };
 
         let cover_image = no_reaction_button()
@@ -68,15 +83,51 @@ impl<I: IndexTag + 'static> Component for IndexComponent<I> {
             .with_corner_radius(DEFAULT_CORNER_RADIUS)
             .child(cover_image)
             .child(
-                rect().width(Size::px(250.)).child(
-                    no_reaction_button()
-                        .child(
+                rect()
+                    .width(Size::px(250.))
+                    .spacing(5.)
+                    .child(
+                        no_reaction_button()
+                            .child(
+                                label()
+                                    .text(self.index.title().clone())
+                                    .font_weight(FontWeight::BOLD),
+                            )
+                            .on_press(on_press),
+                    )
+                    .maybe(self.posts > 0, |r| {
+                        let topic = Topic::from_index(&self.index);
+                        let title = self.index.title().clone();
+
+                        r.child(
+                            no_reaction_button()
+                                .child(
+                                    rect()
+                                        .horizontal()
+                                        .spacing(5.)
+                                        .cross_align(Alignment::Center)
+                                        .child(svg(icons::CHAT_ICON).width(Size::px(16.)))
+                                        .child(label().text(self.posts.to_string()).font_size(12)),
+                                )
+                                .on_press(move |_| {
+                                    RouteContext::get().push(Route::Posts {
+                                        topic: topic.clone(),
+                                        title: title.clone(),
+                                    });
+                                }),
+                        )
+                    })
+                    .maybe(self.peers > 0, |r| {
+                        r.child(
                             label()
-                                .text(self.index.title().clone())
-                                .font_weight(FontWeight::BOLD),
+                                .text(match self.peers {
+                                    1 => "Seen from 1 peer".to_string(),
+                                    n => format!("Seen from {} peers", n),
+                                })
+                                .font_size(12)
+                                .color(Color::GRAY),
                         )
-                        .on_press(on_press),
-                ),
+                    }),
             )
     }
 }
@@ -104,6 +155,11 @@ pub enum AppChannel {
     Server,
     Client,
     TorrentClient,
+    Data,
+    Toasts,
+    Staged,
+    Links,
+    Drafts,
 
     Window,
 }
@@ -155,11 +211,35 @@ pub struct AppState {
     pub config: ResourceState<AkarekoConfig, ()>,
     pub repositories: ResourceState<Repositories, ()>,
     pub torrent_client: ResourceState<TorrentClient, ()>,
-    pub server: ResourceState<(), ()>,
+    pub server: ResourceState<ServerMetrics, ()>,
     pub client: ResourceState<ClientPool, ()>,
+    pub data_versions: DataVersions,
+    pub toasts: Toasts,
+    pub staged: StagedReleases,
+    /// Opened from another app, waiting for the user to confirm it
+    pub pending_link: Option<ExternalLink>,
+    /// Unsent post of each topic, kept while the app runs
+    pub drafts: HashMap<Topic, String>,
     pub windows_state: AppWindowState,
 }
 
+/// How many times each [`DataKind`] was written, see
+/// [`hooks::use_data_changed`]
+#[derive(Default)]
+pub struct DataVersions {
+    versions: HashMap<DataKind, u64>,
+}
+
+impl DataVersions {
+    pub fn get(&self, kind: DataKind) -> u64 {
+        self.versions.get(&kind).copied().unwrap_or_default()
+    }
+
+    pub fn bump(&mut self, kind: DataKind) {
+        *self.versions.entry(kind).or_default() += 1;
+    }
+}
+
 pub struct AppWindowState {
     windows: Vec<AppWindowType>,
 }
@@ -195,6 +275,11 @@ impl AppState {
             torrent_client: ResourceState::Pending,
             server: ResourceState::Pending,
             client: ResourceState::Pending,
+            data_versions: DataVersions::default(),
+            toasts: Toasts::default(),
+            staged: StagedReleases::default(),
+            pending_link: None,
+            drafts: HashMap::new(),
             windows_state: AppWindowState::new(),
         }
     }
@@ -231,9 +316,15 @@ impl App for AkarekoApp {
 struct Layout;
 impl Component for Layout {
     fn render(&self) -> impl IntoElement {
-        rect()
+        let radio = use_radio(AppChannel::Config);
+        // Nothing exchanged is shown on relay-only nodes, so there's no library
+        let relay_only =
+            matches!(&radio.read().config, ResourceState::Loaded(c) if c.relay_only().enabled);
+
+        let main = rect()
             .horizontal()
-            .expanded()
+            .width(Size::Fill)
+            .height(Size::flex(1.))
             .child(
                 rect()
                     .vertical()
@@ -249,10 +340,19 @@ impl Component for Layout {
                                 }),
                         ),
                     )
+                    .child(GoTo)
                     .child(layout_button(Route::Home))
-                    .child(layout_button(Route::MangaList))
+                    .maybe(!relay_only, |r| {
+                        r.child(layout_button(Route::MangaList))
+                            .child(layout_button(Route::History))
+                    })
+                    .child(layout_button(Route::Users))
                     .child(layout_button(Route::Settings))
-                    .child(layout_button(Route::Torrents)),
+                    .maybe(!relay_only, |r| {
+                        r.child(layout_button(Route::Torrents))
+                            .child(layout_button(Route::Conflicts))
+                            .child(layout_button(Route::LibraryStats))
+                    }),
             )
             .child(
                 rect()
@@ -262,7 +362,17 @@ impl Component for Layout {
                     .overflow(Overflow::Clip)
                     .corner_radius(DEFAULT_CORNER_RADIUS)
                     .background(Color::WHITE),
-            )
+            );
+
+        rect()
+            .vertical()
+            .expanded()
+            .content(Content::Flex)
+            .child(main)
+            .child(StatusBar)
+            .child(StagedArea)
+            .child(ToastArea)
+            .child(LinkPrompt)
             .background(Color::GRAY)
     }
 }
@@ -1,12 +1,14 @@
 use anawt::InfoHash;
 use freya::{prelude::*, query::*, radio::RadioStation};
+use tracing::error;
 
 use crate::{
-    db::Magnet,
+    db::{Magnet, torrent_link::TorrentLink},
     errors::TorrentError,
     ui::{
         AppChannel, AppState, ResourceState,
-        queries::{FetchTorrentStatus, FetchTorrentWatchers},
+        app_manager::{client_magnet, watch_torrent_completion, web_seed_fallback},
+        queries::{FetchTorrentLinks, FetchTorrentWatcher, FetchTorrentWatchers},
     },
 };
 
@@ -16,7 +18,12 @@ pub struct AddTorrent;
 impl MutationCapability for AddTorrent {
     type Ok = InfoHash;
     type Err = TorrentError;
-    type Keys = (Magnet, String /* path */);
+    type Keys = (
+        Magnet,
+        String, /* path */
+        Option<TorrentLink>,
+        Option<String>, /* web seed */
+    );
 
     async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
         let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
@@ -24,19 +31,71 @@ impl MutationCapability for AddTorrent {
             return Err(TorrentError::NotInitialized);
         };
 
-        match &radio.read().torrent_client {
-            ResourceState::Loaded(c) => c
-                .add_magnet(&keys.0.0, &keys.1)
+        let (client, repositories) =
+            match (&radio.read().torrent_client, &radio.read().repositories) {
+                (ResourceState::Loaded(c), ResourceState::Loaded(r)) => (c.clone(), r.clone()),
+                _ => return Err(TorrentError::NotInitialized),
+            };
+        let i2p_only = match &radio.read().config {
+            ResourceState::Loaded(c) => c.i2p_only_torrents(),
+            _ => false,
+        };
+        let magnet = client_magnet(&keys.0, i2p_only);
+
+        // Different content can share a torrent, if it's already being
+        // downloaded somewhere we don't want a second copy
+        if let Ok(info_hash) = InfoHash::from_magnet(&magnet.0)
+            && client.get_status(info_hash).await.is_some()
+        {
+            return Ok(info_hash);
+        }
+
+        let info_hash = client
+            .add_magnet(&magnet.0, &keys.1)
+            .await
+            .map_err(|_| TorrentError::Unknown)?;
+
+        if let Some(link) = &keys.2 {
+            if let Err(e) = repositories.upsert_torrent_link(link.clone()).await {
+                error!("Failed to save torrent link: {}", e);
+            }
+        }
+
+        // Without a link there is nothing to record the completion on
+        if let Some(link) = &keys.2
+            && let Some(watcher) = client
+                .subscribe_all()
                 .await
-                .map_err(|_| TorrentError::Unknown),
-            _ => Err(TorrentError::NotInitialized),
+                .into_iter()
+                .find(|w| w.borrow().info_hash == info_hash)
+        {
+            tokio::spawn(watch_torrent_completion(
+                watcher,
+                link.info_hash.clone(),
+                repositories.clone(),
+            ));
         }
+
+        if let Some(web_seed) = &keys.3 {
+            tokio::spawn(web_seed_fallback(
+                client,
+                info_hash,
+                magnet,
+                keys.1.clone(),
+                web_seed.clone(),
+                keys.2.as_ref().map(|l| l.info_hash.clone()),
+                repositories,
+            ));
+        }
+
+        Ok(info_hash)
     }
 
     async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
         if let Ok(hash) = result {
-            QueriesStorage::<FetchTorrentStatus>::invalidate_matching(hash.clone()).await;
+            QueriesStorage::<FetchTorrentWatcher>::invalidate_matching(hash.clone()).await;
             QueriesStorage::<FetchTorrentWatchers>::invalidate_all().await;
+            QueriesStorage::<FetchTorrentLinks>::invalidate_all().await;
         }
     }
 }
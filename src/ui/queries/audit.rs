@@ -0,0 +1,31 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::audit::AuditEntry,
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// How many entries the audit log view shows
+const AUDIT_SHOWN: usize = 200;
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchAuditLog;
+
+impl QueryCapability for FetchAuditLog {
+    type Ok = Vec<AuditEntry>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.get_audit_log(AUDIT_SHOWN).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    errors::UpdateError,
+    server::client::update::ReleaseManifest,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct CheckForUpdate;
+
+impl QueryCapability for CheckForUpdate {
+    type Ok = Option<ReleaseManifest>;
+    type Err = UpdateError;
+    /// Whether the client is up, the check runs again once it is
+    type Keys = bool;
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(UpdateError::NotInitialized);
+        };
+
+        let config = match &radio.read().config {
+            ResourceState::Loaded(c) => c.updates().clone(),
+            _ => return Err(UpdateError::NotInitialized),
+        };
+
+        let pool = match &radio.read().client {
+            ResourceState::Loaded(p) => p.clone(),
+            _ => return Err(UpdateError::NotInitialized),
+        };
+
+        pool.get_client().await.check_for_update(&config).await
+    }
+}
@@ -0,0 +1,75 @@
+use std::marker::PhantomData;
+
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::{audit::AuditEntry, index::tags::IndexTag, suppression::Suppression},
+    errors::DatabaseError,
+    types::{Hash, Signature},
+    ui::{
+        AppChannel, AppState, ResourceState,
+        queries::{FetchContents, FetchSuppressions},
+    },
+};
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct DeleteContent<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> DeleteContent<I> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<I: IndexTag> MutationCapability for DeleteContent<I> {
+    type Ok = ();
+    type Err = DatabaseError;
+    /// Content, its index and, to keep it from being synced back, its
+    /// suppression
+    type Keys = (Signature, Hash, Option<Suppression>);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => {
+                let label = match &keys.2 {
+                    Some(suppression) => suppression.title.clone(),
+                    None => r
+                        .index()
+                        .get_contents::<I>(std::slice::from_ref(&keys.0))
+                        .await?
+                        .first()
+                        .map(|c| Suppression::from_content(c).title)
+                        .unwrap_or_else(|| keys.0.as_base64()),
+                };
+
+                // Suppressed first so an exchange running meanwhile can't
+                // bring it back
+                if let Some(suppression) = &keys.2 {
+                    r.suppress(suppression.clone()).await?;
+                }
+                r.index().remove_content::<I>(keys.0.clone()).await?;
+
+                r.record_audit(AuditEntry::deleted(
+                    keys.0.as_base64(),
+                    label,
+                    keys.2.is_some(),
+                    "Deleted from the library",
+                ))
+                .await
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchContents<I>>::invalidate_matching(keys.1.clone()).await;
+            QueriesStorage::<FetchSuppressions>::invalidate_all().await;
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+use freya::query::*;
+
+use crate::{errors::ExportError, helpers::cbz::export_cbz};
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct ExportChapter;
+
+impl MutationCapability for ExportChapter {
+    /// Number of pages written
+    type Ok = usize;
+    type Err = ExportError;
+    /// Source folder or archive, destination file
+    type Keys = (PathBuf, PathBuf);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        export_cbz(&keys.0, &keys.1).await
+    }
+}
@@ -0,0 +1,39 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::index::{content::Content, tags::IndexTag},
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Contents whose torrent has the info hash given as key
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchContentsByInfoHash<I: IndexTag> {
+    _phantom: std::marker::PhantomData<I>,
+}
+
+impl<I: IndexTag> FetchContentsByInfoHash<I> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I: IndexTag + 'static> QueryCapability for FetchContentsByInfoHash<I> {
+    type Ok = Vec<Content<I>>;
+    type Err = DatabaseError;
+    type Keys = String;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.index().get_contents_by_info_hash::<I>(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::index::{content::Content, tags::IndexTag},
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchInfoHashConflicts<I: IndexTag> {
+    _phantom: std::marker::PhantomData<I>,
+}
+
+impl<I: IndexTag> FetchInfoHashConflicts<I> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I: IndexTag + 'static> QueryCapability for FetchInfoHashConflicts<I> {
+    type Ok = Vec<Vec<Content<I>>>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.index().get_info_hash_conflicts().await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
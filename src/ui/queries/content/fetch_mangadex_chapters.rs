@@ -57,6 +57,8 @@ impl QueryCapability for FetchMangadexChapters {
                 },
                 None,
                 MangaChapter::new(Language::English),
+                vec![],
+                None,
             ));
         }
 
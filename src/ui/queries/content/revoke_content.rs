@@ -0,0 +1,74 @@
+use std::marker::PhantomData;
+
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::{audit::AuditEntry, index::tags::IndexTag, revocation::Revocation},
+    errors::DatabaseError,
+    types::{Hash, Signature},
+    ui::{AppChannel, AppState, ResourceState, queries::FetchContents},
+};
+
+/// Takes back content we published, peers delete it as they exchange with us
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct RevokeContent<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> RevokeContent<I> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<I: IndexTag> MutationCapability for RevokeContent<I> {
+    type Ok = ();
+    type Err = DatabaseError;
+    /// Content and its index
+    type Keys = (Signature, Hash);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let (repositories, priv_key) = match (&radio.read().repositories, &radio.read().config) {
+            (ResourceState::Loaded(r), ResourceState::Loaded(c)) => {
+                (r.clone(), c.private_key().clone())
+            }
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let Some(content) = repositories
+            .index()
+            .get_contents::<I>(std::slice::from_ref(&keys.0))
+            .await?
+            .into_iter()
+            .next()
+        else {
+            return Err(DatabaseError::NotFound {
+                table: I::CONTENT_TABLE.to_string(),
+                id: keys.0.as_base64(),
+            });
+        };
+
+        let label = format!("Ch. {}: {}", content.enumeration(), content.title());
+        repositories
+            .revoke::<I>(Revocation::for_content(&content, &priv_key))
+            .await?;
+
+        repositories
+            .record_audit(AuditEntry::deleted(
+                keys.0.as_base64(),
+                label,
+                true,
+                "Revoked for everyone",
+            ))
+            .await
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchContents<I>>::invalidate_matching(keys.1.clone()).await;
+        }
+    }
+}
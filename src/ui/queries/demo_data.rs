@@ -0,0 +1,39 @@
+use std::path::Path;
+
+use freya::{prelude::*, query::MutationCapability, radio::RadioStation};
+
+use crate::{
+    errors::DemoDataError,
+    fixtures::Graph,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Stores the demo graph made from the seed in the keys, its sample chapters
+/// are written next to the downloads
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct LoadDemoData;
+
+impl MutationCapability for LoadDemoData {
+    type Ok = ();
+    type Err = DemoDataError;
+    type Keys = String;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DemoDataError::NotInitialized);
+        };
+
+        let (repositories, dir) = {
+            let state = radio.read();
+            let (ResourceState::Loaded(r), ResourceState::Loaded(c)) =
+                (&state.repositories, &state.config)
+            else {
+                return Err(DemoDataError::NotInitialized);
+            };
+            (r.clone(), Path::new(&c.storage().data_dir).join("demo"))
+        };
+
+        Graph::from_seed(keys).store_demo(&repositories, &dir).await
+    }
+}
@@ -0,0 +1,31 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    diagnostics::{Check, run_diagnostics},
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct RunDiagnostics;
+
+impl QueryCapability for RunDiagnostics {
+    type Ok = Vec<Check>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let (config, repositories) = match (&radio.read().config, &radio.read().repositories) {
+            (ResourceState::Loaded(c), ResourceState::Loaded(r)) => (c.clone(), Some(r.clone())),
+            (ResourceState::Loaded(c), _) => (c.clone(), None),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        Ok(run_diagnostics(&config, repositories.as_ref()).await)
+    }
+}
@@ -25,9 +25,12 @@ impl<I: IndexTag + 'static> QueryCapability for FetchContents<I> {
 
         match &radio.read().repositories.clone() {
             ResourceState::Loaded(r) => {
-                r.index()
-                    .get_filtered_index_contents(keys.clone(), None, None)
-                    .await
+                let muted = r.user().get_muted().await?;
+                let contents = r.index().get_index_contents(keys.clone()).await?;
+                Ok(contents
+                    .into_iter()
+                    .filter(|c| !c.is_relayed() && !muted.contains(c.poster()))
+                    .collect())
             }
             _ => Err(DatabaseError::NotInitialized),
         }
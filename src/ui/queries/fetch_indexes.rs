@@ -3,6 +3,7 @@ use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
 use crate::{
     db::index::{Index, tags::IndexTag},
     errors::DatabaseError,
+    types::Hash,
     ui::{AppChannel, AppState, ResourceState},
 };
 #[derive(Clone, Hash, PartialEq, Eq)]
@@ -30,7 +31,46 @@ impl<I: IndexTag> QueryCapability for FetchIndexes<I> {
         };
 
         match &radio.read().repositories {
-            ResourceState::Loaded(r) => r.index().get_all_indexes(None, None).await,
+            ResourceState::Loaded(r) => {
+                let muted = r.user().get_muted().await?;
+                let indexes = r.index().get_all_indexes(None, None).await?;
+                Ok(indexes
+                    .into_iter()
+                    .filter(|i| !i.is_relayed() && !muted.contains(i.source()))
+                    .collect())
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+/// A single index by hash, none if we don't have it
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchIndex<I: IndexTag> {
+    _phantom: std::marker::PhantomData<I>,
+}
+
+impl<I: IndexTag> FetchIndex<I> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I: IndexTag> QueryCapability for FetchIndex<I> {
+    type Ok = Option<Index<I>>;
+    type Err = DatabaseError;
+    type Keys = Hash;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.index().get_index::<I>(keys).await,
             _ => Err(DatabaseError::NotInitialized),
         }
     }
@@ -0,0 +1,30 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::{index::tags::MangaTag, stats::TagStats},
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchLibraryStats;
+
+impl QueryCapability for FetchLibraryStats {
+    type Ok = Vec<TagStats>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        Ok(vec![repositories.get_tag_stats::<MangaTag>().await?])
+    }
+}
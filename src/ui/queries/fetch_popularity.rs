@@ -0,0 +1,76 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::{
+        content_source::Popularity,
+        index::{Index, tags::MangaTag},
+    },
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPopularity;
+
+impl QueryCapability for FetchPopularity {
+    type Ok = Popularity;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        repositories.get_popularity().await
+    }
+}
+
+/// Up to `take` indexes seen from the most peers, relayed ones included and
+/// muted uploaders left out
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPopularIndexes;
+
+impl QueryCapability for FetchPopularIndexes {
+    type Ok = Vec<(Index<MangaTag>, usize)>;
+    type Err = DatabaseError;
+    type Keys = usize;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let popularity = repositories.get_popularity().await?;
+        let muted = repositories.user().get_muted().await?;
+
+        let mut popular: Vec<(Index<MangaTag>, usize)> = repositories
+            .index()
+            .get_all_indexes::<MangaTag>(None, None)
+            .await?
+            .into_iter()
+            .filter(|i| !muted.contains(i.source()))
+            .map(|i| {
+                let peers = popularity.index(i.hash());
+                (i, peers)
+            })
+            .filter(|(_, peers)| *peers > 0)
+            .collect();
+        popular.sort_by_key(|(_, peers)| std::cmp::Reverse(*peers));
+        popular.truncate(*keys);
+
+        Ok(popular)
+    }
+}
@@ -3,7 +3,10 @@ use std::marker::PhantomData;
 use freya::{prelude::*, query::*, radio::RadioStation};
 
 use crate::{
-    db::{follow_index::IndexFollow, index::tags::IndexTag},
+    db::{
+        follow_index::{IndexFollow, NotificationPreference},
+        index::tags::IndexTag,
+    },
     errors::DatabaseError,
     types::{Hash, Timestamp},
     ui::{AppChannel, AppState, ResourceState, queries::GetFollowContent},
@@ -35,8 +38,9 @@ impl<I: IndexTag> MutationCapability for FollowContent<I> {
                     r.index_follow()
                         .add_index_follow::<I>(IndexFollow::new(
                             keys.0.clone(),
-                            true,
-                            Timestamp::new(0),
+                            NotificationPreference::default(),
+                            // Only what arrives from now on is announced
+                            Timestamp::now(),
                         ))
                         .await
                         .map(|_| ())
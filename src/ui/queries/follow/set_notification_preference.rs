@@ -0,0 +1,47 @@
+use std::marker::PhantomData;
+
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::{follow_index::NotificationPreference, index::tags::IndexTag},
+    errors::DatabaseError,
+    types::Hash,
+    ui::{AppChannel, AppState, ResourceState, queries::GetFollowContent},
+};
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct SetNotificationPreference<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> SetNotificationPreference<I> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<I: IndexTag> MutationCapability for SetNotificationPreference<I> {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = (Hash, NotificationPreference);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => {
+                r.index_follow()
+                    .set_notification_preference::<I>(keys.0.clone(), keys.1)
+                    .await
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<GetFollowContent<I>>::invalidate_matching(keys.0.clone()).await;
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use freya::{
+    prelude::*,
+    query::{MutationCapability, QueriesStorage, QueryCapability},
+    radio::RadioStation,
+};
+
+use crate::{
+    db::{
+        history::HistoryEntry,
+        index::{
+            content::Content,
+            tags::{IndexTag, MangaTag},
+        },
+    },
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// How many entries the history view shows
+const HISTORY_SHOWN: usize = 100;
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchHistory;
+
+impl QueryCapability for FetchHistory {
+    /// The content is `None` once it's been deleted, it can't be resumed then
+    type Ok = Vec<(HistoryEntry, Option<Content<MangaTag>>)>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let history: Vec<HistoryEntry> = repositories
+            .get_history(HISTORY_SHOWN)
+            .await?
+            .into_iter()
+            .filter(|e| e.tag == MangaTag::TAG)
+            .collect();
+        let signatures: Vec<_> = history.iter().map(|e| e.content.clone()).collect();
+        let contents = repositories
+            .index()
+            .get_contents::<MangaTag>(&signatures)
+            .await?;
+
+        Ok(history
+            .into_iter()
+            .map(|entry| {
+                let content = contents
+                    .iter()
+                    .find(|c| *c.signature() == entry.content)
+                    .cloned();
+                (entry, content)
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct RecordHistory;
+
+impl MutationCapability for RecordHistory {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = HistoryEntry;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.record_history(keys.clone()).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ClearHistory;
+
+impl MutationCapability for ClearHistory {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clear_history().await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchHistory>::invalidate_all().await;
+        }
+    }
+}
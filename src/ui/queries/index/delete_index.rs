@@ -0,0 +1,131 @@
+use std::{collections::HashSet, io::ErrorKind, marker::PhantomData};
+
+use anawt::{InfoHash, RemoveFlags};
+use freya::{prelude::*, query::*, radio::RadioStation};
+use tracing::warn;
+
+use crate::{
+    db::{audit::AuditEntry, index::tags::IndexTag, suppression::Suppression},
+    errors::DatabaseError,
+    types::Hash,
+    ui::{
+        AppChannel, AppState, ResourceState,
+        queries::{FetchIndex, FetchIndexes, FetchSuppressions},
+    },
+};
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct DeleteIndex<I: IndexTag>(PhantomData<I>);
+
+impl<I: IndexTag> DeleteIndex<I> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<I: IndexTag> MutationCapability for DeleteIndex<I> {
+    type Ok = ();
+    type Err = DatabaseError;
+    /// Index, whether its downloaded files go too and, to keep it from being
+    /// synced back, its suppression
+    type Keys = (Hash, bool, Option<Suppression>);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let storage = match &radio.read().config {
+            ResourceState::Loaded(c) => c.storage().clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let torrent_client = match &radio.read().torrent_client {
+            ResourceState::Loaded(c) => Some(c.clone()),
+            _ => None,
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let label = match repositories.index().get_index::<I>(&keys.0).await? {
+            Some(index) => index.title().clone(),
+            None => keys.0.as_base64(),
+        };
+
+        if let Some(suppression) = &keys.2 {
+            repositories.suppress(suppression.clone()).await?;
+        }
+        let contents = repositories.index().delete_index::<I>(&keys.0).await?;
+
+        repositories
+            .record_audit(AuditEntry::deleted(
+                keys.0.as_base64(),
+                label,
+                keys.2.is_some(),
+                "Deleted from the library",
+            ))
+            .await?;
+
+        if keys.1 {
+            let mut removed = HashSet::new();
+            for content in contents.iter().filter(|c| !c.is_local_only()) {
+                let Some(info_hash) = content.magnet_link.info_hash() else {
+                    continue;
+                };
+                if !removed.insert(info_hash.clone()) {
+                    continue;
+                }
+
+                // Content left in other indexes shares the torrent and its
+                // files, they stay for it
+                if !repositories
+                    .index()
+                    .get_contents_by_info_hash::<I>(&info_hash)
+                    .await?
+                    .is_empty()
+                {
+                    continue;
+                }
+
+                // The client would otherwise go on seeding, or download the
+                // files again
+                if let Some(client) = &torrent_client
+                    && let Ok(hash) = InfoHash::from_magnet(&content.magnet_link.0)
+                    && client.get_status(hash).await.is_some()
+                    && let Err(e) = client.remove_torrent(hash, RemoveFlags::empty()).await
+                {
+                    warn!(
+                        "Failed to remove the torrent of {}: {:?}",
+                        content.title(),
+                        e
+                    );
+                    continue;
+                }
+                if let Err(e) = repositories.remove_torrent_link(&info_hash).await {
+                    warn!("Failed to remove the link of {}: {}", content.title(), e);
+                }
+
+                let dir = storage.content_dir(content);
+                match tokio::fs::remove_dir_all(&dir).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => warn!("Failed to remove {}: {}", dir.display(), e),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchIndexes<I>>::invalidate_all().await;
+            QueriesStorage::<FetchIndex<I>>::invalidate_matching(keys.0.clone()).await;
+            QueriesStorage::<FetchSuppressions>::invalidate_all().await;
+        }
+    }
+}
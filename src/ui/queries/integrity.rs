@@ -0,0 +1,130 @@
+use std::collections::HashSet;
+
+use freya::{
+    prelude::*,
+    query::{MutationCapability, QueriesStorage, QueryCapability},
+    radio::RadioStation,
+};
+use tracing::warn;
+
+use crate::{
+    db::{
+        index::tags::MangaTag,
+        integrity::{IntegrityReport, Repair},
+    },
+    errors::{ClientError, DatabaseError},
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct CheckIntegrity;
+
+impl QueryCapability for CheckIntegrity {
+    type Ok = IntegrityReport;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.check_integrity::<MangaTag>().await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+/// Checks again and applies the repair to what's found, returns how many
+/// records it touched
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct RepairIntegrity;
+
+impl MutationCapability for RepairIntegrity {
+    type Ok = usize;
+    type Err = DatabaseError;
+    type Keys = Repair;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => {
+                let report = r.check_integrity::<MangaTag>().await?;
+                r.repair_integrity::<MangaTag>(&report, *keys).await
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<CheckIntegrity>::invalidate_all().await;
+        }
+    }
+}
+
+/// Asks the peers that sent orphaned contents for their missing indexes,
+/// returns how many came back
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct RefetchMissingIndexes;
+
+impl MutationCapability for RefetchMissingIndexes {
+    type Ok = usize;
+    type Err = ClientError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(ClientError::NotInitialized);
+        };
+
+        let (pool, repositories) = match (&radio.read().client, &radio.read().repositories) {
+            (ResourceState::Loaded(p), ResourceState::Loaded(r)) => (p.clone(), r.clone()),
+            _ => return Err(ClientError::NotInitialized),
+        };
+
+        let report = repositories.check_integrity::<MangaTag>().await?;
+
+        let mut recovered = 0;
+        for (index, signatures) in report.orphaned_contents {
+            let mut peers = HashSet::new();
+            for signature in &signatures {
+                for source in repositories.get_content_sources(signature).await? {
+                    peers.insert(source.peer);
+                }
+            }
+
+            for peer in peers {
+                let fetched = pool
+                    .clone()
+                    .get_client()
+                    .await
+                    .fetch_indexes::<MangaTag>(&peer, repositories.index(), vec![index.clone()])
+                    .await;
+                match fetched {
+                    Ok(0) => {}
+                    Ok(_) => {
+                        recovered += 1;
+                        break;
+                    }
+                    Err(e) => warn!("Failed to fetch index from {}: {}", peer.inner(), e),
+                }
+            }
+        }
+
+        Ok(recovered)
+    }
+
+    async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<CheckIntegrity>::invalidate_all().await;
+        }
+    }
+}
@@ -13,39 +13,115 @@ use crate::{
 mod follow {
     pub mod follow_content;
     pub mod get_follow_content;
+    pub mod set_notification_preference;
 }
 pub use follow::follow_content::FollowContent;
 pub use follow::get_follow_content::GetFollowContent;
+pub use follow::set_notification_preference::SetNotificationPreference;
 
 mod content {
+    pub mod delete_content;
+    pub mod export_chapter;
+    pub mod fetch_contents_by_info_hash;
+    pub mod fetch_info_hash_conflicts;
     pub mod fetch_mangadex_chapters;
+    pub mod revoke_content;
     pub mod update_content_count;
 }
+pub use content::delete_content::DeleteContent;
+pub use content::export_chapter::ExportChapter;
+pub use content::fetch_contents_by_info_hash::FetchContentsByInfoHash;
+pub use content::fetch_info_hash_conflicts::FetchInfoHashConflicts;
 pub use content::fetch_mangadex_chapters::FetchMangadexChapters;
+pub use content::revoke_content::RevokeContent;
 pub use content::update_content_count::UpdateContentCount;
 
 mod torrent {
+    pub mod fetch_torrent_links;
+    pub mod fetch_torrent_watcher;
     pub mod fetch_torrent_watchers;
     pub mod remove_torrent;
+    pub mod swarm_health;
 }
+pub use torrent::fetch_torrent_links::FetchTorrentLinks;
+pub use torrent::fetch_torrent_watcher::FetchTorrentWatcher;
 pub use torrent::fetch_torrent_watchers::FetchTorrentWatchers;
 pub use torrent::remove_torrent::RemoveTorrent;
+pub use torrent::swarm_health::{FetchContentSources, FetchUnseededReleases, UnseededRelease};
 
 mod index {
+    pub mod delete_index;
     pub mod fetch_cover;
 }
+pub use index::delete_index::DeleteIndex;
 pub use index::fetch_cover::FetchCover;
 
+mod post {
+    pub mod add_post;
+    pub mod fetch_posts;
+    pub mod resolve_mentions;
+}
+pub use post::add_post::AddPost;
+pub use post::fetch_posts::{FetchPostCount, FetchPostCounts, FetchPosts, SearchPosts};
+pub use post::resolve_mentions::ResolveMentions;
+
+mod user {
+    pub mod add_user;
+    pub mod attestations;
+    pub mod evidence;
+    pub mod fetch_users;
+    pub mod import_catalog;
+    pub mod lookup_peer;
+    pub mod mute;
+    pub mod node_info;
+    pub mod peer_stats;
+    pub mod petnames;
+    pub mod sync_policy;
+    pub mod trust_list;
+}
+pub use user::add_user::AddUser;
+pub use user::attestations::{FetchVouchers, Vouch, Voucher};
+pub use user::evidence::{BlockFromEvidence, ExportEvidence, ReadEvidence};
+pub use user::fetch_users::{FetchUser, FetchUserList, FetchUsers};
+pub use user::import_catalog::{ImportCatalog, LandCatalog};
+pub use user::lookup_peer::LookupPeer;
+pub use user::mute::{FetchMuted, SetMuted};
+pub use user::node_info::FetchNodeInfo;
+pub use user::peer_stats::{FetchPeerStats, PingPeer};
+pub use user::petnames::{FetchDisplayName, FetchPetnames, SetPetname};
+pub use user::sync_policy::{FetchSyncPolicy, SetSyncPolicy};
+pub use user::trust_list::{ApplyTrustEntries, ExportTrustList, ReadTrustList};
+
 mod fetch_indexes;
-pub use fetch_indexes::FetchIndexes;
+pub use fetch_indexes::{FetchIndex, FetchIndexes};
 mod fetch_contents;
 pub use fetch_contents::FetchContents;
 mod update_content_progress;
 pub use update_content_progress::UpdateContentProgress;
-mod fetch_torrent_status;
-pub use fetch_torrent_status::FetchTorrentStatus;
 mod add_torrent;
 pub use add_torrent::AddTorrent;
+mod publish_release;
+pub use publish_release::PublishRelease;
+mod fetch_library_stats;
+pub use fetch_library_stats::FetchLibraryStats;
+mod fetch_popularity;
+pub use fetch_popularity::{FetchPopularIndexes, FetchPopularity};
+mod check_for_update;
+pub use check_for_update::CheckForUpdate;
+mod suppression;
+pub use suppression::{FetchSuppressions, Unsuppress};
+mod integrity;
+pub use integrity::{CheckIntegrity, RefetchMissingIndexes, RepairIntegrity};
+mod diagnostics;
+pub use diagnostics::RunDiagnostics;
+mod history;
+pub use history::{ClearHistory, FetchHistory, RecordHistory};
+mod audit;
+pub use audit::FetchAuditLog;
+#[cfg(feature = "fixtures")]
+mod demo_data;
+#[cfg(feature = "fixtures")]
+pub use demo_data::LoadDemoData;
 
 #[derive(Clone)]
 pub struct AddIndex<I: IndexTag> {
@@ -94,8 +170,9 @@ impl<I: IndexTag + 'static> MutationCapability for AddIndex<I> {
         }
     }
 
-    async fn on_settled(&self, _keys: &Self::Keys, _result: &Result<Self::Ok, Self::Err>) {
+    async fn on_settled(&self, keys: &Self::Keys, _result: &Result<Self::Ok, Self::Err>) {
         QueriesStorage::<FetchIndexes<I>>::invalidate_all().await;
+        QueriesStorage::<FetchIndex<I>>::invalidate_matching(keys.hash().clone()).await;
     }
 }
 
@@ -145,5 +222,55 @@ impl<I: IndexTag + 'static> MutationCapability for AddIndexContent<I> {
 
     async fn on_settled(&self, _keys: &Self::Keys, _result: &Result<Self::Ok, Self::Err>) {
         QueriesStorage::<FetchIndexes<I>>::invalidate_all().await;
+        QueriesStorage::<FetchInfoHashConflicts<I>>::invalidate_all().await;
+    }
+}
+
+#[derive(Clone)]
+pub struct AddLocalContent<I: IndexTag> {
+    _phantom: std::marker::PhantomData<I>,
+}
+
+impl<I: IndexTag> AddLocalContent<I> {
+    pub fn new() -> Self {
+        Self {
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<I: IndexTag> std::hash::Hash for AddLocalContent<I> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::hash::Hash::hash(&0, state);
+    }
+}
+
+impl<I: IndexTag> PartialEq for AddLocalContent<I> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<I: IndexTag> Eq for AddLocalContent<I> {}
+
+impl<I: IndexTag + 'static> MutationCapability for AddLocalContent<I> {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = Content<I>;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.index().add_local_content(keys.clone()).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, _result: &Result<Self::Ok, Self::Err>) {
+        QueriesStorage::<FetchContents<I>>::invalidate_matching(keys.index_hash().clone()).await;
     }
 }
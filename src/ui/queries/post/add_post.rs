@@ -0,0 +1,41 @@
+use freya::{
+    prelude::*,
+    query::{MutationCapability, QueriesStorage},
+    radio::RadioStation,
+};
+
+use crate::{
+    db::comments::Post,
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState, queries::FetchPosts},
+};
+
+/// Stores a post we signed, it goes out to peers with the next exchange
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct AddPost;
+
+impl MutationCapability for AddPost {
+    type Ok = Post;
+    type Err = DatabaseError;
+    type Keys = Post;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        repositories.add_post(keys.clone()).await
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchPosts>::invalidate_matching(keys.topic.clone()).await;
+        }
+    }
+}
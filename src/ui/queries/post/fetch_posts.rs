@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::comments::Post,
+    errors::DatabaseError,
+    types::Topic,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+const POSTS_PAGE_SIZE: usize = 100;
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPosts;
+
+impl QueryCapability for FetchPosts {
+    type Ok = Vec<Post>;
+    type Err = DatabaseError;
+    type Keys = Topic;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let response = repositories
+            .get_posts_by_topic(keys.clone(), POSTS_PAGE_SIZE, 0)
+            .await?;
+
+        Ok(response.values.0)
+    }
+}
+
+/// How many posts a topic has, keyed by the posts data version so it refetches
+/// every time posts are written
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPostCount;
+
+impl QueryCapability for FetchPostCount {
+    type Ok = usize;
+    type Err = DatabaseError;
+    type Keys = (Topic, u64);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        repositories.count_posts_by_topic(keys.0.clone()).await
+    }
+}
+
+/// Post counts of many topics at once, for badges on lists. Keyed by the
+/// posts data version like [`FetchPostCount`]
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPostCounts;
+
+impl QueryCapability for FetchPostCounts {
+    type Ok = HashMap<Topic, usize>;
+    type Err = DatabaseError;
+    type Keys = (Vec<Topic>, u64);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        repositories.count_posts_by_topics(keys.0.clone()).await
+    }
+}
+
+/// Posts of a topic matching a full-text query. An empty query matches nothing
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct SearchPosts;
+
+impl QueryCapability for SearchPosts {
+    type Ok = Vec<Post>;
+    type Err = DatabaseError;
+    type Keys = (Topic, String);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let (topic, query) = keys;
+        if query.trim().is_empty() {
+            return Ok(vec![]);
+        }
+
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let response = repositories
+            .search_posts(topic.clone(), query.trim().to_string(), POSTS_PAGE_SIZE, 0)
+            .await?;
+
+        Ok(response.values)
+    }
+}
@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::user::User,
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Users the mentions of a post point at, keyed by the mentioned handle
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ResolveMentions;
+
+impl QueryCapability for ResolveMentions {
+    type Ok = HashMap<String, User>;
+    type Err = DatabaseError;
+    type Keys = Vec<String>;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().resolve_mentions(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
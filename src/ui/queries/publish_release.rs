@@ -0,0 +1,32 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::index::release::Release,
+    errors::DatabaseError,
+    ui::{AppChannel, AppState},
+};
+
+/// Stages the release, the app manager stores, seeds and announces it once
+/// [`UNDO_WINDOW`](crate::ui::components::UNDO_WINDOW) is over unless it's
+/// undone first. Returns the id to follow it by in
+/// [`StagedReleases`](crate::ui::components::StagedReleases).
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct PublishRelease;
+
+impl MutationCapability for PublishRelease {
+    type Ok = u64;
+    type Err = DatabaseError;
+    type Keys = Release;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(mut radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        Ok(radio
+            .write_channel(AppChannel::Staged)
+            .staged
+            .stage(keys.clone()))
+    }
+}
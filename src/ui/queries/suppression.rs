@@ -0,0 +1,82 @@
+use freya::{
+    prelude::*,
+    query::{MutationCapability, QueriesStorage, QueryCapability},
+    radio::RadioStation,
+};
+
+use crate::{
+    db::{
+        audit::{AuditEntry, AuditKind},
+        suppression::Suppression,
+    },
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchSuppressions;
+
+impl QueryCapability for FetchSuppressions {
+    type Ok = Vec<Suppression>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.get_suppressions().await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct Unsuppress;
+
+impl MutationCapability for Unsuppress {
+    type Ok = ();
+    type Err = DatabaseError;
+    /// [`Suppression::id`]
+    type Keys = String;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let label = repositories
+            .get_suppressions()
+            .await?
+            .into_iter()
+            .find(|s| &s.id == keys)
+            .map(|s| s.title)
+            .unwrap_or_else(|| keys.clone());
+        repositories.unsuppress(keys).await?;
+
+        repositories
+            .record_audit(AuditEntry::new(
+                AuditKind::Unsuppress,
+                keys.clone(),
+                label,
+                String::new(),
+                "Allowed again from the settings",
+            ))
+            .await
+    }
+
+    async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchSuppressions>::invalidate_all().await;
+        }
+    }
+}
@@ -1,16 +1,18 @@
 use anawt::{AnawtTorrentStatus, InfoHash};
 use freya::{prelude::*, query::*, radio::RadioStation};
+use tokio::sync::watch;
 
 use crate::{
     errors::TorrentError,
     ui::{AppChannel, AppState, ResourceState},
 };
 
+/// Watcher of a single torrent, `None` if the torrent was never added
 #[derive(Clone, Hash, PartialEq, Eq)]
-pub struct FetchTorrentStatus;
+pub struct FetchTorrentWatcher;
 
-impl QueryCapability for FetchTorrentStatus {
-    type Ok = Option<AnawtTorrentStatus>;
+impl QueryCapability for FetchTorrentWatcher {
+    type Ok = Option<watch::Receiver<AnawtTorrentStatus>>;
     type Err = TorrentError;
     type Keys = InfoHash;
 
@@ -20,9 +22,15 @@ impl QueryCapability for FetchTorrentStatus {
             return Err(TorrentError::NotInitialized);
         };
 
-        match &radio.read().torrent_client {
-            ResourceState::Loaded(r) => Ok(r.get_status(keys.clone()).await),
-            _ => Err(TorrentError::NotInitialized),
-        }
+        let client = match &radio.read().torrent_client {
+            ResourceState::Loaded(c) => c.clone(),
+            _ => return Err(TorrentError::NotInitialized),
+        };
+
+        Ok(client
+            .subscribe_all()
+            .await
+            .into_iter()
+            .find(|w| w.borrow().info_hash == *keys))
     }
 }
@@ -13,7 +13,7 @@ pub struct FetchTorrentWatchers;
 impl QueryCapability for FetchTorrentWatchers {
     type Ok = Vec<watch::Receiver<AnawtTorrentStatus>>;
     type Err = TorrentError;
-    type Keys = ();
+    type Keys = u64;
 
     async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
         let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
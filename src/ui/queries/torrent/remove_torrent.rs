@@ -5,7 +5,7 @@ use crate::{
     errors::TorrentError,
     ui::{
         AppChannel, AppState, ResourceState,
-        queries::{FetchTorrentStatus, FetchTorrentWatchers},
+        queries::{FetchTorrentWatcher, FetchTorrentWatchers},
     },
 };
 
@@ -34,7 +34,7 @@ impl MutationCapability for RemoveTorrent {
 
     async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
         if result.is_ok() {
-            QueriesStorage::<FetchTorrentStatus>::invalidate_matching(keys.0).await;
+            QueriesStorage::<FetchTorrentWatcher>::invalidate_matching(keys.0).await;
             QueriesStorage::<FetchTorrentWatchers>::invalidate_all().await;
         }
     }
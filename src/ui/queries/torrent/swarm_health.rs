@@ -0,0 +1,94 @@
+use anawt::InfoHash;
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::{content_source::ContentSource, index::tags::MangaTag},
+    errors::DatabaseError,
+    types::Signature,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Peers that sent or announced a content, most recently seen first
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchContentSources;
+
+impl QueryCapability for FetchContentSources {
+    type Ok = Vec<ContentSource>;
+    type Err = DatabaseError;
+    type Keys = Signature;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.get_content_sources(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+/// A chapter we published that nobody else seeds
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnseededRelease {
+    pub title: String,
+    /// We seed it ourselves, otherwise it's not in the torrent client at all
+    pub seeding: bool,
+}
+
+/// Chapters we published without other seeders. The keys only let callers
+/// refetch, swarms change without anything being stored.
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchUnseededReleases;
+
+impl QueryCapability for FetchUnseededReleases {
+    type Ok = Vec<UnseededRelease>;
+    type Err = DatabaseError;
+    type Keys = u64;
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let (repositories, config, client) = {
+            let state = radio.read();
+            match (&state.repositories, &state.config, &state.torrent_client) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c), ResourceState::Loaded(t)) => {
+                    (r.clone(), c.clone(), t.clone())
+                }
+                // Nothing to tell until the torrent client is up
+                (_, _, ResourceState::Loaded(_)) => return Err(DatabaseError::NotInitialized),
+                _ => return Ok(vec![]),
+            }
+        };
+
+        let contents = repositories
+            .index()
+            .get_contents_by_poster::<MangaTag>(config.public_key())
+            .await?;
+
+        let mut unseeded = vec![];
+        for content in contents {
+            let Ok(info_hash) = InfoHash::from_magnet(&content.magnet_link.0) else {
+                continue;
+            };
+
+            let seeding = match client.get_status(info_hash).await {
+                Some(status) if status.num_seeds > 0 => continue,
+                Some(_) => true,
+                None => false,
+            };
+
+            unseeded.push(UnseededRelease {
+                title: format!("Ch. {}: {}", content.enumeration(), content.title()),
+                seeding,
+            });
+        }
+
+        Ok(unseeded)
+    }
+}
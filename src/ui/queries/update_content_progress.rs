@@ -21,7 +21,7 @@ impl<I: IndexTag> UpdateContentProgress<I> {
 }
 
 impl<I: IndexTag> MutationCapability for UpdateContentProgress<I> {
-    type Ok = Option<Content<I>>;
+    type Ok = Content<I>;
     type Err = DatabaseError;
     type Keys = (Signature, u32);
 
@@ -42,7 +42,7 @@ impl<I: IndexTag> MutationCapability for UpdateContentProgress<I> {
     }
 
     async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
-        if let Ok(Some(content)) = result {
+        if let Ok(content) = result {
             QueriesStorage::<FetchContents<I>>::invalidate_matching(content.index_hash().clone())
                 .await;
         }
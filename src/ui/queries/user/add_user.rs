@@ -0,0 +1,40 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::user::User,
+    errors::DatabaseError,
+    ui::{AppChannel, AppState, ResourceState, queries::FetchUsers},
+};
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct AddUser;
+
+impl MutationCapability for AddUser {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = User;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => {
+                let trust = *keys.trust();
+                r.user()
+                    .change_trust(keys.clone(), trust, "Added from an invite")
+                    .await?;
+                Ok(())
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchUsers>::invalidate_all().await;
+        }
+    }
+}
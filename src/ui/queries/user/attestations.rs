@@ -0,0 +1,103 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::user::{Attestation, TrustLevel},
+    errors::DatabaseError,
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Someone who signed an attestation about a user, as shown in its profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct Voucher {
+    pub attestation: Attestation,
+    pub name: String,
+    /// Fully trusted by us, so the attestation counts towards raising them
+    pub counts: bool,
+}
+
+/// Who vouches for a user, newest first
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchVouchers;
+
+impl QueryCapability for FetchVouchers {
+    type Ok = Vec<Voucher>;
+    type Err = DatabaseError;
+    type Keys = PublicKey;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let mut vouchers = vec![];
+        for attestation in repositories.user().get_attestations_for(keys).await? {
+            let name = repositories
+                .user()
+                .get_display_name(&attestation.voucher)
+                .await?;
+            let counts = repositories
+                .user()
+                .get_user(&attestation.voucher)
+                .await?
+                .is_some_and(|u| *u.trust() == TrustLevel::FullTrust);
+
+            vouchers.push(Voucher {
+                attestation,
+                name,
+                counts,
+            });
+        }
+
+        Ok(vouchers)
+    }
+}
+
+/// Vouches for a user with our key, or withdraws it. A withdrawal is a newer
+/// attestation below trusted so it replaces the vouch on peers that have it
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct Vouch;
+
+impl MutationCapability for Vouch {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = (PublicKey, bool);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let (repositories, config) = {
+            let state = radio.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return Err(DatabaseError::NotInitialized),
+            }
+        };
+
+        let (subject, vouch) = keys;
+        let level = match vouch {
+            true => TrustLevel::Trusted,
+            false => TrustLevel::Untrusted,
+        };
+        let attestation = Attestation::new_signed(subject.clone(), level, config.private_key());
+
+        repositories.user().add_attestation(attestation).await?;
+
+        Ok(())
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchVouchers>::invalidate_matching(keys.0.clone()).await;
+        }
+    }
+}
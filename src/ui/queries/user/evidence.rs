@@ -0,0 +1,136 @@
+use std::path::PathBuf;
+
+use freya::{prelude::*, query::MutationCapability, radio::RadioStation};
+
+use crate::{
+    db::{
+        index::tags::MangaTag,
+        user::{
+            EvidenceBundle, EvidenceCheck, EvidenceItem, EvidenceRecord, MAX_EVIDENCE_PER_KIND,
+            TrustLevel, User,
+        },
+    },
+    errors::{DatabaseError, EvidenceError},
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Writes what we hold from a user to a file, along with why we blocked
+/// them, signed with our key
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ExportEvidence;
+
+impl MutationCapability for ExportEvidence {
+    /// Records written
+    type Ok = usize;
+    type Err = EvidenceError;
+    type Keys = (PublicKey, String, PathBuf);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized.into());
+        };
+
+        let (repositories, config) = {
+            let state = radio.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return Err(DatabaseError::NotInitialized.into()),
+            }
+        };
+
+        let (pub_key, reason, path) = keys;
+        let Some(user) = repositories.user().get_user(pub_key).await? else {
+            return Err(EvidenceError::UnknownUser);
+        };
+
+        let posts = repositories
+            .get_posts_by_source(pub_key, MAX_EVIDENCE_PER_KIND)
+            .await?;
+        let indexes = repositories
+            .index()
+            .get_indexes_by_source::<MangaTag>(pub_key, MAX_EVIDENCE_PER_KIND)
+            .await?;
+        let mut contents = repositories
+            .index()
+            .get_contents_by_poster::<MangaTag>(pub_key)
+            .await?;
+        contents.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        contents.truncate(MAX_EVIDENCE_PER_KIND);
+
+        let items: Vec<EvidenceItem> = posts
+            .into_iter()
+            .map(EvidenceRecord::Post)
+            .chain(indexes.into_iter().map(EvidenceRecord::Index))
+            .chain(contents.into_iter().map(EvidenceRecord::Content))
+            .map(EvidenceItem::checked)
+            .collect();
+        let count = items.len();
+
+        let bundle = EvidenceBundle::new_signed(user, reason.clone(), items, config.private_key());
+        tokio::fs::write(path, serde_json::to_vec_pretty(&bundle)?).await?;
+
+        Ok(count)
+    }
+}
+
+/// Reads an evidence bundle and checks its records ourselves, along with who
+/// signed it
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ReadEvidence;
+
+impl MutationCapability for ReadEvidence {
+    type Ok = (Option<PublicKey>, EvidenceBundle, EvidenceCheck);
+    type Err = EvidenceError;
+    type Keys = PathBuf;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let bundle: EvidenceBundle = serde_json::from_slice(&tokio::fs::read(keys).await?)?;
+        if !bundle.verify() {
+            return Err(EvidenceError::BadSignature);
+        }
+
+        let check = bundle.check();
+        let reporter = bundle.reporter.as_ref().map(|(pub_key, _)| pub_key.clone());
+
+        Ok((reporter, bundle, check))
+    }
+}
+
+/// Blocks the user an imported bundle is about. Users we already know keep
+/// the record we have
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct BlockFromEvidence;
+
+impl MutationCapability for BlockFromEvidence {
+    type Ok = ();
+    type Err = DatabaseError;
+    /// User and what the audit log says it was blocked for
+    type Keys = (User, String);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        let (user, reason) = keys;
+        let user = match repositories.user().get_user(user.pub_key()).await? {
+            Some(user) => user,
+            None => user.clone(),
+        };
+
+        repositories
+            .user()
+            .change_trust(user, TrustLevel::Ignore, reason)
+            .await?;
+
+        Ok(())
+    }
+}
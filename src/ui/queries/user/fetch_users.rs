@@ -0,0 +1,73 @@
+use freya::{prelude::*, query::QueryCapability, radio::RadioStation};
+
+use crate::{
+    db::user::{PeerStats, User, UserFilter, UserSort},
+    errors::DatabaseError,
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchUsers;
+
+impl QueryCapability for FetchUsers {
+    type Ok = Vec<User>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_all_users().await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+/// Users for the user list, with their peer stats
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchUserList;
+
+impl QueryCapability for FetchUserList {
+    type Ok = Vec<(User, Option<PeerStats>)>;
+    type Err = DatabaseError;
+    type Keys = (UserSort, UserFilter);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_user_list(keys.0, keys.1).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+/// A single user by key, none if we don't know them
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchUser;
+
+impl QueryCapability for FetchUser {
+    type Ok = Option<User>;
+    type Err = DatabaseError;
+    type Keys = PublicKey;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_user(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::{
+        catalog::{CatalogImport, PendingCatalog, SourceDecisions},
+        index::tags::MangaTag,
+        user::User,
+    },
+    errors::{ClientError, DatabaseError},
+    ui::{AppChannel, AppState, ResourceState, queries::FetchMuted},
+};
+
+/// Fetches the catalog snapshot of a trusted relay for review, see
+/// [`LandCatalog`]
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ImportCatalog;
+
+impl MutationCapability for ImportCatalog {
+    type Ok = Arc<PendingCatalog<MangaTag>>;
+    type Err = ClientError;
+    type Keys = User;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(ClientError::NotInitialized);
+        };
+
+        let (pool, repositories) = match (&radio.read().client, &radio.read().repositories) {
+            (ResourceState::Loaded(p), ResourceState::Loaded(r)) => (p.clone(), r.clone()),
+            _ => return Err(ClientError::NotInitialized),
+        };
+
+        pool.get_client()
+            .await
+            .fetch_catalog::<MangaTag>(keys, &repositories)
+            .await
+            .map(Arc::new)
+    }
+}
+
+/// Stores a reviewed catalog
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct LandCatalog;
+
+impl MutationCapability for LandCatalog {
+    type Ok = CatalogImport;
+    type Err = DatabaseError;
+    type Keys = (Arc<PendingCatalog<MangaTag>>, SourceDecisions);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => {
+                r.land_catalog(Arc::unwrap_or_clone(keys.0.clone()), &keys.1)
+                    .await
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, _keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchMuted>::invalidate_all().await;
+        }
+    }
+}
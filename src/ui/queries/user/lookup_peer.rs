@@ -0,0 +1,39 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::user::Invite,
+    errors::ClientError,
+    server::client::WhoReport,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct LookupPeer;
+
+impl MutationCapability for LookupPeer {
+    type Ok = WhoReport;
+    type Err = ClientError;
+    type Keys = Invite;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(ClientError::NotInitialized);
+        };
+
+        let pool = match &radio.read().client {
+            ResourceState::Loaded(p) => p.clone(),
+            _ => return Err(ClientError::NotInitialized),
+        };
+
+        let report = pool.get_client().await.who_report(&keys.address).await?;
+
+        // The invite is only a claim, the key answering on that address has to be
+        // the one we were given
+        if report.user.pub_key() != &keys.pub_key {
+            return Err(ClientError::IdentityMismatch);
+        }
+
+        Ok(report)
+    }
+}
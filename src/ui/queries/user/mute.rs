@@ -0,0 +1,65 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::index::tags::MangaTag,
+    errors::DatabaseError,
+    types::PublicKey,
+    ui::{
+        AppChannel, AppState, ResourceState,
+        queries::{FetchContents, FetchIndexes},
+    },
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchMuted;
+
+impl QueryCapability for FetchMuted {
+    type Ok = bool;
+    type Err = DatabaseError;
+    type Keys = PublicKey;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().is_muted(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct SetMuted;
+
+impl MutationCapability for SetMuted {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = (PublicKey, bool);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => {
+                r.user()
+                    .set_muted(keys.0.clone(), keys.1, "Changed on their profile")
+                    .await
+            }
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchMuted>::invalidate_matching(keys.0.clone()).await;
+            QueriesStorage::<FetchIndexes<MangaTag>>::invalidate_all().await;
+            QueriesStorage::<FetchContents<MangaTag>>::invalidate_all().await;
+        }
+    }
+}
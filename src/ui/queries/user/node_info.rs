@@ -0,0 +1,37 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::user::User,
+    errors::ClientError,
+    server::client::NodeInfo,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchNodeInfo;
+
+impl QueryCapability for FetchNodeInfo {
+    type Ok = NodeInfo;
+    type Err = ClientError;
+    type Keys = User;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(ClientError::NotInitialized);
+        };
+
+        let pool = match &radio.read().client {
+            ResourceState::Loaded(p) => p.clone(),
+            _ => return Err(ClientError::NotInitialized),
+        };
+
+        let info = pool.get_client().await.node_info(keys.address()).await?;
+
+        if &info.pub_key != keys.pub_key() {
+            return Err(ClientError::IdentityMismatch);
+        }
+
+        Ok(info)
+    }
+}
@@ -0,0 +1,65 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::user::{PeerStats, User},
+    errors::{ClientError, DatabaseError},
+    server::client::PingReport,
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPeerStats;
+
+impl QueryCapability for FetchPeerStats {
+    type Ok = Option<PeerStats>;
+    type Err = DatabaseError;
+    type Keys = PublicKey;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_peer_stats(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+/// Pings the user's address and records the outcome in its [`PeerStats`]
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct PingPeer;
+
+impl MutationCapability for PingPeer {
+    type Ok = PingReport;
+    type Err = ClientError;
+    type Keys = User;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(ClientError::NotInitialized);
+        };
+
+        let (pool, repositories) = match (&radio.read().client, &radio.read().repositories) {
+            (ResourceState::Loaded(p), ResourceState::Loaded(r)) => (p.clone(), r.clone()),
+            _ => return Err(ClientError::NotInitialized),
+        };
+
+        let result = pool.get_client().await.ping(keys.address()).await;
+
+        repositories
+            .user()
+            .record_ping(keys.pub_key().clone(), result.as_ref().ok().map(|r| r.rtt))
+            .await?;
+
+        result
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, _result: &Result<Self::Ok, Self::Err>) {
+        QueriesStorage::<FetchPeerStats>::invalidate_matching(keys.pub_key().clone()).await;
+    }
+}
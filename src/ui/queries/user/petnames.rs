@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    errors::DatabaseError,
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchPetnames;
+
+impl QueryCapability for FetchPetnames {
+    type Ok = HashMap<PublicKey, String>;
+    type Err = DatabaseError;
+    type Keys = ();
+
+    async fn run(&self, _keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_petnames().await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchDisplayName;
+
+impl QueryCapability for FetchDisplayName {
+    type Ok = String;
+    type Err = DatabaseError;
+    type Keys = PublicKey;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_display_name(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct SetPetname;
+
+impl MutationCapability for SetPetname {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = (PublicKey, String);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().set_petname(keys.0.clone(), keys.1.clone()).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchPetnames>::invalidate_all().await;
+            QueriesStorage::<FetchDisplayName>::invalidate_matching(keys.0.clone()).await;
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use freya::{prelude::*, query::*, radio::RadioStation};
+
+use crate::{
+    db::user::SyncPolicy,
+    errors::DatabaseError,
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct FetchSyncPolicy;
+
+impl QueryCapability for FetchSyncPolicy {
+    type Ok = SyncPolicy;
+    type Err = DatabaseError;
+    type Keys = PublicKey;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().get_sync_policy(keys).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Hash)]
+pub struct SetSyncPolicy;
+
+impl MutationCapability for SetSyncPolicy {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = (PublicKey, SyncPolicy);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.user().set_sync_policy(keys.0.clone(), keys.1).await,
+            _ => Err(DatabaseError::NotInitialized),
+        }
+    }
+
+    async fn on_settled(&self, keys: &Self::Keys, result: &Result<Self::Ok, Self::Err>) {
+        if result.is_ok() {
+            QueriesStorage::<FetchSyncPolicy>::invalidate_matching(keys.0.clone()).await;
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use freya::{prelude::*, query::MutationCapability, radio::RadioStation};
+
+use crate::{
+    db::user::{TrustEntry, TrustList, TrustMerge},
+    errors::{DatabaseError, TrustListError},
+    types::PublicKey,
+    ui::{AppChannel, AppState, ResourceState},
+};
+
+/// Writes our trust decisions to a file, signed with our key if asked to
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ExportTrustList;
+
+impl MutationCapability for ExportTrustList {
+    /// Entries written
+    type Ok = usize;
+    type Err = TrustListError;
+    type Keys = (PathBuf, bool);
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized.into());
+        };
+
+        let (repositories, config) = {
+            let state = radio.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return Err(DatabaseError::NotInitialized.into()),
+            }
+        };
+
+        let (path, signed) = keys;
+        let entries = repositories
+            .user()
+            .export_trust(config.public_key())
+            .await?;
+        let count = entries.len();
+        let list = match signed {
+            true => TrustList::new_signed(entries, config.private_key()),
+            false => TrustList::new(entries),
+        };
+
+        tokio::fs::write(path, serde_json::to_vec_pretty(&list)?).await?;
+
+        Ok(count)
+    }
+}
+
+/// Reads a trust list and lines it up with ours, along with who signed it
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ReadTrustList;
+
+impl MutationCapability for ReadTrustList {
+    type Ok = (Option<PublicKey>, TrustMerge);
+    type Err = TrustListError;
+    type Keys = PathBuf;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized.into());
+        };
+
+        let (repositories, config) = {
+            let state = radio.read();
+            match (&state.repositories, &state.config) {
+                (ResourceState::Loaded(r), ResourceState::Loaded(c)) => (r.clone(), c.clone()),
+                _ => return Err(DatabaseError::NotInitialized.into()),
+            }
+        };
+
+        let list: TrustList = serde_json::from_slice(&tokio::fs::read(keys).await?)?;
+        if !list.verify() {
+            return Err(TrustListError::BadSignature);
+        }
+
+        let merge = repositories
+            .user()
+            .merge_trust_list(&list, config.public_key())
+            .await?;
+
+        Ok((list.signer.map(|(pub_key, _)| pub_key), merge))
+    }
+}
+
+/// Takes on the entries picked out of an imported list
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub struct ApplyTrustEntries;
+
+impl MutationCapability for ApplyTrustEntries {
+    type Ok = ();
+    type Err = DatabaseError;
+    type Keys = Vec<TrustEntry>;
+
+    async fn run(&self, keys: &Self::Keys) -> Result<Self::Ok, Self::Err> {
+        let radio = try_consume_root_context::<RadioStation<AppState, AppChannel>>();
+        let Some(radio) = radio else {
+            return Err(DatabaseError::NotInitialized);
+        };
+
+        let repositories = match &radio.read().repositories {
+            ResourceState::Loaded(r) => r.clone(),
+            _ => return Err(DatabaseError::NotInitialized),
+        };
+
+        repositories.user().apply_trust_entries(keys.clone()).await
+    }
+}
@@ -0,0 +1,77 @@
+use freya::{prelude::*, query::*};
+
+use crate::{
+    db::index::{content::Content, tags::MangaTag},
+    ui::{DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, queries::FetchInfoHashConflicts},
+};
+
+/// Content records from different posters or indexes that point at the same
+/// torrent
+#[derive(PartialEq)]
+pub struct Conflicts;
+impl Component for Conflicts {
+    fn render(&self) -> impl IntoElement {
+        let conflicts_query = use_query(Query::new((), FetchInfoHashConflicts::<MangaTag>::new()));
+
+        let conflict_list = match &*conflicts_query.read().state() {
+            QueryStateData::Pending => rect().child(CircularLoader::new()),
+            QueryStateData::Loading { .. } => rect().child(CircularLoader::new()),
+            QueryStateData::Settled { res, .. } => match res {
+                Ok(res) if res.is_empty() => rect().child("No conflicts found"),
+                Ok(res) => {
+                    let children: Vec<Element> = res
+                        .iter()
+                        .map(|group| conflict_group(group).into_element())
+                        .collect();
+
+                    rect().spacing(10.).children(children)
+                }
+                Err(e) => rect().child(label().text(e.to_string())),
+            },
+        };
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .child(label().text("Conflicts").font_size(48))
+            .child(conflict_list)
+    }
+}
+
+fn conflict_group(group: &[Content<MangaTag>]) -> impl IntoElement {
+    let info_hash = group[0]
+        .magnet_link
+        .info_hash()
+        .unwrap_or_else(|| "unknown".to_string());
+
+    let entries: Vec<Element> = group
+        .iter()
+        .map(|c| {
+            label()
+                .text(format!(
+                    "Ch. {}: {} | index {} | by {}",
+                    c.enumeration(),
+                    c.title(),
+                    c.index_hash(),
+                    c.poster().fingerprint()
+                ))
+                .color(Color::WHITE)
+                .into_element()
+        })
+        .collect();
+
+    rect()
+        .width(Size::Fill)
+        .padding(10.)
+        .spacing(5.)
+        .corner_radius(DEFAULT_CORNER_RADIUS)
+        .background(Color::DARK_GRAY)
+        .child(
+            label()
+                .text(format!("Torrent {}", info_hash))
+                .font_weight(FontWeight::BOLD)
+                .color(Color::WHITE),
+        )
+        .children(entries)
+}
@@ -0,0 +1,95 @@
+use freya::{prelude::*, query::*};
+
+use crate::{
+    db::{
+        history::HistoryEntry,
+        index::{content::Content, tags::MangaTag},
+    },
+    ui::{
+        DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, Route, RouteContext,
+        queries::{ClearHistory, FetchHistory},
+    },
+};
+
+/// Chapters recently opened in the reader, most recent first
+#[derive(PartialEq)]
+pub struct HistoryView;
+impl Component for HistoryView {
+    fn render(&self) -> impl IntoElement {
+        let history_query = use_query(Query::new((), FetchHistory));
+        let clear_mutation = use_mutation(Mutation::new(ClearHistory));
+
+        let history_list = match &*history_query.read().state() {
+            QueryStateData::Pending | QueryStateData::Loading { .. } => {
+                rect().child(CircularLoader::new())
+            }
+            QueryStateData::Settled { res, .. } => match res {
+                Ok(res) if res.is_empty() => rect().child("Nothing read yet"),
+                Ok(res) => {
+                    let children: Vec<Element> = res
+                        .iter()
+                        .map(|(entry, content)| history_row(entry, content.clone()).into_element())
+                        .collect();
+
+                    rect().spacing(10.).children(children)
+                }
+                Err(e) => rect().child(label().text(e.to_string())),
+            },
+        };
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .child(label().text("History").font_size(48))
+            .child(Button::new().child("Clear history").on_press(move |_| {
+                clear_mutation.mutate(());
+            }))
+            .child(history_list)
+    }
+}
+
+fn history_row(entry: &HistoryEntry, content: Option<Content<MangaTag>>) -> impl IntoElement {
+    let page = entry.entry;
+    let resume = Button::new()
+        .child("Resume")
+        .enabled(content.is_some())
+        .on_press(move |_| {
+            let Some(mut content) = content.clone() else {
+                return;
+            };
+            // The viewer opens on the page after the progress
+            content.progress = page;
+            RouteContext::get().push(Route::ChapterViewerInternal { content });
+        });
+
+    rect()
+        .horizontal()
+        .width(Size::Fill)
+        .padding(10.)
+        .spacing(10.)
+        .content(freya::prelude::Content::Flex)
+        .cross_align(Alignment::Center)
+        .corner_radius(DEFAULT_CORNER_RADIUS)
+        .background(Color::DARK_GRAY)
+        .child(
+            rect()
+                .width(Size::flex(1.))
+                .child(
+                    label()
+                        .text(entry.title.clone())
+                        .font_weight(FontWeight::BOLD)
+                        .color(Color::WHITE),
+                )
+                .child(
+                    label()
+                        .text(format!(
+                            "Page {} | {}",
+                            entry.entry,
+                            entry.read_at.format_date()
+                        ))
+                        .color(Color::WHITE),
+                ),
+        )
+        .child(resume)
+}
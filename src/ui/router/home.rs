@@ -1,11 +1,40 @@
-use crate::ui::{AppChannel, DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, ResourceState, icons};
-use freya::{prelude::*, radio::use_radio};
+use crate::ui::{
+    AppChannel, DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, ResourceState,
+    components::no_reaction_button,
+    hooks::use_view_task,
+    icons,
+    queries::{CheckForUpdate, FetchPopularIndexes, FetchUnseededReleases},
+    router::{Route, RouteContext},
+};
+use freya::{prelude::*, query::*, radio::use_radio};
+use std::time::Duration;
+
+const SERVER_LOAD_REFRESH: Duration = Duration::from_secs(2);
+/// Titles listed under "Popular on the network"
+const POPULAR_COUNT: usize = 10;
+/// Swarms are looked at again every this many [`SERVER_LOAD_REFRESH`]
+const SWARM_REFRESH_TICKS: u64 = 15;
 
 #[derive(PartialEq)]
 pub struct Home;
 impl Component for Home {
     fn render(&self) -> impl IntoElement {
         let radio = use_radio(AppChannel::Status);
+        let client_ready = matches!(radio.read().client, ResourceState::Loaded(_));
+        let update_query = use_query(Query::new(client_ready, CheckForUpdate));
+        let popular_query = use_query(Query::new(POPULAR_COUNT, FetchPopularIndexes));
+
+        // Metrics are plain counters, re-read them every so often
+        let mut tick = use_state(|| 0u64);
+        let ticker = use_view_task();
+        use_hook(move || {
+            ticker.spawn(async move {
+                loop {
+                    tokio::time::sleep(SERVER_LOAD_REFRESH).await;
+                    *tick.write() += 1;
+                }
+            })
+        });
 
         fn render_status<T, E>(name: &'static str, state: &ResourceState<T, E>) -> Element {
             let icon = match state {
@@ -71,11 +100,127 @@ impl Component for Home {
                 render_status("Client", &radio.read().client),
             ]);
 
+        let unseeded_query = use_query(Query::new(
+            *tick.read() / SWARM_REFRESH_TICKS,
+            FetchUnseededReleases,
+        ));
+        let unseeded = match &*unseeded_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(unseeded), ..
+            }
+            | QueryStateData::Loading {
+                res: Some(Ok(unseeded)),
+            } if !unseeded.is_empty() => rect()
+                .padding(10.)
+                .corner_radius(DEFAULT_CORNER_RADIUS)
+                .background(Color::from_rgb(198, 40, 40))
+                .child(
+                    label()
+                        .text("Nobody else seeds these, they disappear if you stop")
+                        .font_weight(FontWeight::BOLD)
+                        .color(Color::WHITE),
+                )
+                .children(unseeded.iter().map(|release| {
+                    let note = match release.seeding {
+                        true => "",
+                        false => " (not seeding it either)",
+                    };
+                    label()
+                        .text(format!("{}{}", release.title, note))
+                        .color(Color::WHITE)
+                        .into_element()
+                })),
+            _ => rect(),
+        };
+
+        let _ = tick.read();
+        let server_load = match &radio.read().server {
+            ResourceState::Loaded(metrics) => label().text(format!(
+                "{}/{} peers connected, {} served, {} turned away, {} relayed records evicted",
+                metrics.active(),
+                metrics.max(),
+                metrics.accepted(),
+                metrics.rejected(),
+                metrics.evicted()
+            )),
+            _ => label(),
+        };
+
+        let update = match &*update_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(Some(release)),
+                ..
+            } => rect()
+                .padding(10.)
+                .corner_radius(DEFAULT_CORNER_RADIUS)
+                .background(Color::DARK_GRAY)
+                .child(
+                    label()
+                        .text(format!("Akareko {} is available", release.version))
+                        .font_weight(FontWeight::BOLD)
+                        .color(Color::WHITE),
+                )
+                .child(label().text(release.notes.clone()).color(Color::WHITE))
+                .child(
+                    label()
+                        .text(release.download.clone())
+                        .color(Color::LIGHT_GRAY),
+                ),
+            _ => rect(),
+        };
+
+        let popular = match &*popular_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(popular), ..
+            } if popular.is_empty() => rect().child("Nothing seen from other peers yet"),
+            QueryStateData::Settled {
+                res: Ok(popular), ..
+            } => rect()
+                .spacing(5.)
+                .children(popular.iter().map(|(index, peers)| {
+                    let index = index.clone();
+
+                    rect()
+                        .horizontal()
+                        .spacing(10.)
+                        .cross_align(Alignment::Center)
+                        .child(
+                            no_reaction_button()
+                                .child(
+                                    label()
+                                        .text(index.title().clone())
+                                        .font_weight(FontWeight::BOLD),
+                                )
+                                .on_press(move |_| {
+                                    RouteContext::get().push(Route::Manga {
+                                        hash: index.hash().clone(),
+                                    });
+                                }),
+                        )
+                        .child(
+                            label()
+                                .text(match peers {
+                                    1 => "1 peer".to_string(),
+                                    n => format!("{} peers", n),
+                                })
+                                .color(Color::GRAY),
+                        )
+                        .into_element()
+                })),
+            QueryStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            _ => rect().child(CircularLoader::new()),
+        };
+
         rect().padding(DEFAULT_PAGE_PADDING).child(
             rect()
                 .center()
+                .child(update)
+                .child(unseeded)
                 .child(label().text("Status").font_size(32.))
-                .child(status),
+                .child(status)
+                .child(server_load)
+                .child(label().text("Popular on the network").font_size(32.))
+                .child(popular),
         )
     }
 }
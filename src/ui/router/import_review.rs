@@ -0,0 +1,206 @@
+use std::{collections::HashMap, sync::Arc};
+
+use freya::{prelude::*, query::*};
+
+use crate::{
+    db::{
+        catalog::{CatalogSource, PendingCatalog, SourceDecisions},
+        index::tags::MangaTag,
+        user::TrustLevel,
+    },
+    types::PublicKey,
+    ui::{DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, queries::LandCatalog},
+};
+
+/// Sources found in a fetched catalog, to be trusted or blocked before any of
+/// it is stored
+#[derive(PartialEq)]
+pub struct ImportReview {
+    pub pending: Arc<PendingCatalog<MangaTag>>,
+}
+impl Component for ImportReview {
+    fn render(&self) -> impl IntoElement {
+        let mut blocked = use_state(Vec::<PublicKey>::new);
+        let trust = use_state(HashMap::<PublicKey, TrustLevel>::new);
+        let land_mutation = use_mutation(Mutation::new(LandCatalog));
+
+        let unknown: Vec<PublicKey> = self
+            .pending
+            .sources
+            .iter()
+            .filter(|s| s.trust.is_none())
+            .map(|s| s.key.clone())
+            .collect();
+
+        let landed = matches!(
+            &*land_mutation.read().state(),
+            MutationStateData::Loading { .. } | MutationStateData::Settled { res: Ok(_), .. }
+        );
+        let result = match &*land_mutation.read().state() {
+            MutationStateData::Pending => None,
+            MutationStateData::Loading { .. } => Some("Importing...".to_string()),
+            MutationStateData::Settled {
+                res: Ok(imported), ..
+            } => Some(format!(
+                "Imported {} indexes and {} contents, left out {} from blocked sources",
+                imported.indexes, imported.contents, imported.blocked
+            )),
+            MutationStateData::Settled { res: Err(e), .. } => Some(e.to_string()),
+        };
+
+        let rows: Vec<Element> = self
+            .pending
+            .sources
+            .iter()
+            .map(|source| {
+                SourceRow {
+                    source: source.clone(),
+                    blocked,
+                    trust,
+                }
+                .into_element()
+            })
+            .collect();
+
+        let pending = self.pending.clone();
+        let on_import = move |_| {
+            let decisions = SourceDecisions {
+                blocked: blocked.read().clone(),
+                trust: trust.read().iter().map(|(k, t)| (k.clone(), *t)).collect(),
+            };
+            land_mutation.mutate((pending.clone(), decisions));
+        };
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .child(label().text("Review import").font_size(48))
+            .child(format!(
+                "{} indexes and {} contents from {} sources, nothing is stored until you import",
+                self.pending.catalog.indexes.len(),
+                self.pending.catalog.contents.len(),
+                self.pending.sources.len()
+            ))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .child(
+                        Button::new()
+                            .child(format!("Block all unknown ({})", unknown.len()))
+                            .on_press(move |_| {
+                                let mut blocked = blocked.write();
+                                for key in &unknown {
+                                    if !blocked.contains(key) {
+                                        blocked.push(key.clone());
+                                    }
+                                }
+                            }),
+                    )
+                    .child(Button::new().child("Unblock all").on_press(move |_| {
+                        blocked.write().clear();
+                    })),
+            )
+            .child(rect().spacing(10.).children(rows))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Button::new()
+                            .child("Import")
+                            .enabled(!landed)
+                            .on_press(on_import),
+                    )
+                    .maybe(result.is_some(), |r| r.child(result.unwrap_or_default())),
+            )
+    }
+}
+
+struct SourceRow {
+    source: CatalogSource,
+    blocked: State<Vec<PublicKey>>,
+    trust: State<HashMap<PublicKey, TrustLevel>>,
+}
+
+/// The states are shared by every row, the row reads them itself
+impl PartialEq for SourceRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Component for SourceRow {
+    fn render(&self) -> impl IntoElement {
+        let mut blocked_state = self.blocked;
+        let mut trust_state = self.trust;
+        let key = self.source.key.clone();
+        let is_blocked = self.blocked.read().contains(&key);
+
+        let trust_selector = self.source.trust.map(|current| {
+            let selected = self.trust.read().get(&key).copied().unwrap_or(current);
+            let segments = TrustLevel::ALL.map(|level| -> Element {
+                let key = key.clone();
+                ButtonSegment::new()
+                    .selected(level == selected)
+                    .on_press(move |_| {
+                        trust_state.write().insert(key.clone(), level);
+                    })
+                    .child(level.to_string())
+                    .into()
+            });
+            SegmentedButton::new().children(segments)
+        });
+
+        rect()
+            .width(Size::Fill)
+            .padding(10.)
+            .spacing(5.)
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::DARK_GRAY)
+            .child(
+                label()
+                    .text(self.source.name.clone())
+                    .font_weight(FontWeight::BOLD)
+                    .color(Color::WHITE),
+            )
+            .child(
+                label()
+                    .text(format!(
+                        "{} indexes, {} contents{}",
+                        self.source.indexes,
+                        self.source.contents,
+                        match self.source.trust {
+                            Some(_) => "",
+                            None => " | never seen before",
+                        }
+                    ))
+                    .color(Color::WHITE),
+            )
+            .maybe(trust_selector.is_some(), |r| {
+                r.child(trust_selector.unwrap())
+            })
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(Switch::new().toggled(is_blocked).on_toggle(move |_| {
+                        let mut blocked = blocked_state.write();
+                        match blocked.iter().position(|k| *k == key) {
+                            Some(i) => {
+                                blocked.remove(i);
+                            }
+                            None => blocked.push(key.clone()),
+                        }
+                    }))
+                    .child(
+                        label()
+                            .text("Block, leave out and mute")
+                            .color(Color::WHITE),
+                    ),
+            )
+    }
+}
@@ -0,0 +1,146 @@
+use freya::{prelude::*, query::*};
+
+use crate::{
+    db::stats::{TagStats, UploaderCount},
+    ui::{
+        DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING,
+        queries::{FetchDisplayName, FetchLibraryStats},
+    },
+};
+
+const BAR_MAX_WIDTH: f32 = 300.;
+
+#[derive(PartialEq)]
+pub struct LibraryStats;
+impl Component for LibraryStats {
+    fn render(&self) -> impl IntoElement {
+        let stats_query = use_query(Query::new((), FetchLibraryStats));
+
+        let stats = match &*stats_query.read().state() {
+            QueryStateData::Pending => rect().child(CircularLoader::new()),
+            QueryStateData::Loading { .. } => rect().child(CircularLoader::new()),
+            QueryStateData::Settled { res, .. } => match res {
+                Ok(res) => {
+                    let children: Vec<Element> =
+                        res.iter().map(|s| tag_stats(s).into_element()).collect();
+
+                    rect().spacing(10.).children(children)
+                }
+                Err(e) => rect().child(label().text(e.to_string())),
+            },
+        };
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .child(label().text("Library").font_size(48))
+            .child(stats)
+    }
+}
+
+fn tag_stats(stats: &TagStats) -> impl IntoElement {
+    let downloaded_ratio = if stats.contents == 0 {
+        0.
+    } else {
+        stats.downloaded as f32 / stats.contents as f32 * 100.
+    };
+
+    let uploaders: Vec<Element> = stats
+        .top_uploaders
+        .iter()
+        .map(|u| UploaderRow(u.clone()).into_element())
+        .collect();
+
+    let max_month = stats.growth.iter().map(|(_, c)| *c).max().unwrap_or(1);
+    let growth: Vec<Element> = stats
+        .growth
+        .iter()
+        .map(|(month, count)| bar(month, *count, max_month).into_element())
+        .collect();
+
+    rect()
+        .width(Size::Fill)
+        .padding(10.)
+        .spacing(5.)
+        .corner_radius(DEFAULT_CORNER_RADIUS)
+        .background(Color::DARK_GRAY)
+        .child(
+            label()
+                .text(stats.tag)
+                .font_size(24)
+                .font_weight(FontWeight::BOLD)
+                .color(Color::WHITE),
+        )
+        .child(stat("Indexes", stats.indexes.to_string()))
+        .child(stat("Contents", stats.contents.to_string()))
+        .child(stat(
+            "Downloaded",
+            format!(
+                "{} ({:.0}%), {} metadata only",
+                stats.downloaded,
+                downloaded_ratio,
+                stats.contents.saturating_sub(stats.downloaded)
+            ),
+        ))
+        .child(
+            ProgressBar::new(downloaded_ratio)
+                .show_progress(false)
+                .width(Size::px(BAR_MAX_WIDTH))
+                .height(5.),
+        )
+        .child(label().text("Top uploaders").color(Color::WHITE))
+        .children(uploaders)
+        .child(label().text("Content added per month").color(Color::WHITE))
+        .children(growth)
+}
+
+fn stat(name: &str, value: String) -> impl IntoElement {
+    rect()
+        .horizontal()
+        .spacing(10.)
+        .child(
+            label()
+                .text(name.to_string())
+                .font_weight(FontWeight::BOLD)
+                .color(Color::WHITE),
+        )
+        .child(label().text(value).color(Color::WHITE))
+}
+
+fn bar(name: &str, count: usize, max: usize) -> impl IntoElement {
+    let width = (count as f32 / max.max(1) as f32 * BAR_MAX_WIDTH).max(1.);
+
+    rect()
+        .horizontal()
+        .spacing(10.)
+        .cross_align(Alignment::Center)
+        .child(
+            label()
+                .text(name.to_string())
+                .width(Size::px(80.))
+                .color(Color::LIGHT_GRAY),
+        )
+        .child(
+            rect()
+                .width(Size::px(width))
+                .height(Size::px(10.))
+                .corner_radius(2.)
+                .background(Color::LIGHT_GRAY),
+        )
+        .child(label().text(count.to_string()).color(Color::WHITE))
+}
+
+#[derive(PartialEq)]
+struct UploaderRow(UploaderCount);
+impl Component for UploaderRow {
+    fn render(&self) -> impl IntoElement {
+        let name_query = use_query(Query::new(self.0.poster.clone(), FetchDisplayName));
+        let name = match &*name_query.read().state() {
+            QueryStateData::Settled { res: Ok(name), .. } => name.clone(),
+            _ => self.0.poster.fingerprint(),
+        };
+
+        stat(&name, self.0.count.to_string())
+    }
+}
@@ -1,17 +1,26 @@
+use std::path::Path;
+
 use freya::{prelude::*, query::*, radio::use_radio};
+use tracing::error;
 
 use crate::{
+    config::ContentLimitsConfig,
     db::{
         Magnet,
         index::{
             Index,
-            content::Content,
+            content::{Content, is_valid_web_seed},
+            manifest::build_manifest,
             tags::{MangaChapter, MangaTag},
         },
     },
     helpers::Language,
     types::Timestamp,
-    ui::{AppChannel, ResourceState, queries::AddIndexContent},
+    ui::{
+        AppChannel, ResourceState,
+        app_manager::push_to_subscribers,
+        queries::{AddIndexContent, AddLocalContent},
+    },
 };
 
 #[derive(PartialEq)]
@@ -23,17 +32,82 @@ impl Component for AddMangaChapter {
         let title = use_state(String::new);
         let path = use_state(String::new);
         let magnet_link = use_state(String::new);
+        // Local copy of the files, hashed into the manifest when set
+        let files_path = use_state(String::new);
+        // Eepsite serving the same files, used when the torrent has no seeds
+        let web_seed = use_state(String::new);
         let enumeration = use_state(|| "1".to_string());
+        let mut local_only = use_state(|| false);
         let state = use_radio(AppChannel::Config);
 
         let mutation = use_mutation(Mutation::new(AddIndexContent::<MangaTag>::new()));
+        let local_mutation = use_mutation(Mutation::new(AddLocalContent::<MangaTag>::new()));
 
         let hash = self.index.hash().clone();
 
+        let (dev_mode, limits, allowed_trackers) = match &state.read().config {
+            ResourceState::Loaded(c) => (
+                c.dev_mode(),
+                c.content_limits().clone(),
+                c.allowed_trackers().to_vec(),
+            ),
+            _ => (false, ContentLimitsConfig::default(), vec![]),
+        };
+        let is_local = dev_mode && *local_only.read();
+        // Signed without the clearnet trackers, the publisher is told which
+        let (scrubbed_magnet, stripped_trackers) =
+            Magnet(magnet_link.read().trim().to_string()).scrub_trackers(&allowed_trackers);
+
+        let local_only_switch = rect()
+            .horizontal()
+            .spacing(10.)
+            .cross_align(Alignment::Center)
+            .child(
+                Switch::new()
+                    .toggled(*local_only.read())
+                    .on_toggle(move |_| {
+                        let toggled = !*local_only.read();
+                        *local_only.write() = toggled;
+                    }),
+            )
+            .child("Local only (never shared)");
+
+        let path_placeholder = if is_local {
+            "Folder or .cbz on this machine"
+        } else {
+            "Path"
+        };
+        let path_exists = !is_local || Path::new(&*path.read()).exists();
+        let files_exist = files_path.read().is_empty() || Path::new(&*files_path.read()).exists();
+        let web_seed_valid = web_seed.read().is_empty() || is_valid_web_seed(&web_seed.read());
+        // The rest of the limits can only be checked once the files are hashed
+        let within_limits = is_local
+            || (title.read().chars().count() <= limits.max_title_chars
+                && path.read().chars().count() <= limits.max_path_chars);
+
         rect()
             .child(Input::new(title).placeholder("Title"))
-            .child(Input::new(magnet_link).placeholder("Magnet Link"))
-            .child(Input::new(path).placeholder("Path"))
+            .maybe(dev_mode, |r| r.child(local_only_switch))
+            .maybe(!is_local, |r| {
+                r.child(Input::new(magnet_link).placeholder("Magnet Link"))
+                    .children(stripped_trackers.iter().map(|t| {
+                        label()
+                            .text(format!("Clearnet tracker removed from the magnet: {}", t))
+                            .color(Color::from_rgb(230, 140, 0))
+                            .into()
+                    }))
+            })
+            .child(Input::new(path).placeholder(path_placeholder))
+            .maybe(!is_local, |r| {
+                r.child(
+                    Input::new(files_path)
+                        .placeholder("Local copy of the files (optional, adds checksums)"),
+                )
+                .child(
+                    Input::new(web_seed)
+                        .placeholder("Fallback eepsite URL (optional, http://….i2p/…)"),
+                )
+            })
             .child(
                 Input::new(enumeration)
                     .placeholder("Enumeration")
@@ -43,22 +117,82 @@ impl Component for AddMangaChapter {
                     })
                     .text_align(TextAlign::Left),
             )
-            .child(Button::new().child("Add").on_press(move |_| {
-                if let ResourceState::Loaded(c) = &state.read().config {
-                    mutation.mutate(Content::new_signed(
-                        hash.clone(),
-                        Timestamp::now(),
-                        Magnet(magnet_link.read().clone()),
-                        path.read().clone(),
-                        title.read().clone(),
-                        0.0,
-                        None,
-                        MangaChapter::new(Language::Unknown),
-                        c.private_key(),
-                    ));
-                }
-                // RouterContext::get().push(Route::Manga { hash: hash.clone()
-                // });
-            }))
+            .child(
+                Button::new()
+                    .child("Add")
+                    .enabled(path_exists && files_exist && web_seed_valid && within_limits)
+                    .on_press(move |_| {
+                        let state = state.read();
+                        let ResourceState::Loaded(c) = &state.config else {
+                            return;
+                        };
+                        // Subscribers of the index get it pushed right away
+                        let push = match (&state.client, &state.repositories) {
+                            (ResourceState::Loaded(p), ResourceState::Loaded(r)) if !is_local => {
+                                Some((p.clone(), r.clone()))
+                            }
+                            _ => None,
+                        };
+
+                        // Local entries have no torrent, the path is read as is
+                        let magnet = match is_local {
+                            true => Magnet(String::new()),
+                            false => scrubbed_magnet.clone(),
+                        };
+                        let files = match is_local {
+                            true => String::new(),
+                            false => files_path.read().clone(),
+                        };
+                        let web_seed = match is_local || web_seed.read().is_empty() {
+                            true => None,
+                            false => Some(web_seed.read().clone()),
+                        };
+                        let private_key = c.private_key().clone();
+                        let hash = hash.clone();
+                        let source = path.read().clone();
+                        let title = title.read().clone();
+
+                        spawn(async move {
+                            let manifest = match files.is_empty() {
+                                true => vec![],
+                                false => match build_manifest(Path::new(&files)).await {
+                                    Ok(manifest) => manifest,
+                                    Err(e) => {
+                                        error!("Failed to hash {}: {}", files, e);
+                                        return;
+                                    }
+                                },
+                            };
+
+                            let content = Content::new_signed(
+                                hash,
+                                Timestamp::now(),
+                                magnet,
+                                source,
+                                title,
+                                0.0,
+                                None,
+                                MangaChapter::new(Language::Unknown),
+                                manifest,
+                                web_seed,
+                                &private_key,
+                            );
+                            if let Some((client, repositories)) = push {
+                                tokio::spawn(push_to_subscribers(
+                                    client,
+                                    repositories,
+                                    content.index_hash().clone(),
+                                    vec![content.clone()],
+                                ));
+                            }
+                            match is_local {
+                                true => local_mutation.mutate(content),
+                                false => mutation.mutate(content),
+                            }
+                        });
+                        // RouterContext::get().push(Route::Manga { hash:
+                        // hash.clone() });
+                    }),
+            )
     }
 }
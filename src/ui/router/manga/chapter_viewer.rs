@@ -4,34 +4,83 @@ use async_zip::tokio::read::seek::ZipFileReader;
 use freya::{
     elements::image::{ImageHolder, image},
     prelude::*,
-    query::{Mutation, use_mutation},
+    query::{Mutation, MutationStateData, use_mutation},
     radio::use_radio,
 };
 use futures::AsyncReadExt as _;
+use image::imageops::FilterType;
 use mangadex_api::utils::download::chapter::DownloadMode;
 use tokio::{fs::File, io::BufReader};
 use tracing::error;
 
 use crate::{
     config::ImageVisualizationType,
-    db::index::{
-        content::{Content, ContentType, ExternalContent, InternalContent},
-        tags::{ChapterExternalSource, IndexTag, MangaTag},
+    db::{
+        history::HistoryEntry,
+        index::{
+            content::{Content, ContentType, ExternalContent, InternalContent},
+            tags::{ChapterExternalSource, MangaTag},
+        },
     },
+    helpers::Lru,
+    storage::StorageConfig,
     ui::{
         AppChannel, ResourceState,
         components::AkLayers,
-        queries::{UpdateContentCount, UpdateContentProgress},
+        queries::{ExportChapter, RecordHistory, UpdateContentCount, UpdateContentProgress},
     },
 };
 
+/// How many decoded pages are kept in memory, older ones are decoded again
+/// when revisited
+const DECODED_PAGES: usize = 8;
+
+/// Pages wider than this are downscaled after decoding, it's above the width
+/// of common screens so it only shrinks huge scans that would be scaled down
+/// when drawn anyway. Height isn't capped, long strips would be shrunk past
+/// reading.
+const MAX_DECODED_WIDTH: u32 = 3840;
+
+/// Page ready to be drawn
+#[derive(Clone)]
+struct DecodedPage {
+    image: ImageHolder,
+    /// Height before downscaling, pages are shown at their original size
+    height: f32,
+}
+
+type DecodedPages = Vec<Option<DecodedPage>>;
+
+/// Pages decoded ahead of the current one in the reading direction, kept below
+/// [`DECODED_PAGES`] so prefetching never evicts the page being read
+const PREFETCH_PAGES: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReadingDirection {
+    Forward,
+    Backward,
+}
+
+/// Pages to prefetch from `index`, nearest first
+fn prefetch_pages(index: usize, total: usize, direction: ReadingDirection) -> Vec<usize> {
+    match direction {
+        ReadingDirection::Forward => (index + 1..total).take(PREFETCH_PAGES).collect(),
+        ReadingDirection::Backward => (0..index).rev().take(PREFETCH_PAGES).collect(),
+    }
+}
+
 #[derive(PartialEq)]
 pub struct ChapterViewer<S: ContentType<MangaTag> + ImageLoaderExt<S>> {
     pub content: Content<MangaTag, S>,
 }
 impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S> {
     fn render(&self) -> impl IntoElement {
-        let images = use_state(Vec::<Option<ImageHolder>>::new);
+        // Encoded pages, filled by the loader
+        let pages = use_state(Vec::<Option<Bytes>>::new);
+        let decoded = use_state(DecodedPages::new);
+        let lru = use_state(Lru::<usize, DECODED_PAGES>::new);
+        let mut reading_direction = use_state(|| ReadingDirection::Forward);
+        let mut prefetching = use_state(Vec::<TaskHandle>::new);
         let mut cur_page_index = use_state(|| {
             if self.content.progress == 0 || self.content.progress == self.content.count {
                 0
@@ -44,18 +93,51 @@ impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S
         let count_mutation = use_mutation(Mutation::new(UpdateContentCount::<MangaTag>::new()));
         let progress_mutation =
             use_mutation(Mutation::new(UpdateContentProgress::<MangaTag>::new()));
-
-        S::start_loader(&self.content, images);
+        let history_mutation = use_mutation(Mutation::new(RecordHistory));
 
         let mut config = use_radio(AppChannel::Config);
 
+        let storage = config.read().config.unwrap_ref().storage().clone();
+        S::start_loader(&self.content, &storage, pages);
+
         let mut scroll_controller = use_scroll_controller(ScrollConfig::default);
 
         let signature = self.content.signature().clone();
         use_side_effect(move || {
-            count_mutation.mutate((signature.clone(), images.read().len() as u32));
+            count_mutation.mutate((signature.clone(), pages.read().len() as u32));
+        });
+
+        use_side_effect(move || {
+            let index = cur_page_index() as usize;
+            let pages = pages.read();
+            if let Some(Some(bytes)) = pages.get(index) {
+                request_decode(index, bytes.clone(), decoded, lru);
+            }
+
+            let direction = *reading_direction.peek();
+            let mut prefetching = prefetching.write();
+            for i in prefetch_pages(index, pages.len(), direction) {
+                if let Some(Some(bytes)) = pages.get(i)
+                    && let Some(task) = request_decode(i, bytes.clone(), decoded, lru)
+                {
+                    prefetching.push(task);
+                }
+            }
+            // Older handles belong to pages already reached
+            let excess = prefetching.len().saturating_sub(PREFETCH_PAGES * 2);
+            prefetching.drain(..excess);
         });
 
+        // Prefetched pages behind us are useless once the reader turns back
+        let mut turn = move |direction: ReadingDirection| {
+            if *reading_direction.peek() != direction {
+                *reading_direction.write() = direction;
+                for task in prefetching.write().drain(..) {
+                    task.try_cancel();
+                }
+            }
+        };
+
         let signature = self.content.signature().clone();
         let prog = self.content.progress;
         use_side_effect(move || {
@@ -65,7 +147,17 @@ impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S
             };
         });
 
+        // Opening the chapter and every page turn move it up the history
+        let content = self.content.clone();
+        use_side_effect(move || {
+            let cur_page = cur_page_index() + 1;
+            if S::KEEPS_HISTORY {
+                history_mutation.mutate(HistoryEntry::from_content(&content, cur_page));
+            }
+        });
+
         let mut back_page = move || {
+            turn(ReadingDirection::Backward);
             let mut cur_page = cur_page_index.write();
             if *cur_page > 0 {
                 *cur_page -= 1;
@@ -73,8 +165,9 @@ impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S
             }
         };
         let mut forward_page = move || {
+            turn(ReadingDirection::Forward);
             let mut cur_page = cur_page_index.write();
-            let total_pages: u32 = images.read().len() as u32;
+            let total_pages: u32 = pages.read().len() as u32;
             if *cur_page + 1 < total_pages {
                 *cur_page += 1;
                 scroll_controller.scroll_to(ScrollPosition::Start, Direction::Vertical);
@@ -156,9 +249,9 @@ impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S
             .horizontal()
             .min_height(Size::Fill)
             .width(Size::Fill)
-            .child(match images.read().get(*cur_page_index.read() as usize) {
-                Some(Some(img)) => image(img.clone())
-                    .height(Size::px(img.image.borrow().height() as f32 * zoom))
+            .child(match decoded.read().get(*cur_page_index.read() as usize) {
+                Some(Some(page)) => image(page.image.clone())
+                    .height(Size::px(page.height * zoom))
                     .into_element(),
                 _ => CircularLoader::new().into_element(),
             });
@@ -168,18 +261,25 @@ impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S
             .text(format!(
                 "{}/{}",
                 *cur_page_index.read() + 1,
-                images.read().len()
+                pages.read().len()
             ))
             .text_align(TextAlign::Center)
             .font_size(21);
 
+        let export_panel = S::local_path(&self.content, &storage).map(|source| ExportPanel {
+            source,
+            title: self.content.title().to_string(),
+        });
+
         let right_side_bar = rect()
             .layer(AkLayers::Sidebars)
             .width(Size::px(200.0))
             .height(Size::percent(100.0))
             .position(Position::new_absolute().right(0.0))
             .background(Color::GRAY)
+            .spacing(10.)
             .child(page_counter)
+            .maybe(export_panel.is_some(), |r| r.child(export_panel.unwrap()))
             .on_mouse_down(|e: Event<MouseEventData>| {
                 e.stop_propagation();
             });
@@ -244,28 +344,164 @@ impl<S: ContentType<MangaTag> + ImageLoaderExt<S>> Component for ChapterViewer<S
     }
 }
 
+#[derive(PartialEq)]
+struct ExportPanel {
+    source: PathBuf,
+    title: String,
+}
+impl Component for ExportPanel {
+    fn render(&self) -> impl IntoElement {
+        let title = self.title.clone();
+        let destination = use_state(move || format!("./exports/{}.cbz", title));
+        let export_mutation = use_mutation(Mutation::new(ExportChapter));
+
+        let status = match &*export_mutation.read().state() {
+            MutationStateData::Pending => None,
+            MutationStateData::Loading { .. } => Some("Exporting...".to_string()),
+            MutationStateData::Settled { res: Ok(pages), .. } => {
+                Some(format!("Exported {} pages", pages))
+            }
+            MutationStateData::Settled { res: Err(e), .. } => Some(e.to_string()),
+        };
+
+        let source = self.source.clone();
+
+        rect()
+            .padding(5.)
+            .spacing(5.)
+            .width(Size::Fill)
+            .child(Input::new(destination).width(Size::Fill))
+            .child(Button::new().child("Export CBZ").on_press(move |_| {
+                let destination = PathBuf::from(destination.read().clone());
+                export_mutation.mutate((source.clone(), destination));
+            }))
+            .maybe(status.is_some(), |r| {
+                r.child(label().text(status.unwrap_or_default()).font_size(12))
+            })
+    }
+}
+
+/// Decodes page `index` on the blocking pool unless it's already decoded,
+/// evicting the least recently used page once over [`DECODED_PAGES`]. Returns
+/// the decoding task if one was started.
+fn request_decode(
+    index: usize,
+    bytes: Bytes,
+    mut decoded: State<DecodedPages>,
+    mut lru: State<Lru<usize, DECODED_PAGES>>,
+) -> Option<TaskHandle> {
+    if let Some(evicted) = lru.write().touch(index)
+        && let Some(page) = decoded.write().get_mut(evicted)
+    {
+        *page = None;
+    }
+
+    if decoded.peek().get(index).is_some_and(|p| p.is_some()) {
+        return None;
+    }
+
+    Some(spawn(async move {
+        let encoded = bytes.clone();
+        let Some((image, height)) = blocking::unblock(move || decode_page(&encoded)).await else {
+            error!("Failed to decode page {}", index);
+            return;
+        };
+
+        // Evicted while decoding
+        if !lru.peek().contains(&index) {
+            return;
+        }
+
+        let mut decoded = decoded.write();
+        if decoded.len() <= index {
+            decoded.resize(index + 1, None);
+        }
+        decoded[index] = Some(DecodedPage {
+            image: ImageHolder {
+                image: Rc::new(RefCell::new(image)),
+                bytes,
+            },
+            height,
+        });
+    }))
+}
+
+/// Decodes to RGBA up front so drawing doesn't have to, downscaling pages
+/// wider than [`MAX_DECODED_WIDTH`]. Returns the original height too.
+fn decode_page(bytes: &[u8]) -> Option<(skia_safe::Image, f32)> {
+    let page = image::load_from_memory(bytes).ok()?.into_rgba8();
+    let original_height = page.height() as f32;
+
+    let page = if page.width() > MAX_DECODED_WIDTH {
+        let height = (page.height() as u64 * MAX_DECODED_WIDTH as u64 / page.width() as u64) as u32;
+        image::imageops::resize(
+            &page,
+            MAX_DECODED_WIDTH,
+            height.max(1),
+            FilterType::Triangle,
+        )
+    } else {
+        page
+    };
+
+    let (width, height) = page.dimensions();
+    let info = skia_safe::ImageInfo::new(
+        (width as i32, height as i32),
+        skia_safe::ColorType::RGBA8888,
+        skia_safe::AlphaType::Unpremul,
+        None,
+    );
+    let image = skia_safe::images::raster_from_data(
+        &info,
+        skia_safe::Data::new_copy(page.as_raw()),
+        width as usize * 4,
+    )?;
+
+    Some((image, original_height))
+}
+
 trait ImageLoaderExt<S: ContentType<MangaTag>> {
+    /// Only content stored in the database can be resumed from the history
+    const KEEPS_HISTORY: bool;
+
+    /// Where the downloaded files of this chapter live, if they are local
+    fn local_path(content: &Content<MangaTag, S>, storage: &StorageConfig) -> Option<PathBuf>;
+
     fn start_loader(
         content: &Content<MangaTag, S>,
-        images: State<Vec<Option<ImageHolder>>>,
+        storage: &StorageConfig,
+        pages: State<Vec<Option<Bytes>>>,
     ) -> TaskHandle;
 }
 
 impl ImageLoaderExt<InternalContent> for InternalContent {
+    const KEEPS_HISTORY: bool = true;
+
+    fn local_path(
+        content: &Content<MangaTag, InternalContent>,
+        storage: &StorageConfig,
+    ) -> Option<PathBuf> {
+        if content.is_local_only() {
+            return Some(content.source().into());
+        }
+
+        storage.content_source(content)
+    }
+
     fn start_loader(
         content: &Content<MangaTag, InternalContent>,
-        mut images: State<Vec<Option<ImageHolder>>>,
+        storage: &StorageConfig,
+        mut pages: State<Vec<Option<Bytes>>>,
     ) -> TaskHandle {
         let chapter_loader = use_hook(move || {
-            let source: PathBuf = format!(
-                "./data{}/{}/{}",
-                MangaTag::TAG,
-                content.signature(),
-                content.source()
-            )
-            .into();
+            let source = Self::local_path(content, storage);
 
             spawn(async move {
+                let Some(source) = source else {
+                    error!("Content source points outside of its folder");
+                    return;
+                };
+
                 if !source.exists() {
                     error!("Path does not exist");
                     return;
@@ -281,23 +517,11 @@ impl ImageLoaderExt<InternalContent> for InternalContent {
                         }
                     }
 
-                    *images.write() = vec![None; paths.len()];
+                    *pages.write() = vec![None; paths.len()];
 
                     for (i, source) in paths.iter().enumerate() {
                         let bytes: Bytes = tokio::fs::read(source).await.unwrap().into();
-                        let (image, bytes) = blocking::unblock(move || {
-                            let image = skia_safe::Image::from_encoded(unsafe {
-                                skia_safe::Data::new_bytes(&bytes)
-                            })
-                            .unwrap();
-                            (image, bytes)
-                        })
-                        .await;
-
-                        images.write()[i] = Some(ImageHolder {
-                            image: Rc::new(RefCell::new(image)),
-                            bytes,
-                        });
+                        pages.write()[i] = Some(bytes);
                     }
                     return;
                 }
@@ -310,7 +534,7 @@ impl ImageLoaderExt<InternalContent> for InternalContent {
                         // TODO: Check how many actual images and ignore other files
                         let total_images = zip.file().entries().len();
 
-                        *images.write() = vec![None; total_images];
+                        *pages.write() = vec![None; total_images];
 
                         // Add priority system so files near the current
                         // page are loaded first
@@ -318,19 +542,7 @@ impl ImageLoaderExt<InternalContent> for InternalContent {
                             let mut f = zip.reader_with_entry(i).await.unwrap();
                             let mut buffer = vec![];
                             f.read_to_end(&mut buffer).await.unwrap();
-                            let (image, bytes) = blocking::unblock(move || {
-                                let image = skia_safe::Image::from_encoded(unsafe {
-                                    skia_safe::Data::new_bytes(&buffer)
-                                })
-                                .unwrap();
-                                (image, buffer.into())
-                            })
-                            .await;
-
-                            images.write()[i] = Some(ImageHolder {
-                                image: Rc::new(RefCell::new(image)),
-                                bytes,
-                            });
+                            pages.write()[i] = Some(buffer.into());
                         }
                     }
                 }
@@ -346,9 +558,19 @@ impl ImageLoaderExt<InternalContent> for InternalContent {
 }
 
 impl ImageLoaderExt<ExternalContent> for ExternalContent {
+    const KEEPS_HISTORY: bool = false;
+
+    fn local_path(
+        _content: &Content<MangaTag, ExternalContent>,
+        _storage: &StorageConfig,
+    ) -> Option<PathBuf> {
+        None
+    }
+
     fn start_loader(
         content: &Content<MangaTag, ExternalContent>,
-        mut images: State<Vec<Option<ImageHolder>>>,
+        _storage: &StorageConfig,
+        mut pages: State<Vec<Option<Bytes>>>,
     ) -> TaskHandle {
         let source = content.source().clone();
         let chapter_loader = use_hook(move || {
@@ -365,25 +587,11 @@ impl ImageLoaderExt<ExternalContent> for ExternalContent {
                             .unwrap();
 
                         let file_names = res.build_at_home_urls().await.unwrap();
-                        *images.write() = vec![None; file_names.len()];
+                        *pages.write() = vec![None; file_names.len()];
 
                         for (i, filename) in file_names.iter().enumerate() {
                             let (_, bytes) = filename.download().await;
-                            let bytes = bytes.unwrap();
-
-                            let (image, bytes) = blocking::unblock(move || {
-                                let image = skia_safe::Image::from_encoded(unsafe {
-                                    skia_safe::Data::new_bytes(&bytes)
-                                })
-                                .unwrap();
-                                (image, bytes)
-                            })
-                            .await;
-
-                            images.write()[i] = Some(ImageHolder {
-                                image: Rc::new(RefCell::new(image)),
-                                bytes,
-                            });
+                            pages.write()[i] = Some(bytes.unwrap());
                         }
                     }
                 }
@@ -397,3 +605,47 @@ impl ImageLoaderExt<ExternalContent> for ExternalContent {
         chapter_loader
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefetches_in_reading_direction() {
+        assert_eq!(
+            prefetch_pages(2, 10, ReadingDirection::Forward),
+            vec![3, 4, 5]
+        );
+        assert_eq!(prefetch_pages(8, 10, ReadingDirection::Forward), vec![9]);
+        assert_eq!(
+            prefetch_pages(4, 10, ReadingDirection::Backward),
+            vec![3, 2, 1]
+        );
+        assert!(prefetch_pages(0, 10, ReadingDirection::Backward).is_empty());
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image::RgbaImage::new(width, height)
+            .write_to(&mut bytes, image::ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn long_strips_keep_their_size() {
+        let (page, height) = decode_page(&png(800, 12_000)).unwrap();
+
+        assert_eq!((page.width(), page.height()), (800, 12_000));
+        assert_eq!(height, 12_000.0);
+    }
+
+    #[test]
+    fn wide_pages_are_downscaled() {
+        let (page, height) = decode_page(&png(MAX_DECODED_WIDTH * 2, 1000)).unwrap();
+
+        assert_eq!(page.width() as u32, MAX_DECODED_WIDTH);
+        assert_eq!(page.height(), 500);
+        assert_eq!(height, 1000.0);
+    }
+}
@@ -1,17 +1,27 @@
+use std::collections::HashMap;
+
 use freya::{
     elements::image::image,
     prelude::*,
-    query::{Mutation, Query, QueryStateData, use_mutation, use_query},
+    query::{Mutation, MutationStateData, Query, QueryStateData, use_mutation, use_query},
 };
 
 use crate::{
-    db::index::{Index, tags::MangaTag},
+    db::{
+        changes::DataKind,
+        follow_index::NotificationPreference,
+        index::{Index, tags::MangaTag},
+        suppression::Suppression,
+    },
+    types::{Link, Topic},
     ui::{
         DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, Route, RouteContext, UNKNOWN_COVER,
-        components::{ContentEntry, Spacer, svg_button},
+        components::{ContentEntry, Spacer, copy_button, svg_button},
+        hooks::use_data_changed,
         icons::{self},
         queries::{
-            FetchContents, FetchCover, FetchMangadexChapters, FollowContent, GetFollowContent,
+            DeleteIndex, FetchContents, FetchCover, FetchMangadexChapters, FetchPostCounts,
+            FollowContent, GetFollowContent, SetNotificationPreference,
         },
     },
 };
@@ -38,13 +48,19 @@ impl Component for Manga {
         ));
 
         let bookmark_mut = use_mutation(Mutation::new(FollowContent::<MangaTag>::new()));
+        let notify_mut = use_mutation(Mutation::new(SetNotificationPreference::<MangaTag>::new()));
 
         let title = label().text(self.index.title().clone()).font_size(24);
 
-        let index = self.index.clone();
+        let hash = self.index.hash().clone();
         let add_chapter_press = move |_| {
-            RouteContext::get().push(Route::AddMangaChapter {
-                index: index.clone(),
+            RouteContext::get().push(Route::AddMangaChapter { hash: hash.clone() });
+        };
+
+        let hash = self.index.hash().clone();
+        let publish_press = move |_| {
+            RouteContext::get().push(Route::Publish {
+                hash: Some(hash.clone()),
             });
         };
 
@@ -78,6 +94,35 @@ impl Component for Manga {
             },
         };
 
+        // How new chapters are announced, only for followed titles
+        let notification_selector = match &*bookmark_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(Some(follow)),
+                ..
+            } => {
+                let current = follow.notify();
+                let segments = NotificationPreference::ALL.map(|preference| -> Element {
+                    let index_hash = self.index.hash().clone();
+                    ButtonSegment::new()
+                        .selected(preference == current)
+                        .on_press(move |_| {
+                            notify_mut.mutate((index_hash.clone(), preference));
+                        })
+                        .child(preference.to_string())
+                        .into()
+                });
+                Some(
+                    rect()
+                        .horizontal()
+                        .spacing(10.)
+                        .cross_align(Alignment::Center)
+                        .child("New chapters:")
+                        .child(SegmentedButton::new().children(segments)),
+                )
+            }
+            _ => None,
+        };
+
         let follow_button = Button::new()
             .child(bookmark_icon)
             .maybe(bookmark_action.is_some(), |el| {
@@ -133,13 +178,49 @@ impl Component for Manga {
             )
             .child(Spacer::horizontal(20.))
             .child(
-                rect().child(title).child(source_selector).child(
-                    rect()
-                        .horizontal()
-                        .child(add_chapter_button)
-                        .child(follow_button),
-                ),
-            );
+                rect()
+                    .child(
+                        rect()
+                            .horizontal()
+                            .spacing(5.)
+                            .cross_align(Alignment::Center)
+                            .child(title)
+                            .child(copy_button(
+                                Link::Index(self.index.hash().clone()).to_string(),
+                                Color::BLACK,
+                            )),
+                    )
+                    .child(source_selector)
+                    .child(
+                        rect()
+                            .horizontal()
+                            .child(add_chapter_button)
+                            .child(Button::new().child("Publish").on_press(publish_press))
+                            .child(follow_button),
+                    )
+                    .maybe(notification_selector.is_some(), |r| {
+                        r.child(notification_selector.unwrap())
+                    }),
+            )
+            .child(Spacer::horizontal(20.))
+            .child(DeleteIndexButton {
+                index: self.index.clone(),
+            });
+
+        let posts_version = use_data_changed(DataKind::Posts);
+        let topics: Vec<Topic> = match &*contents_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(contents), ..
+            } => contents.iter().map(Topic::from_content).collect(),
+            _ => vec![],
+        };
+        let counts_query = use_query(Query::new((topics, posts_version), FetchPostCounts));
+        let post_counts = match &*counts_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(counts), ..
+            } => counts.clone(),
+            _ => HashMap::new(),
+        };
 
         let chapters = {
             match &*selected.read() {
@@ -147,9 +228,13 @@ impl Component for Manga {
                     QueryStateData::Settled {
                         res: Ok(contents), ..
                     } => {
-                        let chapters = contents
-                            .iter()
-                            .map(|c| ContentEntry::new(c.clone()).into_element());
+                        let chapters = contents.iter().map(|c| {
+                            let posts = post_counts
+                                .get(&Topic::from_content(c))
+                                .copied()
+                                .unwrap_or_default();
+                            ContentEntry::new(c.clone()).posts(posts).into_element()
+                        });
                         rect().vertical().children(chapters).into_element()
                     }
                     QueryStateData::Pending | QueryStateData::Loading { .. } => {
@@ -185,3 +270,91 @@ impl Component for Manga {
             .padding(DEFAULT_PAGE_PADDING)
     }
 }
+
+/// Asks before deleting, the index can only come back from another peer
+#[derive(PartialEq)]
+struct DeleteIndexButton {
+    index: Index<MangaTag>,
+}
+
+impl Component for DeleteIndexButton {
+    fn render(&self) -> impl IntoElement {
+        let mut confirming = use_state(|| false);
+        let mut delete_files = use_state(|| false);
+        let mut suppress = use_state(|| false);
+        let delete_mutation = use_mutation(Mutation::new(DeleteIndex::<MangaTag>::new()));
+
+        match &*delete_mutation.read().state() {
+            MutationStateData::Settled { res: Ok(()), .. } => {
+                return rect().spacing(5.).child("Deleted").child(
+                    Button::new()
+                        .child("Back")
+                        .on_press(move |_| RouteContext::get().go_back()),
+                );
+            }
+            MutationStateData::Loading { .. } => return rect().child("Deleting..."),
+            MutationStateData::Settled { res: Err(e), .. } => {
+                return rect().child(label().text(e.to_string()));
+            }
+            MutationStateData::Pending => {}
+        }
+
+        if !*confirming.read() {
+            return rect().child(
+                Button::new()
+                    .child("Delete")
+                    .on_press(move |_| confirming.set(true)),
+            );
+        }
+
+        let hash = self.index.hash().clone();
+        let suppression = Suppression::from_index(&self.index);
+
+        rect()
+            .spacing(5.)
+            .child(format!(
+                "Delete {} and everything stored for it?",
+                self.index.title()
+            ))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Switch::new()
+                            .toggled(*delete_files.read())
+                            .on_toggle(move |_| {
+                                let toggled = !*delete_files.read();
+                                delete_files.set(toggled);
+                            }),
+                    )
+                    .child("Also delete downloaded files"),
+            )
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(Switch::new().toggled(*suppress.read()).on_toggle(move |_| {
+                        let toggled = !*suppress.read();
+                        suppress.set(toggled);
+                    }))
+                    .child("Don't fetch it again from other peers"),
+            )
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .child(Button::new().child("Delete").on_press(move |_| {
+                        let suppression = suppress.read().then(|| suppression.clone());
+                        delete_mutation.mutate((hash.clone(), *delete_files.read(), suppression));
+                    }))
+                    .child(
+                        Button::new()
+                            .child("Cancel")
+                            .on_press(move |_| confirming.set(false)),
+                    ),
+            )
+    }
+}
@@ -1,33 +1,118 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
 use freya::{prelude::*, query::*};
 
 use crate::{
-    db::index::tags::MangaTag,
+    db::{changes::DataKind, index::tags::MangaTag},
+    types::Topic,
     ui::{
         DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, IndexComponent,
-        components::svg_button,
+        components::{lazy_list, svg_button},
+        hooks::use_data_changed,
         icons::{self, PLUS_ICON},
-        queries::FetchIndexes,
+        queries::{FetchIndexes, FetchPopularity, FetchPostCounts},
         router::{Route, RouteContext},
     },
 };
 
+/// Cover height plus borders and the gap between rows
+const INDEX_ROW_SIZE: f32 = 214.;
+
+/// Order the library is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum LibrarySort {
+    /// As stored
+    #[default]
+    Added,
+    Title,
+    /// Seen from the most peers first
+    Popular,
+}
+
+impl LibrarySort {
+    const ALL: [LibrarySort; 3] = [LibrarySort::Added, LibrarySort::Title, LibrarySort::Popular];
+}
+
+impl Display for LibrarySort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibrarySort::Added => write!(f, "Added"),
+            LibrarySort::Title => write!(f, "Title"),
+            LibrarySort::Popular => write!(f, "Popular on the network"),
+        }
+    }
+}
+
 #[derive(PartialEq)]
 pub struct MangaList;
 impl Component for MangaList {
     fn render(&self) -> impl IntoElement {
         let manga_query = use_query(Query::new((), FetchIndexes::<MangaTag>::new()));
+        let posts_version = use_data_changed(DataKind::Posts);
+        let topics: Vec<Topic> = match &*manga_query.read().state() {
+            QueryStateData::Settled { res: Ok(res), .. } => {
+                res.iter().map(Topic::from_index).collect()
+            }
+            _ => vec![],
+        };
+        let counts_query = use_query(Query::new((topics, posts_version), FetchPostCounts));
+        let post_counts = match &*counts_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(counts), ..
+            } => counts.clone(),
+            _ => HashMap::new(),
+        };
+
+        let popularity_query = use_query(Query::new((), FetchPopularity));
+        let popularity = match &*popularity_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(popularity),
+                ..
+            } => popularity.clone(),
+            _ => Default::default(),
+        };
+
+        let mut sort = use_state(LibrarySort::default);
+        let sort_selector = SegmentedButton::new().children(LibrarySort::ALL.map(|s| -> Element {
+            ButtonSegment::new()
+                .selected(*sort.read() == s)
+                .on_press(move |_| sort.set(s))
+                .child(s.to_string())
+                .into()
+        }));
 
         let manga_list = match &*manga_query.read().state() {
             QueryStateData::Pending => rect().child(CircularLoader::new()),
             QueryStateData::Loading { .. } => rect().child(CircularLoader::new()),
             QueryStateData::Settled { res, .. } => match res {
                 Ok(res) => {
-                    let children: Vec<Element> = res
-                        .into_iter()
-                        .map(|i| IndexComponent { index: i.clone() }.into_element())
-                        .collect();
+                    let mut indexes = res.clone();
+                    match *sort.read() {
+                        LibrarySort::Added => {}
+                        LibrarySort::Title => {
+                            indexes.sort_by_cached_key(|i| i.title().to_lowercase())
+                        }
+                        LibrarySort::Popular => {
+                            indexes.sort_by_key(|i| std::cmp::Reverse(popularity.index(i.hash())))
+                        }
+                    }
 
-                    rect().children(children)
+                    rect()
+                        .height(Size::Fill)
+                        .child(lazy_list(indexes, INDEX_ROW_SIZE, move |i| {
+                            IndexComponent {
+                                index: i.clone(),
+                                posts: post_counts
+                                    .get(&Topic::from_index(i))
+                                    .copied()
+                                    .unwrap_or_default(),
+                                peers: popularity.index(i.hash()),
+                            }
+                            .into_element()
+                        }))
                 }
                 Err(e) => rect().child(label().text(e.to_string())),
             },
@@ -45,12 +130,24 @@ impl Component for MangaList {
             .spacing(10.)
             .padding(DEFAULT_PAGE_PADDING)
             .width(Size::Fill)
+            .height(Size::Fill)
             .child(search_bar)
             .child(
-                Button::new()
-                    .child(svg(PLUS_ICON))
-                    .on_press(|_| RouteContext::get().push(Route::AddManga)),
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .child(
+                        Button::new()
+                            .child(svg(PLUS_ICON))
+                            .on_press(|_| RouteContext::get().push(Route::AddManga)),
+                    )
+                    .child(
+                        Button::new()
+                            .child("Publish a release")
+                            .on_press(|_| RouteContext::get().push(Route::Publish { hash: None })),
+                    ),
             )
+            .child(sort_selector)
             .child(manga_list)
     }
 }
@@ -0,0 +1,449 @@
+use std::path::Path;
+
+use freya::{prelude::*, query::*, radio::use_radio};
+
+use crate::{
+    db::index::{
+        Index,
+        release::{ChapterDraft, DraftProblem, IndexDraft, Release, ReleaseDraft, TorrentDraft},
+        tags::MangaTag,
+    },
+    ui::{
+        AppChannel, DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, ResourceState,
+        components::{StageState, UNDO_WINDOW},
+        hooks::use_view_task,
+        queries::PublishRelease,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Index,
+    Chapters,
+    Torrent,
+    Review,
+}
+
+impl Step {
+    const ALL: [Step; 4] = [Step::Index, Step::Chapters, Step::Torrent, Step::Review];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Step::Index => "Index",
+            Step::Chapters => "Chapters",
+            Step::Torrent => "Torrent",
+            Step::Review => "Review",
+        }
+    }
+
+    fn next(self) -> Step {
+        match self {
+            Step::Index => Step::Chapters,
+            Step::Chapters => Step::Torrent,
+            Step::Torrent | Step::Review => Step::Review,
+        }
+    }
+
+    fn previous(self) -> Step {
+        match self {
+            Step::Index | Step::Chapters => Step::Index,
+            Step::Torrent => Step::Chapters,
+            Step::Review => Step::Torrent,
+        }
+    }
+}
+
+/// Index, chapters and torrent of a release gathered one step at a time.
+/// Everything is signed in memory for the review, nothing is stored, seeded
+/// or announced until it's published from there and the undo window is over.
+#[derive(PartialEq)]
+pub struct Publish {
+    /// Publishes into an index we already have instead of creating one
+    pub index: Option<Index<MangaTag>>,
+}
+impl Component for Publish {
+    fn render(&self) -> impl IntoElement {
+        let mut step = use_state(|| Step::Index);
+        let state = use_radio(AppChannel::Config);
+
+        let title = use_state(String::new);
+        let release_year = use_state(String::new);
+        let mangadex_id = use_state(String::new);
+
+        let mut chapters = use_state(Vec::<ChapterDraft>::new);
+        let mut chapter_title = use_state(String::new);
+        let mut chapter_source = use_state(String::new);
+        let mut chapter_enumeration = use_state(|| "1".to_string());
+
+        let magnet = use_state(String::new);
+        let files_path = use_state(String::new);
+        let web_seed = use_state(String::new);
+
+        let mut signed = use_state(|| None::<Result<Release, String>>);
+        let signing = use_view_task();
+        let mutation = use_mutation(Mutation::new(PublishRelease));
+
+        let allowed_trackers = match &state.read().config {
+            ResourceState::Loaded(c) => c.allowed_trackers().to_vec(),
+            _ => vec![],
+        };
+        let draft = ReleaseDraft {
+            index: match &self.index {
+                Some(index) => IndexDraft::Existing(index.clone()),
+                None => IndexDraft::New {
+                    title: title.read().clone(),
+                    release_date: release_year.read().parse().unwrap_or(0),
+                    mangadex: mangadex_id.read().trim().to_string(),
+                },
+            },
+            chapters: chapters.read().clone(),
+            torrent: TorrentDraft {
+                magnet: magnet.read().trim().to_string(),
+                files_path: files_path.read().clone(),
+                web_seed: web_seed.read().trim().to_string(),
+                allowed_trackers,
+            },
+        };
+
+        let current = *step.read();
+        let problems = match current {
+            Step::Index => draft.index.problems(),
+            Step::Chapters => draft.chapter_problems(),
+            Step::Torrent => draft.torrent_problems(),
+            Step::Review => draft.problems(),
+        };
+        let stripped_trackers = match current {
+            Step::Torrent | Step::Review => draft.torrent.scrubbed_magnet().1,
+            _ => vec![],
+        };
+
+        let mut staged = use_radio(AppChannel::Staged);
+        let stage = match &*mutation.read().state() {
+            MutationStateData::Settled { res: Ok(id), .. } => {
+                Some((*id, staged.read().staged.state(*id)))
+            }
+            _ => None,
+        };
+        // Undoing unlocks the draft again, nothing of it went out
+        let published = matches!(&*mutation.read().state(), MutationStateData::Loading { .. })
+            || matches!(stage, Some((_, StageState::Staged | StageState::Sent)));
+
+        let steps = rect()
+            .horizontal()
+            .spacing(20.)
+            .children(Step::ALL.iter().enumerate().map(|(i, s)| {
+                label()
+                    .text(format!("{}. {}", i + 1, s.name()))
+                    .font_weight(match *s == current {
+                        true => FontWeight::BOLD,
+                        false => FontWeight::NORMAL,
+                    })
+                    .into()
+            }));
+
+        let body: Element =
+            match current {
+                Step::Index => match &self.index {
+                    Some(index) => rect()
+                        .child(format!("Publishing into {}", index.title()))
+                        .into(),
+                    None => rect()
+                        .spacing(10.)
+                        .child(Input::new(title).placeholder("Title"))
+                        .child(
+                            Input::new(release_year)
+                                .placeholder("Release year (optional)")
+                                .on_validate(|v: InputValidator| {
+                                    let r = v.text().is_empty() || v.text().parse::<i32>().is_ok();
+                                    v.set_valid(r);
+                                }),
+                        )
+                        .child(Input::new(mangadex_id).placeholder(
+                            "MangaDex id (optional) a1c7c817-4e59-43b7-9365-09675a149a6f",
+                        ))
+                        .into(),
+                },
+                Step::Chapters => {
+                    let rows: Vec<Element> = chapters
+                        .read()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, chapter)| {
+                            rect()
+                                .horizontal()
+                                .spacing(10.)
+                                .cross_align(Alignment::Center)
+                                .child(Button::new().child("Remove").on_press(move |_| {
+                                    chapters.write().remove(i);
+                                }))
+                                .child(format!(
+                                    "Ch. {}: {} ({})",
+                                    chapter.enumeration, chapter.title, chapter.source
+                                ))
+                                .into()
+                        })
+                        .collect();
+
+                    let on_add = move |_| {
+                        let enumeration = chapter_enumeration.read().trim().to_string();
+                        chapters.write().push(ChapterDraft {
+                            title: chapter_title.read().trim().to_string(),
+                            source: chapter_source.read().trim().to_string(),
+                            enumeration: enumeration.clone(),
+                        });
+                        chapter_title.set(String::new());
+                        chapter_source.set(String::new());
+                        // Most releases are consecutive chapters
+                        if let Ok(e) = enumeration.parse::<f32>() {
+                            chapter_enumeration.set((e.floor() + 1.).to_string());
+                        }
+                    };
+
+                    rect()
+                        .spacing(10.)
+                        .child(rect().spacing(5.).children(rows))
+                        .child(Input::new(chapter_title).placeholder("Chapter title"))
+                        .child(Input::new(chapter_source).placeholder("Path inside the torrent"))
+                        .child(
+                            Input::new(chapter_enumeration)
+                                .placeholder("Enumeration")
+                                .on_validate(|v: InputValidator| {
+                                    let r = v.text().parse::<f32>();
+                                    v.set_valid(r.is_ok());
+                                }),
+                        )
+                        .child(Button::new().child("Add chapter").on_press(on_add))
+                        .into()
+                }
+                Step::Torrent => rect()
+                    .spacing(10.)
+                    .child("All chapters share one torrent, the magnet of one already made")
+                    .child(Input::new(magnet).placeholder("Magnet Link"))
+                    .child(Input::new(files_path).placeholder(
+                        "Local copy of the files (optional, adds checksums and seeds)",
+                    ))
+                    .child(
+                        Input::new(web_seed)
+                            .placeholder("Fallback eepsite URL (optional, http://….i2p/…)"),
+                    )
+                    .into(),
+                Step::Review => match &*signed.read() {
+                    None => rect().child(CircularLoader::new()).into(),
+                    Some(Err(e)) => label()
+                        .text(format!("Failed to hash the files: {}", e))
+                        .color(Color::RED)
+                        .into(),
+                    Some(Ok(release)) => ReleaseReview {
+                        release: release.clone(),
+                    }
+                    .into_element(),
+                },
+            };
+
+        let problem_list = rect()
+            .spacing(5.)
+            .children(
+                problems
+                    .iter()
+                    .map(|p: &DraftProblem| label().text(p.to_string()).color(Color::RED).into()),
+            )
+            // Not a problem, the magnet is signed without them
+            .children(stripped_trackers.iter().map(|t| {
+                label()
+                    .text(format!("Clearnet tracker removed from the magnet: {}", t))
+                    .color(Color::from_rgb(230, 140, 0))
+                    .into()
+            }));
+
+        let on_back = {
+            let signing = signing.clone();
+            move |_| {
+                // Hashing for a review that was left is wasted
+                signing.cancel();
+                step.set(current.previous());
+            }
+        };
+
+        let on_next = move |_| {
+            let next = current.next();
+            if next == Step::Review && current != Step::Review {
+                let ResourceState::Loaded(c) = &state.read().config else {
+                    return;
+                };
+                let private_key = c.private_key().clone();
+                let draft = draft.clone();
+                signed.set(None);
+                signing.spawn(async move {
+                    let result = draft.sign(&private_key).await.map_err(|e| e.to_string());
+                    signed.set(Some(result));
+                });
+            }
+            step.set(next);
+        };
+
+        let on_publish = move |_| {
+            if let Some(Ok(release)) = &*signed.read() {
+                mutation.mutate(release.clone());
+            }
+        };
+
+        let result = match &*mutation.read().state() {
+            MutationStateData::Pending => None,
+            MutationStateData::Loading { .. } => Some("Staging...".to_string()),
+            MutationStateData::Settled { res: Ok(_), .. } => match stage {
+                Some((_, StageState::Staged)) => Some(format!(
+                    "Goes out in {}s unless undone",
+                    UNDO_WINDOW.as_secs()
+                )),
+                Some((_, StageState::Undone)) => {
+                    Some("Undone, nothing was stored or sent".to_string())
+                }
+                _ => Some("Published".to_string()),
+            },
+            MutationStateData::Settled { res: Err(e), .. } => Some(e.to_string()),
+        };
+        let undo = match stage {
+            Some((id, StageState::Staged)) => Some(id),
+            _ => None,
+        };
+
+        let can_publish = problems.is_empty() && matches!(&*signed.read(), Some(Ok(_)));
+        let forward = match current {
+            Step::Review => Button::new()
+                .child("Publish")
+                .enabled(can_publish && !published)
+                .on_press(on_publish),
+            _ => Button::new()
+                .child("Next")
+                .enabled(problems.is_empty())
+                .on_press(on_next),
+        };
+        let buttons = rect()
+            .horizontal()
+            .spacing(10.)
+            .cross_align(Alignment::Center)
+            .child(
+                Button::new()
+                    .child("Back")
+                    .enabled(current != Step::Index && !published)
+                    .on_press(on_back),
+            )
+            .child(forward)
+            .maybe(undo.is_some(), |r| {
+                r.child(Button::new().child("Undo").on_press(move |_| {
+                    if let Some(id) = undo {
+                        staged.write().staged.undo(id);
+                    }
+                }))
+            })
+            .maybe(result.is_some(), |r| r.child(result.unwrap_or_default()));
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .child(label().text("Publish").font_size(48))
+            .child(steps)
+            .child(body)
+            .child(problem_list)
+            .child(buttons)
+    }
+}
+
+/// Every field that goes out, as it was signed
+#[derive(PartialEq)]
+struct ReleaseReview {
+    release: Release,
+}
+
+impl Component for ReleaseReview {
+    fn render(&self) -> impl IntoElement {
+        let release = &self.release;
+        let index = &release.index;
+
+        let field = |name: &str, value: String| {
+            rect()
+                .horizontal()
+                .spacing(10.)
+                .child(label().text(name.to_string()).font_weight(FontWeight::BOLD))
+                .child(label().text(value))
+        };
+
+        let mut index_box = rect()
+            .padding(10.)
+            .spacing(5.)
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::LIGHT_GRAY)
+            .child(
+                label()
+                    .text(match release.new_index {
+                        true => "New index",
+                        false => "Existing index, not signed again",
+                    })
+                    .font_size(20),
+            )
+            .child(field("Title", index.title().clone()))
+            .child(field("Signed by", index.source().fingerprint()));
+        if release.new_index {
+            index_box = index_box
+                .child(field("Release year", index.release_date().to_string()))
+                .child(field(
+                    "MangaDex",
+                    index
+                        .out_links()
+                        .mangadex
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| "none".to_string()),
+                ))
+                .child(field("Signature", index.signature().to_string()));
+        }
+
+        let contents = release.contents.iter().map(|content| {
+            rect()
+                .padding(10.)
+                .spacing(5.)
+                .corner_radius(DEFAULT_CORNER_RADIUS)
+                .background(Color::LIGHT_GRAY)
+                .child(
+                    label()
+                        .text(format!(
+                            "Ch. {}: {}",
+                            content.enumeration(),
+                            content.title()
+                        ))
+                        .font_size(20),
+                )
+                .child(field("Path", content.source().clone()))
+                .child(field("Magnet", content.magnet_link.0.clone()))
+                .child(field(
+                    "Checksums",
+                    match content.manifest.len() {
+                        0 => "none".to_string(),
+                        n => format!("{} files", n),
+                    },
+                ))
+                .child(field(
+                    "Fallback",
+                    content
+                        .web_seed
+                        .clone()
+                        .unwrap_or_else(|| "none".to_string()),
+                ))
+                .child(field("Signed by", content.poster().fingerprint()))
+                .child(field("Signature", content.signature().to_string()))
+                .into()
+        });
+
+        let seeding = match Path::new(&release.files_path).parent() {
+            Some(dir) => format!("The torrent is seeded from {}", dir.display()),
+            None => "No local files given, the torrent isn't seeded by us".to_string(),
+        };
+
+        rect()
+            .spacing(10.)
+            .child(index_box)
+            .children(contents)
+            .child(seeding)
+            .child("Once published it can be undone for a short while, then it's stored and announced to trusted peers and full sync targets and signed records can't be taken back")
+    }
+}
@@ -1,10 +1,19 @@
-use crate::db::index::content::Content;
+use crate::db::catalog::PendingCatalog;
+use crate::db::index::content::{Content, ExternalContent};
 use crate::db::index::tags::MangaTag;
-use crate::db::index::{Index, content::ExternalContent};
 use crate::helpers::LiFo;
-use freya::prelude::*;
+use crate::types::{Hash, Link, PublicKey, Topic};
+use crate::ui::DEFAULT_PAGE_PADDING;
+use crate::ui::components::ErrorBoundary;
+use crate::ui::queries::{FetchIndex, FetchUser};
+use freya::{prelude::*, query::*};
+use std::sync::Arc;
 
+mod conflicts;
+mod history;
 mod home;
+mod import_review;
+mod library_stats;
 mod settings;
 mod manga {
     mod manga;
@@ -17,14 +26,30 @@ mod manga {
     pub use add_manga_chapter::AddMangaChapter;
     mod chapter_viewer;
     pub use chapter_viewer::ChapterViewer;
+    mod publish;
+    pub use publish::Publish;
 }
+mod posts;
+use posts::Posts;
 mod torrents;
 use torrents::Torrents;
+mod user_profile;
+use user_profile::UserProfile;
+mod users;
+use users::UserList;
 
+use conflicts::Conflicts;
+use history::HistoryView;
 use home::Home;
-use manga::{AddManga, AddMangaChapter, ChapterViewer, Manga, MangaList};
+use import_review::ImportReview;
+use library_stats::LibraryStats;
+use manga::{AddManga, AddMangaChapter, ChapterViewer, Manga, MangaList, Publish};
 use settings::Settings;
 
+/// Where the app is. Records that can be looked up are named by their id and
+/// loaded when the route is shown, so history stays small, a route can be
+/// written down and followed later, and the page shows what's stored now
+/// rather than what was on screen when it was pushed.
 #[derive(Clone, PartialEq)]
 pub enum Route {
     // #[layout(Layout)]
@@ -36,13 +61,17 @@ pub enum Route {
     MangaList,
     // #[route("/:hash")]
     Manga {
-        index: Index<MangaTag>,
+        hash: Hash,
     },
     // #[route("/add")]
     AddManga,
     // #[route("/:hash/add")]
     AddMangaChapter {
-        index: Index<MangaTag>,
+        hash: Hash,
+    },
+    /// Publishing a release, of a given index or one picked on the page
+    Publish {
+        hash: Option<Hash>,
     },
     // #[route("/chapter/:signature")]
     ChapterViewerInternal {
@@ -53,6 +82,20 @@ pub enum Route {
     },
     Settings,
     Torrents,
+    Users,
+    UserProfile {
+        pub_key: PublicKey,
+    },
+    Conflicts,
+    LibraryStats,
+    History,
+    ImportReview {
+        pending: Arc<PendingCatalog<MangaTag>>,
+    },
+    Posts {
+        topic: Topic,
+        title: String,
+    },
 }
 
 impl Route {
@@ -63,10 +106,27 @@ impl Route {
             Route::Manga { .. } => "",
             Route::AddManga => "Add Manga",
             Route::AddMangaChapter { .. } => "",
+            Route::Publish { .. } => "Publish",
             Route::ChapterViewerInternal { .. } => "Chapter Viewer",
             Route::ChapterViewerExternal { .. } => "Chapter Viewer",
             Route::Settings => "Settings",
             Route::Torrents => "Torrents",
+            Route::Users => "Users",
+            Route::UserProfile { .. } => "",
+            Route::Conflicts => "Conflicts",
+            Route::LibraryStats => "Library",
+            Route::History => "History",
+            Route::ImportReview { .. } => "Review Import",
+            Route::Posts { .. } => "Posts",
+        }
+    }
+}
+
+impl From<Link> for Route {
+    fn from(link: Link) -> Self {
+        match link {
+            Link::Index(hash) => Route::Manga { hash },
+            Link::User(pub_key) => Route::UserProfile { pub_key },
         }
     }
 }
@@ -74,6 +134,8 @@ impl Route {
 pub struct RouteState {
     route: Route,
     history: LiFo<Route, 10>,
+    /// Bumped on every navigation, identifies the page currently shown
+    generation: u64,
 }
 
 impl RouteState {
@@ -81,9 +143,14 @@ impl RouteState {
         &self.route
     }
 
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     fn change_route(&mut self, route: Route) {
         let old = std::mem::replace(&mut self.route, route);
         self.history.push(old);
+        self.generation += 1;
     }
 }
 
@@ -98,6 +165,7 @@ impl RouteContext {
             state: State::create_global(RouteState {
                 route: Route::Home,
                 history: LiFo::new(),
+                generation: 0,
             }),
         }
     }
@@ -132,34 +200,179 @@ pub struct RouteComponent;
 impl Component for RouteComponent {
     fn render(&self) -> impl IntoElement {
         let route_context = RouteContext::get();
-        route_context.state.read().route.clone()
+        let state = route_context.state.read();
+        Page {
+            route: state.route.clone(),
+            key: DiffKey::None,
+        }
+        .key(state.generation)
+    }
+}
+
+/// The current route keyed by the navigation that led to it. Going from a
+/// page to another of the same kind would otherwise keep the first one's
+/// hooks, so its state and the late results of its tasks would show up on
+/// the second. Keyed, every navigation drops the old page and its tasks.
+#[derive(PartialEq)]
+struct Page {
+    route: Route,
+    key: DiffKey,
+}
+
+impl KeyExt for Page {
+    fn write_key(&mut self) -> &mut DiffKey {
+        &mut self.key
+    }
+}
+
+impl Component for Page {
+    fn render(&self) -> impl IntoElement {
+        self.route.clone()
+    }
+
+    fn render_key(&self) -> DiffKey {
+        self.key.clone().or(self.default_key())
     }
 }
 
 impl Component for Route {
     fn render(&self) -> impl IntoElement {
         match self {
-            Route::Home => Home.into_element(),
-            Route::MangaList => MangaList.into_element(),
-            Route::Manga { index } => Manga {
-                index: index.clone(),
+            Route::Home => ErrorBoundary(Home).into_element(),
+            Route::MangaList => ErrorBoundary(MangaList).into_element(),
+            Route::Manga { hash } => ResolveIndex {
+                hash: hash.clone(),
+                page: IndexPage::Manga,
             }
             .into_element(),
-            Route::AddManga => AddManga.into_element(),
-            Route::AddMangaChapter { index } => AddMangaChapter {
-                index: index.clone(),
+            Route::AddManga => ErrorBoundary(AddManga).into_element(),
+            Route::AddMangaChapter { hash } => ResolveIndex {
+                hash: hash.clone(),
+                page: IndexPage::AddChapter,
             }
             .into_element(),
-            Route::ChapterViewerInternal { content } => ChapterViewer {
-                content: content.clone(),
+            Route::Publish { hash: Some(hash) } => ResolveIndex {
+                hash: hash.clone(),
+                page: IndexPage::Publish,
             }
             .into_element(),
-            Route::ChapterViewerExternal { content } => ChapterViewer {
+            Route::Publish { hash: None } => ErrorBoundary(Publish { index: None }).into_element(),
+            Route::ChapterViewerInternal { content } => ErrorBoundary(ChapterViewer {
                 content: content.clone(),
+            })
+            .into_element(),
+            Route::ChapterViewerExternal { content } => ErrorBoundary(ChapterViewer {
+                content: content.clone(),
+            })
+            .into_element(),
+            Route::Settings => ErrorBoundary(Settings).into_element(),
+            Route::Torrents => ErrorBoundary(Torrents).into_element(),
+            Route::Users => ErrorBoundary(UserList).into_element(),
+            Route::UserProfile { pub_key } => ResolveUser {
+                pub_key: pub_key.clone(),
             }
             .into_element(),
-            Route::Settings => Settings.into_element(),
-            Route::Torrents => Torrents.into_element(),
+            Route::Conflicts => ErrorBoundary(Conflicts).into_element(),
+            Route::LibraryStats => ErrorBoundary(LibraryStats).into_element(),
+            Route::History => ErrorBoundary(HistoryView).into_element(),
+            Route::ImportReview { pending } => ErrorBoundary(ImportReview {
+                pending: pending.clone(),
+            })
+            .into_element(),
+            Route::Posts { topic, title } => ErrorBoundary(Posts {
+                topic: topic.clone(),
+                title: title.clone(),
+            })
+            .into_element(),
+        }
+    }
+}
+
+/// Page shown for an index once it's loaded
+#[derive(Clone, Copy, PartialEq)]
+enum IndexPage {
+    Manga,
+    AddChapter,
+    Publish,
+}
+
+#[derive(PartialEq)]
+struct ResolveIndex {
+    hash: Hash,
+    page: IndexPage,
+}
+
+impl Component for ResolveIndex {
+    fn render(&self) -> impl IntoElement {
+        let index_query = use_query(Query::new(self.hash.clone(), FetchIndex::<MangaTag>::new()));
+
+        match &*index_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(Some(index)),
+                ..
+            }
+            | QueryStateData::Loading {
+                res: Some(Ok(Some(index))),
+            } => {
+                let index = index.clone();
+                match self.page {
+                    IndexPage::Manga => ErrorBoundary(Manga { index }).into_element(),
+                    IndexPage::AddChapter => {
+                        ErrorBoundary(AddMangaChapter { index }).into_element()
+                    }
+                    IndexPage::Publish => {
+                        ErrorBoundary(Publish { index: Some(index) }).into_element()
+                    }
+                }
+            }
+            QueryStateData::Settled { res: Ok(None), .. } => {
+                not_found("This manga isn't in the library")
+            }
+            QueryStateData::Settled { res: Err(e), .. } => not_found(e.to_string()),
+            _ => loading(),
         }
     }
 }
+
+#[derive(PartialEq)]
+struct ResolveUser {
+    pub_key: PublicKey,
+}
+
+impl Component for ResolveUser {
+    fn render(&self) -> impl IntoElement {
+        let user_query = use_query(Query::new(self.pub_key.clone(), FetchUser));
+
+        match &*user_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(Some(user)),
+                ..
+            }
+            | QueryStateData::Loading {
+                res: Some(Ok(Some(user))),
+            } => ErrorBoundary(UserProfile { user: user.clone() }).into_element(),
+            QueryStateData::Settled { res: Ok(None), .. } => not_found("This user isn't known"),
+            QueryStateData::Settled { res: Err(e), .. } => not_found(e.to_string()),
+            _ => loading(),
+        }
+    }
+}
+
+fn loading() -> Element {
+    rect()
+        .expanded()
+        .center()
+        .child(CircularLoader::new())
+        .into_element()
+}
+
+fn not_found(message: impl Into<String>) -> Element {
+    rect()
+        .padding(DEFAULT_PAGE_PADDING)
+        .spacing(15.)
+        .child(label().text(message.into()).font_size(24))
+        .child(Button::new().child("Go back").on_press(|_| {
+            RouteContext::get().go_back();
+        }))
+        .into_element()
+}
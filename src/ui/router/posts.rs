@@ -0,0 +1,481 @@
+use freya::{prelude::*, query::*, radio::use_radio};
+use tracing::error;
+
+use crate::{
+    db::{
+        changes::DataKind,
+        comments::{Post, PostSegment, post_segments, split_quote},
+        index::tags::MangaTag,
+        user::User,
+    },
+    types::{Hash, Link, PublicKey, Signature, Timestamp, Topic},
+    ui::{
+        AppChannel, DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, ResourceState, Route,
+        RouteContext,
+        components::{lazy_list, no_reaction_button},
+        hooks::use_data_changed,
+        queries::{
+            AddPost, FetchDisplayName, FetchIndex, FetchPostCount, FetchPosts, ResolveMentions,
+            SearchPosts,
+        },
+    },
+};
+
+const POST_ROW_SIZE: f32 = 110.;
+
+/// Posts under a topic. Posts coming in from other peers don't replace the list
+/// while it's being read, instead a pill shows up to load them. Searching swaps
+/// the list for the matching posts until the search is cleared. What's typed in
+/// the composer is kept per topic until it's posted.
+#[derive(PartialEq)]
+pub struct Posts {
+    pub topic: Topic,
+    pub title: String,
+}
+
+impl Component for Posts {
+    fn render(&self) -> impl IntoElement {
+        let posts_version = use_data_changed(DataKind::Posts);
+        let posts_query = use_query(Query::new(self.topic.clone(), FetchPosts));
+        let count_query = use_query(Query::new(
+            (self.topic.clone(), posts_version),
+            FetchPostCount,
+        ));
+
+        let search_text = use_state(String::new);
+        let mut searched = use_state(String::new);
+        let search_query = use_query(Query::new(
+            (self.topic.clone(), searched.read().clone()),
+            SearchPosts,
+        ));
+        let searching = !searched.read().trim().is_empty();
+
+        let (post_list, shown) = match &*posts_query.read().state() {
+            QueryStateData::Pending => (rect().child(CircularLoader::new()), None),
+            QueryStateData::Loading { .. } => (rect().child(CircularLoader::new()), None),
+            QueryStateData::Settled { res, .. } => match res {
+                Ok(res) if res.is_empty() => (rect().child("No posts yet"), Some(0)),
+                Ok(res) => {
+                    let list = lazy_list(res.clone(), POST_ROW_SIZE, |p| {
+                        PostEntry {
+                            post: p.clone(),
+                            quotable: true,
+                        }
+                        .into_element()
+                    });
+
+                    (rect().height(Size::Fill).child(list), Some(res.len()))
+                }
+                Err(e) => (rect().child(label().text(e.to_string())), None),
+            },
+        };
+
+        let post_list =
+            if searching {
+                match &*search_query.read().state() {
+                    QueryStateData::Settled { res: Ok(res), .. } if res.is_empty() => {
+                        rect().child("No posts match the search")
+                    }
+                    QueryStateData::Settled { res: Ok(res), .. } => rect()
+                        .height(Size::Fill)
+                        .child(lazy_list(res.clone(), POST_ROW_SIZE, |p| {
+                            PostEntry {
+                                post: p.clone(),
+                                quotable: true,
+                            }
+                            .into_element()
+                        })),
+                    QueryStateData::Settled { res: Err(e), .. } => {
+                        rect().child(label().text(e.to_string()))
+                    }
+                    _ => rect().child(CircularLoader::new()),
+                }
+            } else {
+                post_list
+            };
+
+        let search_bar = rect()
+            .horizontal()
+            .width(Size::Fill)
+            .content(Content::Flex)
+            .spacing(10.)
+            .cross_align(Alignment::Center)
+            .child(
+                Input::new(search_text)
+                    .placeholder("Search posts")
+                    .corner_radius(DEFAULT_CORNER_RADIUS)
+                    .width(Size::flex(1.)),
+            )
+            .child(
+                Button::new()
+                    .child("Search")
+                    .on_press(move |_| searched.set(search_text.read().clone())),
+            )
+            .maybe(searching, |r| {
+                r.child(
+                    Button::new()
+                        .child("Clear")
+                        .on_press(move |_| searched.set(String::new())),
+                )
+            });
+
+        let config = use_radio(AppChannel::Config);
+        let mut drafts = use_radio(AppChannel::Drafts);
+        let saved = drafts.read().drafts.get(&self.topic).cloned();
+        let mut draft = use_state(move || saved.unwrap_or_default());
+        let mut previewing = use_state(|| false);
+        use_provide_context(move || Composer { draft, previewing });
+        let add_post = use_mutation(Mutation::new(AddPost));
+        let length = draft.read().chars().count();
+        let too_long = length > Post::MAX_LENGTH;
+
+        let draft_topic = self.topic.clone();
+        use_side_effect(move || {
+            let text = draft.read().clone();
+            let mut state = drafts.write();
+            if text.is_empty() {
+                state.drafts.remove(&draft_topic);
+            } else {
+                state.drafts.insert(draft_topic.clone(), text);
+            }
+        });
+
+        let editor = match (*previewing.read(), &config.read().config) {
+            (true, ResourceState::Loaded(c)) => rect()
+                .width(Size::flex(1.))
+                .child(PostEntry {
+                    post: Post::new(
+                        draft.read().clone(),
+                        Timestamp::now(),
+                        c.public_key().clone(),
+                        self.topic.clone(),
+                        Signature::empty(),
+                    ),
+                    quotable: false,
+                })
+                .into_element(),
+            _ => Input::new(draft)
+                .placeholder("Write a post")
+                .corner_radius(DEFAULT_CORNER_RADIUS)
+                .width(Size::flex(1.))
+                .into_element(),
+        };
+
+        let post_topic = self.topic.clone();
+        let composer = rect()
+            .horizontal()
+            .width(Size::Fill)
+            .content(Content::Flex)
+            .spacing(10.)
+            .cross_align(Alignment::Center)
+            .child(editor)
+            .child(
+                label()
+                    .text(format!("{}/{}", length, Post::MAX_LENGTH))
+                    .color(if too_long { Color::RED } else { Color::GRAY }),
+            )
+            .child(
+                Button::new()
+                    .child("Post")
+                    .enabled(length > 0 && !too_long)
+                    .on_press(move |_| {
+                        let ResourceState::Loaded(c) = &config.read().config else {
+                            return;
+                        };
+
+                        match Post::new_signed(
+                            draft.read().clone(),
+                            Timestamp::now(),
+                            post_topic.clone(),
+                            c.private_key(),
+                        ) {
+                            Ok(post) => {
+                                add_post.mutate(post);
+                                draft.set(String::new());
+                                previewing.set(false);
+                            }
+                            Err(e) => error!("Couldn't sign post: {}", e),
+                        }
+                    }),
+            );
+
+        let new_posts = match (&*count_query.read().state(), shown) {
+            (QueryStateData::Settled { res: Ok(total), .. }, Some(shown)) => {
+                total.saturating_sub(shown)
+            }
+            _ => 0,
+        };
+
+        let topic = self.topic.clone();
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .height(Size::Fill)
+            .child(label().text(self.title.clone()).font_size(48))
+            .child(search_bar)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(5.)
+                    .child(
+                        Button::new()
+                            .child("Write")
+                            .enabled(*previewing.read())
+                            .on_press(move |_| previewing.set(false)),
+                    )
+                    .child(
+                        Button::new()
+                            .child("Preview")
+                            .enabled(!*previewing.read() && length > 0)
+                            .on_press(move |_| previewing.set(true)),
+                    ),
+            )
+            .child(composer)
+            .maybe(too_long, |r| {
+                r.child(
+                    label()
+                        .text(format!(
+                            "Posts can be at most {} characters long, this one is {} over",
+                            Post::MAX_LENGTH,
+                            length - Post::MAX_LENGTH
+                        ))
+                        .color(Color::RED),
+                )
+            })
+            .maybe(new_posts > 0 && !searching, |r| {
+                r.child(
+                    Button::new()
+                        .child(format!("{} new posts", new_posts))
+                        .on_press(move |_| {
+                            let topic = topic.clone();
+                            spawn(async move {
+                                QueriesStorage::<FetchPosts>::invalidate_matching(topic).await;
+                            });
+                        }),
+                )
+            })
+            .child(post_list)
+    }
+}
+
+/// Composer of the topic being shown, for posts to be quoted into
+#[derive(Clone, Copy)]
+struct Composer {
+    draft: State<String>,
+    previewing: State<bool>,
+}
+
+/// Content of a post as shown, spoilers are blanked out until `revealed`
+fn post_text(segments: &[PostSegment], revealed: bool) -> String {
+    segments
+        .iter()
+        .map(|s| match s {
+            PostSegment::Text(text) => text.to_string(),
+            PostSegment::Mention(handle) => format!("@{}", handle),
+            PostSegment::Spoiler(hidden) if revealed => hidden.to_string(),
+            PostSegment::Spoiler(_) => "[spoiler, click to reveal]".to_string(),
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+struct PostEntry {
+    post: Post,
+    /// Offers to quote the post in the composer, off for the preview
+    quotable: bool,
+}
+
+/// Previews aren't signed, so their content is compared too
+impl PartialEq for PostEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.post.signature == other.post.signature
+            && self.post.content == other.post.content
+            && self.quotable == other.quotable
+    }
+}
+
+impl Component for PostEntry {
+    fn render(&self) -> impl IntoElement {
+        let author_query = use_query(Query::new(self.post.source.clone(), FetchDisplayName));
+        let author = match &*author_query.read().state() {
+            QueryStateData::Settled { res: Ok(name), .. } => name.clone(),
+            _ => self.post.source.fingerprint(),
+        };
+
+        let handles: Vec<String> = self.post.mentions().into_iter().map(String::from).collect();
+        let mentions_query = use_query(Query::new(handles.clone(), ResolveMentions));
+        let mentioned: Vec<(String, User)> = match &*mentions_query.read().state() {
+            QueryStateData::Settled { res: Ok(users), .. } => handles
+                .into_iter()
+                .filter_map(|h| users.get(&h).map(|u| (h, u.clone())))
+                .collect(),
+            _ => vec![],
+        };
+
+        let links = self.post.links();
+
+        let mut revealed = use_state(|| false);
+        let has_spoilers = post_segments(&self.post.content)
+            .iter()
+            .any(|s| matches!(s, PostSegment::Spoiler(_)));
+        let (quoted, body) = split_quote(&self.post.content);
+        let quote = post_text(&post_segments(&quoted.join("\n")), *revealed.read());
+        let text = post_text(&post_segments(body), *revealed.read());
+
+        let composer = try_consume_context::<Composer>().filter(|_| self.quotable);
+        let quote_reply = self.post.quote_reply();
+
+        rect()
+            .width(Size::Fill)
+            .padding(10.)
+            .spacing(5.)
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::DARK_GRAY)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .child(
+                        label()
+                            .text(author)
+                            .font_weight(FontWeight::BOLD)
+                            .color(Color::WHITE),
+                    )
+                    .child(
+                        label()
+                            .text(self.post.timestamp.format_date())
+                            .color(Color::LIGHT_GRAY),
+                    )
+                    .maybe(composer.is_some(), |r| {
+                        r.child(
+                            no_reaction_button()
+                                .child(
+                                    label()
+                                        .text("Quote")
+                                        .text_decoration(TextDecoration::Underline)
+                                        .color(Color::LIGHT_GRAY),
+                                )
+                                .on_press(move |_| {
+                                    let Some(Composer {
+                                        mut draft,
+                                        mut previewing,
+                                    }) = composer
+                                    else {
+                                        return;
+                                    };
+                                    let current = draft.read().clone();
+                                    draft.set(format!("{}{}", quote_reply, current));
+                                    previewing.set(false);
+                                }),
+                        )
+                    }),
+            )
+            .maybe(!quoted.is_empty(), |r| {
+                r.child(
+                    rect()
+                        .width(Size::Fill)
+                        .padding(5.)
+                        .corner_radius(DEFAULT_CORNER_RADIUS)
+                        .background(Color::from_rgb(70, 70, 70))
+                        .child(label().text(quote).color(Color::LIGHT_GRAY)),
+                )
+            })
+            .child(
+                no_reaction_button()
+                    .child(label().text(text).color(Color::WHITE))
+                    .maybe(has_spoilers, |b| {
+                        b.on_press(move |_| {
+                            let shown = *revealed.read();
+                            revealed.set(!shown);
+                        })
+                    }),
+            )
+            .maybe(!mentioned.is_empty(), |r| {
+                r.child(
+                    rect()
+                        .horizontal()
+                        .spacing(10.)
+                        .children(mentioned.into_iter().map(|(handle, user)| {
+                            no_reaction_button()
+                                .child(
+                                    label()
+                                        .text(format!("@{}", handle))
+                                        .text_decoration(TextDecoration::Underline)
+                                        .color(Color::LIGHT_GRAY),
+                                )
+                                .on_press(move |_| {
+                                    RouteContext::get().push(Route::UserProfile {
+                                        pub_key: user.pub_key().clone(),
+                                    });
+                                })
+                                .into_element()
+                        })),
+                )
+            })
+            .maybe(!links.is_empty(), |r| {
+                r.child(
+                    rect()
+                        .horizontal()
+                        .spacing(10.)
+                        .children(links.into_iter().map(|link| match link {
+                            Link::Index(hash) => IndexLink { hash }.into_element(),
+                            Link::User(pub_key) => UserLink { pub_key }.into_element(),
+                        })),
+                )
+            })
+    }
+}
+
+/// Link to an index found in a post, named after it when we have it
+#[derive(PartialEq)]
+struct IndexLink {
+    hash: Hash,
+}
+
+impl Component for IndexLink {
+    fn render(&self) -> impl IntoElement {
+        let index_query = use_query(Query::new(self.hash.clone(), FetchIndex::<MangaTag>::new()));
+        let title = match &*index_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(Some(index)),
+                ..
+            } => index.title().clone(),
+            _ => "Unknown manga".to_string(),
+        };
+
+        link_button(title, Link::Index(self.hash.clone()))
+    }
+}
+
+/// Link to a user found in a post, named like mentions are
+#[derive(PartialEq)]
+struct UserLink {
+    pub_key: PublicKey,
+}
+
+impl Component for UserLink {
+    fn render(&self) -> impl IntoElement {
+        let name_query = use_query(Query::new(self.pub_key.clone(), FetchDisplayName));
+        let name = match &*name_query.read().state() {
+            QueryStateData::Settled { res: Ok(name), .. } => name.clone(),
+            _ => self.pub_key.fingerprint(),
+        };
+
+        link_button(format!("@{}", name), Link::User(self.pub_key.clone()))
+    }
+}
+
+fn link_button(text: String, link: Link) -> Button {
+    no_reaction_button()
+        .child(
+            label()
+                .text(text)
+                .text_decoration(TextDecoration::Underline)
+                .color(Color::LIGHT_GRAY),
+        )
+        .on_press(move |_| {
+            RouteContext::get().push(Route::from(link.clone()));
+        })
+}
@@ -1,9 +1,30 @@
 use const_format::formatcp;
-use freya::{prelude::*, radio::use_radio};
+use freya::{
+    prelude::*,
+    query::{
+        Mutation, MutationStateData, QueriesStorage, Query, QueryStateData, use_mutation, use_query,
+    },
+    radio::use_radio,
+};
 
 use crate::{
-    config::DEFAULT_SAM_TCP_PORT,
-    ui::{AppChannel, DEFAULT_PAGE_PADDING, ResourceState},
+    build_info,
+    config::{DEFAULT_SAM_TCP_PORT, ToastCorner},
+    db::{
+        integrity::Repair,
+        suppression::{SuppressedKind, Suppression},
+        user::Invite,
+    },
+    diagnostics::CheckStatus,
+    ui::{
+        AppChannel, DEFAULT_PAGE_PADDING, ResourceState,
+        components::copy_button,
+        link_handler,
+        queries::{
+            CheckIntegrity, FetchAuditLog, FetchSuppressions, RefetchMissingIndexes,
+            RepairIntegrity, RunDiagnostics, Unsuppress,
+        },
+    },
 };
 
 #[derive(PartialEq)]
@@ -19,6 +40,9 @@ impl Component for Settings {
             let sam_port = new_config.read().sam_tcp_port();
             sam_port.to_string()
         });
+        let vouch_string = use_state(move || new_config.read().vouch_threshold().to_string());
+        let max_toasts_string =
+            use_state(move || new_config.read().toasts().max_visible.to_string());
 
         let dev_mode_switch = Switch::new()
             .toggled(new_config.read().dev_mode())
@@ -28,6 +52,62 @@ impl Component for Settings {
                 config.set_dev_mode(dev_mode);
             });
 
+        let relay_only_switch = rect()
+            .spacing(10.)
+            .horizontal()
+            .cross_align(Alignment::Center)
+            .child(
+                Switch::new()
+                    .toggled(new_config.read().relay_only().enabled)
+                    .on_toggle(move |_| {
+                        let mut config = new_config.write();
+                        let enabled = !config.relay_only().enabled;
+                        config.set_relay_only(enabled);
+                    }),
+            )
+            .child("Relay-only mode, nothing exchanged is shown (needs a restart)");
+
+        let mentions_switch = rect()
+            .spacing(10.)
+            .horizontal()
+            .cross_align(Alignment::Center)
+            .child(
+                Switch::new()
+                    .toggled(new_config.read().notify_mentions())
+                    .on_toggle(move |_| {
+                        let mut config = new_config.write();
+                        let notify = !config.notify_mentions();
+                        config.set_notify_mentions(notify);
+                    }),
+            )
+            .child("Notify me when a post mentions my key");
+
+        let vouch_input = rect()
+            .spacing(10.)
+            .horizontal()
+            .cross_align(Alignment::Center)
+            .child("Trust users vouched for by")
+            .child(
+                Input::new(vouch_string)
+                    .placeholder("0")
+                    .width(Size::px(60.))
+                    .on_validate(move |v: InputValidator| {
+                        if v.text().is_empty() {
+                            new_config.write().set_vouch_threshold(0);
+                            return;
+                        }
+
+                        let r = v.text().parse::<u8>();
+                        if let Ok(threshold) = r {
+                            new_config.write().set_vouch_threshold(threshold);
+                            return;
+                        }
+
+                        v.set_valid(false);
+                    }),
+            )
+            .child("of my fully trusted users (0 turns it off)");
+
         let sam_port_input = rect()
             .spacing(10.)
             .horizontal()
@@ -58,18 +138,179 @@ impl Component for Settings {
                 rect()
                     .spacing(20.)
                     .horizontal()
+                    .cross_align(Alignment::Center)
                     .child("I2P Address:")
-                    .child(new_config.read().eepsite_address().inner().clone()),
+                    .child(new_config.read().eepsite_address().inner().clone())
+                    .child(copy_button(
+                        new_config.read().eepsite_address().inner().clone(),
+                        Color::BLACK,
+                    )),
+            )
+            .child(sam_port_input)
+            .child(relay_only_switch);
+
+        let torrent_configs = rect()
+            .spacing(10.)
+            .child(label().text("Torrents").font_size(32))
+            .child(
+                rect()
+                    .spacing(10.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Switch::new()
+                            .toggled(new_config.read().i2p_only_torrents())
+                            .on_toggle(move |_| {
+                                let mut config = new_config.write();
+                                let i2p_only = !config.i2p_only_torrents();
+                                config.set_i2p_only_torrents(i2p_only);
+                            }),
+                    )
+                    .child("Only use I2P for torrents (needs a restart)"),
+            )
+            .child(
+                "By default the torrent client also finds peers over clearnet, through the \
+                 DHT, local discovery and the trackers in magnets, which shows your IP to \
+                 anyone watching those torrents. With this on it only talks to I2P peers and \
+                 trackers. Torrents without I2P seeds won't download.",
+            );
+
+        let corner_selector =
+            SegmentedButton::new().children(ToastCorner::ALL.map(|c| -> Element {
+                ButtonSegment::new()
+                    .selected(new_config.read().toasts().corner == c)
+                    .on_press(move |_| new_config.write().set_toast_corner(c))
+                    .child(c.to_string())
+                    .into()
+            }));
+
+        let toast_configs = rect()
+            .spacing(10.)
+            .child(label().text("Notifications").font_size(32))
+            .child(
+                rect()
+                    .spacing(10.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child("Show them in the")
+                    .child(corner_selector),
             )
-            .child(sam_port_input);
+            .child(
+                rect()
+                    .spacing(10.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child("Show at most")
+                    .child(
+                        Input::new(max_toasts_string)
+                            .placeholder("3")
+                            .width(Size::px(60.))
+                            .on_validate(move |v: InputValidator| match v.text().parse::<u8>() {
+                                Ok(max) if max > 0 => {
+                                    new_config.write().set_max_visible_toasts(max)
+                                }
+                                _ => v.set_valid(false),
+                            }),
+                    )
+                    .child("at once, the rest wait their turn"),
+            );
+
+        let mut show_private_key = use_state(|| false);
+
+        let private_key_text = if *show_private_key.read() {
+            new_config.read().private_key().to_base64()
+        } else {
+            new_config.read().private_key().to_string()
+        };
+
+        let identity_configs = rect()
+            .spacing(10.)
+            .child(label().text("Identity").font_size(32))
+            .child(
+                rect()
+                    .spacing(20.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child("Public Key:")
+                    .child(new_config.read().public_key().to_base64())
+                    .child(copy_button(
+                        new_config.read().public_key().to_base64(),
+                        Color::BLACK,
+                    )),
+            )
+            .child(
+                rect()
+                    .spacing(20.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child("Private Key:")
+                    .child(private_key_text)
+                    .child(
+                        Button::new()
+                            .child(if *show_private_key.read() {
+                                "Hide"
+                            } else {
+                                "Reveal"
+                            })
+                            .on_press(move |_| {
+                                let shown = *show_private_key.read();
+                                show_private_key.set(!shown);
+                            }),
+                    ),
+            );
+
+        let invite = Invite::new(
+            new_config.read().public_key().clone(),
+            new_config.read().eepsite_address().clone(),
+        );
+        let qr_code = invite.to_qr_svg();
+        let invite = invite.to_string();
+
+        let mut invite_config = rect().spacing(10.).child(
+            rect()
+                .spacing(20.)
+                .horizontal()
+                .cross_align(Alignment::Center)
+                .child("Invite:")
+                .child(invite.clone())
+                .child(copy_button(invite, Color::BLACK)),
+        );
+        if let Ok(qr_code) = qr_code {
+            invite_config = invite_config.child(
+                svg(qr_code.into_bytes())
+                    .width(Size::px(200.))
+                    .height(Size::px(200.)),
+            );
+        }
+
+        let about = rect()
+            .spacing(5.)
+            .child(label().text("About").font_size(32))
+            .child(format!("Version: {}", build_info::VERSION))
+            .child(
+                rect()
+                    .spacing(20.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child(format!("Commit: {}", build_info::GIT_HASH))
+                    .child(copy_button(build_info::describe(), Color::BLACK)),
+            )
+            .child(format!("Built: {}", build_info::built_at().format_date()));
 
         let is_dirty = *radio.read().config.unwrap_ref() != *new_config.read();
+        let dev_mode = radio.read().config.unwrap_ref().dev_mode();
 
         rect()
             .padding(DEFAULT_PAGE_PADDING)
             .spacing(15.)
             .child(label().text("Settings").font_size(48))
+            .child(identity_configs)
+            .child(invite_config)
+            .child(mentions_switch)
+            .child(vouch_input)
             .child(i2p_configs)
+            .child(torrent_configs)
+            .child(toast_configs)
             .child(dev_mode_switch)
             .child(
                 rect()
@@ -85,5 +326,405 @@ impl Component for Settings {
                     )
                     .child(Button::new().child("Cancel")),
             )
+            .maybe(cfg!(feature = "fixtures") && dev_mode, |r| {
+                r.child(DemoData)
+            })
+            .child(LinkHandler)
+            .child(SuppressionList)
+            .child(Diagnostics)
+            .child(IntegrityCheck)
+            .child(AuditLog)
+            .child(about)
+    }
+}
+
+/// Fills the database with generated users, indexes, chapters and posts to
+/// work on the UI without a network
+#[derive(PartialEq)]
+struct DemoData;
+
+#[cfg(not(feature = "fixtures"))]
+impl Component for DemoData {
+    fn render(&self) -> impl IntoElement {
+        rect()
+    }
+}
+
+#[cfg(feature = "fixtures")]
+impl Component for DemoData {
+    fn render(&self) -> impl IntoElement {
+        let seed = use_state(|| "demo".to_string());
+        let mutation = use_mutation(Mutation::new(crate::ui::queries::LoadDemoData));
+
+        let outcome = match &*mutation.read().state() {
+            MutationStateData::Settled { res: Ok(()), .. } => Some("Demo data stored".to_string()),
+            MutationStateData::Settled { res: Err(e), .. } => Some(e.to_string()),
+            _ => None,
+        };
+
+        rect()
+            .spacing(10.)
+            .child(label().text("Demo data").font_size(32))
+            .child("The same seed always makes the same records. They are stored like received ones, use a throwaway database.")
+            .child(
+                rect()
+                    .spacing(10.)
+                    .horizontal()
+                    .cross_align(Alignment::Center)
+                    .child(Input::new(seed).placeholder("Seed"))
+                    .child(
+                        Button::new()
+                            .child("Fill with demo data")
+                            .enabled(!seed.read().is_empty())
+                            .on_press(move |_| mutation.mutate(seed.read().clone())),
+                    ),
+            )
+            .maybe(outcome.is_some(), |r| r.child(outcome.unwrap_or_default()))
+    }
+}
+
+/// Lets the OS open links clicked in other apps here, it's left alone
+/// unless asked
+#[derive(PartialEq)]
+struct LinkHandler;
+
+impl Component for LinkHandler {
+    fn render(&self) -> impl IntoElement {
+        let mut outcome = use_state(|| None::<String>);
+
+        let register = move |magnets: bool| {
+            move |_| {
+                spawn(async move {
+                    let res = blocking::unblock(move || link_handler::register(magnets)).await;
+                    outcome.set(Some(match res {
+                        Ok(()) => "Links now open here".to_string(),
+                        Err(e) => e.to_string(),
+                    }));
+                });
+            }
+        };
+
+        rect()
+            .spacing(10.)
+            .child(label().text("Links").font_size(32))
+            .child("Links are always shown before anything is fetched.")
+            .child(
+                rect()
+                    .spacing(10.)
+                    .horizontal()
+                    .child(
+                        Button::new()
+                            .child("Open akareko:// links here")
+                            .on_press(register(false)),
+                    )
+                    .child(
+                        Button::new()
+                            .child("Open magnet links too")
+                            .on_press(register(true)),
+                    ),
+            )
+            .maybe(outcome.read().is_some(), |r| {
+                r.child(outcome.read().clone().unwrap_or_default())
+            })
+    }
+}
+
+/// Everything deleted with "don't fetch again", unsuppressing lets it be
+/// synced back
+#[derive(PartialEq)]
+struct SuppressionList;
+
+impl Component for SuppressionList {
+    fn render(&self) -> impl IntoElement {
+        let suppressions_query = use_query(Query::new((), FetchSuppressions));
+
+        let list = match &*suppressions_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(suppressions),
+                ..
+            } if suppressions.is_empty() => rect().child("Nothing is suppressed").into_element(),
+            QueryStateData::Settled {
+                res: Ok(suppressions),
+                ..
+            } => rect()
+                .spacing(5.)
+                .children(suppressions.iter().map(|s| {
+                    SuppressionEntry {
+                        suppression: s.clone(),
+                    }
+                    .into_element()
+                }))
+                .into_element(),
+            QueryStateData::Settled { res: Err(e), .. } => {
+                rect().child(label().text(e.to_string())).into_element()
+            }
+            QueryStateData::Pending | QueryStateData::Loading { .. } => {
+                rect().child(CircularLoader::new()).into_element()
+            }
+        };
+
+        rect()
+            .spacing(10.)
+            .child(label().text("Suppressed").font_size(32))
+            .child(list)
+    }
+}
+
+/// Every trust and moderation decision taken on this node, newest first
+#[derive(PartialEq)]
+struct AuditLog;
+
+impl Component for AuditLog {
+    fn render(&self) -> impl IntoElement {
+        let audit_query = use_query(Query::new((), FetchAuditLog));
+
+        let list = match &*audit_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(entries), ..
+            } if entries.is_empty() => rect().child("Nothing decided yet").into_element(),
+            QueryStateData::Settled {
+                res: Ok(entries), ..
+            } => rect()
+                .spacing(5.)
+                .children(entries.iter().map(|e| {
+                    let change = match e.detail.is_empty() {
+                        true => format!("{}: {}", e.kind, e.label),
+                        false => format!("{}: {}, {}", e.kind, e.label, e.detail),
+                    };
+
+                    rect()
+                        .spacing(20.)
+                        .horizontal()
+                        .cross_align(Alignment::Center)
+                        .child(e.at.format_date())
+                        .child(change)
+                        .child(label().text(e.reason.clone()).color(Color::GRAY))
+                        .into_element()
+                }))
+                .into_element(),
+            QueryStateData::Settled { res: Err(e), .. } => {
+                rect().child(label().text(e.to_string())).into_element()
+            }
+            QueryStateData::Pending | QueryStateData::Loading { .. } => {
+                rect().child(CircularLoader::new()).into_element()
+            }
+        };
+
+        rect()
+            .spacing(10.)
+            .child(label().text("Audit log").font_size(32))
+            .child(list)
+    }
+}
+
+/// What the node needs to run, with what to do about each check that
+/// didn't pass. Also runs at startup, see
+/// [`run_diagnostics`](crate::diagnostics::run_diagnostics).
+#[derive(PartialEq)]
+struct Diagnostics;
+
+impl Component for Diagnostics {
+    fn render(&self) -> impl IntoElement {
+        let diagnostics_query = use_query(Query::new((), RunDiagnostics));
+
+        let checks = match &*diagnostics_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(checks), ..
+            } => rect()
+                .spacing(5.)
+                .children(checks.iter().map(|check| {
+                    let (mark, color, detail) = match &check.status {
+                        CheckStatus::Passed => ("✔", Color::from_rgb(0, 150, 0), None),
+                        CheckStatus::Warning(d) => ("!", Color::from_rgb(230, 140, 0), Some(d)),
+                        CheckStatus::Failed(d) => ("✘", Color::RED, Some(d)),
+                    };
+
+                    rect()
+                        .spacing(2.)
+                        .child(
+                            rect()
+                                .spacing(10.)
+                                .horizontal()
+                                .child(label().text(mark).color(color))
+                                .child(check.name),
+                        )
+                        .maybe(detail.is_some(), |r| {
+                            r.child(
+                                label()
+                                    .text(detail.cloned().unwrap_or_default())
+                                    .color(color),
+                            )
+                            .child(check.hint)
+                        })
+                        .into()
+                }))
+                .into_element(),
+            QueryStateData::Settled { res: Err(e), .. } => {
+                rect().child(label().text(e.to_string())).into_element()
+            }
+            QueryStateData::Pending | QueryStateData::Loading { .. } => {
+                rect().child(CircularLoader::new()).into_element()
+            }
+        };
+
+        rect()
+            .spacing(10.)
+            .child(label().text("Diagnostics").font_size(32))
+            .child(checks)
+            .child(Button::new().child("Check again").on_press(move |_| {
+                spawn(async move {
+                    QueriesStorage::<RunDiagnostics>::invalidate_all().await;
+                });
+            }))
+    }
+}
+
+/// Finds broken records on demand, a full scan is too slow to run whenever
+/// settings are opened
+#[derive(PartialEq)]
+struct IntegrityCheck;
+
+impl Component for IntegrityCheck {
+    fn render(&self) -> impl IntoElement {
+        let mut started = use_state(|| false);
+
+        let body = match *started.read() {
+            true => IntegrityResults.into_element(),
+            false => Button::new()
+                .child("Check the database")
+                .on_press(move |_| started.set(true))
+                .into_element(),
+        };
+
+        rect()
+            .spacing(10.)
+            .child(label().text("Integrity").font_size(32))
+            .child(body)
+    }
+}
+
+#[derive(PartialEq)]
+struct IntegrityResults;
+
+impl Component for IntegrityResults {
+    fn render(&self) -> impl IntoElement {
+        let check_query = use_query(Query::new((), CheckIntegrity));
+        let repair_mutation = use_mutation(Mutation::new(RepairIntegrity));
+        let refetch_mutation = use_mutation(Mutation::new(RefetchMissingIndexes));
+
+        let repair_button = move |text: &'static str, repair: Repair| {
+            Button::new()
+                .child(text)
+                .on_press(move |_| repair_mutation.mutate(repair))
+        };
+
+        let issue = |count: usize, text: &str| {
+            rect()
+                .spacing(20.)
+                .horizontal()
+                .cross_align(Alignment::Center)
+                .child(format!("{} {}", count, text))
+        };
+
+        let report = match &*check_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(report), ..
+            } if report.is_clean() => rect().child("No problems found").into_element(),
+            QueryStateData::Settled {
+                res: Ok(report), ..
+            } => rect()
+                .spacing(5.)
+                .maybe(!report.orphaned_contents.is_empty(), |r| {
+                    r.child(
+                        issue(report.orphaned_count(), "contents without their index")
+                            .child(
+                                Button::new()
+                                    .child("Fetch the indexes")
+                                    .on_press(move |_| refetch_mutation.mutate(())),
+                            )
+                            .child(repair_button("Delete", Repair::DeleteOrphanedContents)),
+                    )
+                })
+                .maybe(!report.invalid_indexes.is_empty(), |r| {
+                    r.child(
+                        issue(report.invalid_indexes.len(), "indexes with a bad signature")
+                            .child(repair_button(
+                                "Quarantine",
+                                Repair::QuarantineInvalidIndexes,
+                            ))
+                            .child(repair_button("Delete", Repair::DeleteInvalidIndexes)),
+                    )
+                })
+                .maybe(!report.malformed_users.is_empty(), |r| {
+                    r.child(
+                        issue(report.malformed_users.len(), "users with a malformed key")
+                            .child(repair_button("Delete", Repair::DeleteMalformedUsers)),
+                    )
+                })
+                .into_element(),
+            QueryStateData::Settled { res: Err(e), .. } => {
+                rect().child(label().text(e.to_string())).into_element()
+            }
+            QueryStateData::Pending | QueryStateData::Loading { .. } => {
+                rect().child(CircularLoader::new()).into_element()
+            }
+        };
+
+        let outcome = match (
+            &*repair_mutation.read().state(),
+            &*refetch_mutation.read().state(),
+        ) {
+            (MutationStateData::Settled { res: Err(e), .. }, _) => Some(e.to_string()),
+            (_, MutationStateData::Settled { res: Err(e), .. }) => Some(e.to_string()),
+            (_, MutationStateData::Settled { res: Ok(count), .. }) => {
+                Some(format!("{} indexes fetched again", count))
+            }
+            (MutationStateData::Settled { res: Ok(count), .. }, _) => {
+                Some(format!("{} records repaired", count))
+            }
+            _ => None,
+        };
+
+        rect()
+            .spacing(10.)
+            .child(report)
+            .maybe(outcome.is_some(), |r| r.child(outcome.unwrap_or_default()))
+            .child(Button::new().child("Check again").on_press(move |_| {
+                spawn(async move {
+                    QueriesStorage::<CheckIntegrity>::invalidate_all().await;
+                });
+            }))
+    }
+}
+
+#[derive(PartialEq)]
+struct SuppressionEntry {
+    suppression: Suppression,
+}
+
+impl Component for SuppressionEntry {
+    fn render(&self) -> impl IntoElement {
+        let unsuppress_mutation = use_mutation(Mutation::new(Unsuppress));
+
+        let kind = match self.suppression.kind {
+            SuppressedKind::Index => "Index",
+            SuppressedKind::Content => "Content",
+        };
+        let id = self.suppression.id.clone();
+
+        rect()
+            .spacing(20.)
+            .horizontal()
+            .cross_align(Alignment::Center)
+            .child(format!("{}: {}", kind, self.suppression.title))
+            .child(format!(
+                "since {}",
+                self.suppression.created_at.format_date()
+            ))
+            .child(
+                Button::new()
+                    .child("Unsuppress")
+                    .on_press(move |_| unsuppress_mutation.mutate(id.clone())),
+            )
     }
 }
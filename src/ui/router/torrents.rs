@@ -1,6 +1,6 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
-use anawt::{AnawtTorrentStatus, RemoveFlags, TorrentState};
+use anawt::{AnawtTorrentStatus, InfoHash, RemoveFlags, TorrentState};
 use freya::{
     prelude::*,
     query::{Mutation, Query, QueryStateData, use_mutation, use_query},
@@ -8,11 +8,16 @@ use freya::{
 };
 use tokio::sync::watch;
 
-use crate::ui::{
-    DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING,
-    components::Spacer,
-    icons,
-    queries::{FetchTorrentWatchers, RemoveTorrent},
+use crate::{
+    db::{changes::DataKind, torrent_link::TorrentLink},
+    helpers::format_bytes,
+    ui::{
+        DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING,
+        components::Spacer,
+        hooks::use_data_changed,
+        icons,
+        queries::{FetchTorrentLinks, FetchTorrentWatchers, RemoveTorrent},
+    },
 };
 
 #[derive(PartialEq)]
@@ -20,17 +25,57 @@ pub struct Torrents;
 
 impl Component for Torrents {
     fn render(&self) -> impl IntoElement {
-        let watchers_query = use_query(Query::new((), FetchTorrentWatchers));
+        // Torrents get linked when added, so this also picks up torrents added
+        // from other pages
+        let links_version = use_data_changed(DataKind::TorrentLinks);
+        let watchers_query = use_query(Query::new(links_version, FetchTorrentWatchers));
+        let links_query = use_query(Query::new((), FetchTorrentLinks));
+
+        let links = match &*links_query.read().state() {
+            QueryStateData::Settled { res: Ok(links), .. } => links.clone(),
+            _ => vec![],
+        };
 
         let torrent_list = match &*watchers_query.read().state() {
             QueryStateData::Settled {
                 res: Ok(watchers), ..
             } => {
+                let mut usage: BTreeMap<String, i64> = BTreeMap::new();
                 let children = watchers
                     .iter()
-                    .map(|w| TorrentEntry::new(w.clone()).into_element())
+                    .map(|w| {
+                        let (info_hash, total_bytes) = {
+                            let status = w.borrow();
+                            (status.info_hash, status.total_bytes)
+                        };
+                        let link = find_link(&links, info_hash);
+
+                        let tag = link
+                            .as_ref()
+                            .map(|l| l.tag.clone())
+                            .unwrap_or_else(|| "Unlinked".to_string());
+                        *usage.entry(tag).or_default() += total_bytes;
+
+                        TorrentEntry::new(w.clone(), link).into_element()
+                    })
+                    .collect::<Vec<_>>();
+
+                let usage_labels = usage
+                    .into_iter()
+                    .map(|(tag, bytes)| {
+                        label()
+                            .text(format!("{}: {}", tag, format_bytes(bytes)))
+                            .font_size(14.)
+                            .into_element()
+                    })
                     .collect::<Vec<_>>();
-                rect().vertical().children(children).into_element()
+
+                rect()
+                    .vertical()
+                    .spacing(10.)
+                    .child(rect().horizontal().spacing(15.).children(usage_labels))
+                    .children(children)
+                    .into_element()
             }
             QueryStateData::Settled { res: Err(e), .. } => {
                 rect().child(label().text(e.to_string())).into_element()
@@ -42,13 +87,22 @@ impl Component for Torrents {
     }
 }
 
+/// Finds which content a torrent was downloaded for
+fn find_link(links: &[TorrentLink], info_hash: InfoHash) -> Option<TorrentLink> {
+    links
+        .iter()
+        .find(|l| InfoHash::from_magnet(&l.magnet.0).is_ok_and(|h| h == info_hash))
+        .cloned()
+}
+
 pub struct TorrentEntry {
     watcher: watch::Receiver<AnawtTorrentStatus>,
+    link: Option<TorrentLink>,
 }
 
 impl TorrentEntry {
-    pub fn new(watcher: watch::Receiver<AnawtTorrentStatus>) -> Self {
-        Self { watcher }
+    pub fn new(watcher: watch::Receiver<AnawtTorrentStatus>, link: Option<TorrentLink>) -> Self {
+        Self { watcher, link }
     }
 }
 
@@ -59,25 +113,6 @@ impl PartialEq for TorrentEntry {
     }
 }
 
-fn format_bytes(bytes: i64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-
-    let float_bytes = bytes as f64;
-
-    let unit_index = (float_bytes.log2() / 10.0).floor() as usize;
-    let unit_index = unit_index.min(UNITS.len() - 1);
-
-    let divisor = 1024.0f64.powf(unit_index as f64);
-
-    let value = float_bytes / divisor;
-
-    if unit_index > 2 {
-        format!("{:.2}{}", value, UNITS[unit_index])
-    } else {
-        format!("{:.0}{}", value, UNITS[unit_index])
-    }
-}
-
 impl Component for TorrentEntry {
     fn render(&self) -> impl IntoElement {
         use_track_watcher(&self.watcher);
@@ -136,6 +171,18 @@ impl Component for TorrentEntry {
                     .cross_align(Alignment::Center)
                     .content(Content::Flex)
                     .child(status.name)
+                    .maybe(self.link.is_some(), |r| {
+                        r.child(
+                            label()
+                                .text(
+                                    self.link
+                                        .as_ref()
+                                        .map(|l| format!("  ({})", l.title))
+                                        .unwrap_or_default(),
+                                )
+                                .color(Color::DARK_GRAY),
+                        )
+                    })
                     .child(Spacer::horizontal_fill())
                     .child(rem)
                     .child(
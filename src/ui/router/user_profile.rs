@@ -0,0 +1,401 @@
+use std::path::PathBuf;
+
+use freya::{prelude::*, query::*, radio::use_radio};
+
+use crate::{
+    db::user::{SyncPolicy, TrustLevel, User},
+    types::{Link, PublicKey},
+    ui::{
+        AppChannel, DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, Route, RouteContext,
+        components::copy_button,
+        queries::{
+            ExportEvidence, FetchDisplayName, FetchMuted, FetchNodeInfo, FetchPeerStats,
+            FetchSyncPolicy, FetchVouchers, ImportCatalog, PingPeer, SetMuted, SetSyncPolicy,
+            Vouch,
+        },
+    },
+};
+
+#[derive(PartialEq)]
+pub struct UserProfile {
+    pub user: User,
+}
+
+impl Component for UserProfile {
+    fn render(&self) -> impl IntoElement {
+        let name_query = use_query(Query::new(self.user.pub_key().clone(), FetchDisplayName));
+        let stats_query = use_query(Query::new(self.user.pub_key().clone(), FetchPeerStats));
+        let ping_mutation = use_mutation(Mutation::new(PingPeer));
+        let node_query = use_query(Query::new(self.user.clone(), FetchNodeInfo));
+        let policy_query = use_query(Query::new(self.user.pub_key().clone(), FetchSyncPolicy));
+        let policy_mutation = use_mutation(Mutation::new(SetSyncPolicy));
+        let muted_query = use_query(Query::new(self.user.pub_key().clone(), FetchMuted));
+        let muted_mutation = use_mutation(Mutation::new(SetMuted));
+        let import_mutation = use_mutation(Mutation::new(ImportCatalog));
+        let vouchers_query = use_query(Query::new(self.user.pub_key().clone(), FetchVouchers));
+        let vouch_mutation = use_mutation(Mutation::new(Vouch));
+        let config = use_radio(AppChannel::Config);
+
+        let display_name = match &*name_query.read().state() {
+            QueryStateData::Settled { res: Ok(name), .. } => name.clone(),
+            _ => self.user.name().to_string(),
+        };
+
+        let stats = match &*stats_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(Some(s)), ..
+            } => rect()
+                .spacing(5.)
+                .child(field(
+                    "Last round trip",
+                    s.last_rtt_ms
+                        .map(|ms| format!("{} ms", ms))
+                        .unwrap_or_else(|| "-".to_string()),
+                ))
+                .child(field(
+                    "First seen",
+                    s.first_seen
+                        .map(|t| t.format_date())
+                        .unwrap_or_else(|| "Never".to_string()),
+                ))
+                .child(field(
+                    "Last seen",
+                    s.last_seen
+                        .map(|t| t.format_date())
+                        .unwrap_or_else(|| "Never".to_string()),
+                ))
+                .child(field(
+                    "Pings answered",
+                    format!("{}/{}", s.successes, s.successes + s.failures),
+                ))
+                .child(field(
+                    "Last synced",
+                    s.last_exchange
+                        .map(|t| t.format_date())
+                        .unwrap_or_else(|| "Never".to_string()),
+                )),
+            QueryStateData::Settled { res: Ok(None), .. } => rect().child("Never pinged"),
+            QueryStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            _ => rect().child(CircularLoader::new()),
+        };
+
+        let ping_result = match &*ping_mutation.read().state() {
+            MutationStateData::Pending => rect(),
+            MutationStateData::Loading { .. } => rect().child("Pinging..."),
+            MutationStateData::Settled {
+                res: Ok(report), ..
+            } => rect().child(format!(
+                "Answered in {} ms, protocol v{}",
+                report.rtt.as_millis(),
+                report.protocol_version
+            )),
+            MutationStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+        };
+
+        let node_info = match &*node_query.read().state() {
+            QueryStateData::Settled { res: Ok(info), .. } => rect()
+                .spacing(5.)
+                .child(field(
+                    "Software version",
+                    format!("{} ({})", info.software_version, info.git_hash),
+                ))
+                .child(field("Built", info.built_at.format_date()))
+                .child(field(
+                    "Relay",
+                    if info.is_relay { "Yes" } else { "No" }.to_string(),
+                ))
+                .child(field("Serves", info.tags.join(", ")))
+                .child(field(
+                    "Protocol versions",
+                    info.protocol_versions
+                        .iter()
+                        .map(|v| format!("v{}", v))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ))
+                .child(field(
+                    "Max relay hops",
+                    info.limits.max_relay_hops.to_string(),
+                )),
+            QueryStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            _ => rect().child("Asking peer..."),
+        };
+
+        let sync_policy = match &*policy_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(policy), ..
+            } => {
+                let policy = *policy;
+                let pub_key = self.user.pub_key().clone();
+
+                rect().spacing(5.).children(
+                    [
+                        (SyncPolicy::ACCEPT_INDEXES, "Accept their indexes"),
+                        (SyncPolicy::ACCEPT_CONTENT, "Accept their content"),
+                        (SyncPolicy::ACCEPT_POSTS, "Accept their posts"),
+                        (SyncPolicy::SEND_PUBLISHED, "Send them what I published"),
+                    ]
+                    .into_iter()
+                    .map(|(flag, text)| {
+                        let pub_key = pub_key.clone();
+
+                        rect()
+                            .horizontal()
+                            .spacing(10.)
+                            .cross_align(Alignment::Center)
+                            .child(Switch::new().toggled(policy.contains(flag)).on_toggle(
+                                move |_| policy_mutation.mutate((pub_key.clone(), policy ^ flag)),
+                            ))
+                            .child(text)
+                            .into_element()
+                    }),
+                )
+            }
+            QueryStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            _ => rect().child(CircularLoader::new()),
+        };
+
+        let mute = match &*muted_query.read().state() {
+            QueryStateData::Settled { res: Ok(muted), .. } => {
+                let muted = *muted;
+                let pub_key = self.user.pub_key().clone();
+
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Switch::new()
+                            .toggled(muted)
+                            .on_toggle(move |_| muted_mutation.mutate((pub_key.clone(), !muted))),
+                    )
+                    .child("Hide their uploads from my library, they are still relayed")
+            }
+            QueryStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            _ => rect().child(CircularLoader::new()),
+        };
+
+        let own_key = config.read().config.unwrap_ref().public_key().clone();
+        let vouching = match &*vouchers_query.read().state() {
+            QueryStateData::Settled {
+                res: Ok(vouchers), ..
+            } => {
+                let vouched = vouchers
+                    .iter()
+                    .any(|v| v.attestation.voucher == own_key && v.attestation.vouches());
+                let pub_key = self.user.pub_key().clone();
+                let others = vouchers
+                    .iter()
+                    .filter(|v| v.attestation.voucher != own_key && v.attestation.vouches())
+                    .collect::<Vec<_>>();
+
+                rect()
+                    .spacing(5.)
+                    .child(if others.is_empty() {
+                        rect().child("Nobody vouches for them")
+                    } else {
+                        rect().spacing(5.).children(others.into_iter().map(|v| {
+                            let counts = match v.counts {
+                                true => "counts, fully trusted by you",
+                                false => "doesn't count",
+                            };
+                            field(
+                                &v.name,
+                                format!(
+                                    "{} since {}, {}",
+                                    v.attestation.level,
+                                    v.attestation.timestamp.format_date(),
+                                    counts
+                                ),
+                            )
+                            .into_element()
+                        }))
+                    })
+                    .child(
+                        Button::new()
+                            .child(if vouched {
+                                "Withdraw my vouch"
+                            } else {
+                                "Vouch for them"
+                            })
+                            .on_press(move |_| vouch_mutation.mutate((pub_key.clone(), !vouched))),
+                    )
+            }
+            QueryStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            _ => rect().child(CircularLoader::new()),
+        };
+
+        let import_result = match &*import_mutation.read().state() {
+            MutationStateData::Pending => rect(),
+            MutationStateData::Loading { .. } => rect().child("Fetching..."),
+            MutationStateData::Settled {
+                res: Ok(pending), ..
+            } => {
+                let pending = pending.clone();
+                let fetched = match pending.since {
+                    Some(since) => format!(
+                        "{} indexes and {} contents changed since generation {}",
+                        pending.catalog.indexes.len(),
+                        pending.catalog.contents.len(),
+                        since
+                    ),
+                    None => format!(
+                        "{} indexes and {} contents",
+                        pending.catalog.indexes.len(),
+                        pending.catalog.contents.len()
+                    ),
+                };
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(fetched)
+                    .child(Button::new().child("Review sources").on_press(move |_| {
+                        RouteContext::get().push(Route::ImportReview {
+                            pending: pending.clone(),
+                        });
+                    }))
+            }
+            MutationStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+        };
+        // Only a relay serves snapshots and only a trusted one is imported from
+        let can_import = self.user.trust() >= &TrustLevel::Trusted
+            && matches!(
+                &*node_query.read().state(),
+                QueryStateData::Settled { res: Ok(info), .. } if info.is_relay
+            );
+
+        let user = self.user.clone();
+        let import_user = self.user.clone();
+        let address = self.user.address().inner().clone();
+        let blocked = self.user.trust() == &TrustLevel::Ignore && self.user.pub_key() != &own_key;
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .child(label().text(display_name).font_size(48))
+            .child(
+                rect()
+                    .width(Size::Fill)
+                    .padding(10.)
+                    .spacing(5.)
+                    .corner_radius(DEFAULT_CORNER_RADIUS)
+                    .background(Color::DARK_GRAY)
+                    .child(field("Signed name", self.user.name().to_string()))
+                    .child(
+                        rect()
+                            .horizontal()
+                            .spacing(5.)
+                            .cross_align(Alignment::Center)
+                            .child(field("Fingerprint", self.user.pub_key().fingerprint()))
+                            .child(copy_button(
+                                Link::User(self.user.pub_key().clone()).to_string(),
+                                Color::WHITE,
+                            )),
+                    )
+                    .child(field("Trust", self.user.trust().to_string()))
+                    .child(
+                        rect()
+                            .horizontal()
+                            .spacing(5.)
+                            .cross_align(Alignment::Center)
+                            .child(field("Address", address.clone()))
+                            .child(copy_button(address, Color::WHITE)),
+                    ),
+            )
+            .child(label().text("Vouched for by").font_size(24))
+            .child(vouching)
+            .child(label().text("Node").font_size(24))
+            .child(node_info)
+            .child(label().text("Connection").font_size(24))
+            .child(stats)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Button::new()
+                            .child("Test connection")
+                            .on_press(move |_| ping_mutation.mutate(user.clone())),
+                    )
+                    .child(ping_result),
+            )
+            .child(label().text("Sync").font_size(24))
+            .child(sync_policy)
+            .maybe(can_import, |r| {
+                r.child(
+                    rect()
+                        .horizontal()
+                        .spacing(10.)
+                        .cross_align(Alignment::Center)
+                        .child(
+                            Button::new()
+                                .child("Sync their catalog")
+                                .on_press(move |_| import_mutation.mutate(import_user.clone())),
+                        )
+                        .child(import_result),
+                )
+            })
+            .child(mute)
+            .maybe(blocked, |r| {
+                r.child(EvidenceExport {
+                    pub_key: self.user.pub_key().clone(),
+                })
+            })
+    }
+}
+
+/// Export of what we hold from a blocked user, for other operators to check
+/// and block them too
+#[derive(PartialEq)]
+struct EvidenceExport {
+    pub_key: PublicKey,
+}
+
+impl Component for EvidenceExport {
+    fn render(&self) -> impl IntoElement {
+        let reason = use_state(String::new);
+        let path = use_state(String::new);
+        let export_mutation = use_mutation(Mutation::new(ExportEvidence));
+
+        let export_status = match &*export_mutation.read().state() {
+            MutationStateData::Pending => String::new(),
+            MutationStateData::Loading { .. } => "Exporting...".to_string(),
+            MutationStateData::Settled { res: Ok(count), .. } => {
+                format!("Exported {} records", count)
+            }
+            MutationStateData::Settled { res: Err(e), .. } => e.to_string(),
+        };
+        let pub_key = self.pub_key.clone();
+
+        rect()
+            .spacing(5.)
+            .child(label().text("Evidence").font_size(24))
+            .child(Input::new(reason).placeholder("Why they were blocked"))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(Input::new(path).placeholder("Export to"))
+                    .child(Button::new().child("Export").on_press(move |_| {
+                        export_mutation.mutate((
+                            pub_key.clone(),
+                            reason.read().clone(),
+                            PathBuf::from(path.read().clone()),
+                        ));
+                    }))
+                    .child(export_status),
+            )
+    }
+}
+
+fn field(name: &str, value: String) -> impl IntoElement {
+    rect()
+        .horizontal()
+        .spacing(10.)
+        .child(label().text(name.to_string()).font_weight(FontWeight::BOLD))
+        .child(label().text(value))
+}
@@ -0,0 +1,579 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use freya::{prelude::*, query::*};
+
+use crate::{
+    db::user::{Invite, PeerStats, TrustEntry, TrustLevel, User, UserFilter, UserSort},
+    types::Timestamp,
+    ui::{
+        DEFAULT_CORNER_RADIUS, DEFAULT_PAGE_PADDING, Route, RouteContext,
+        components::{copy_button, lazy_list, no_reaction_button},
+        queries::{
+            AddUser, ApplyTrustEntries, BlockFromEvidence, ExportTrustList, FetchPetnames,
+            FetchUserList, FetchUsers, LookupPeer, ReadEvidence, ReadTrustList, SetPetname,
+        },
+    },
+};
+
+const USER_ROW_SIZE: f32 = 150.;
+
+#[derive(PartialEq)]
+pub struct UserList;
+impl Component for UserList {
+    fn render(&self) -> impl IntoElement {
+        let mut sort = use_state(UserSort::default);
+        let mut filter = use_state(UserFilter::default);
+        let list_query = use_query(Query::new((*sort.read(), *filter.read()), FetchUserList));
+        // Everyone, the list may be filtered but impersonation is checked
+        // against all trusted users
+        let users_query = use_query(Query::new((), FetchUsers));
+        let petnames_query = use_query(Query::new((), FetchPetnames));
+
+        let petnames = match &*petnames_query.read().state() {
+            QueryStateData::Settled { res: Ok(p), .. } => p.clone(),
+            _ => Default::default(),
+        };
+        let all_users = match &*users_query.read().state() {
+            QueryStateData::Settled { res: Ok(u), .. } => u.clone(),
+            _ => vec![],
+        };
+
+        let sort_selector = SegmentedButton::new().children(UserSort::ALL.map(|s| -> Element {
+            ButtonSegment::new()
+                .selected(*sort.read() == s)
+                .on_press(move |_| sort.set(s))
+                .child(s.to_string())
+                .into()
+        }));
+        let filter_selector =
+            SegmentedButton::new().children(UserFilter::ALL.map(|f| -> Element {
+                ButtonSegment::new()
+                    .selected(*filter.read() == f)
+                    .on_press(move |_| filter.set(f))
+                    .child(f.to_string())
+                    .into()
+            }));
+
+        let user_list = match &*list_query.read().state() {
+            QueryStateData::Pending => rect().child(CircularLoader::new()),
+            QueryStateData::Loading { .. } => rect().child(CircularLoader::new()),
+            QueryStateData::Settled { res, .. } => match res {
+                Ok(res) => {
+                    let rows: Vec<UserEntry> = res
+                        .iter()
+                        .map(|(u, stats)| UserEntry {
+                            user: u.clone(),
+                            stats: stats.clone(),
+                            petname: petnames.get(u.pub_key()).cloned(),
+                            impersonation_warning: shares_name_with_trusted(u, &all_users),
+                        })
+                        .collect();
+
+                    rect()
+                        .height(Size::Fill)
+                        .child(lazy_list(rows, USER_ROW_SIZE, |u| u.clone().into_element()))
+                }
+                Err(e) => rect().child(label().text(e.to_string())),
+            },
+        };
+
+        rect()
+            .spacing(10.)
+            .padding(DEFAULT_PAGE_PADDING)
+            .width(Size::Fill)
+            .height(Size::Fill)
+            .child(label().text("Users").font_size(48))
+            .child(AddFromInvite)
+            .child(TrustListTransfer)
+            .child(EvidenceImport)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child("Sort by")
+                    .child(sort_selector)
+                    .child("Show")
+                    .child(filter_selector),
+            )
+            .child(user_list)
+    }
+}
+
+/// Whether `user` uses the same name as a trusted user with a different key
+fn shares_name_with_trusted(user: &User, users: &[User]) -> bool {
+    users.iter().any(|u| {
+        u.name() == user.name()
+            && u.pub_key() != user.pub_key()
+            && *u.trust() >= TrustLevel::Trusted
+    })
+}
+
+fn format_seen(timestamp: Option<Timestamp>) -> String {
+    match timestamp {
+        Some(t) => t.format_date(),
+        None => "never".to_string(),
+    }
+}
+
+#[derive(Clone)]
+struct UserEntry {
+    user: User,
+    stats: Option<PeerStats>,
+    petname: Option<String>,
+    impersonation_warning: bool,
+}
+
+impl PartialEq for UserEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.user.pub_key() == other.user.pub_key()
+            && self.user.timestamp() == other.user.timestamp()
+            && self.user.trust() == other.user.trust()
+            && self.stats == other.stats
+            && self.petname == other.petname
+            && self.impersonation_warning == other.impersonation_warning
+    }
+}
+
+impl Component for UserEntry {
+    fn render(&self) -> impl IntoElement {
+        let address = self.user.address().inner().clone();
+        let pub_key = self.user.pub_key().clone();
+        let profile_key = pub_key.clone();
+
+        let petname_string = use_state(|| self.petname.clone().unwrap_or_default());
+        let petname_mutation = use_mutation(Mutation::new(SetPetname));
+
+        // Petnames are what we call them, so they take the spot of the name they
+        // gave themselves
+        let (display_name, signed_name) = match &self.petname {
+            Some(petname) => (petname.clone(), Some(self.user.name().to_string())),
+            None => (self.user.name().to_string(), None),
+        };
+
+        rect()
+            .width(Size::Fill)
+            .padding(10.)
+            .spacing(5.)
+            .corner_radius(DEFAULT_CORNER_RADIUS)
+            .background(Color::DARK_GRAY)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .child(
+                        no_reaction_button()
+                            .child(
+                                label()
+                                    .text(display_name)
+                                    .font_weight(FontWeight::BOLD)
+                                    .text_decoration(TextDecoration::Underline)
+                                    .color(Color::WHITE),
+                            )
+                            .on_press(move |_| {
+                                RouteContext::get().push(Route::UserProfile {
+                                    pub_key: profile_key.clone(),
+                                });
+                            }),
+                    )
+                    .maybe(signed_name.is_some(), |r| {
+                        r.child(
+                            label()
+                                .text(format!("\"{}\"", signed_name.unwrap_or_default()))
+                                .color(Color::LIGHT_GRAY),
+                        )
+                    })
+                    .child(
+                        label()
+                            .text(self.user.pub_key().fingerprint())
+                            .color(Color::LIGHT_GRAY),
+                    )
+                    .child(
+                        label()
+                            .text(self.user.trust().to_string())
+                            .color(Color::LIGHT_GRAY),
+                    )
+                    .maybe(self.impersonation_warning, |r| {
+                        r.child(badge("Same name as a trusted user", false))
+                    }),
+            )
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(5.)
+                    .cross_align(Alignment::Center)
+                    .child(label().text(address.clone()).color(Color::WHITE))
+                    .child(copy_button(address, Color::WHITE)),
+            )
+            .child(
+                label()
+                    .text(format!(
+                        "First seen {}, last seen {}, last exchange {}",
+                        format_seen(self.stats.as_ref().and_then(|s| s.first_seen)),
+                        format_seen(self.stats.as_ref().and_then(|s| s.last_seen)),
+                        format_seen(self.stats.as_ref().and_then(|s| s.last_exchange)),
+                    ))
+                    .font_size(12)
+                    .color(Color::LIGHT_GRAY),
+            )
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(5.)
+                    .cross_align(Alignment::Center)
+                    .child(Input::new(petname_string).placeholder("Petname"))
+                    .child(Button::new().child("Set").on_press(move |_| {
+                        petname_mutation.mutate((pub_key.clone(), petname_string.read().clone()));
+                    })),
+            )
+    }
+}
+
+#[derive(PartialEq)]
+struct AddFromInvite;
+impl Component for AddFromInvite {
+    fn render(&self) -> impl IntoElement {
+        let invite_string = use_state(String::new);
+        let users_query = use_query(Query::new((), FetchUsers));
+        let lookup_mutation = use_mutation(Mutation::new(LookupPeer));
+        let add_mutation = use_mutation(Mutation::new(AddUser));
+
+        let invite = invite_string.read().parse::<Invite>();
+
+        let lookup_result = match &*lookup_mutation.read().state() {
+            MutationStateData::Pending => rect(),
+            MutationStateData::Loading { .. } => rect().child("Asking peer..."),
+            MutationStateData::Settled { res: Err(e), .. } => rect().child(e.to_string()),
+            MutationStateData::Settled {
+                res: Ok(report), ..
+            } => {
+                let user = report.user.clone();
+                let impersonation_warning = match &*users_query.read().state() {
+                    QueryStateData::Settled { res: Ok(users), .. } => {
+                        shares_name_with_trusted(&user, users)
+                    }
+                    _ => false,
+                };
+
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(label().text(report.user.name().to_string()))
+                    .child(label().text(report.user.pub_key().fingerprint()))
+                    .maybe(impersonation_warning, |r| {
+                        r.child(badge("Same name as a trusted user", false))
+                    })
+                    .child(badge("Address signature", report.address_signature_valid))
+                    .child(badge("Name signature", report.user_signature_valid))
+                    .child(badge("Address matches", report.address_matches))
+                    .child(
+                        Button::new()
+                            .child("Add User")
+                            .enabled(report.is_verified())
+                            .on_press(move |_| add_mutation.mutate(user.clone())),
+                    )
+            }
+        };
+
+        rect()
+            .spacing(5.)
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(
+                        Input::new(invite_string)
+                            .placeholder("akareko:<public key>@<address>")
+                            .width(Size::px(500.)),
+                    )
+                    .child(
+                        Button::new()
+                            .child("Look up")
+                            .enabled(invite.is_ok())
+                            .on_press(move |_| {
+                                if let Ok(invite) = invite_string.read().parse::<Invite>() {
+                                    lookup_mutation.mutate(invite);
+                                }
+                            }),
+                    ),
+            )
+            .child(lookup_result)
+    }
+}
+
+fn badge(text: &str, valid: bool) -> impl IntoElement {
+    let (mark, color) = if valid {
+        ("✔", Color::from_rgb(46, 125, 50))
+    } else {
+        ("✘", Color::from_rgb(198, 40, 40))
+    };
+
+    rect()
+        .padding((2., 8.))
+        .corner_radius(DEFAULT_CORNER_RADIUS)
+        .background(color)
+        .child(
+            label()
+                .text(format!("{} {}", mark, text))
+                .color(Color::WHITE)
+                .font_size(12),
+        )
+}
+
+fn describe_entry(entry: &TrustEntry) -> String {
+    match &entry.petname {
+        Some(petname) => format!("{} as \"{}\"", entry.trust, petname),
+        None => entry.trust.to_string(),
+    }
+}
+
+/// Export of our trust decisions and import of someone else's, conflicts are
+/// settled one by one before anything is written
+#[derive(PartialEq)]
+struct TrustListTransfer;
+impl Component for TrustListTransfer {
+    fn render(&self) -> impl IntoElement {
+        let export_path = use_state(String::new);
+        let mut signed = use_state(|| true);
+        let import_path = use_state(String::new);
+        // Conflicts settled in favour of the imported list, by position
+        let mut take_theirs = use_state(HashSet::<usize>::new);
+        let export_mutation = use_mutation(Mutation::new(ExportTrustList));
+        let read_mutation = use_mutation(Mutation::new(ReadTrustList));
+        let apply_mutation = use_mutation(Mutation::new(ApplyTrustEntries));
+
+        let export_status = match &*export_mutation.read().state() {
+            MutationStateData::Pending => String::new(),
+            MutationStateData::Loading { .. } => "Exporting...".to_string(),
+            MutationStateData::Settled { res: Ok(count), .. } => {
+                format!("Exported {} users", count)
+            }
+            MutationStateData::Settled { res: Err(e), .. } => e.to_string(),
+        };
+        let apply_status = match &*apply_mutation.read().state() {
+            MutationStateData::Pending => String::new(),
+            MutationStateData::Loading { .. } => "Importing...".to_string(),
+            MutationStateData::Settled { res: Ok(()), .. } => "Imported".to_string(),
+            MutationStateData::Settled { res: Err(e), .. } => e.to_string(),
+        };
+
+        let review = match &*read_mutation.read().state() {
+            MutationStateData::Pending => rect(),
+            MutationStateData::Loading { .. } => rect().child("Reading..."),
+            MutationStateData::Settled { res: Err(e), .. } => {
+                rect().child(label().text(e.to_string()))
+            }
+            MutationStateData::Settled {
+                res: Ok((signer, merge)),
+                ..
+            } => {
+                let signer = match signer {
+                    Some(pub_key) => format!("Signed by {}", pub_key.fingerprint()),
+                    None => "Unsigned".to_string(),
+                };
+
+                let conflicts = merge.conflicts.iter().enumerate().map(|(i, conflict)| {
+                    let theirs = take_theirs.read().contains(&i);
+
+                    rect()
+                        .horizontal()
+                        .spacing(10.)
+                        .cross_align(Alignment::Center)
+                        .child(format!(
+                            "{} ({})",
+                            conflict.ours.user.name(),
+                            conflict.ours.user.pub_key().fingerprint()
+                        ))
+                        .child(
+                            SegmentedButton::new().children([
+                                ButtonSegment::new()
+                                    .selected(!theirs)
+                                    .on_press(move |_| {
+                                        take_theirs.write().remove(&i);
+                                    })
+                                    .child(format!("Keep {}", describe_entry(&conflict.ours)))
+                                    .into(),
+                                ButtonSegment::new()
+                                    .selected(theirs)
+                                    .on_press(move |_| {
+                                        take_theirs.write().insert(i);
+                                    })
+                                    .child(format!("Take {}", describe_entry(&conflict.theirs)))
+                                    .into(),
+                            ]),
+                        )
+                        .into_element()
+                });
+
+                let mut entries = merge.new.clone();
+                entries.extend(
+                    merge
+                        .conflicts
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| take_theirs.read().contains(i))
+                        .map(|(_, c)| c.theirs.clone()),
+                );
+
+                rect()
+                    .spacing(5.)
+                    .child(signer)
+                    .child(format!(
+                        "{} new, {} already the same, {} conflicting, {} with a bad signature",
+                        merge.new.len(),
+                        merge.unchanged,
+                        merge.conflicts.len(),
+                        merge.invalid
+                    ))
+                    .children(conflicts)
+                    .child(
+                        rect()
+                            .horizontal()
+                            .spacing(10.)
+                            .cross_align(Alignment::Center)
+                            .child(
+                                Button::new()
+                                    .child(format!("Import {} users", entries.len()))
+                                    .enabled(!entries.is_empty())
+                                    .on_press(move |_| apply_mutation.mutate(entries.clone())),
+                            )
+                            .child(apply_status),
+                    )
+            }
+        };
+
+        rect()
+            .spacing(5.)
+            .child(label().text("Trust list").font_size(24))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(Input::new(export_path).placeholder("Export to"))
+                    .child(Switch::new().toggled(*signed.read()).on_toggle(move |_| {
+                        let sign = !*signed.read();
+                        signed.set(sign);
+                    }))
+                    .child("Sign")
+                    .child(Button::new().child("Export").on_press(move |_| {
+                        export_mutation
+                            .mutate((PathBuf::from(export_path.read().clone()), *signed.read()));
+                    }))
+                    .child(export_status),
+            )
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(Input::new(import_path).placeholder("Import from"))
+                    .child(Button::new().child("Read").on_press(move |_| {
+                        take_theirs.set(HashSet::new());
+                        read_mutation.mutate(PathBuf::from(import_path.read().clone()));
+                    })),
+            )
+            .child(review)
+    }
+}
+
+/// Import of an evidence bundle another operator exported. Its records are
+/// checked here, and the user is only blocked when we can confirm at least
+/// one of them ourselves
+#[derive(PartialEq)]
+struct EvidenceImport;
+impl Component for EvidenceImport {
+    fn render(&self) -> impl IntoElement {
+        let import_path = use_state(String::new);
+        let read_mutation = use_mutation(Mutation::new(ReadEvidence));
+        let block_mutation = use_mutation(Mutation::new(BlockFromEvidence));
+
+        let block_status = match &*block_mutation.read().state() {
+            MutationStateData::Pending => String::new(),
+            MutationStateData::Loading { .. } => "Blocking...".to_string(),
+            MutationStateData::Settled { res: Ok(()), .. } => "Blocked".to_string(),
+            MutationStateData::Settled { res: Err(e), .. } => e.to_string(),
+        };
+
+        let review = match &*read_mutation.read().state() {
+            MutationStateData::Pending => rect(),
+            MutationStateData::Loading { .. } => rect().child("Reading..."),
+            MutationStateData::Settled { res: Err(e), .. } => {
+                rect().child(label().text(e.to_string()))
+            }
+            MutationStateData::Settled {
+                res: Ok((reporter, bundle, check)),
+                ..
+            } => {
+                let reporter_text = match reporter {
+                    Some(pub_key) => format!("Reported by {}", pub_key.fingerprint()),
+                    None => "Unsigned".to_string(),
+                };
+                let user = bundle.user.clone();
+                let block_reason = match &reporter {
+                    Some(pub_key) => format!(
+                        "Evidence reported by {}: {}",
+                        pub_key.fingerprint(),
+                        bundle.reason
+                    ),
+                    None => format!("Unsigned evidence: {}", bundle.reason),
+                };
+                let conclusive = check.is_conclusive();
+
+                rect()
+                    .spacing(5.)
+                    .child(reporter_text)
+                    .child(format!(
+                        "{} ({}), exported {}",
+                        bundle.user.name(),
+                        bundle.user.pub_key().fingerprint(),
+                        bundle.exported_at
+                    ))
+                    .child(format!("Reason: {}", bundle.reason))
+                    .child(format!(
+                        "{} records signed by them, {} that aren't, {} we check differently than the reporter",
+                        check.confirmed, check.unconfirmed, check.disputed
+                    ))
+                    .maybe(!check.user_verified, |r| {
+                        r.child(
+                            label()
+                                .text("The user record fails its signature")
+                                .color(Color::RED),
+                        )
+                    })
+                    .child(
+                        rect()
+                            .horizontal()
+                            .spacing(10.)
+                            .cross_align(Alignment::Center)
+                            .child(
+                                Button::new()
+                                    .child("Block")
+                                    .enabled(conclusive)
+                                    .on_press(move |_| {
+                                        block_mutation.mutate((user.clone(), block_reason.clone()))
+                                    }),
+                            )
+                            .child(block_status),
+                    )
+            }
+        };
+
+        rect()
+            .spacing(5.)
+            .child(label().text("Evidence").font_size(24))
+            .child(
+                rect()
+                    .horizontal()
+                    .spacing(10.)
+                    .cross_align(Alignment::Center)
+                    .child(Input::new(import_path).placeholder("Import from"))
+                    .child(Button::new().child("Read").on_press(move |_| {
+                        read_mutation.mutate(PathBuf::from(import_path.read().clone()));
+                    })),
+            )
+            .child(review)
+    }
+}
@@ -0,0 +1,7 @@
+/// Malformed `handler!` invocations end in its own error, not in whatever
+/// the expansion happens to trip on
+#[test]
+fn malformed_invocations_are_refused() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/handler/*.rs");
+}
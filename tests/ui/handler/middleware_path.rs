@@ -0,0 +1,5 @@
+akareko_lib::handler!(V1, {
+    GetContents = 4 ("manga/get_contents", middleware::RelayMiddleware) => index::GetContents,
+});
+
+fn main() {}
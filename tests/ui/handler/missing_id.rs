@@ -0,0 +1,5 @@
+akareko_lib::handler!(V1, {
+    Ping ("meta/ping") => meta::Ping,
+});
+
+fn main() {}
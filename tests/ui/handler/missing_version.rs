@@ -0,0 +1,5 @@
+akareko_lib::handler!({
+    Ping = 7 ("meta/ping") => meta::Ping,
+});
+
+fn main() {}
@@ -0,0 +1,5 @@
+akareko_lib::handler!(V1, {
+    Ping = 7 (meta_ping) => meta::Ping,
+});
+
+fn main() {}